@@ -0,0 +1,212 @@
+// src/archive.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use anyhow::{Result, Context};
+
+use crate::{check_overwrite, restore_stream, split_stream, ChunkTally, ChunkSink, SetPlacement, SplitEvent, SplitInfo, SplitInput, ChunkSource, LocalDir, RestoreOptions, SplitOptions};
+
+/// Bytes the archiver buffers before handing them to the split
+const BLOCK_SIZE: usize = 1 << 20;
+
+/// Blocks buffered between the archiver and the split or restore
+const PIPE_DEPTH: usize = 4;
+
+/// Archive format a split set's content was bundled in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// A tar stream of a directory tree, holding the directory itself as its top-level entry
+    Tar,
+}
+
+impl ArchiveFormat {
+    /// Short lowercase name of the format (e.g., "tar")
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Splits a directory tree, bundled into a tar stream on the fly, into chunks below `output_root_dir`
+///
+/// Works like [`split_directory_to_sink`] with the chunks stored on the local disk. Returns the split info,
+/// along with the path of its info file.
+pub fn split_directory(
+    dir: &Path,
+    size_limit: u64,
+    output_root_dir: &Path,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<(SplitInfo, PathBuf)> {
+    let info = split_directory_to_sink(dir, size_limit, &LocalDir::new(output_root_dir), options, event_callback)?;
+    let info_path = output_root_dir.join(info.info_name());
+    Ok((info, info_path))
+}
+
+/// Splits a directory tree, bundled into a tar stream on the fly, into chunks stored in `sink`
+///
+/// The split set is named after the directory (see [`directory_split_name`]) and laid out like that of a file
+/// of the same name, with one info file for the whole tree. The tar stream is never stored: it is split as it
+/// is written, so no extra disk space is needed. Restoring the split set unpacks the tree into the output
+/// directory. Symbolic links are archived as links, unless [`SplitOptions::follow_symlinks`] is set. Since the size of the stream is not known in advance,
+/// [`SplitEvent::Progress`] reports a total of 0. Returns the split info saved in `sink`.
+pub fn split_directory_to_sink(
+    dir: &Path,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<SplitInfo> {
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("Not a directory: {}", dir.display()));
+    }
+    let name = directory_split_name(dir)?;
+    let (tx, rx) = mpsc::sync_channel(PIPE_DEPTH);
+    thread::scope(|scope| {
+        let name = &name;
+        let follow_symlinks = options.follow_symlinks;
+        scope.spawn(move || {
+            let error_tx = tx.clone();
+            let archived = (|| -> Result<()> {
+                let mut builder = tar::Builder::new(BufWriter::with_capacity(BLOCK_SIZE, BlockWriter(tx)));
+                builder.follow_symlinks(follow_symlinks);
+                builder.append_dir_all(name, dir)?;
+                builder.into_inner()?.flush()?;
+                Ok(())
+            })()
+            .with_context(|| format!("Failed to archive directory: {}", dir.display()));
+            // Fail the split's next read, so that a truncated archive is never recorded as complete
+            if let Err(e) = archived {
+                let _ = error_tx.send(Err(io::Error::other(format!("{:#}", e))));
+            }
+        });
+        split_stream(
+            SplitInput::Reader(Box::new(BlockReader::new(rx))),
+            name,
+            None,
+            None,
+            size_limit,
+            sink,
+            options,
+            event_callback,
+            SetPlacement { archive: Some(ArchiveFormat::Tar), ..Default::default() },
+        )
+    })
+}
+
+/// Name of the split set [`split_directory_to_sink`] makes of `dir`: the directory's own name
+pub fn directory_split_name(dir: &Path) -> Result<String> {
+    let dir = dir.canonicalize()
+        .with_context(|| format!("Failed to open directory: {}", dir.display()))?;
+    dir.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("Invalid directory name: {}", dir.display()))
+}
+
+/// Restores a directory tree split by [`split_directory_to_sink`], unpacking it into `output_dir`, and counts the
+/// chunks checked on the way
+pub(crate) fn unpack_from_source(
+    file_info: &crate::SplitInfo,
+    source: &dyn ChunkSource,
+    output_dir: &Path,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + '_>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + '_>>,
+) -> Result<ChunkTally> {
+    if options.resume {
+        return Err(anyhow::anyhow!(
+            "Restoring the directory '{}' cannot be resumed; restore it again",
+            file_info.original_filename
+        ));
+    }
+    // The tree unpacks into a directory of its own name
+    check_overwrite(&output_dir.join(&file_info.original_filename), options)?;
+    let (tx, rx) = mpsc::sync_channel(PIPE_DEPTH);
+    thread::scope(|scope| {
+        // The restore runs inside the caller's span, so its events are attributed to the directory being restored
+        let span = tracing::Span::current();
+        let restore = scope.spawn(move || {
+            span.in_scope(|| restore_stream(file_info, source, BlockWriter(tx), options, progress_callback, message_callback))
+        });
+        let unpacked = (|| -> Result<()> {
+            let mut archive = tar::Archive::new(BlockReader::new(rx));
+            archive.set_preserve_mtime(true);
+            archive.unpack(output_dir)?;
+            // Read past the end of the archive, so that the whole-file checksum covers everything
+            io::copy(&mut archive.into_inner(), &mut io::sink())?;
+            Ok(())
+        })()
+        .with_context(|| format!("Failed to unpack '{}' into {}", file_info.original_filename, output_dir.display()));
+        let restored = restore.join().expect("restore thread panicked");
+        match (unpacked, restored) {
+            // A failed restore cuts the archive short, so its error is the better explanation
+            (_, Err(e)) if !is_broken_pipe(&e) => Err(e),
+            (Err(e), _) => Err(e),
+            (Ok(()), restored) => restored,
+        }
+    })
+}
+
+/// Whether `e` was caused by the other end of a [`BlockWriter`] going away
+fn is_broken_pipe(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| cause.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe))
+}
+
+/// Writing end of an in-memory pipe between two threads: every write is passed on as one block
+struct BlockWriter(mpsc::SyncSender<io::Result<Vec<u8>>>);
+
+impl Write for BlockWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(Ok(buf.to_vec()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the reading end of the pipe is closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reading end of an in-memory pipe: yields the blocks in order until the writer is gone, and fails at an error
+/// sent in place of a block
+struct BlockReader {
+    blocks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    block: Vec<u8>,
+    position: usize,
+}
+
+impl BlockReader {
+    fn new(blocks: mpsc::Receiver<io::Result<Vec<u8>>>) -> Self {
+        BlockReader { blocks, block: Vec::new(), position: 0 }
+    }
+}
+
+impl Read for BlockReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.block.len() {
+            match self.blocks.recv() {
+                Ok(block) => {
+                    self.block = block?;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0), // The writer is done
+            }
+        }
+        let n = buf.len().min(self.block.len() - self.position);
+        buf[..n].copy_from_slice(&self.block[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,257 @@
+// src/async_api.rs
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use tokio::sync::mpsc;
+
+use crate::{
+    parse_split_info, restore_single_file, split_single_file, ChecksumAlgorithm, ChunkCodec, ChunkNaming, ChunkSource, DedupIndex, Decryption,
+    Encryption, LocalDir, ManifestFormat, PauseGate, RateLimiter, RestoreOptions, RestoreReport, RetryPolicy, SplitEvent, SplitInfo,
+    SplitMode, SplitOptions,
+};
+
+/// Future returned by the async progress and message callbacks
+pub type CallbackFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Async callback for reporting progress (current_bytes, total_bytes)
+pub type AsyncProgressCallback = Box<dyn Fn(u64, u64) -> CallbackFuture + Send + Sync>;
+
+/// Async callback for reporting messages (message string)
+pub type AsyncMessageCallback = Box<dyn Fn(String) -> CallbackFuture + Send + Sync>;
+
+/// Async callback receiving the events of a split (see [`SplitEvent`])
+pub type AsyncSplitEventCallback = Box<dyn Fn(SplitEvent) -> CallbackFuture + Send + Sync>;
+
+/// Owned counterpart of [`SplitOptions`] that can be moved onto tokio's blocking thread pool
+#[derive(Clone, Default)]
+pub struct AsyncSplitOptions {
+    /// See [`SplitOptions::codec`]
+    pub codec: Option<Arc<dyn ChunkCodec>>,
+    /// See [`SplitOptions::encryption`]
+    pub encryption: Option<Arc<Encryption>>,
+    /// See [`SplitOptions::checksum_algorithm`]
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// See [`SplitOptions::split_mode`]
+    pub split_mode: SplitMode,
+    /// See [`SplitOptions::max_lines`]
+    pub max_lines: Option<u64>,
+    /// See [`SplitOptions::delimiter`]
+    pub delimiter: Option<Vec<u8>>,
+    /// See [`SplitOptions::chunk_pool`]
+    pub chunk_pool: Option<String>,
+    /// See [`SplitOptions::dedup`]; share one index between the splits of a batch
+    pub dedup: Option<Arc<DedupIndex>>,
+    /// See [`SplitOptions::parity`]
+    pub parity: usize,
+    /// Resume an interrupted split into the same output directory (see [`SplitOptions::resume_from`])
+    pub resume: bool,
+    /// Skip the split if the output directory already holds an up-to-date one (see [`SplitOptions::skip_unchanged`])
+    pub skip_unchanged: bool,
+    /// See [`SplitOptions::overwrite`]
+    pub overwrite: bool,
+    /// See [`SplitOptions::fsync`]
+    pub fsync: bool,
+    /// See [`SplitOptions::follow_symlinks`]
+    pub follow_symlinks: bool,
+    /// See [`SplitOptions::mmap`]
+    pub mmap: bool,
+    /// See [`SplitOptions::threads`]
+    pub threads: usize,
+    /// See [`SplitOptions::max_memory`]
+    pub max_memory: Option<u64>,
+    /// See [`SplitOptions::chunk_headers`]
+    pub chunk_headers: bool,
+    /// See [`SplitOptions::info_trailer`]
+    pub info_trailer: bool,
+    /// See [`SplitOptions::manifest_format`]
+    pub manifest_format: ManifestFormat,
+    /// See [`SplitOptions::info_extension`]
+    pub info_extension: Option<String>,
+    /// See [`SplitOptions::chunk_naming`]
+    pub chunk_naming: ChunkNaming,
+    /// See [`SplitOptions::chunk_prefix`]
+    pub chunk_prefix: Option<String>,
+    /// See [`SplitOptions::chunk_extension`]
+    pub chunk_extension: Option<String>,
+    /// See [`SplitOptions::chunk_start_index`]
+    pub chunk_start_index: Option<u64>,
+    /// See [`SplitOptions::cat_compatible`]
+    pub cat_compatible: bool,
+    /// See [`SplitOptions::cancel`]. Dropping the future does not stop the blocking work, so keep a clone of
+    /// the flag to abort it.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// See [`SplitOptions::pause`]
+    pub pause: Option<Arc<PauseGate>>,
+    /// See [`SplitOptions::rate_limit`]
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// See [`SplitOptions::retry`]
+    pub retry: RetryPolicy,
+}
+
+/// Owned counterpart of [`RestoreOptions`] that can be moved onto tokio's blocking thread pool
+#[derive(Clone, Default)]
+pub struct AsyncRestoreOptions {
+    /// See [`RestoreOptions::codec`]
+    pub codec: Option<Arc<dyn ChunkCodec>>,
+    /// See [`RestoreOptions::decryption`]
+    pub decryption: Option<Arc<Decryption>>,
+    /// See [`RestoreOptions::threads`]
+    pub threads: usize,
+    /// See [`RestoreOptions::max_memory`]
+    pub max_memory: Option<u64>,
+    /// See [`RestoreOptions::resume`]
+    pub resume: bool,
+    /// See [`RestoreOptions::sparse`]
+    pub sparse: bool,
+    /// See [`RestoreOptions::overwrite`]
+    pub overwrite: bool,
+    /// See [`RestoreOptions::warn_only`]
+    pub warn_only: bool,
+    /// See [`RestoreOptions::cancel`]; as with [`AsyncSplitOptions::cancel`], dropping the future does not stop
+    /// the restore
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// See [`RestoreOptions::pause`]
+    pub pause: Option<Arc<PauseGate>>,
+    /// See [`RestoreOptions::rate_limit`]
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// See [`RestoreOptions::retry`]
+    pub retry: RetryPolicy,
+}
+
+/// Progress and message events forwarded from the blocking worker of a restore to the async callbacks
+enum Event {
+    Progress(u64, u64),
+    Message(String),
+}
+
+/// Reads and parses a split info file with `tokio::fs`, in any [`ManifestFormat`] (see [`parse_split_info`])
+pub async fn load_split_info_async(info_path: impl AsRef<Path>) -> Result<SplitInfo> {
+    let info_path = info_path.as_ref();
+    let metadata_content = tokio::fs::read_to_string(info_path).await
+        .with_context(|| format!("Failed to read split info file: {}", info_path.display()))?;
+    parse_split_info(&info_path.to_string_lossy(), &metadata_content)
+        .with_context(|| format!("Failed to parse split info file: {}", info_path.display()))
+}
+
+/// Async version of [`split_single_file`]
+///
+/// The file I/O and the hashing/compression/encryption run on tokio's blocking thread pool (the same
+/// pool `tokio::fs` uses), so awaiting this never blocks the runtime's worker threads. Callbacks are
+/// awaited on the calling task, in order.
+pub async fn split_single_file_async(
+    file_path: impl Into<PathBuf>,
+    size_limit: u64,
+    output_root_dir: impl Into<PathBuf>,
+    options: AsyncSplitOptions,
+    event_callback: Option<AsyncSplitEventCallback>,
+) -> Result<(SplitInfo, PathBuf)> {
+    let file_path = file_path.into();
+    let output_root_dir = output_root_dir.into();
+    tokio::fs::create_dir_all(&output_root_dir).await
+        .with_context(|| format!("Failed to create output directory: {}", output_root_dir.display()))?;
+
+    run_blocking(|event| event_callback.as_ref().map(|cb| cb(event)), move |report| {
+        let output_dir = LocalDir::new(&output_root_dir);
+        let options = SplitOptions {
+            codec: options.codec.as_deref(),
+            encryption: options.encryption.as_deref(),
+            checksum_algorithm: options.checksum_algorithm,
+            split_mode: options.split_mode,
+            max_lines: options.max_lines,
+            delimiter: options.delimiter.as_deref(),
+            chunk_pool: options.chunk_pool.as_deref(),
+            dedup: options.dedup.as_deref(),
+            parity: options.parity,
+            resume_from: options.resume.then_some(&output_dir as &dyn ChunkSource),
+            skip_unchanged: options.skip_unchanged.then_some(&output_dir as &dyn ChunkSource),
+            overwrite: options.overwrite,
+            fsync: options.fsync,
+            follow_symlinks: options.follow_symlinks,
+            mmap: options.mmap,
+            threads: options.threads,
+            max_memory: options.max_memory,
+            chunk_headers: options.chunk_headers,
+            info_trailer: options.info_trailer,
+            manifest_format: options.manifest_format,
+            info_extension: options.info_extension.as_deref(),
+            chunk_naming: options.chunk_naming,
+            chunk_prefix: options.chunk_prefix.as_deref(),
+            chunk_extension: options.chunk_extension.as_deref(),
+            chunk_start_index: options.chunk_start_index,
+            cat_compatible: options.cat_compatible,
+            cancel: options.cancel.as_deref(),
+            pause: options.pause.as_deref(),
+            rate_limit: options.rate_limit.as_deref(),
+            retry: options.retry,
+        };
+        split_single_file(&file_path, size_limit, &output_root_dir, &options, Some(Box::new(move |event| report(event))))
+    }).await
+}
+
+/// Async version of [`restore_single_file`]
+///
+/// Like [`split_single_file_async`], the blocking work runs on tokio's blocking thread pool.
+pub async fn restore_single_file_async(
+    file_info: SplitInfo,
+    input_root_dir: impl Into<PathBuf>,
+    output_dir: impl Into<PathBuf>,
+    options: AsyncRestoreOptions,
+    progress_callback: Option<AsyncProgressCallback>,
+    message_callback: Option<AsyncMessageCallback>,
+) -> Result<RestoreReport> {
+    let input_root_dir = input_root_dir.into();
+    let output_dir = output_dir.into();
+    tokio::fs::create_dir_all(&output_dir).await
+        .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
+
+    let handle = |event| match event {
+        Event::Progress(current, total) => progress_callback.as_ref().map(|cb| cb(current, total)),
+        Event::Message(msg) => message_callback.as_ref().map(|cb| cb(msg)),
+    };
+    run_blocking(handle, move |report| {
+        let report_message = report.clone();
+        let progress_cb = Box::new(move |current, total| report(Event::Progress(current, total)));
+        let message_cb = Box::new(move |msg| report_message(Event::Message(msg)));
+        let options = RestoreOptions {
+            codec: options.codec.as_deref(),
+            decryption: options.decryption.as_deref(),
+            threads: options.threads,
+            max_memory: options.max_memory,
+            resume: options.resume,
+            sparse: options.sparse,
+            overwrite: options.overwrite,
+            warn_only: options.warn_only,
+            cancel: options.cancel.as_deref(),
+            pause: options.pause.as_deref(),
+            rate_limit: options.rate_limit.as_deref(),
+            retry: options.retry,
+        };
+        restore_single_file(&file_info, &input_root_dir, &output_dir, &options, Some(progress_cb), Some(message_cb))
+    }).await
+}
+
+/// Runs `work` on the blocking thread pool, awaiting the callback future `handle` makes of every event it reports
+async fn run_blocking<E, F, T>(handle: impl Fn(E) -> Option<CallbackFuture>, work: F) -> Result<T>
+where
+    E: Send + 'static,
+    F: FnOnce(Arc<dyn Fn(E) + Send + Sync>) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let report: Arc<dyn Fn(E) + Send + Sync> = Arc::new(move |event| {
+        let _ = tx.send(event);
+    });
+    let worker = tokio::task::spawn_blocking(move || work(report));
+
+    // The channel closes once the worker has finished and dropped its callbacks
+    while let Some(event) = rx.recv().await {
+        if let Some(callback) = handle(event) {
+            callback.await;
+        }
+    }
+
+    worker.await.context("Worker task failed")?
+}
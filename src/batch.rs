@@ -0,0 +1,79 @@
+// src/batch.rs
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+
+use crate::{Failure, FailureKind, SplitInfo};
+
+/// Index of the split sets made in one batch (e.g., of several files split at once), so that the whole batch
+/// verifies or restores in one go
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchIndex {
+    /// Every split set of the batch, in the order the files were split
+    pub files: Vec<BatchEntry>,
+    /// Size of all original files together in bytes
+    pub total_size: u64,
+    /// Number of chunks of all split sets together, parity chunks aside
+    pub total_chunks: usize,
+}
+
+/// One split set of a [`BatchIndex`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchEntry {
+    /// Original filename
+    pub original_filename: String,
+    /// Key of the info file, relative to the directory holding the index (e.g., "my_file_parts/my_file.json")
+    pub info_file: String,
+    /// Size of the original file in bytes
+    pub original_file_size: u64,
+    /// Algorithm-tagged checksum of the original file (e.g., "sha256:abcd...")
+    pub original_checksum: String,
+    /// Number of chunks, parity chunks aside
+    pub chunks: usize,
+}
+
+impl BatchIndex {
+    /// Name of the index file in the split output directory
+    pub const FILE_NAME: &'static str = "split-set.json";
+
+    /// Indexes the split sets of a batch, whose info files sit at [`SplitInfo::info_name`] next to the index
+    pub fn new(split_infos: &[SplitInfo]) -> Self {
+        let files: Vec<BatchEntry> = split_infos.iter().map(|info| BatchEntry {
+            original_filename: info.original_filename.clone(),
+            info_file: info.info_name(),
+            original_file_size: info.original_file_size,
+            original_checksum: info.original_checksum.clone(),
+            chunks: info.chunks.len(),
+        }).collect();
+        BatchIndex {
+            total_size: files.iter().map(|file| file.original_file_size).sum(),
+            total_chunks: files.iter().map(|file| file.chunks).sum(),
+            files,
+        }
+    }
+}
+
+impl BatchEntry {
+    /// Checks that `split_info`, read from this entry's info file, is still the split set the index lists (e.g.,
+    /// that the file was not split again since with other content)
+    pub fn check(&self, split_info: &SplitInfo) -> Result<()> {
+        if split_info.original_filename != self.original_filename
+            || split_info.original_file_size != self.original_file_size
+            || split_info.original_checksum != self.original_checksum
+            || split_info.chunks.len() != self.chunks
+        {
+            return Err(Failure::new(
+                FailureKind::Verification,
+                format!(
+                    "The info file {} does not match the batch index, which lists '{}' of {} bytes in {} chunks \
+                     ({}); it was split again or replaced since",
+                    self.info_file,
+                    self.original_filename,
+                    self.original_file_size,
+                    self.chunks,
+                    self.original_checksum
+                ),
+            ).into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+// src/checksum.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use anyhow::Result;
+use sha2::{Sha256, Digest};
+
+/// Hash algorithm used for the chunk and whole-file checksums
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    /// SHA-256, the original and default algorithm
+    #[default]
+    Sha256,
+    /// BLAKE3, several times faster than SHA-256 on modern CPUs
+    Blake3,
+    /// XXH3 (64-bit), non-cryptographic: detects corruption only, for trusted transfers
+    Xxh3,
+    /// CRC-32, non-cryptographic: detects corruption only, for trusted transfers
+    Crc32,
+}
+
+impl ChecksumAlgorithm {
+    /// All supported algorithms, in the order they are offered to users
+    pub const ALL: &'static [ChecksumAlgorithm] = &[
+        ChecksumAlgorithm::Sha256,
+        ChecksumAlgorithm::Blake3,
+        ChecksumAlgorithm::Xxh3,
+        ChecksumAlgorithm::Crc32,
+    ];
+
+    /// Short lowercase name of the algorithm (e.g., "sha256")
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::Xxh3 => "xxh3",
+            ChecksumAlgorithm::Crc32 => "crc32",
+        }
+    }
+
+    /// Prefixes a hex digest with this algorithm's name (e.g., "sha256:abcd...")
+    pub fn tag(&self, hex_digest: &str) -> String {
+        format!("{}:{}", self.name(), hex_digest)
+    }
+
+    /// Creates a new incremental hasher for this algorithm
+    pub fn hasher(&self) -> ChecksumHasher {
+        ChecksumHasher(match self {
+            ChecksumAlgorithm::Sha256 => HasherState::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Blake3 => HasherState::Blake3(Box::new(blake3::Hasher::new())),
+            ChecksumAlgorithm::Xxh3 => HasherState::Xxh3(Box::new(xxhash_rust::xxh3::Xxh3::new())),
+            ChecksumAlgorithm::Crc32 => HasherState::Crc32(crc32fast::Hasher::new()),
+        })
+    }
+}
+
+impl fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ChecksumAlgorithm::ALL
+            .iter()
+            .copied()
+            .find(|algo| algo.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown checksum algorithm '{}' (expected one of: {})",
+                s,
+                ChecksumAlgorithm::ALL.iter().map(|a| a.name()).collect::<Vec<_>>().join(", ")
+            ))
+    }
+}
+
+/// Splits a recorded checksum into its algorithm and hex digest
+///
+/// Values are tagged as `"<algorithm>:<hex>"`; untagged values (written before checksums were tagged)
+/// use `default_algorithm`. Unknown algorithm tags are rejected rather than silently mis-verified.
+pub fn parse_checksum(value: &str, default_algorithm: ChecksumAlgorithm) -> Result<(ChecksumAlgorithm, &str)> {
+    match value.split_once(':') {
+        Some((name, digest)) => {
+            let algorithm = name.parse::<ChecksumAlgorithm>()
+                .map_err(|_| anyhow::anyhow!("Unsupported checksum algorithm '{}' in checksum '{}'", name, value))?;
+            Ok((algorithm, digest))
+        }
+        None => Ok((default_algorithm, value)),
+    }
+}
+
+#[derive(Clone)]
+enum HasherState {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+    Crc32(crc32fast::Hasher),
+}
+
+/// Incremental hasher producing hex-encoded checksums
+#[derive(Clone)]
+pub struct ChecksumHasher(HasherState);
+
+impl ChecksumHasher {
+    /// Feeds more data into the hash
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.0 {
+            HasherState::Sha256(hasher) => hasher.update(data),
+            HasherState::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            HasherState::Xxh3(hasher) => hasher.update(data),
+            HasherState::Crc32(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Consumes the hasher and returns the hex-encoded digest
+    pub fn finalize(self) -> String {
+        match self.0 {
+            HasherState::Sha256(hasher) => hex::encode(hasher.finalize()),
+            HasherState::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            HasherState::Xxh3(hasher) => format!("{:016x}", hasher.digest()),
+            HasherState::Crc32(hasher) => format!("{:08x}", hasher.finalize()),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// src/cloud.rs
+use std::io::{self, Cursor, Read, Write};
+use anyhow::{Result, Context};
+use opendal::{ErrorKind, Operator, Writer};
+use tokio::runtime::Runtime;
+
+use crate::{ChunkEncoder, ChunkSink, ChunkSource};
+
+/// Environment variables starting with this pass the rest of their name, lowercased, as a service setting
+/// (e.g., `OPENDAL_BUCKET_ID` sets `bucket_id`)
+const SETTING_PREFIX: &str = "OPENDAL_";
+
+/// Size of the pieces a chunk is sent in, where the service can take an object in several writes
+const PART_SIZE: usize = 16 << 20;
+
+/// Chunk storage in any service supported by OpenDAL, selected by URL scheme
+///
+/// Supported URLs are `gcs://bucket/prefix` (Google Cloud Storage), `b2://bucket/prefix` (Backblaze B2),
+/// `oss://bucket/prefix` (Aliyun OSS), `azblob://container/prefix` (Azure Blob Storage), `s3://bucket/prefix`
+/// (S3 and compatible services), `webdav://host/path`, `fs:///path` and `memory://`. Service settings (e.g.,
+/// `endpoint`, `region`, `bucket_id`) come from the URL's query string and from `OPENDAL_*` environment
+/// variables; credentials can also come from the service's usual environment variables (e.g.,
+/// `GOOGLE_APPLICATION_CREDENTIALS`, `AWS_ACCESS_KEY_ID`).
+pub struct ObjectStorage {
+    operator: Operator,
+    /// The URL without its query string, which may hold credentials
+    url: String,
+    runtime: Runtime,
+}
+
+impl ObjectStorage {
+    /// Opens the storage location `url`
+    pub fn from_url(url: &str) -> Result<Self> {
+        let settings = std::env::vars()
+            .filter_map(|(key, value)| Some((key.strip_prefix(SETTING_PREFIX)?.to_ascii_lowercase(), value)));
+        let operator = Operator::from_uri((url, settings))
+            .with_context(|| format!("Unsupported storage URL: {}", url))?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start the storage client runtime")?;
+
+        let (base, _query) = url.split_once('?').unwrap_or((url, ""));
+        Ok(ObjectStorage {
+            operator,
+            url: base.trim_end_matches('/').to_string(),
+            runtime,
+        })
+    }
+
+    /// Size of the object stored under `name`, or `None` if there is none
+    fn head(&self, name: &str) -> Result<Option<u64>> {
+        match self.runtime.block_on(self.operator.stat(name)) {
+            Ok(meta) => Ok(Some(meta.content_length())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Object being written by [`ObjectStorage::create`]: held in memory until it outgrows one part, then sent part
+/// by part where the service can take an object in several writes (else held whole, and written at once)
+struct CloudUpload<'a> {
+    storage: &'a ObjectStorage,
+    name: String,
+    /// Whether the object may be sent in several writes
+    multi: bool,
+    /// Data not yet sent
+    buffer: Vec<u8>,
+    /// Writer of the object, once its first part is sent
+    writer: Option<Writer>,
+}
+
+impl CloudUpload<'_> {
+    /// Sends the buffered data as the next part, opening the writer first if needed
+    fn send_part(&mut self) -> Result<()> {
+        let storage = self.storage;
+        if self.writer.is_none() {
+            self.writer = Some(storage.runtime.block_on(storage.operator.writer(&self.name))?);
+        }
+        let writer = self.writer.as_mut().unwrap();
+        storage.runtime.block_on(writer.write(std::mem::take(&mut self.buffer)))?;
+        Ok(())
+    }
+}
+
+impl Write for CloudUpload<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.multi {
+            self.buffer.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+        let len = buf.len().min(PART_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == PART_SIZE {
+            self.send_part().map_err(io::Error::other)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ChunkEncoder for CloudUpload<'_> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let storage = self.storage;
+        if self.writer.is_none() {
+            storage.runtime.block_on(storage.operator.write(&self.name, std::mem::take(&mut self.buffer)))?;
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.send_part()?;
+        }
+        let mut writer = self.writer.take().unwrap();
+        storage.runtime.block_on(writer.close())?;
+        Ok(())
+    }
+}
+
+impl Drop for CloudUpload<'_> {
+    /// Aborts a write that was not completed, so that the parts sent so far are not kept
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            let _ = self.storage.runtime.block_on(writer.abort());
+        }
+    }
+}
+
+impl ChunkSink for ObjectStorage {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.runtime.block_on(self.operator.write(name, data.to_vec()))?;
+        Ok(())
+    }
+
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        Ok(Box::new(CloudUpload {
+            storage: self,
+            name: name.to_string(),
+            multi: self.operator.info().capability().write_can_multi,
+            buffer: Vec::new(),
+            writer: None,
+        }))
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.head(name)?.is_some())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        // Deleting a missing object succeeds too
+        self.runtime.block_on(self.operator.delete(name))?;
+        Ok(())
+    }
+
+    fn describe(&self, name: &str) -> String {
+        format!("{}/{}", self.url, name)
+    }
+}
+
+impl ChunkSource for ObjectStorage {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let data = self.runtime.block_on(self.operator.read(name))?;
+        Ok(Box::new(Cursor::new(data.to_bytes())))
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        self.head(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        format!("{}/{}", self.url, name)
+    }
+}
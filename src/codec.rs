@@ -0,0 +1,173 @@
+// src/codec.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufReader, Read, Write};
+use std::str::FromStr;
+use anyhow::Result;
+use flate2::{
+    write::GzEncoder,
+    read::GzDecoder,
+    Compression,
+};
+
+/// A pluggable compressor/decompressor for chunk files
+///
+/// Implement this trait to use a compression format other than the built-in ones.
+/// The codec's `name` is recorded in the split info, and the same codec has to be
+/// supplied again when restoring.
+pub trait ChunkCodec: Send + Sync {
+    /// Identifier recorded in the split info (e.g., "gzip")
+    fn name(&self) -> &str;
+
+    /// Wraps `writer` so that everything written to the returned encoder is compressed into it
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>) -> Result<Box<dyn ChunkEncoder + 'a>>;
+
+    /// Wraps `reader` so that reading from the returned reader yields the decompressed data
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>>;
+}
+
+/// A streaming compressor returned by [`ChunkCodec::encoder`]
+pub trait ChunkEncoder: Write {
+    /// Writes any buffered data and trailer to the underlying writer
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Compression algorithm applied to the split sub-files
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionAlgorithm {
+    /// Gzip (deflate), the original and default codec
+    #[default]
+    Gzip,
+    /// Zstandard, faster with a better ratio than Gzip
+    Zstd,
+    /// xz (LZMA2) at the highest preset, slow but the smallest output for archival chunks
+    Xz,
+    /// LZ4 frame format, nearly free in CPU terms for speed-critical splits
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    /// All supported algorithms, in the order they are offered to users
+    pub const ALL: &'static [CompressionAlgorithm] = &[
+        CompressionAlgorithm::Gzip,
+        CompressionAlgorithm::Zstd,
+        CompressionAlgorithm::Xz,
+        CompressionAlgorithm::Lz4,
+    ];
+
+    /// Short lowercase name of the algorithm (e.g., "gzip")
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionAlgorithm::Gzip => "gzip",
+            CompressionAlgorithm::Zstd => "zstd",
+            CompressionAlgorithm::Xz => "xz",
+            CompressionAlgorithm::Lz4 => "lz4",
+        }
+    }
+
+    /// Algorithm whose magic bytes `header` (the start of a chunk file) begins with, if any
+    pub(crate) fn detect(header: &[u8]) -> Option<CompressionAlgorithm> {
+        const MAGIC: [(CompressionAlgorithm, &[u8]); 4] = [
+            (CompressionAlgorithm::Gzip, &[0x1f, 0x8b]),
+            (CompressionAlgorithm::Zstd, &[0x28, 0xb5, 0x2f, 0xfd]),
+            (CompressionAlgorithm::Xz, &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+            (CompressionAlgorithm::Lz4, &[0x04, 0x22, 0x4d, 0x18]),
+        ];
+        MAGIC.iter().find(|(_, magic)| header.starts_with(magic)).map(|(algo, _)| *algo)
+    }
+}
+
+impl fmt::Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CompressionAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        CompressionAlgorithm::ALL
+            .iter()
+            .copied()
+            .find(|algo| algo.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown compression algorithm '{}' (expected one of: {})",
+                s,
+                CompressionAlgorithm::ALL.iter().map(|a| a.name()).collect::<Vec<_>>().join(", ")
+            ))
+    }
+}
+
+impl ChunkCodec for CompressionAlgorithm {
+    fn name(&self) -> &str {
+        CompressionAlgorithm::name(self)
+    }
+
+    fn encoder<'a>(&self, writer: Box<dyn Write + 'a>) -> Result<Box<dyn ChunkEncoder + 'a>> {
+        Ok(match self {
+            CompressionAlgorithm::Gzip => Box::new(GzEncoder::new(writer, Compression::default())),
+            CompressionAlgorithm::Zstd => Box::new(zstd::stream::write::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?),
+            CompressionAlgorithm::Xz => Box::new(xz2::write::XzEncoder::new(writer, 9)),
+            CompressionAlgorithm::Lz4 => Box::new(lz4_flex::frame::FrameEncoder::new(writer)),
+        })
+    }
+
+    fn decoder<'a>(&self, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            CompressionAlgorithm::Gzip => Box::new(GzDecoder::new(reader)),
+            CompressionAlgorithm::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            CompressionAlgorithm::Xz => Box::new(xz2::read::XzDecoder::new(BufReader::new(reader))),
+            CompressionAlgorithm::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+        })
+    }
+}
+
+impl<W: Write> ChunkEncoder for GzEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        GzEncoder::finish(*self)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ChunkEncoder for zstd::stream::write::Encoder<'_, W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        zstd::stream::write::Encoder::finish(*self)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ChunkEncoder for xz2::write::XzEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        xz2::write::XzEncoder::finish(*self)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ChunkEncoder for lz4_flex::frame::FrameEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        lz4_flex::frame::FrameEncoder::finish(*self)?;
+        Ok(())
+    }
+}
+
+/// Pass-through encoder used when chunks are stored uncompressed
+pub(crate) struct StoredEncoder<W: Write>(pub(crate) W);
+
+impl<W: Write> Write for StoredEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> ChunkEncoder for StoredEncoder<W> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+}
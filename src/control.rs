@@ -0,0 +1,140 @@
+// src/control.rs
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+
+/// How often a paused operation looks at its cancellation flag
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How far a rate-limited operation may get ahead of its limit after falling behind it (e.g., while waiting for
+/// the disk), so that a long stall is not made up for with a burst
+const RATE_LIMIT_BURST: Duration = Duration::from_secs(1);
+
+/// Gate to pause a running split or restore with (see [`crate::SplitOptions::pause`]), e.g. from a GUI's
+/// Pause/Resume buttons
+///
+/// While the gate is paused, the operation blocks at its next check between buffers, holding on to its files,
+/// until the gate is resumed or the operation is cancelled.
+#[derive(Debug, Default)]
+pub struct PauseGate {
+    paused: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl PauseGate {
+    /// Creates an open gate
+    pub fn new() -> Self {
+        PauseGate::default()
+    }
+
+    /// Makes the operations checking this gate wait
+    pub fn pause(&self) {
+        *self.paused.lock().unwrap() = true;
+    }
+
+    /// Lets the waiting operations continue
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap() = false;
+        self.changed.notify_all();
+    }
+
+    /// Whether the gate is paused
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+
+    /// Blocks while the gate is paused, unless `cancel` is set
+    fn wait(&self, cancel: Option<&AtomicBool>) {
+        let mut paused = self.paused.lock().unwrap();
+        while *paused && !is_set(cancel) {
+            paused = match cancel {
+                Some(_) => self.changed.wait_timeout(paused, CANCEL_POLL_INTERVAL).unwrap().0,
+                None => self.changed.wait(paused).unwrap(),
+            };
+        }
+    }
+}
+
+/// Limit on the bytes per second splits and restores work through (see [`crate::SplitOptions::rate_limit`]),
+/// e.g. so that a backup split overnight leaves a database on the same disk enough of its throughput
+///
+/// One limiter can be shared by several operations (and by the worker threads of one), which then share the rate.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    /// When the bytes passed so far are due at the limit, once any have passed
+    due: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter letting `bytes_per_sec` bytes pass per second (at least 1)
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter { bytes_per_sec: bytes_per_sec.max(1), due: Mutex::new(None) }
+    }
+
+    /// Bytes per second the limiter lets pass
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Counts `bytes` as passed, and returns when they are due at the limit
+    fn pass(&self, bytes: u64) -> Instant {
+        let mut due = self.due.lock().unwrap();
+        let now = Instant::now();
+        let behind = now.checked_sub(RATE_LIMIT_BURST).unwrap_or(now);
+        let start = due.map_or(now, |due| due.max(behind));
+        let end = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        *due = Some(end);
+        end
+    }
+}
+
+/// Where a running split or restore checks whether it is paused or cancelled, and keeps to its rate limit
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Checkpoint<'a> {
+    pub(crate) cancel: Option<&'a AtomicBool>,
+    pub(crate) pause: Option<&'a PauseGate>,
+    pub(crate) rate_limit: Option<&'a RateLimiter>,
+}
+
+impl Checkpoint<'_> {
+    /// Waits while paused, then fails if cancelled
+    pub(crate) fn check(self) -> Result<()> {
+        if let Some(pause) = self.pause {
+            pause.wait(self.cancel);
+        }
+        match self.cancelled() {
+            true => Err(anyhow::anyhow!("Cancelled")),
+            false => Ok(()),
+        }
+    }
+
+    /// Whether the cancellation flag is set
+    pub(crate) fn cancelled(self) -> bool {
+        is_set(self.cancel)
+    }
+
+    /// Waits until `bytes` more are within the rate limit, if any, or until cancelled
+    pub(crate) fn throttle(self, bytes: u64) {
+        let Some(rate_limit) = self.rate_limit else {
+            return;
+        };
+        self.wait_until(rate_limit.pass(bytes));
+    }
+
+    /// Waits until `deadline`, or until cancelled
+    pub(crate) fn wait_until(self, deadline: Instant) {
+        while !self.cancelled() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            std::thread::sleep((deadline - now).min(CANCEL_POLL_INTERVAL));
+        }
+    }
+}
+
+fn is_set(flag: Option<&AtomicBool>) -> bool {
+    flag.is_some_and(|flag| flag.load(Ordering::Relaxed))
+}
@@ -0,0 +1,474 @@
+// src/crypto.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use anyhow::{Context, Result};
+use sha2::{Sha256, Digest};
+use aes_gcm::{
+    aead::{
+        rand_core::RngCore,
+        stream::{DecryptorBE32, EncryptorBE32},
+        KeyInit, OsRng,
+    },
+    Aes256Gcm,
+};
+
+use crate::codec::ChunkEncoder;
+
+/// Plaintext bytes per encrypted segment; each segment carries its own authentication tag
+const SEGMENT_SIZE: usize = 64 * 1024;
+/// Size of the AES-GCM authentication tag appended to every segment
+const TAG_SIZE: usize = 16;
+/// Length of the per-chunk nonce prefix used by the STREAM construction (12-byte nonce minus 5 bytes of counter/flag)
+const NONCE_PREFIX_SIZE: usize = 7;
+
+/// Authenticated encryption algorithm applied to the split sub-files
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum EncryptionAlgorithm {
+    /// AES-256 in Galois/Counter Mode, applied in 64 KiB segments (STREAM construction)
+    #[default]
+    Aes256Gcm,
+    /// age (X25519 recipients, ChaCha20-Poly1305 payload), one age file per chunk
+    Age,
+}
+
+impl fmt::Display for EncryptionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionAlgorithm::Aes256Gcm => f.write_str("aes-256-gcm"),
+            EncryptionAlgorithm::Age => f.write_str("age"),
+        }
+    }
+}
+
+/// Encryption parameters recorded in the split info
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptionInfo {
+    /// Algorithm the chunks were encrypted with
+    pub algorithm: EncryptionAlgorithm,
+    /// Plaintext bytes per authenticated segment
+    pub segment_size: u32,
+    /// Short fingerprint of the key, used to reject a wrong key before any chunk is decrypted (symmetric keys only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_check: Option<String>,
+    /// Key derivation parameters, if the key was derived from a password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<KdfParams>,
+    /// Public keys of the age recipients the chunks were encrypted to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+}
+
+impl EncryptionInfo {
+    /// Re-derives the key from `password` using the recorded key derivation parameters
+    pub fn key_from_password(&self, password: &str) -> Result<EncryptionKey> {
+        let kdf = self.kdf.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("This split set was encrypted with a raw key, not a password"))?;
+        let key = EncryptionKey::from_password(password, kdf)?;
+        if self.key_check.as_deref() != Some(key.key_check().as_str()) {
+            return Err(anyhow::anyhow!("Wrong password"));
+        }
+        Ok(key)
+    }
+}
+
+/// Argon2id parameters used to derive an encryption key from a password
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Key derivation function, always "argon2id"
+    pub algorithm: String,
+    /// Hex-encoded random salt
+    pub salt: String,
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Argon2id parameters with a fresh random salt and the library's recommended costs
+    pub fn generate() -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        KdfParams {
+            algorithm: "argon2id".to_string(),
+            salt: hex::encode(salt),
+            memory_kib: argon2::Params::DEFAULT_M_COST,
+            iterations: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// A 256-bit key used to encrypt and decrypt chunks
+#[derive(Clone)]
+pub struct EncryptionKey {
+    bytes: [u8; 32],
+    /// Parameters the key was derived with, recorded in the split info so restores can re-derive it
+    kdf: Option<KdfParams>,
+}
+
+impl EncryptionKey {
+    /// Creates a key from 32 raw bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        EncryptionKey { bytes, kdf: None }
+    }
+
+    /// Derives a key from a password with Argon2id
+    pub fn from_password(password: &str, kdf: &KdfParams) -> Result<Self> {
+        if kdf.algorithm != "argon2id" {
+            return Err(anyhow::anyhow!("Unsupported key derivation function '{}'", kdf.algorithm));
+        }
+        let salt = hex::decode(&kdf.salt)
+            .map_err(|e| anyhow::anyhow!("Invalid key derivation salt: {}", e))?;
+        let params = argon2::Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+            .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+        let mut bytes = [0u8; 32];
+        argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &salt, &mut bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to derive key from password: {}", e))?;
+        Ok(EncryptionKey { bytes, kdf: Some(kdf.clone()) })
+    }
+
+    /// Parses a key from 64 hexadecimal characters
+    pub fn from_hex(hex_key: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_key.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid encryption key: {}", e))?;
+        let bytes: [u8; 32] = bytes.try_into()
+            .map_err(|b: Vec<u8>| anyhow::anyhow!("Invalid encryption key: expected 32 bytes, got {}", b.len()))?;
+        Ok(EncryptionKey::from_bytes(bytes))
+    }
+
+    /// Reads a key from a keyfile containing exactly 32 raw bytes
+    pub fn from_keyfile(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("Failed to read keyfile: {}", path.display()))?;
+        let bytes: [u8; 32] = bytes.try_into()
+            .map_err(|b: Vec<u8>| anyhow::anyhow!("Invalid keyfile {}: expected 32 bytes, got {}", path.display(), b.len()))?;
+        Ok(EncryptionKey::from_bytes(bytes))
+    }
+
+    /// Generates a new random key
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        EncryptionKey::from_bytes(bytes)
+    }
+
+    /// Returns the key as 64 hexadecimal characters
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.bytes)
+    }
+
+    /// Short fingerprint of the key stored in [`EncryptionInfo::key_check`]
+    pub fn key_check(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"file_splitter key check");
+        hasher.update(self.bytes);
+        hex::encode(&hasher.finalize()[..8])
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.bytes.into())
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// How the split sub-files are encrypted
+#[derive(Clone, Debug)]
+pub enum Encryption {
+    /// AES-256-GCM with a symmetric key (raw or password-derived)
+    Key(EncryptionKey),
+    /// age encryption to one or more X25519 recipients ("age1...")
+    AgeRecipients(Vec<age::x25519::Recipient>),
+}
+
+impl From<EncryptionKey> for Encryption {
+    fn from(key: EncryptionKey) -> Self {
+        Encryption::Key(key)
+    }
+}
+
+impl Encryption {
+    /// Parses age recipients from their "age1..." public key strings
+    pub fn age_recipients<S: AsRef<str>>(recipients: &[S]) -> Result<Self> {
+        let recipients = recipients
+            .iter()
+            .map(|r| r.as_ref().trim().parse::<age::x25519::Recipient>()
+                .map_err(|e| anyhow::anyhow!("Invalid age recipient '{}': {}", r.as_ref(), e)))
+            .collect::<Result<Vec<_>>>()?;
+        if recipients.is_empty() {
+            return Err(anyhow::anyhow!("At least one age recipient is required"));
+        }
+        Ok(Encryption::AgeRecipients(recipients))
+    }
+
+    /// Builds the encryption parameters recorded in the split info
+    pub(crate) fn info(&self) -> EncryptionInfo {
+        match self {
+            Encryption::Key(key) => EncryptionInfo {
+                algorithm: EncryptionAlgorithm::Aes256Gcm,
+                segment_size: SEGMENT_SIZE as u32,
+                key_check: Some(key.key_check()),
+                kdf: key.kdf.clone(),
+                recipients: Vec::new(),
+            },
+            Encryption::AgeRecipients(recipients) => EncryptionInfo {
+                algorithm: EncryptionAlgorithm::Age,
+                segment_size: SEGMENT_SIZE as u32, // age's own STREAM chunk size
+                key_check: None,
+                kdf: None,
+                recipients: recipients.iter().map(|r| r.to_string()).collect(),
+            },
+        }
+    }
+
+    /// Wraps the chunk file writer; returns the encrypting writer and the per-chunk nonce to record, if any
+    pub(crate) fn writer<'a, W: Write + 'a>(&self, inner: W) -> Result<(Box<dyn ChunkEncoder + 'a>, Option<String>)> {
+        match self {
+            Encryption::Key(key) => {
+                let nonce = generate_nonce();
+                Ok((Box::new(EncryptingWriter::new(inner, key, &nonce)?), Some(nonce)))
+            }
+            Encryption::AgeRecipients(recipients) => {
+                let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+                    .map_err(|e| anyhow::anyhow!("Failed to set up age encryption: {}", e))?;
+                Ok((Box::new(encryptor.wrap_output(inner)?), None))
+            }
+        }
+    }
+}
+
+/// Secret material used to decrypt encrypted split sub-files
+#[derive(Clone)]
+pub enum Decryption {
+    /// Symmetric AES-256-GCM key (raw or password-derived)
+    Key(EncryptionKey),
+    /// age identities ("AGE-SECRET-KEY-1..."); any one matching a recipient is enough
+    AgeIdentities(Vec<age::x25519::Identity>),
+}
+
+impl From<EncryptionKey> for Decryption {
+    fn from(key: EncryptionKey) -> Self {
+        Decryption::Key(key)
+    }
+}
+
+impl fmt::Debug for Decryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decryption::Key(_) => f.write_str("Decryption::Key(..)"),
+            Decryption::AgeIdentities(identities) => write!(f, "Decryption::AgeIdentities({} identities)", identities.len()),
+        }
+    }
+}
+
+impl Decryption {
+    /// Reads age identities from an identity file (as written by `age-keygen`), skipping comments and blank lines
+    pub fn age_identity_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read age identity file: {}", path.display()))?;
+        let identities = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.parse::<age::x25519::Identity>()
+                .map_err(|e| anyhow::anyhow!("Invalid identity in {}: {}", path.display(), e)))
+            .collect::<Result<Vec<_>>>()?;
+        if identities.is_empty() {
+            return Err(anyhow::anyhow!("No age identities found in {}", path.display()));
+        }
+        Ok(Decryption::AgeIdentities(identities))
+    }
+
+    /// Fails with a clear error if this secret cannot decrypt the split set described by `info`
+    pub(crate) fn verify(&self, info: &EncryptionInfo) -> Result<()> {
+        match (self, info.algorithm) {
+            (Decryption::Key(key), EncryptionAlgorithm::Aes256Gcm) => {
+                if info.key_check.as_deref() != Some(key.key_check().as_str()) {
+                    return Err(anyhow::anyhow!("Wrong decryption key: it does not match the key the chunks were encrypted with"));
+                }
+                Ok(())
+            }
+            (Decryption::AgeIdentities(identities), EncryptionAlgorithm::Age) => {
+                // Recipients are recorded for information only; a mismatch here is reported up front
+                let ours: Vec<String> = identities.iter().map(|i| i.to_public().to_string()).collect();
+                if !info.recipients.is_empty() && !info.recipients.iter().any(|r| ours.contains(r)) {
+                    return Err(anyhow::anyhow!("None of the supplied age identities matches the recipients the chunks were encrypted to"));
+                }
+                Ok(())
+            }
+            (_, algorithm) => Err(anyhow::anyhow!("The supplied secret cannot decrypt {} encrypted chunks", algorithm)),
+        }
+    }
+
+    /// Wraps a chunk reader so that reading yields the decrypted data
+    pub(crate) fn reader<'a>(&self, inner: Box<dyn Read + 'a>, nonce: Option<&str>) -> Result<Box<dyn Read + 'a>> {
+        match self {
+            Decryption::Key(key) => {
+                let nonce = nonce.ok_or_else(|| anyhow::anyhow!("Missing nonce for encrypted chunk"))?;
+                Ok(Box::new(DecryptingReader::new(inner, key, nonce)?))
+            }
+            Decryption::AgeIdentities(identities) => {
+                let decryptor = age::Decryptor::new_buffered(BufReader::new(inner))
+                    .map_err(|e| anyhow::anyhow!("Invalid age chunk: {}", e))?;
+                let reader = decryptor.decrypt(identities.iter().map(|i| i as &dyn age::Identity))
+                    .map_err(|e| anyhow::anyhow!("age decryption failed: {}", e))?;
+                Ok(Box::new(reader))
+            }
+        }
+    }
+}
+
+impl<W: Write> ChunkEncoder for age::stream::StreamWriter<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        age::stream::StreamWriter::finish(*self)?;
+        Ok(())
+    }
+}
+
+/// Generates a random per-chunk nonce prefix, hex encoded for the split info
+fn generate_nonce() -> String {
+    let mut nonce = [0u8; NONCE_PREFIX_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    hex::encode(nonce)
+}
+
+fn parse_nonce(nonce_hex: &str) -> Result<[u8; NONCE_PREFIX_SIZE]> {
+    hex::decode(nonce_hex)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid chunk nonce '{}'", nonce_hex))
+}
+
+fn crypto_error(context: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, context.to_string())
+}
+
+/// Writer that encrypts everything written to it in fixed-size authenticated segments
+struct EncryptingWriter<W: Write> {
+    inner: W,
+    encryptor: Option<EncryptorBE32<Aes256Gcm>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    fn new(inner: W, key: &EncryptionKey, nonce_hex: &str) -> Result<Self> {
+        let nonce = parse_nonce(nonce_hex)?;
+        Ok(EncryptingWriter {
+            inner,
+            encryptor: Some(EncryptorBE32::from_aead(key.cipher(), (&nonce).into())),
+            buffer: Vec::with_capacity(SEGMENT_SIZE),
+        })
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Keep at least one byte buffered so the final segment is always emitted by `finish`
+        if self.buffer.len() == SEGMENT_SIZE {
+            let encryptor = self.encryptor.as_mut().ok_or_else(|| crypto_error("Encryption already finished"))?;
+            let segment = encryptor.encrypt_next(self.buffer.as_slice())
+                .map_err(|_| crypto_error("Failed to encrypt chunk segment"))?;
+            self.inner.write_all(&segment)?;
+            self.buffer.clear();
+        }
+        let take = buf.len().min(SEGMENT_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..take]);
+        Ok(take)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> ChunkEncoder for EncryptingWriter<W> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let encryptor = self.encryptor.take().ok_or_else(|| crypto_error("Encryption already finished"))?;
+        let segment = encryptor.encrypt_last(self.buffer.as_slice())
+            .map_err(|_| crypto_error("Failed to encrypt chunk segment"))?;
+        self.inner.write_all(&segment)?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// Reader that decrypts and authenticates segments produced by [`EncryptingWriter`]
+struct DecryptingReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<Aes256Gcm>>,
+    /// Ciphertext read ahead of the current segment (used to detect the last segment)
+    pending: Vec<u8>,
+    plaintext: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    fn new(inner: R, key: &EncryptionKey, nonce_hex: &str) -> Result<Self> {
+        let nonce = parse_nonce(nonce_hex)?;
+        Ok(DecryptingReader {
+            inner,
+            decryptor: Some(DecryptorBE32::from_aead(key.cipher(), (&nonce).into())),
+            pending: Vec::with_capacity(SEGMENT_SIZE + TAG_SIZE + 1),
+            plaintext: Vec::new(),
+            position: 0,
+        })
+    }
+
+    /// Decrypts the next segment into `plaintext`; returns false once the stream is exhausted
+    fn next_segment(&mut self) -> io::Result<bool> {
+        if self.decryptor.is_none() {
+            return Ok(false);
+        }
+        // Read one byte past a full segment to learn whether this is the last one
+        let wanted = SEGMENT_SIZE + TAG_SIZE + 1;
+        while self.pending.len() < wanted {
+            let start = self.pending.len();
+            self.pending.resize(wanted, 0);
+            let read = self.inner.read(&mut self.pending[start..])?;
+            self.pending.truncate(start + read);
+            if read == 0 {
+                break;
+            }
+        }
+
+        let auth_error = || crypto_error("Chunk decryption failed: wrong key or corrupted/tampered data");
+        if self.pending.len() == wanted {
+            let rest = self.pending.split_off(SEGMENT_SIZE + TAG_SIZE);
+            let decryptor = self.decryptor.as_mut().expect("checked above");
+            self.plaintext = decryptor.decrypt_next(self.pending.as_slice()).map_err(|_| auth_error())?;
+            self.pending = rest;
+        } else {
+            let decryptor = self.decryptor.take().expect("checked above");
+            self.plaintext = decryptor.decrypt_last(self.pending.as_slice()).map_err(|_| auth_error())?;
+            self.pending.clear();
+        }
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if !self.next_segment()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.plaintext.len() - self.position);
+        buf[..n].copy_from_slice(&self.plaintext[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,105 @@
+// src/event.rs
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// What a running split reports to its event callback (see [`crate::split_single_file`])
+///
+/// A split reports `Started` first and `Finished` last, unless it fails; [`crate::split_tree_to_sink`] reports
+/// both for every file of the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitEvent {
+    /// Splitting the input called `name` started; `total` is its size in bytes, if known in advance
+    Started { name: String, total: Option<u64> },
+    /// A chunk of the split set is stored: written, or found already stored (e.g., in a chunk pool or by the
+    /// split being resumed). `index` is its position in [`crate::SplitInfo::chunks`], `name` its file name, `key`
+    /// where it is stored in the sink (see [`crate::SplitInfo::chunk_name`]), `size` the bytes stored in its file,
+    /// and `checksum` the tagged checksum of its original content.
+    ChunkWritten { index: usize, name: String, key: String, size: u64, checksum: String },
+    /// `bytes` of the input are split, out of `total` (0 if not known in advance), at the speed `throughput`
+    Progress { bytes: u64, total: u64, throughput: Throughput },
+    /// A human-readable note on what the split does (e.g., where the info file went, or which chunks a resumed
+    /// split keeps)
+    Message(String),
+    /// The split set of the input called `name` is complete: its `chunks` chunks are stored, and its info file
+    /// is saved under `info_name` (see [`crate::SplitInfo::info_name`])
+    Finished { name: String, info_name: String, chunks: usize },
+}
+
+/// How fast a split or restore advances, as measured by a [`ProgressMeter`]
+///
+/// Displays as e.g. "120.0 MB/s, 3 min left".
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Throughput {
+    /// Bytes processed per second, averaged over the last few seconds
+    pub bytes_per_sec: f64,
+    /// Estimated time until the operation is done, if its total size is known and it is advancing
+    pub remaining: Option<Duration>,
+}
+
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+        let mut rate = self.bytes_per_sec;
+        let mut unit = 0;
+        while rate >= 1000.0 && unit < UNITS.len() - 1 {
+            rate /= 1000.0;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}/s", rate, UNITS[unit])?;
+        let Some(remaining) = self.remaining else {
+            return Ok(());
+        };
+        let secs = remaining.as_secs();
+        match secs {
+            0..60 => write!(f, ", {} s left", secs),
+            60..3600 => write!(f, ", {} min left", secs.div_ceil(60)),
+            _ => write!(f, ", {} h {} min left", secs / 3600, secs % 3600 / 60),
+        }
+    }
+}
+
+/// Span of the recent progress the speed is averaged over
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+
+/// Measures the [`Throughput`] of an operation from its progress updates, e.g. those of a restore's
+/// progress callback (splits report it in [`SplitEvent::Progress`])
+#[derive(Debug)]
+pub struct ProgressMeter {
+    started: Instant,
+    /// Recent updates, oldest first, as (time, bytes)
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Default for ProgressMeter {
+    fn default() -> Self {
+        ProgressMeter::new()
+    }
+}
+
+impl ProgressMeter {
+    /// Starts measuring from now
+    pub fn new() -> Self {
+        let started = Instant::now();
+        ProgressMeter { started, samples: VecDeque::from([(started, 0)]) }
+    }
+
+    /// Records that `bytes` out of `total` (0 if not known) are done, and returns the throughput so far
+    pub fn update(&mut self, bytes: u64, total: u64) -> Throughput {
+        let now = Instant::now();
+        // Keep the newest update older than the window, so the window is always covered
+        while self.samples.len() > 1 && now.duration_since(self.samples[1].0) >= THROUGHPUT_WINDOW {
+            self.samples.pop_front();
+        }
+        let &(since, bytes_since) = self.samples.front().unwrap_or(&(self.started, 0));
+        self.samples.push_back((now, bytes));
+        let elapsed = now.duration_since(since).as_secs_f64();
+        if elapsed <= 0.0 {
+            return Throughput::default();
+        }
+        let bytes_per_sec = bytes.saturating_sub(bytes_since) as f64 / elapsed;
+        let remaining = (total > 0 && bytes_per_sec > 0.0)
+            .then(|| Duration::from_secs_f64(total.saturating_sub(bytes) as f64 / bytes_per_sec));
+        Throughput { bytes_per_sec, remaining }
+    }
+}
@@ -0,0 +1,60 @@
+// src/failure.rs
+use std::fmt;
+use std::io;
+
+/// Class of a failed operation, for callers that handle failures differently (e.g., the CLI's exit codes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Content does not match the size or checksum its split set records
+    Verification,
+    /// A chunk file of a split set is not there
+    MissingChunk,
+    /// Reading or writing a file, or a store, failed
+    Io,
+    /// Anything else (e.g., an invalid argument or info file)
+    Other,
+}
+
+impl FailureKind {
+    /// Classifies `error` by its causes: the kind of the outermost [`Failure`] among them, else [`FailureKind::Io`]
+    /// if an I/O error is among them
+    pub fn of(error: &anyhow::Error) -> FailureKind {
+        let failure = error.downcast_ref::<Failure>()
+            .or_else(|| error.chain().find_map(|cause| cause.downcast_ref::<Failure>()));
+        match failure {
+            Some(failure) => failure.kind,
+            None if error.chain().any(|cause| cause.is::<io::Error>()) => FailureKind::Io,
+            None => FailureKind::Other,
+        }
+    }
+}
+
+/// Error of a known [`FailureKind`], found among the causes of errors the library returns
+#[derive(Debug)]
+pub struct Failure {
+    kind: FailureKind,
+    message: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Failure { kind, message: message.into() }
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Whether `error` was caused by something not being found (e.g., a missing chunk file)
+pub(crate) fn is_not_found(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::NotFound))
+}
@@ -0,0 +1,404 @@
+// src/ftp.rs
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use anyhow::{Result, Context};
+
+use crate::{ChunkEncoder, ChunkSink, ChunkSource};
+
+/// Attempts per chunk transfer; each retry resumes where the previous attempt stopped
+const TRANSFER_ATTEMPTS: usize = 3;
+
+/// Socket timeout, so that a stalled server counts as an interrupted transfer
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Chunk storage on an FTP server, using passive mode
+///
+/// Logs in with the user and password from the URL (or the `FTP_PASSWORD` environment variable), and anonymously
+/// if no user is given. An interrupted upload or download is retried on a new connection and resumes where it
+/// stopped (`APPE` / `REST`). A file left on the server by an earlier run is replaced rather than resumed, as
+/// nothing tells whether it is the start of the same chunk. A chunk streamed through [`ChunkSink::create`] goes
+/// straight to the server, and is written again from its start if interrupted.
+pub struct FtpStore {
+    host: String,
+    port: u16,
+    user: String,
+    password: String,
+    /// Remote directory, relative to the login directory unless it starts with `/`
+    root: String,
+    // Control connection, reopened after a failed transfer
+    connection: Mutex<Option<Connection>>,
+}
+
+impl FtpStore {
+    /// Parses an `ftp://[user[:password]@]host[:port]/path` URL and connects
+    ///
+    /// The path is relative to the login directory; start it with a second slash (`ftp://host//srv/drop`) for an
+    /// absolute path.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let rest = url.strip_prefix("ftp://")
+            .ok_or_else(|| anyhow::anyhow!("Invalid FTP URL (expected ftp://user@host/path): {}", url))?;
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let (userinfo, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => (Some(userinfo), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().with_context(|| format!("Invalid port in FTP URL: {}", url))?),
+            None => (host_port, 21),
+        };
+        if host.is_empty() {
+            return Err(anyhow::anyhow!("Invalid FTP URL (missing host): {}", url));
+        }
+        let (user, password) = match userinfo.map(|u| u.split_once(':').map_or((u, None), |(u, p)| (u, Some(p)))) {
+            Some((user, password)) => (user.to_string(), password.map(str::to_string)),
+            None => ("anonymous".to_string(), Some("anonymous@".to_string())),
+        };
+        let password = password.or_else(|| std::env::var("FTP_PASSWORD").ok()).unwrap_or_default();
+
+        FtpStore::connect(host, port, &user, &password, path)
+    }
+
+    /// Logs in to `host` and stores chunks below the remote directory `root` on it
+    pub fn connect(host: &str, port: u16, user: &str, password: &str, root: &str) -> Result<Self> {
+        let connection = Connection::open(host, port, user, password)?;
+        Ok(FtpStore {
+            host: host.to_string(),
+            port,
+            user: user.to_string(),
+            password: password.to_string(),
+            root: root.trim_end_matches('/').to_string(),
+            connection: Mutex::new(Some(connection)),
+        })
+    }
+
+    /// Remote path of `name`, always `/`-separated regardless of the local platform
+    fn remote_path(&self, name: &str) -> String {
+        if self.root.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.root, name)
+        }
+    }
+
+    fn url(&self, name: &str) -> String {
+        format!("ftp://{}/{}", self.host, self.remote_path(name))
+    }
+
+    /// Runs `transfer`, reconnecting and running it again after a network error
+    fn with_retries(&self, transfer: impl FnMut(&mut Connection) -> Result<()>) -> Result<()> {
+        self.retry_on(&mut self.connection.lock().unwrap(), transfer)
+    }
+
+    /// Runs `transfer` on `connection` (opening it first if it is closed), reconnecting and running it again after
+    /// a network error
+    fn retry_on<T>(
+        &self,
+        connection: &mut Option<Connection>,
+        mut transfer: impl FnMut(&mut Connection) -> Result<T>,
+    ) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            let result = match connection.as_mut() {
+                Some(open) => transfer(open),
+                None => Connection::open(&self.host, self.port, &self.user, &self.password)
+                    .and_then(|open| transfer(connection.insert(open))),
+            };
+            match result {
+                Err(e) if e.downcast_ref::<io::Error>().is_some() => {
+                    *connection = None;
+                    if attempt == TRANSFER_ATTEMPTS {
+                        return Err(e);
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// An FTP control connection
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// Directories already created on this connection
+    created_dirs: HashSet<String>,
+}
+
+impl Connection {
+    fn open(host: &str, port: u16, user: &str, password: &str) -> Result<Self> {
+        let stream = TcpStream::connect((host, port))
+            .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+        stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+        stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+        let mut connection = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            created_dirs: HashSet::new(),
+        };
+
+        connection.expect_reply("greeting", &[220])?;
+        match connection.command(&format!("USER {}", user))? {
+            (230, _) => {}
+            (331, _) => connection.expect(&format!("PASS {}", password), &[230, 202])
+                .with_context(|| format!("FTP login as '{}' on {} failed", user, host))?,
+            (_, text) => return Err(anyhow::anyhow!("FTP login as '{}' on {} failed: {}", user, host, text)),
+        }
+        connection.expect("TYPE I", &[200])?;
+        Ok(connection)
+    }
+
+    /// Reads one (possibly multi-line) reply
+    fn read_reply(&mut self) -> Result<(u32, String)> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let code: u32 = line.get(..3).and_then(|code| code.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid FTP reply: {}", line.trim_end()))?;
+        let mut text = line.trim_end().to_string();
+        // A multi-line reply starts with "123-" and ends with a line starting with "123 "
+        if line.as_bytes().get(3) == Some(&b'-') {
+            let last_line_start = format!("{} ", &line[..3]);
+            loop {
+                line.clear();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                text.push('\n');
+                text.push_str(line.trim_end());
+                if line.starts_with(&last_line_start) {
+                    break;
+                }
+            }
+        }
+        Ok((code, text))
+    }
+
+    fn command(&mut self, command: &str) -> Result<(u32, String)> {
+        self.writer.write_all(format!("{}\r\n", command).as_bytes())?;
+        self.read_reply()
+    }
+
+    /// Sends `command` and fails unless the reply has one of the `expected` codes
+    fn expect(&mut self, command: &str, expected: &[u32]) -> Result<()> {
+        let reply = self.command(command)?;
+        // Name only the verb, so that passwords stay out of error messages
+        check_reply(command.split(' ').next().unwrap_or(command), reply, expected)
+    }
+
+    fn expect_reply(&mut self, what: &str, expected: &[u32]) -> Result<()> {
+        let reply = self.read_reply()?;
+        check_reply(what, reply, expected)
+    }
+
+    /// Opens a passive-mode data connection
+    fn data_connection(&mut self) -> Result<TcpStream> {
+        let peer = self.writer.peer_addr()?;
+        let port = match self.command("EPSV")? {
+            // "229 Entering Extended Passive Mode (|||6446|)"
+            (229, text) => text.split('|').nth(3).and_then(|port| port.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("Invalid FTP reply: {}", text))?,
+            _ => {
+                // "227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)"
+                let (code, text) = self.command("PASV")?;
+                check_reply("PASV", (code, text.clone()), &[227])?;
+                let numbers: Vec<u16> = text[3..].split(|c: char| !c.is_ascii_digit())
+                    .filter_map(|number| number.parse().ok())
+                    .collect();
+                match numbers[..] {
+                    [.., high, low] if numbers.len() >= 6 => high * 256 + low,
+                    _ => return Err(anyhow::anyhow!("Invalid FTP reply: {}", text)),
+                }
+            }
+        };
+        // The advertised address is often wrong behind NAT, so connect to the control connection's host
+        let stream = TcpStream::connect_timeout(&SocketAddr::new(peer.ip(), port), SOCKET_TIMEOUT)?;
+        stream.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+        stream.set_write_timeout(Some(SOCKET_TIMEOUT))?;
+        Ok(stream)
+    }
+
+    /// Creates the missing parent directories of `path` one level at a time
+    fn create_parent_dirs(&mut self, path: &str) -> Result<()> {
+        let Some((parent, _)) = path.rsplit_once('/') else {
+            return Ok(());
+        };
+        let ends = parent.match_indices('/').map(|(i, _)| i).chain([parent.len()]);
+        for dir in ends.map(|end| &parent[..end]).filter(|dir| !dir.is_empty()) {
+            if !self.created_dirs.contains(dir) {
+                // Fails if the directory already exists, which is fine; other problems surface when storing the file
+                self.command(&format!("MKD {}", dir))?;
+                self.created_dirs.insert(dir.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Size of the file at `path`, or `None` if there is none
+    fn size(&mut self, path: &str) -> Result<Option<u64>> {
+        match self.command(&format!("SIZE {}", path))? {
+            (213, text) => Ok(text[3..].trim().parse().ok()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Opens a data connection and starts storing what is sent through it at `path` with `verb` (`STOR` or `APPE`)
+    fn start_upload(&mut self, path: &str, verb: &str) -> Result<TcpStream> {
+        let stream = self.data_connection()?;
+        self.expect(&format!("{} {}", verb, path), &[125, 150])?;
+        Ok(stream)
+    }
+
+    /// Uploads `data` to `path`; with `resume` (when retrying an interrupted upload of the same `data`), appends to
+    /// what the earlier attempt stored instead of starting over
+    fn upload(&mut self, path: &str, data: &[u8], resume: bool) -> Result<()> {
+        self.create_parent_dirs(path)?;
+        let offset = match resume {
+            true => self.size(path)?.filter(|&size| size < data.len() as u64).unwrap_or(0) as usize,
+            false => 0,
+        };
+        let verb = if offset > 0 { "APPE" } else { "STOR" };
+        let mut stream = self.start_upload(path, verb)?;
+        stream.write_all(&data[offset..])?;
+        drop(stream); // Closing the data connection marks the end of the file
+        self.expect_reply(verb, &[226, 250])
+    }
+
+    /// Downloads `path`, appending to `data` and resuming after the bytes already in it
+    fn download(&mut self, path: &str, data: &mut Vec<u8>) -> Result<()> {
+        let mut stream = self.data_connection()?;
+        if !data.is_empty() {
+            self.expect(&format!("REST {}", data.len()), &[350])?;
+        }
+        self.expect(&format!("RETR {}", path), &[125, 150])?;
+        stream.read_to_end(data)?;
+        self.expect_reply("RETR", &[226, 250])
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.writer.write_all(b"QUIT\r\n"); // Best effort; the server closes idle connections anyway
+    }
+}
+
+fn check_reply(what: &str, (code, text): (u32, String), expected: &[u32]) -> Result<()> {
+    if expected.contains(&code) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("FTP server rejected {}: {}", what, text))
+    }
+}
+
+impl ChunkSink for FtpStore {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.remote_path(name);
+        // Only a retry resumes, as only then is what is on the server known to be the start of `data`
+        let mut retry = false;
+        self.with_retries(|connection| connection.upload(&path, data, std::mem::replace(&mut retry, true)))
+    }
+
+    /// Sends the chunk straight to the server over a data connection, holding the control connection until the
+    /// chunk is finished
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        let path = self.remote_path(name);
+        let mut connection = self.connection.lock().unwrap();
+        let stream = self.retry_on(&mut connection, |connection| {
+            connection.create_parent_dirs(&path)?;
+            connection.start_upload(&path, "STOR")
+        })?;
+        Ok(Box::new(FtpEntry { connection, data: Some(BufWriter::new(stream)) }))
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        let path = self.remote_path(name);
+        let mut found = false;
+        self.with_retries(|connection| {
+            found = connection.size(&path)?.is_some();
+            Ok(())
+        })?;
+        Ok(found)
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        let path = self.remote_path(name);
+        self.with_retries(|connection| match connection.command(&format!("DELE {}", path))? {
+            (250, _) => Ok(()),
+            (550, _) if connection.size(&path)?.is_none() => Ok(()), // There was nothing to delete
+            reply => check_reply("DELE", reply, &[250]),
+        })
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.url(name)
+    }
+}
+
+/// File being written by [`FtpStore::create`]
+struct FtpEntry<'a> {
+    connection: MutexGuard<'a, Option<Connection>>,
+    /// Data connection, until the file is finished
+    data: Option<BufWriter<TcpStream>>,
+}
+
+impl Write for FtpEntry<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.data.as_mut().unwrap().flush()
+    }
+}
+
+impl ChunkEncoder for FtpEntry<'_> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let data = self.data.take().unwrap();
+        // Closing the data connection marks the end of the file
+        let result = data.into_inner().map_err(|e| e.into_error().into())
+            .and_then(|stream| {
+                drop(stream);
+                self.connection.as_mut().unwrap().expect_reply("STOR", &[226, 250])
+            });
+        if result.is_err() {
+            *self.connection = None;
+        }
+        result
+    }
+}
+
+impl Drop for FtpEntry<'_> {
+    /// Closes the control connection after an unfinished upload, as its state is unknown
+    fn drop(&mut self) {
+        if self.data.take().is_some() {
+            *self.connection = None;
+        }
+    }
+}
+
+impl ChunkSource for FtpStore {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let path = self.remote_path(name);
+        let mut data = Vec::new();
+        self.with_retries(|connection| connection.download(&path, &mut data))?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        let path = self.remote_path(name);
+        let mut size = None;
+        self.with_retries(|connection| {
+            size = connection.size(&path)?;
+            Ok(())
+        })?;
+        Ok(size)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.url(name)
+    }
+}
@@ -0,0 +1,157 @@
+// src/galois.rs
+use std::sync::OnceLock;
+
+/// Generator polynomial x^16 + x^12 + x^3 + x + 1 of GF(2^16), the field PAR2 also uses
+const GENERATOR: u32 = 0x1100B;
+
+/// Number of non-zero elements
+const ORDER: usize = 65535;
+
+/// Logarithm and antilogarithm tables for GF(2^16), built on first use
+struct Tables {
+    log: Vec<u16>,
+    /// Twice as long as needed, so that sums of two logarithms can be looked up without reducing them
+    exp: Vec<u16>,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut log = vec![0u16; ORDER + 1];
+        let mut exp = vec![0u16; 2 * ORDER];
+        let mut x: u32 = 1;
+        for (i, e) in exp.iter_mut().take(ORDER).enumerate() {
+            *e = x as u16;
+            log[x as usize] = i as u16;
+            x <<= 1;
+            if x & 0x10000 != 0 {
+                x ^= GENERATOR;
+            }
+        }
+        exp.copy_within(..ORDER, ORDER);
+        Tables { log, exp }
+    })
+}
+
+pub(crate) fn mul(a: u16, b: u16) -> u16 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    let tables = tables();
+    tables.exp[tables.log[a as usize] as usize + tables.log[b as usize] as usize]
+}
+
+/// Multiplicative inverse of a non-zero element
+pub(crate) fn inv(a: u16) -> u16 {
+    assert!(a != 0, "zero has no inverse");
+    let tables = tables();
+    tables.exp[ORDER - tables.log[a as usize] as usize]
+}
+
+/// `a` raised to the power `n`
+pub(crate) fn pow(a: u16, n: usize) -> u16 {
+    if a == 0 {
+        return u16::from(n == 0);
+    }
+    let tables = tables();
+    tables.exp[(tables.log[a as usize] as usize * n) % ORDER]
+}
+
+/// The element whose logarithm (to the base 2) is `n`
+pub(crate) fn exp(n: usize) -> u16 {
+    tables().exp[n % ORDER]
+}
+
+/// Adds `coefficient * src` to `dst`, treating both as little-endian 16-bit words
+///
+/// `dst` must have an even length; a shorter `src` counts as padded with zeros.
+pub(crate) fn mul_add(coefficient: u16, src: &[u8], dst: &mut [u8]) {
+    if coefficient == 0 {
+        return;
+    }
+    // Products with the low and high byte of every word, so each word takes two lookups
+    let mut low = [0u16; 256];
+    let mut high = [0u16; 256];
+    for b in 0..256u16 {
+        low[b as usize] = mul(coefficient, b);
+        high[b as usize] = mul(coefficient, b << 8);
+    }
+    for (d, s) in dst.chunks_exact_mut(2).zip(src.chunks(2)) {
+        let product = low[s[0] as usize] ^ high[s.get(1).copied().unwrap_or(0) as usize];
+        d[0] ^= product as u8;
+        d[1] ^= (product >> 8) as u8;
+    }
+}
+
+/// Inverts a square matrix by Gauss-Jordan elimination, or returns `None` if it is singular
+pub(crate) fn invert(mut matrix: Vec<Vec<u16>>) -> Option<Vec<Vec<u16>>> {
+    let n = matrix.len();
+    let mut inverse: Vec<Vec<u16>> = (0..n).map(|i| (0..n).map(|j| u16::from(i == j)).collect()).collect();
+    for col in 0..n {
+        let pivot = (col..n).find(|&row| matrix[row][col] != 0)?;
+        matrix.swap(col, pivot);
+        inverse.swap(col, pivot);
+        let scale = inv(matrix[col][col]);
+        for j in 0..n {
+            matrix[col][j] = mul(matrix[col][j], scale);
+            inverse[col][j] = mul(inverse[col][j], scale);
+        }
+        for row in (0..n).filter(|&row| row != col) {
+            let factor = matrix[row][col];
+            if factor != 0 {
+                for j in 0..n {
+                    matrix[row][j] ^= mul(factor, matrix[col][j]);
+                    inverse[row][j] ^= mul(factor, inverse[col][j]);
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Product by shifting and adding, reducing by the generator polynomial
+    fn slow_mul(a: u16, b: u16) -> u16 {
+        let mut product = 0u32;
+        for bit in 0..16 {
+            if b & (1 << bit) != 0 {
+                product ^= u32::from(a) << bit;
+            }
+        }
+        for bit in (16..32).rev() {
+            if product & (1 << bit) != 0 {
+                product ^= GENERATOR << (bit - 16);
+            }
+        }
+        product as u16
+    }
+
+    #[test]
+    fn products_match_polynomial_multiplication() {
+        for (a, b) in [(0, 5), (1, 0xffff), (2, 0x8000), (0x1234, 0xabcd), (0xffff, 0xffff), (3, 7)] {
+            assert_eq!(mul(a, b), slow_mul(a, b), "{:#x} * {:#x}", a, b);
+        }
+        for a in (1..=0xffffu16).step_by(97) {
+            assert_eq!(mul(a, inv(a)), 1);
+            assert_eq!(pow(a, 3), mul(a, mul(a, a)));
+        }
+    }
+
+    #[test]
+    fn inverted_matrix_times_matrix_is_identity() {
+        let n = 5;
+        let matrix: Vec<Vec<u16>> = (0..n).map(|i| (0..n).map(|j| inv((i ^ (n + j)) as u16)).collect()).collect();
+        let inverse = invert(matrix.clone()).unwrap();
+        for (i, row) in inverse.iter().enumerate() {
+            for j in 0..n {
+                let sum = row.iter().zip(&matrix).fold(0, |sum, (&a, column)| sum ^ mul(a, column[j]));
+                assert_eq!(sum, u16::from(i == j), "entry ({}, {})", i, j);
+            }
+        }
+        // A row that is a multiple of another makes the matrix singular
+        assert!(invert(vec![vec![1, 2], vec![3, mul(3, 2)]]).is_none());
+    }
+}
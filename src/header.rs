@@ -0,0 +1,135 @@
+// src/header.rs
+use std::io::Read;
+use anyhow::{Context, Result};
+
+/// First bytes of a chunk file that starts with a [`ChunkHeader`]
+const MAGIC: &[u8; 8] = b"FSPCHUNK";
+
+/// Version of the header layout written
+const VERSION: u8 = 1;
+
+/// Flag set if the chunk's content is compressed
+const FLAG_COMPRESSED: u8 = 1;
+
+/// Flag set if the chunk's content is encrypted
+const FLAG_ENCRYPTED: u8 = 2;
+
+/// Size of the header up to its variable-length fields: magic, version, flags, header length, index and total
+const FIXED_SIZE: usize = 8 + 1 + 1 + 4 + 8 + 8;
+
+/// Largest header accepted when reading, well above what three short strings take up
+const MAX_SIZE: u32 = 1 << 20;
+
+/// Small binary header written in front of each chunk's stored bytes (see [`crate::SplitOptions::chunk_headers`]),
+/// so that a chunk file tells what it is without the info file
+///
+/// It is laid out as the magic bytes "FSPCHUNK", a version byte, a flags byte (1: compressed, 2: encrypted), the
+/// little-endian `u32` length of the whole header, the `u64` index and total count, and the original file name,
+/// codec name and checksum as UTF-8 strings of a little-endian `u16` length each. The header itself is never
+/// compressed or encrypted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkHeader {
+    /// Name of the file the chunk was split from (e.g., "my_file")
+    pub original_filename: String,
+    /// Position of the chunk in the file, starting from 1
+    pub index: u64,
+    /// Number of chunks the file was split into, if known when the chunk was written (it is not for stdin and
+    /// splits by lines or delimiter)
+    pub total: Option<u64>,
+    /// Name of the codec the content is compressed with (e.g., "zstd"), if compressed
+    pub codec: Option<String>,
+    /// Whether the content is encrypted
+    pub encrypted: bool,
+    /// Algorithm-tagged checksum (e.g., "sha256:abcd...") of the chunk's original content
+    pub checksum: String,
+}
+
+impl ChunkHeader {
+    /// Encodes the header, to be stored in front of the chunk's bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let strings = [self.original_filename.as_str(), self.codec.as_deref().unwrap_or_default(), self.checksum.as_str()]
+            .map(|s| &s.as_bytes()[..s.len().min(u16::MAX as usize)]);
+        let len = FIXED_SIZE + strings.iter().map(|s| 2 + s.len()).sum::<usize>();
+        let mut flags = 0;
+        if self.codec.is_some() {
+            flags |= FLAG_COMPRESSED;
+        }
+        if self.encrypted {
+            flags |= FLAG_ENCRYPTED;
+        }
+
+        let mut bytes = Vec::with_capacity(len);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(flags);
+        bytes.extend_from_slice(&(len as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.index.to_le_bytes());
+        bytes.extend_from_slice(&self.total.unwrap_or(0).to_le_bytes());
+        for s in strings {
+            bytes.extend_from_slice(&(s.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(s);
+        }
+        bytes
+    }
+
+    /// Reads the header at the start of `reader`, leaving it at the chunk's stored bytes
+    ///
+    /// Returns `None` if the reader does not start with the magic bytes of a header (a chunk written without one);
+    /// fails if it does but the header is cut short or malformed. Fields added by later versions are skipped.
+    pub fn read_from<R: Read + ?Sized>(reader: &mut R) -> Result<Option<ChunkHeader>> {
+        let mut magic = [0u8; MAGIC.len()];
+        let mut filled = 0;
+        while filled < magic.len() {
+            match reader.read(&mut magic[filled..]) {
+                Ok(0) => return Ok(None),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).context("Failed to read chunk header"),
+            }
+        }
+        if &magic != MAGIC {
+            return Ok(None);
+        }
+
+        let mut fixed = [0u8; FIXED_SIZE - MAGIC.len()];
+        reader.read_exact(&mut fixed).context("Chunk header is cut short")?;
+        let (version, flags) = (fixed[0], fixed[1]);
+        if version == 0 {
+            return Err(anyhow::anyhow!("Unsupported chunk header version {}", version));
+        }
+        let len = u32::from_le_bytes(fixed[2..6].try_into().unwrap());
+        if len > MAX_SIZE || (len as usize) < FIXED_SIZE {
+            return Err(anyhow::anyhow!("Invalid chunk header length {}", len));
+        }
+        let index = u64::from_le_bytes(fixed[6..14].try_into().unwrap());
+        let total = u64::from_le_bytes(fixed[14..22].try_into().unwrap());
+
+        let mut rest = vec![0u8; len as usize - FIXED_SIZE];
+        reader.read_exact(&mut rest).context("Chunk header is cut short")?;
+        let mut fields = rest.as_slice();
+        let mut string = || -> Result<String> {
+            let Some((size, tail)) = fields.split_first_chunk::<2>() else {
+                return Err(anyhow::anyhow!("Chunk header is cut short"));
+            };
+            let size = u16::from_le_bytes(*size) as usize;
+            if tail.len() < size {
+                return Err(anyhow::anyhow!("Chunk header is cut short"));
+            }
+            let (value, tail) = tail.split_at(size);
+            fields = tail;
+            String::from_utf8(value.to_vec()).context("Chunk header holds a name that is not UTF-8")
+        };
+        let original_filename = string()?;
+        let codec = string()?;
+        let checksum = string()?;
+
+        Ok(Some(ChunkHeader {
+            original_filename,
+            index,
+            total: (total > 0).then_some(total),
+            codec: (flags & FLAG_COMPRESSED != 0).then_some(codec),
+            encrypted: flags & FLAG_ENCRYPTED != 0,
+            checksum,
+        }))
+    }
+}
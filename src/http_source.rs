@@ -0,0 +1,122 @@
+// src/http_source.rs
+use std::io::{self, Read};
+use anyhow::{Result, Context};
+use ureq::{Agent, BodyReader};
+
+use crate::serve::percent_decode;
+use crate::{parse_split_info, ChunkSource, SplitInfo};
+
+/// Read-only chunk source that downloads chunks over HTTP(S), e.g. from `file_splitter serve` or any static web server
+///
+/// Every chunk is fetched with its own GET request below the base URL, so restoring with several threads
+/// downloads several chunks at once. A download that breaks off continues where it stopped with a `Range` request.
+pub struct HttpSource {
+    agent: Agent,
+    base_url: String,
+}
+
+impl HttpSource {
+    /// Reads chunks below `base_url` (the URL of the split output directory)
+    pub fn new(base_url: &str) -> Self {
+        HttpSource {
+            agent: Agent::new_with_defaults(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Downloads the info file at `url` and returns it together with a source for its chunks
+    ///
+    /// The info file may sit in its `*_parts` directory (`https://host/backups/my_file_parts/my_file.json`) or
+    /// next to it (`https://host/backups/my_file.json`, as published by `file_splitter serve`).
+    pub fn from_info_url(url: &str) -> Result<(SplitInfo, Self)> {
+        let (dir_url, _) = url.rsplit_once('/')
+            .filter(|(dir_url, _)| dir_url.contains("://"))
+            .ok_or_else(|| anyhow::anyhow!("Invalid info file URL: {}", url))?;
+        let mut content = String::new();
+        Agent::new_with_defaults().get(url).call()
+            .and_then(|response| Ok(response.into_body().into_reader().read_to_string(&mut content)?))
+            .with_context(|| format!("Failed to download restore info file: {}", url))?;
+        let file_info = parse_split_info(url, &content)
+            .with_context(|| format!("Failed to parse restore info file: {}", url))?;
+
+        // Chunk names are relative to the directory that holds the `*_parts` subdirectory
+        let base_url = match dir_url.rsplit_once('/') {
+            Some((parent_url, dir_name)) if percent_decode(dir_name).as_deref() == Some(&file_info.chunks_sub_dir) => parent_url,
+            _ => dir_url,
+        };
+        let source = HttpSource::new(base_url);
+        Ok((file_info, source))
+    }
+
+    fn url(&self, name: &str) -> String {
+        let path: Vec<String> = name.split('/').map(percent_encode).collect();
+        format!("{}/{}", self.base_url, path.join("/"))
+    }
+
+    /// Requests `url` from byte `offset` on; a server that ignores the range would send it from the start again
+    fn get(&self, url: &str, offset: u64) -> Result<BodyReader<'static>> {
+        let response = match offset {
+            0 => self.agent.get(url).call()?,
+            _ => self.agent.get(url).header("Range", format!("bytes={}-", offset)).call()?,
+        };
+        if offset > 0 && response.status().as_u16() != 206 {
+            return Err(anyhow::anyhow!("Cannot resume downloading {}: the server does not support Range requests", url));
+        }
+        Ok(response.into_body().into_reader())
+    }
+}
+
+/// Attempts per chunk download; each retry continues where the previous attempt stopped
+const DOWNLOAD_ATTEMPTS: usize = 3;
+
+/// Body of a chunk download that requests the rest of the chunk again when the connection breaks off
+struct ResumingReader<'a> {
+    source: &'a HttpSource,
+    url: String,
+    body: BodyReader<'static>,
+    /// Bytes of the chunk read so far
+    offset: u64,
+    attempt: usize,
+}
+
+impl Read for ResumingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.body.read(buf) {
+                Ok(read) => {
+                    self.offset += read as u64;
+                    return Ok(read);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if self.attempt == DOWNLOAD_ATTEMPTS => return Err(e),
+                Err(e) => {
+                    tracing::warn!(url = %self.url, offset = self.offset, error = %e, "download broke off, resuming");
+                    self.attempt += 1;
+                    self.body = self.source.get(&self.url, self.offset).map_err(io::Error::other)?;
+                }
+            }
+        }
+    }
+}
+
+/// Escapes everything but unreserved characters in a URL path segment
+fn percent_encode(segment: &str) -> String {
+    segment.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+impl ChunkSource for HttpSource {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let url = self.url(name);
+        let body = self.get(&url, 0)?;
+        Ok(Box::new(ResumingReader { source: self, url, body, offset: 0, attempt: 1 }))
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.url(name)
+    }
+}
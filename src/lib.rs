@@ -1,16 +1,112 @@
 // src/lib.rs
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path; // Removed PathBuf from import
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use anyhow::{Result, Context};
-use sha2::{Sha256, Digest};
-use hex;
-use flate2::{
-    write::GzEncoder,
-    read::GzDecoder,
-    Compression,
+use rayon::prelude::*;
+
+mod archive;
+#[cfg(feature = "async")]
+mod async_api;
+mod batch;
+mod checksum;
+#[cfg(feature = "cloud")]
+mod cloud;
+mod codec;
+mod control;
+mod crypto;
+mod event;
+mod failure;
+#[cfg(feature = "ftp")]
+mod ftp;
+mod galois;
+mod header;
+mod manifest;
+#[cfg(feature = "http")]
+mod http_source;
+mod metadata;
+mod naming;
+mod par2;
+mod parity;
+mod preallocate;
+mod retry;
+#[cfg(feature = "s3")]
+mod s3;
+mod scan;
+mod scripts;
+#[cfg(feature = "http")]
+mod serve;
+#[cfg(feature = "sftp")]
+mod sftp;
+mod space;
+mod split_mode;
+mod spread;
+mod storage;
+mod trailer;
+mod tree;
+mod verify;
+mod volume;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use archive::{directory_split_name, split_directory, split_directory_to_sink, ArchiveFormat};
+#[cfg(feature = "async")]
+pub use async_api::{
+    load_split_info_async, restore_single_file_async, split_single_file_async, AsyncMessageCallback,
+    AsyncProgressCallback, AsyncRestoreOptions, AsyncSplitOptions, CallbackFuture,
 };
+pub use batch::{BatchEntry, BatchIndex};
+pub use checksum::{parse_checksum, ChecksumAlgorithm, ChecksumHasher};
+#[cfg(feature = "cloud")]
+pub use cloud::ObjectStorage;
+pub use codec::{ChunkCodec, ChunkEncoder, CompressionAlgorithm};
+pub use crypto::{Decryption, Encryption, EncryptionAlgorithm, EncryptionInfo, EncryptionKey, KdfParams};
+pub use failure::{Failure, FailureKind};
+use failure::is_not_found;
+#[cfg(feature = "ftp")]
+pub use ftp::FtpStore;
+#[cfg(feature = "http")]
+pub use http_source::HttpSource;
+pub use header::ChunkHeader;
+pub use manifest::{parse_split_info, ManifestFormat};
+pub use metadata::FileMetadata;
+pub use naming::{shell_quote, ChunkNaming};
+use naming::{ChunkNamer, SORTABLE_CHUNK_COUNT};
+pub use par2::write_par2;
+pub use parity::{repair_split_set, ParityChunk, ParityInfo, RepairReport};
+use parity::{ParityEncoder, ParityRecovery};
+pub use retry::RetryPolicy;
+#[cfg(feature = "s3")]
+pub use s3::S3Store;
+pub use scan::{rebuild_split_info, restore_from_chunk_dir, ChunkDirRestoreReport};
+pub use scripts::write_restore_scripts;
+#[cfg(feature = "http")]
+pub use serve::serve_directory;
+#[cfg(feature = "sftp")]
+pub use sftp::SftpStore;
+pub use space::{available_space, check_combined_free_space, check_free_space};
+pub use split_mode::{escape_delimiter, parse_delimiter, parse_size, SizePreset, SplitMode};
+pub use spread::{SpreadPolicy, SpreadSink};
+use split_mode::ChunkBoundary;
+pub use control::{PauseGate, RateLimiter};
+pub use event::{ProgressMeter, SplitEvent, Throughput};
+use control::Checkpoint;
+pub use storage::{open_store, ChunkSink, ChunkSource, ChunkStore, DedupIndex, LocalDir, MemoryStore, Placement};
+use storage::{SyncedSink, TrackingSink};
+use trailer::encode_info_trailer;
+pub use trailer::read_info_trailer;
+pub use tree::{split_tree, split_tree_to_sink, TreeFile, TreeIndex, TreeLink};
+pub use verify::{compare_file, verify_split_set, ChunkIssue, ChunkProblem, ChunkRange, CompareReport, VerifyReport};
+pub use volume::{VolumeSink, VolumeSource};
+#[cfg(feature = "watch")]
+pub use watch::watch_directory;
+use codec::StoredEncoder;
 
 /// Information for a single chunk after file splitting
 #[derive(Serialize, Deserialize, Debug, Clone)] // Added Clone for GUI state management
@@ -19,27 +115,614 @@ pub struct ChunkInfo {
     pub chunk_filename: String,
     /// Actual size of this chunk in bytes (if compressed, this is the compressed size)
     pub chunk_size: u64,
-    /// SHA256 checksum of the original (uncompressed) content of this chunk (optional, for finer-grained verification)
+    /// Size of the original (uncompressed) content of this chunk. Info files written before this field
+    /// existed only hold fixed-size chunks of `chunk_limit` bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_size: Option<u64>,
+    /// Algorithm-tagged checksum (e.g., "sha256:abcd...") of the original (uncompressed) content of this chunk
+    /// (optional, for finer-grained verification)
     pub chunk_checksum: Option<String>,
+    /// Hex-encoded nonce prefix this chunk was encrypted with (only present for encrypted split sets)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Storage key of an identical chunk stored earlier (e.g., "other_parts/other-003") that holds this chunk's
+    /// data, if it was not stored separately (see [`SplitOptions::dedup`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_as: Option<String>,
+    /// Index into [`SplitInfo::destinations`] of the directory this chunk was spread to, if the chunks were
+    /// spread across several (see [`SpreadSink`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<usize>,
 }
 
+/// Version of the info file format written by this version of the library. Info files of a later version are
+/// rejected when read, as they may hold what this version would silently misread.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Number the chunk file names start from unless told otherwise (e.g., "my_file-001")
+pub const DEFAULT_CHUNK_START_INDEX: u64 = 1;
+
 /// Split information for an original file
 #[derive(Serialize, Deserialize, Debug, Clone)] // Added Clone for GUI state management
 pub struct SplitInfo {
+    /// Version of the info file format (see [`FORMAT_VERSION`]). Info files written before this field existed
+    /// are version 1.
+    #[serde(default = "first_format_version", deserialize_with = "supported_format_version")]
+    pub format_version: u32,
+    /// Algorithm-tagged checksum of the info file's own content, written along with it and checked whenever it
+    /// is read, so that a truncated or damaged info file is rejected before its chunk list is trusted. Info files
+    /// written before this field existed have none and are read unchecked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_checksum: Option<String>,
     /// Original filename
     pub original_filename: String,
     /// Total size of the original file in bytes
     pub original_file_size: u64,
     /// Maximum size limit set for each chunk during splitting (bytes)
     pub chunk_limit: u64,
+    /// Where the chunk boundaries were placed. Info files written before this field existed always split by bytes.
+    #[serde(default)]
+    pub split_mode: SplitMode,
+    /// Maximum number of lines per chunk, if limited (only with [`SplitMode::Lines`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_lines: Option<u64>,
+    /// Record delimiter every chunk but the last ends with, written with backslash escapes (only with
+    /// [`SplitMode::Delimiter`]; see [`parse_delimiter`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
     /// Name of the subdirectory containing all chunks for this file (e.g., "my_file_parts")
     pub chunks_sub_dir: String,
+    /// Shared content-addressed directory the chunks are stored in instead of `chunks_sub_dir` (e.g., "pool").
+    /// Chunk file names are then derived from the SHA-256 of the stored bytes (e.g., "ab/abcd...").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_pool: Option<String>,
     /// Detailed list of all chunks
     pub chunks: Vec<ChunkInfo>,
-    /// SHA256 checksum of the original file
+    /// Number of digits the chunk numbers in the chunk file names are zero-padded to, chosen from the expected
+    /// chunk count so that the files sort in order by name (e.g., 4 for "my_file-0001" of 1000 to 9999 chunks).
+    /// Info files written before this field existed always padded to 3.
+    #[serde(default = "default_chunk_index_width")]
+    pub chunk_index_width: usize,
+    /// Number of the first chunk in the chunk file names (e.g., 0 for "my_file-000"), the chunks being numbered on
+    /// from it in order. Info files written before this field existed always numbered from 1.
+    #[serde(default = "default_chunk_start_index")]
+    pub chunk_start_index: u64,
+    /// How the chunk files are named (the names themselves are recorded with each chunk)
+    #[serde(default, skip_serializing_if = "ChunkNaming::is_default")]
+    pub chunk_naming: ChunkNaming,
+    /// Base name of the chunk files, if not the default of `chunk_naming` (see [`SplitOptions::chunk_prefix`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_prefix: Option<String>,
+    /// Extension ending the chunk file names, without the dot (e.g., "part" for "my_file-001.part"), if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_extension: Option<String>,
+    /// Whether the chunk files are stored as they are and sort in order by name, so that joining them with `cat`
+    /// in the chunk directory restores the original file without this tool (see [`SplitInfo::cat_command`])
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cat_compatible: bool,
+    /// Directories the chunks were spread across (see [`SpreadSink`]), each chunk recording its own (see
+    /// [`ChunkInfo::destination`]). The first holds the info file and everything else; empty if all chunks are
+    /// stored next to the info file. A directory listed once for each of several volumes is where removable volumes
+    /// were mounted in turn (see [`SplitInfo::spans_volumes`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<String>,
+    /// Algorithm-tagged checksum of the original file (e.g., "sha256:abcd...")
     pub original_checksum: String,
+    /// Hash algorithm the file was split with; also used to verify untagged checksums from older info files.
+    /// Info files written before this field existed always used SHA256.
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumAlgorithm,
     /// Whether the split sub-files were compressed
     pub is_compressed: bool,
+    /// Algorithm used to compress the split sub-files (only meaningful when `is_compressed` is true).
+    /// Info files written before this field existed were always Gzip compressed.
+    #[serde(default)]
+    pub compression_algorithm: CompressionAlgorithm,
+    /// Name of the user-supplied [`ChunkCodec`] the sub-files were compressed with, if not a built-in algorithm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_codec: Option<String>,
+    /// Encryption parameters, if the split sub-files were encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionInfo>,
+    /// Reed-Solomon parity chunks to rebuild missing or damaged chunks from, if any were written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parity: Option<ParityInfo>,
+    /// Whether every chunk file starts with a [`ChunkHeader`], which restoring strips again
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub chunk_headers: bool,
+    /// Whether the last chunk file holds this info file behind the chunk's `chunk_size` bytes (see
+    /// [`read_info_trailer`])
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub info_trailer: bool,
+    /// When every chunk was last found intact (e.g., by [`repair_split_set`]), if ever
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_verified: Option<chrono::DateTime<chrono::Utc>>,
+    /// Archive format the content was bundled in, if it is a directory tree (see [`split_directory_to_sink`]);
+    /// restoring unpacks it into a directory named `original_filename`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<ArchiveFormat>,
+    /// Path of the file in the directory tree it was split with, starting with the tree's own directory
+    /// (e.g., "photos/2024/img.jpg"; see [`split_tree_to_sink`]). Restoring recreates this path below the output
+    /// directory; without it the file is restored straight into the output directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Permissions, modification time and extended attributes of the original file, set again on the restored
+    /// file. Not recorded for content that was not read from a file (e.g., stdin).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<FileMetadata>,
+    /// Format the info file is written in, which names its extension (see [`SplitInfo::info_name`]). It is not
+    /// stored in the info file itself, but set from the format it was read in (see [`parse_split_info`]).
+    #[serde(skip)]
+    pub manifest_format: ManifestFormat,
+    /// Extension of the info file if it is not the one of `manifest_format` (e.g., "fsmeta.json"; see
+    /// [`SplitOptions::info_extension`]). Like the format, it is not stored but set from the info file's name.
+    #[serde(skip)]
+    pub info_extension: Option<String>,
+}
+
+fn first_format_version() -> u32 {
+    1
+}
+
+fn default_chunk_index_width() -> usize {
+    naming::MIN_INDEX_WIDTH
+}
+
+fn default_chunk_start_index() -> u64 {
+    DEFAULT_CHUNK_START_INDEX
+}
+
+/// Reads a format version, failing if it is later than [`FORMAT_VERSION`]
+fn supported_format_version<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if version > FORMAT_VERSION {
+        return Err(serde::de::Error::custom(format!(
+            "info file format version {} is not supported (this version of file_splitter reads up to version {}); \
+             upgrade file_splitter to restore it",
+            version,
+            FORMAT_VERSION
+        )));
+    }
+    Ok(version)
+}
+
+impl SplitInfo {
+    /// Returns the built-in compression algorithm the chunks were written with, or `None` if they are
+    /// stored as-is or were written with a custom codec
+    pub fn compression(&self) -> Option<CompressionAlgorithm> {
+        if self.is_compressed && self.custom_codec.is_none() {
+            Some(self.compression_algorithm)
+        } else {
+            None
+        }
+    }
+
+    /// Where restoring into `output_dir` puts the file (or directory tree, see [`SplitInfo::archive`])
+    pub fn restored_path(&self, output_dir: &Path) -> PathBuf {
+        output_dir.join(self.path.as_deref().unwrap_or(&self.original_filename))
+    }
+
+    /// Storage key of the split set's info file, relative to the split output directory
+    /// (e.g., "my_file_parts/my_file.json", or "my_file_parts/my_file.toml" in TOML)
+    pub fn info_name(&self) -> String {
+        let extension = self.info_extension.as_deref().unwrap_or(self.manifest_format.extension());
+        format!("{}/{}.{}", self.chunks_sub_dir, self.original_filename, extension)
+    }
+
+    /// Shell command that restores the original file without this tool when run in the chunk directory (e.g.,
+    /// `cat my_file-[0-9]* > my_file`), if the split is cat-compatible (see `cat_compatible`)
+    pub fn cat_command(&self) -> Option<String> {
+        if !self.cat_compatible {
+            return None;
+        }
+        let base = self.chunk_prefix.as_deref().unwrap_or_else(|| self.chunk_naming.default_prefix(&self.original_filename));
+        let extension = self.chunk_extension.as_ref().map_or(String::new(), |extension| naming::shell_quote(&format!(".{}", extension)));
+        Some(format!(
+            "cat {}{}{} > {}",
+            naming::shell_quote(base),
+            self.chunk_naming.cat_pattern(self.chunks.len()),
+            extension,
+            naming::shell_quote(&self.original_filename)
+        ))
+    }
+
+    /// Storage key of `chunk`, relative to the split output directory (e.g., "my_file_parts/my_file-001")
+    pub fn chunk_name(&self, chunk: &ChunkInfo) -> String {
+        chunk_key(self.chunk_pool.as_deref().unwrap_or(&self.chunks_sub_dir), chunk)
+    }
+
+    /// Root directory holding the chunk file of `chunk` for a split set whose info file is below `input_root_dir`:
+    /// the directory the chunk was spread to (see `destinations`), or `input_root_dir` itself
+    pub fn chunk_root(&self, input_root_dir: &Path, chunk: &ChunkInfo) -> PathBuf {
+        match chunk.destination.and_then(|index| self.destinations.get(index).filter(|_| index > 0)) {
+            Some(destination) => PathBuf::from(destination),
+            None => input_root_dir.to_path_buf(),
+        }
+    }
+
+    /// Whether the chunks span removable volumes mounted one after another at the same directory (see
+    /// [`VolumeSink`]), so that restoring asks for each volume in turn (see [`VolumeSource`])
+    pub fn spans_volumes(&self) -> bool {
+        self.destinations.len() > 1 && self.destinations.iter().all(|destination| *destination == self.destinations[0])
+    }
+
+    /// Whether the chunk file of the chunk at `index` holds the info file behind the chunk's bytes
+    pub(crate) fn has_info_trailer(&self, index: usize) -> bool {
+        self.info_trailer && index + 1 == self.chunks.len()
+    }
+
+    /// Whether a chunk file of `size` bytes holds the chunk at `index` as recorded, going by its size
+    pub(crate) fn stored_size_matches(&self, index: usize, size: u64) -> bool {
+        let chunk_size = self.chunks[index].chunk_size;
+        size == chunk_size || (self.has_info_trailer(index) && size > chunk_size)
+    }
+
+    /// Total size of the chunk files this split set stored, not counting parity chunks
+    ///
+    /// Chunks shared with an earlier split set (see [`ChunkInfo::stored_as`]) are not counted, and identical
+    /// chunks in a pool only once.
+    pub fn stored_size(&self) -> u64 {
+        let mut names = std::collections::HashSet::new();
+        self.chunks.iter()
+            .filter(|chunk| chunk.stored_as.is_none() && names.insert(self.chunk_name(chunk)))
+            .map(|chunk| chunk.chunk_size)
+            .sum()
+    }
+
+    /// Checks that the split info is consistent: a plain file name, chunk names that stay below the output
+    /// directory, chunk sizes that add up to the file size, and checksums and a delimiter in a known format
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| anyhow::anyhow!("Invalid split info for '{}': {}", self.original_filename, reason);
+        if Path::new(&self.original_filename).file_name().and_then(|n| n.to_str()) != Some(&self.original_filename) {
+            return Err(invalid("the original file name is not a plain file name".to_string()));
+        }
+        if let Some(path) = &self.path {
+            if !is_relative_key(path) || path.rsplit('/').next() != Some(&self.original_filename) {
+                return Err(invalid(format!("'{}' is not a relative path ending with the original file name", path)));
+            }
+        }
+        if self.chunks.is_empty() {
+            return Err(invalid("it lists no chunks".to_string()));
+        }
+        for dir in std::iter::once(&self.chunks_sub_dir).chain(&self.chunk_pool) {
+            if !is_relative_key(dir) {
+                return Err(invalid(format!("'{}' is not a relative directory", dir)));
+            }
+        }
+
+        let mut names = std::collections::HashSet::new();
+        for chunk in &self.chunks {
+            let name = self.chunk_name(chunk);
+            if !is_relative_key(&name) {
+                return Err(invalid(format!("chunk '{}' is not a relative path", name)));
+            }
+            // Pooled and deduplicated chunks may legitimately be shared
+            if self.chunk_pool.is_none() && chunk.stored_as.is_none() && !names.insert(name.clone()) {
+                return Err(invalid(format!("chunk '{}' is listed twice", name)));
+            }
+            if chunk.destination.is_some_and(|index| index >= self.destinations.len()) {
+                return Err(invalid(format!("chunk '{}' was spread to a directory that is not listed", name)));
+            }
+            if let Some(checksum) = &chunk.chunk_checksum {
+                parse_checksum(checksum, self.checksum_algorithm).map_err(|e| invalid(format!("{:#}", e)))?;
+            }
+        }
+        parse_checksum(&self.original_checksum, self.checksum_algorithm).map_err(|e| invalid(format!("{:#}", e)))?;
+
+        match self.chunks.iter().map(|chunk| chunk.original_size).sum::<Option<u64>>() {
+            Some(total) if total != self.original_file_size => {
+                return Err(invalid(format!("the chunks add up to {} bytes instead of {}", total, self.original_file_size)));
+            }
+            Some(_) => {}
+            // Older info files do not record chunk sizes, but hold fixed-size chunks of `chunk_limit` bytes
+            None => {
+                let expected = self.original_file_size.checked_div(self.chunk_limit).map(|_| self.original_file_size.div_ceil(self.chunk_limit).max(1));
+                if expected != Some(self.chunks.len() as u64) {
+                    return Err(invalid(format!(
+                        "{} chunks cannot hold {} bytes in chunks of {} bytes",
+                        self.chunks.len(),
+                        self.original_file_size,
+                        self.chunk_limit
+                    )));
+                }
+            }
+        }
+
+        if let Some(delimiter) = &self.delimiter {
+            parse_delimiter(delimiter).map_err(|e| invalid(format!("{:#}", e)))?;
+        }
+        if let Some(parity) = &self.parity {
+            if let Some(chunk) = self.chunks.iter().find(|chunk| chunk.chunk_size > parity.shard_size) {
+                return Err(invalid(format!("chunk '{}' is larger than its parity chunks", chunk.chunk_filename)));
+            }
+            for parity_chunk in &parity.chunks {
+                if !is_relative_key(&parity_chunk.chunk_filename) {
+                    return Err(invalid(format!("parity chunk '{}' is not a relative path", parity_chunk.chunk_filename)));
+                }
+                parse_checksum(&parity_chunk.chunk_checksum, ChecksumAlgorithm::Sha256).map_err(|e| invalid(format!("{:#}", e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the codec the chunks were written with (built-in or custom), or `None` if uncompressed
+    pub fn codec_name(&self) -> Option<&str> {
+        if !self.is_compressed {
+            None
+        } else if let Some(name) = &self.custom_codec {
+            Some(name)
+        } else {
+            Some(self.compression_algorithm.name())
+        }
+    }
+}
+
+/// Options controlling how a file is split
+#[derive(Clone, Copy, Default)]
+pub struct SplitOptions<'a> {
+    /// Codec used to compress the split sub-files (a [`CompressionAlgorithm`] or a custom [`ChunkCodec`]),
+    /// or `None` to store them uncompressed. On Linux, uncompressed and unencrypted chunks of a file split by bytes
+    /// into a local directory are copied by the kernel (see [`ChunkSink::copy_from`]), and only read to hash them.
+    pub codec: Option<&'a dyn ChunkCodec>,
+    /// How to encrypt the split sub-files (AES-256-GCM key or age recipients), or `None` to store them in the clear.
+    /// Password-derived keys (see [`EncryptionKey::from_password`]) also record their salt and Argon2id parameters.
+    pub encryption: Option<&'a Encryption>,
+    /// Hash algorithm for the chunk and whole-file checksums
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// Where chunk boundaries may fall; [`SplitMode::Lines`] ends every chunk on a newline
+    pub split_mode: SplitMode,
+    /// Maximum number of lines per chunk with [`SplitMode::Lines`], in addition to the size limit
+    pub max_lines: Option<u64>,
+    /// Record delimiter for [`SplitMode::Delimiter`] (e.g., `b"\0"`); every chunk but the last ends right after one
+    pub delimiter: Option<&'a [u8]>,
+    /// Directory (relative to the output root, e.g. "pool") to store chunks in by content instead of in the
+    /// file's own `*_parts` directory. Split sets sharing a pool store identical chunks only once. Encrypted
+    /// chunks get a fresh nonce every time, so they are never shared.
+    pub chunk_pool: Option<&'a str>,
+    /// Index of the chunks already stored in the same sink, e.g. by the other files of a batch. A chunk identical
+    /// to one of them is not stored again; the info file refers to the earlier chunk instead, so the split sets
+    /// must be kept together. Like the pool, this only finds unencrypted chunks.
+    pub dedup: Option<&'a DedupIndex>,
+    /// Number of Reed-Solomon parity chunks to write alongside the data chunks (0 writes none). Restoring
+    /// rebuilds up to this many missing or damaged chunks from them. The parity chunks are as large as the
+    /// largest stored chunk, and each is held in memory while splitting.
+    pub parity: usize,
+    /// Where an interrupted split of the same input into the same sink stored its chunks (usually the sink
+    /// itself), to resume it. While splitting, a journal of the chunks stored so far is saved every few seconds
+    /// next to them; a resumed split keeps every journaled chunk that is still stored with its recorded size and
+    /// whose original content still matches its checksum, and encodes and stores only the others. Splits with
+    /// different settings, or with parity chunks (which need the stored bytes of every chunk), cannot resume.
+    pub resume_from: Option<&'a dyn ChunkSource>,
+    /// Where an earlier split of the same input into the same sink may be stored (usually the sink itself), to
+    /// skip splitting again if nothing changed. If its info file records the same size, whole-file checksum and
+    /// settings, and every chunk is still stored with its recorded size, nothing is written. Only files are
+    /// checked, as they can be hashed before splitting (which then reads them twice); a new password-derived key
+    /// has a new salt, so a split encrypted with a password always runs again.
+    pub skip_unchanged: Option<&'a dyn ChunkSource>,
+    /// Whether an existing split set of the input in the sink may be replaced. Otherwise the split fails up front
+    /// if the info file or first chunk file it would write is already stored, unless it resumes (see
+    /// `resume_from`) or replaces a split set that is out of date (see `skip_unchanged`).
+    pub overwrite: bool,
+    /// Whether every chunk, parity chunk and info file is flushed to the disk, with the directory holding it,
+    /// before the split goes on (see [`ChunkSink::sync`]), so that a drive removed right after the split (e.g., a
+    /// USB stick) holds all of it. Slower, especially with many small chunks.
+    pub fsync: bool,
+    /// Whether a symbolic link is split as the file it points to. Otherwise splitting a link fails, and
+    /// [`split_directory_to_sink`] and [`split_tree_to_sink`] keep links as links.
+    pub follow_symlinks: bool,
+    /// Map each file into memory and hand its chunks to the hasher and codec straight from the mapping, instead of
+    /// reading them into buffers, which saves the read calls and copies. Only files split from the local disk on
+    /// 64-bit systems are mapped, and chunks streamed into their files (see `threads`) are still copied through a
+    /// small buffer. The files must not be changed while they are split: truncating a mapped file crashes the process.
+    pub mmap: bool,
+    /// Number of chunks hashed, compressed and encrypted concurrently (0 or 1 encodes one chunk at a time;
+    /// [`available_threads`] gives one worker per CPU core). Reading and writing always overlap with encoding.
+    /// Each worker holds one chunk in memory, plus a few chunks buffered between the pipeline stages, so peak
+    /// memory is roughly `(threads + 4) * size_limit`. Chunks that would not fit into memory that way are streamed
+    /// into their files through a small buffer instead, one at a time, unless they are stored in a pool,
+    /// deduplicated, protected by parity chunks or resumed, which need them in memory. Chunks over 64 MiB of a
+    /// local file cut by bytes are always streamed, `threads` at a time, each worker reading its own chunk.
+    pub threads: usize,
+    /// Bytes the chunks held in memory may take up at most (e.g., on a NAS with little memory). Fewer chunks are
+    /// then encoded concurrently than `threads` asks for, and chunks are streamed into their files if not even one
+    /// worker fits; a split that needs its chunks in memory (see `threads`) fails instead. `None` sets no limit.
+    pub max_memory: Option<u64>,
+    /// Write a [`ChunkHeader`] in front of each chunk's stored bytes, naming the file, the chunk's position and
+    /// checksum, and how it is encoded, so that a stray chunk can be identified and a set restored from its chunks
+    /// alone (see [`restore_from_chunk_dir`]). The header is stored in the clear, also for encrypted chunks, and
+    /// chunks with headers are held in memory (see `threads`) and never shared through a pool or deduplication,
+    /// as no two of them are identical.
+    pub chunk_headers: bool,
+    /// Append the info file to the last chunk file as well (see [`read_info_trailer`]), so that the chunks hold
+    /// everything needed to restore the file even if the info file is lost. The last chunk is held in memory until
+    /// the info file is complete, so chunks are not streamed (see `threads`); a split into a pool, deduplicated or
+    /// resumed cannot have a trailer.
+    pub info_trailer: bool,
+    /// Format to write the info file in. The trailer (see `info_trailer`) is always JSON.
+    pub manifest_format: ManifestFormat,
+    /// Extension to give the info file instead of the one of `manifest_format` (e.g., "fsmeta.json"), so that
+    /// info files are not taken for data files of the same format. Ending it with the format's extension keeps
+    /// the format recognizable by the name; otherwise it is told by the content.
+    pub info_extension: Option<&'a str>,
+    /// How the chunk files are named (e.g., "my_file-001" or "my_file.001"). Chunks stored in a pool are named
+    /// by their content instead.
+    pub chunk_naming: ChunkNaming,
+    /// Base name of the chunk files instead of the original file name (e.g., "part" for "part-001"), or of "x"
+    /// with [`ChunkNaming::Gnu`]
+    pub chunk_prefix: Option<&'a str>,
+    /// Extension to end the chunk file names with (e.g., "part" for "my_file-001.part"), so that they are not
+    /// taken for truncated copies of the original file. Restoring needs no telling, as the info file records the
+    /// chunk names; restoring from a chunk directory alone strips it.
+    pub chunk_extension: Option<&'a str>,
+    /// Number of the first chunk in the chunk file names (e.g., 0 for "my_file-000", as some tools expect), or
+    /// `None` to number from [`DEFAULT_CHUNK_START_INDEX`]. Chunks named like GNU split's are always lettered
+    /// from "aa".
+    pub chunk_start_index: Option<u64>,
+    /// Whether the chunks must be joinable with `cat` (e.g., `cat my_file-* > my_file`), which the info file then
+    /// records: the split fails up front if they would be compressed, encrypted, have headers or an info file
+    /// trailer, or go into a pool, and chunk numbers are padded to 6 digits if the chunk count is not known up
+    /// front, so that the files sort in order by name. Implied by [`ChunkNaming::Gnu`].
+    pub cat_compatible: bool,
+    /// Flag to abort the split with: once it is set (e.g., from another thread), the split stops after the buffer
+    /// or chunk at hand and fails, and every chunk file and journal it stored is removed again. A batch whose files
+    /// share a [`DedupIndex`] should be aborted as a whole, as the index may refer to the removed chunks.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Gate to pause the split with: while it is paused, the split waits after the buffer or chunk at hand
+    pub pause: Option<&'a PauseGate>,
+    /// Limit on the bytes per second read from the input, which the chunks are written at as well (compression
+    /// aside). A limiter shared by a batch of splits limits them together.
+    pub rate_limit: Option<&'a RateLimiter>,
+    /// How chunk and info file writes that fail with an I/O error are tried again. A chunk streamed into its file
+    /// (see `threads`) is read again for that, unless it comes from a stream, which makes it fail at once.
+    pub retry: RetryPolicy,
+}
+
+impl<'a> SplitOptions<'a> {
+    fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint { cancel: self.cancel, pause: self.pause, rate_limit: self.rate_limit }
+    }
+
+    /// Base name of the chunk files of `filename`: `chunk_prefix`, else the default of `chunk_naming`
+    fn chunk_base<'s>(&'s self, filename: &'s str) -> &'s str {
+        self.chunk_prefix.unwrap_or_else(|| self.chunk_naming.default_prefix(filename))
+    }
+
+    /// Whether the chunks must be joinable with `cat`, as asked for or as chunks named like GNU split's always are
+    fn is_cat_compatible(&self) -> bool {
+        self.cat_compatible || self.chunk_naming == ChunkNaming::Gnu
+    }
+
+    /// Names the chunks of a split of `filename` into `chunk_count` chunks, if known
+    fn chunk_namer<'s>(&'s self, filename: &'s str, chunk_count: Option<u64>) -> ChunkNamer<'s> {
+        let chunk_count = chunk_count.or(self.is_cat_compatible().then_some(SORTABLE_CHUNK_COUNT));
+        ChunkNamer::new(self.chunk_base(filename), self.chunk_naming, chunk_count, self.start_index(), self.chunk_file_extension())
+    }
+
+    /// Number of the first chunk in the chunk file names
+    fn start_index(&self) -> u64 {
+        self.chunk_start_index.unwrap_or(DEFAULT_CHUNK_START_INDEX)
+    }
+
+    /// Extension the chunk file names end with, without a leading dot, if any
+    fn chunk_file_extension(&self) -> Option<&'a str> {
+        self.chunk_extension.map(|extension| extension.trim_start_matches('.'))
+    }
+
+    /// Extension the info file is given: `info_extension` without a leading dot, else that of `manifest_format`
+    fn info_file_extension(&self) -> &str {
+        match self.info_extension {
+            Some(extension) => extension.trim_start_matches('.'),
+            None => self.manifest_format.extension(),
+        }
+    }
+}
+
+/// Number of CPU cores available to the process (1 if it cannot be told), a good value for the `threads` of
+/// [`SplitOptions`] and [`RestoreOptions`] when the machine is not busy otherwise
+pub fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Chunk size that splits `file_size` bytes into exactly `parts` chunks
+///
+/// Every chunk but the last gets the returned size. Fails if no chunk size gives exactly `parts` chunks,
+/// which only happens for tiny files (e.g., 10 bytes cannot be split into 6 parts this way).
+pub fn size_limit_for_parts(file_size: u64, parts: u64) -> Result<u64> {
+    if parts == 0 {
+        return Err(anyhow::anyhow!("The number of parts must be at least 1"));
+    }
+    let size_limit = file_size.div_ceil(parts).max(1);
+    let actual_parts = file_size.div_ceil(size_limit).max(1); // An empty file still gets one (empty) chunk
+    if actual_parts != parts {
+        return Err(anyhow::anyhow!(
+            "Cannot split {} bytes into exactly {} parts (the closest is {} parts of {} bytes)",
+            file_size,
+            parts,
+            actual_parts,
+            size_limit
+        ));
+    }
+    Ok(size_limit)
+}
+
+/// Chunks that splitting the file at `file_path` would produce, without writing anything
+///
+/// Only the size limit and the split mode settings of `options` matter. With [`SplitMode::Bytes`] the chunks
+/// follow from the file size alone; the other modes read the whole file to find where the chunks end. Lengths
+/// are of the original content: compression makes the stored chunk files smaller, and encryption slightly larger.
+/// Like splitting, planning the split of a symbolic link fails unless [`SplitOptions::follow_symlinks`] is set.
+pub fn plan_split(file_path: &Path, size_limit: u64, options: &SplitOptions) -> Result<Vec<ChunkRange>> {
+    check_symlink(file_path, options)?;
+    let file = File::open(file_path)
+        .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
+    let filename_str = file_path.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid filename: {}", file_path.display()))?;
+    if options.split_mode != SplitMode::Bytes {
+        return plan_split_reader(BufReader::new(file), filename_str, size_limit, options);
+    }
+    ChunkBoundary::new(options)?;
+    check_size_limit(size_limit)?;
+    let file_size = file.metadata()?.len();
+    // An empty file still gets one (empty) chunk
+    let chunk_count = file_size.div_ceil(size_limit).max(1);
+    let namer = options.chunk_namer(filename_str, Some(chunk_count));
+    Ok((0..chunk_count).map(|index| {
+        let offset = index * size_limit;
+        ChunkRange {
+            index: index as usize,
+            chunk_filename: namer.name(index as usize + 1),
+            offset,
+            length: size_limit.min(file_size - offset),
+        }
+    }).collect())
+}
+
+/// Chunks that splitting everything read from `reader` as `name` would produce, without writing anything
+///
+/// Reads `reader` to the end; see [`plan_split`].
+pub fn plan_split_reader<R: Read + Send>(reader: R, name: &str, size_limit: u64, options: &SplitOptions) -> Result<Vec<ChunkRange>> {
+    if name.is_empty() || Path::new(name).file_name().and_then(|n| n.to_str()) != Some(name) {
+        return Err(anyhow::anyhow!("Invalid name for split input: '{}'", name));
+    }
+    let boundary = ChunkBoundary::new(options)?;
+    check_size_limit(size_limit)?;
+    thread::scope(|scope| {
+        let (read_tx, read_rx) = mpsc::sync_channel::<Result<(String, Cow<[u8]>)>>(PIPELINE_DEPTH);
+        let namer = options.chunk_namer(name, None);
+        scope.spawn(move || read_chunks(reader, size_limit, boundary, namer, None, Checkpoint::default(), read_tx));
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        for chunk in read_rx {
+            let (chunk_filename, data) = chunk?;
+            let length = data.len() as u64;
+            chunks.push(ChunkRange { index: chunks.len(), chunk_filename, offset, length });
+            offset += length;
+        }
+        Ok(chunks)
+    })
+}
+
+/// Fails if `file_path` is a symbolic link that is not to be followed (see [`SplitOptions::follow_symlinks`])
+fn check_symlink(file_path: &Path, options: &SplitOptions) -> Result<()> {
+    if options.follow_symlinks {
+        return Ok(());
+    }
+    match fs::read_link(file_path) {
+        Ok(target) => Err(anyhow::anyhow!(
+            "'{}' is a symbolic link to '{}'; follow symbolic links to split the file it points to",
+            file_path.display(),
+            target.display()
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+fn check_size_limit(size_limit: u64) -> Result<()> {
+    if size_limit == 0 {
+        return Err(anyhow::anyhow!("The size limit must be at least 1 byte"));
+    }
+    Ok(())
 }
 
 /// Splits a single file or copies it (if no splitting is needed)
@@ -47,17 +730,67 @@ pub struct SplitInfo {
 /// `file_path`: Path to the file to split.
 /// `size_limit`: Maximum size limit for each chunk in bytes.
 /// `output_root_dir`: Root directory where split sub-files and info files will be stored.
-/// `compress`: Whether to Gzip compress the split sub-files.
-/// `progress_callback`: Optional callback for reporting progress (current_bytes, total_bytes).
-/// `message_callback`: Optional callback for reporting messages (message string).
+/// `options`: Compression, encryption and checksum settings (see [`SplitOptions`]).
+/// `event_callback`: Optional callback receiving the split's progress, stored chunks and messages (see [`SplitEvent`]).
+///
+/// Returns the split info saved for the file, along with the path of its info file. To pass a plain closure
+/// instead of a boxed one, use [`split_single_file_with`].
 pub fn split_single_file(
     file_path: &Path,
     size_limit: u64,
     output_root_dir: &Path,
-    compress: bool,
-    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + 'static>>,
-    message_callback: Option<Box<dyn Fn(String) + Send + Sync + 'static>>,
-) -> Result<()> {
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<(SplitInfo, PathBuf)> {
+    split_single_file_with(file_path, size_limit, output_root_dir, options, |event| {
+        if let Some(cb) = &event_callback {
+            cb(event);
+        }
+    })
+}
+
+/// Splits a single file like [`split_single_file`], passing its events to the closure `on_event`
+///
+/// `on_event` is called with one event at a time, and is dropped when the split returns, so it may borrow from
+/// the caller, e.g. `|event| events.push(event)`.
+pub fn split_single_file_with<F: FnMut(SplitEvent) + Send>(
+    file_path: &Path,
+    size_limit: u64,
+    output_root_dir: &Path,
+    options: &SplitOptions,
+    on_event: F,
+) -> Result<(SplitInfo, PathBuf)> {
+    let on_event = Mutex::new(on_event);
+    let sink = LocalDir::new(output_root_dir);
+    let info = split_file_in(file_path, "", size_limit, &sink, options, Some(Box::new(|event| on_event.lock().unwrap()(event))))?;
+    let info_path = output_root_dir.join(info.info_name());
+    Ok((info, info_path))
+}
+
+/// Splits a single file into chunks stored in `sink` (see [`ChunkSink`])
+///
+/// Works like [`split_single_file`], storing the chunks and info file below `"<file name>_parts/"` in `sink`.
+/// Returns the split info saved in `sink` (see [`SplitInfo::info_name`]).
+pub fn split_file_to_sink(
+    file_path: &Path,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<SplitInfo> {
+    split_file_in(file_path, "", size_limit, sink, options, event_callback)
+}
+
+/// Splits a single file into chunks stored below `parent` (empty, or ending with a slash) in `sink`
+pub(crate) fn split_file_in(
+    file_path: &Path,
+    parent: &str,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + '_>>,
+) -> Result<SplitInfo> {
+    check_symlink(file_path, options)?;
     let file = File::open(file_path)
         .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
     
@@ -68,187 +801,1842 @@ pub fn split_single_file(
         .ok_or_else(|| anyhow::anyhow!("Invalid filename: {}", file_path.display()))?
         .to_string();
 
-    // Create a dedicated subdirectory for this file's chunks
-    let chunks_sub_dir_name = format!("{}_parts", filename_str);
-    let chunks_output_dir = output_root_dir.join(&chunks_sub_dir_name); // This returns PathBuf
-    fs::create_dir_all(&chunks_output_dir)
-        .with_context(|| format!("Failed to create subdirectory: {}", chunks_output_dir.display()))?;
+    // The whole-file checksum is computed while splitting, so the file is read once, unless an earlier split
+    // has to be checked against it first
+    let original_checksum = match options.skip_unchanged {
+        Some(_) => Some(options.checksum_algorithm.tag(&calculate_checksum_with(file_path, options.checksum_algorithm)?)),
+        None => None,
+    };
 
-    let original_checksum = calculate_checksum(file_path)?;
+    // A 32-bit address space is too small to map large files
+    let map = match options.mmap && cfg!(target_pointer_width = "64") {
+        // SAFETY: the mapping is only read, and the file is documented not to change while it is split
+        true => Some(unsafe { memmap2::Mmap::map(&file) }
+            .with_context(|| format!("Failed to map file into memory: {}", file_path.display()))?),
+        false => None,
+    };
+    #[cfg(unix)]
+    if let Some(map) = &map {
+        // Only a hint for the read-ahead, so failing to give it changes nothing
+        let _ = map.advise(memmap2::Advice::Sequential);
+    }
 
-    let mut reader = BufReader::new(file);
-    let mut chunk_index = 0; // Starts from 001
-    let mut chunks_info = Vec::new();
-    let mut total_bytes_processed = 0u64;
+    split_stream(
+        SplitInput::File { file: &file, map: map.as_deref() },
+        &filename_str,
+        Some(original_file_size),
+        original_checksum,
+        size_limit,
+        sink,
+        options,
+        event_callback,
+        SetPlacement { parent, metadata: Some(&FileMetadata::read(file_path, &metadata)?), ..Default::default() },
+    )
+}
 
-    if let Some(cb) = &message_callback {
-        cb(format!("Splitting '{}'", filename_str));
+/// Splits everything read from `reader` (e.g., stdin) as if it were a file called `name`
+///
+/// The chunks and info file are laid out exactly like [`split_single_file`] would for a file named `name`,
+/// so the result restores the same way. The whole-file checksum is computed while reading, and since the
+/// total size is not known in advance, [`SplitEvent::Progress`] reports a total of 0. Returns the split
+/// info, along with the path of its info file.
+pub fn split_reader<R: Read + Send>(
+    reader: R,
+    name: &str,
+    size_limit: u64,
+    output_root_dir: &Path,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<(SplitInfo, PathBuf)> {
+    let info = split_to_sink(reader, name, size_limit, &LocalDir::new(output_root_dir), options, event_callback)?;
+    let info_path = output_root_dir.join(info.info_name());
+    Ok((info, info_path))
+}
+
+/// Splits everything read from `reader` into chunks stored in `sink` (see [`ChunkSink`])
+///
+/// The chunks and info file are stored under `"<name>_parts/"`, the same layout [`split_reader`] uses on disk.
+/// Returns the split info saved in `sink`.
+pub fn split_to_sink<R: Read + Send>(
+    reader: R,
+    name: &str,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<SplitInfo> {
+    if name.is_empty() || Path::new(name).file_name().and_then(|n| n.to_str()) != Some(name) {
+        return Err(anyhow::anyhow!("Invalid name for split input: '{}'", name));
     }
+    split_stream(SplitInput::Reader(Box::new(reader)), name, None, None, size_limit, sink, options, event_callback, SetPlacement::default())
+}
 
-    loop {
-        chunk_index += 1;
-        let chunk_filename = format!("{}-{:03}", filename_str, chunk_index);
-        let chunk_path = chunks_output_dir.join(&chunk_filename); // This returns PathBuf
-        
-        let mut buffer = vec![0u8; size_limit as usize]; // Use size_limit as buffer size
-        let bytes_read = reader.read(&mut buffer)?; // Read original data
-        
-        if bytes_read == 0 {
-            // If the file size is less than or equal to size_limit, and this is the only read, then only one chunk is generated.
-            // But if the file is empty, it will break here directly, and chunks_info will be empty, which needs to be handled.
-            if chunks_info.is_empty() && original_file_size == 0 {
-                // Handle empty file case
-                chunks_info.push(ChunkInfo {
-                    chunk_filename: format!("{}-001", filename_str), // Even for empty files, give a chunk name
-                    chunk_size: 0,
-                    chunk_checksum: Some(calculate_buffer_checksum(&[])), // Checksum for empty file
-                });
+/// What [`split_stream`] splits
+pub(crate) enum SplitInput<'a> {
+    /// A stream, read chunk by chunk
+    Reader(Box<dyn Read + Send + 'a>),
+    /// A local file, read from its current position, unless it is mapped into memory (see [`SplitOptions::mmap`]);
+    /// then its chunks are sliced out of the mapping
+    File { file: &'a File, map: Option<&'a [u8]> },
+}
+
+/// Where a [`SplitInput`] that can go back stood, to read again from there
+enum InputMark<'a> {
+    /// Position in the file
+    File(u64),
+    /// What was left of the mapping
+    Map(&'a [u8]),
+}
+
+impl<'a> SplitInput<'a> {
+    /// Where reading stands now, or `None` for a stream, which cannot go back
+    fn mark(&mut self) -> Option<InputMark<'a>> {
+        match self {
+            SplitInput::Reader(_) => None,
+            SplitInput::File { map: Some(map), .. } => Some(InputMark::Map(map)),
+            SplitInput::File { file, .. } => file.stream_position().ok().map(InputMark::File),
+        }
+    }
+
+    /// Goes back to `mark`, to read from there again
+    fn rewind(&mut self, mark: &InputMark<'a>) -> std::io::Result<()> {
+        match (self, mark) {
+            (SplitInput::File { map: Some(map), .. }, InputMark::Map(marked)) => *map = marked,
+            (SplitInput::File { file, .. }, InputMark::File(position)) => {
+                file.seek(SeekFrom::Start(*position))?;
             }
-            break;
+            _ => {}
         }
-        
-        let original_chunk_data = &buffer[..bytes_read];
-        let original_chunk_checksum = Some(calculate_buffer_checksum(original_chunk_data));
+        Ok(())
+    }
+}
 
-        let mut file_writer = File::create(&chunk_path)
-            .with_context(|| format!("Failed to create chunk file: {}", chunk_path.display()))?;
+impl Read for SplitInput<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SplitInput::Reader(reader) => reader.read(buf),
+            SplitInput::File { map: Some(map), .. } => map.read(buf),
+            SplitInput::File { file, .. } => file.read(buf),
+        }
+    }
+}
 
-        let actual_chunk_size;
+/// Where a split set goes in its sink, and what its content is, beyond its name
+#[derive(Default, Clone, Copy)]
+pub(crate) struct SetPlacement<'a> {
+    /// Directory holding the split set's `*_parts` directory, ending with a slash (e.g., "photos/2024/"),
+    /// or empty for the root of the sink
+    pub(crate) parent: &'a str,
+    /// Archive format the content is bundled in, if it is a directory tree
+    pub(crate) archive: Option<ArchiveFormat>,
+    /// Attributes of the file the content was read from, if any
+    pub(crate) metadata: Option<&'a FileMetadata>,
+}
 
-        if compress {
-            let mut encoder = GzEncoder::new(file_writer, Compression::default());
-            encoder.write_all(original_chunk_data)?;
-            actual_chunk_size = encoder.finish()?.metadata()?.len(); // Get compressed file size
-        } else {
-            file_writer.write_all(original_chunk_data)?;
-            file_writer.flush()?;
-            actual_chunk_size = original_chunk_data.len() as u64; // Uncompressed, directly the original data size
+/// Splits `input` into chunks and saves the split info, which is returned
+///
+/// `expected_size` is checked against the bytes actually read, and `original_checksum` is computed
+/// while reading if not already known. An up-to-date split set that is kept returns its existing split info.
+/// A cancelled split (see [`SplitOptions::cancel`]) removes whatever it stored.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn split_stream(
+    input: SplitInput,
+    filename_str: &str,
+    expected_size: Option<u64>,
+    original_checksum: Option<String>,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + '_>>,
+    placement: SetPlacement,
+) -> Result<SplitInfo> {
+    let synced = options.fsync.then(|| SyncedSink::new(sink));
+    let sink: &dyn ChunkSink = match &synced {
+        Some(synced) => synced,
+        None => sink,
+    };
+    let Some(cancel) = options.cancel else {
+        return split_input(input, filename_str, expected_size, original_checksum, size_limit, sink, options, event_callback, placement);
+    };
+    let tracked = TrackingSink::new(sink);
+    let split = split_input(input, filename_str, expected_size, original_checksum, size_limit, &tracked, options, event_callback, placement);
+    if split.is_err() && cancel.load(Ordering::Relaxed) {
+        tracing::info!(file = %filename_str, "split cancelled, removing what it stored");
+        tracked.remove_stored();
+    }
+    split
+}
+
+/// Does the work of [`split_stream`], storing everything in `sink`
+#[allow(clippy::too_many_arguments)]
+fn split_input(
+    mut input: SplitInput,
+    filename_str: &str,
+    expected_size: Option<u64>,
+    original_checksum: Option<String>,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + '_>>,
+    placement: SetPlacement,
+) -> Result<SplitInfo> {
+    // Events of the worker threads name the span as their parent, as they do not run inside it
+    let span = tracing::info_span!("split", file = %filename_str, size = expected_size);
+    let _entered = span.enter();
+    let checksum_algorithm = options.checksum_algorithm;
+    let boundary = ChunkBoundary::new(options)?;
+    check_size_limit(size_limit)?;
+    let chunk_pool = options.chunk_pool;
+    if let Some(pool) = chunk_pool.filter(|pool| !is_relative_key(pool)) {
+        return Err(anyhow::anyhow!("Invalid chunk pool '{}': expected a relative path like 'pool'", pool));
+    }
+    if options.info_trailer && (chunk_pool.is_some() || options.dedup.is_some() || options.resume_from.is_some()) {
+        return Err(anyhow::anyhow!("A split into a chunk pool, deduplicated or resumed cannot append the info file to its last chunk"));
+    }
+    let info_extension = options.info_file_extension();
+    if info_extension.is_empty() || info_extension.contains(['/', '\\']) {
+        return Err(anyhow::anyhow!("Invalid info file extension '{}': expected a name like 'fsmeta.json'", info_extension));
+    }
+    if let Some(prefix) = options.chunk_prefix.filter(|prefix| prefix.is_empty() || prefix.contains(['/', '\\'])) {
+        return Err(anyhow::anyhow!("Invalid chunk file prefix '{}': expected a name like 'part'", prefix));
+    }
+    if let Some(extension) = options.chunk_file_extension().filter(|extension| !naming::is_valid_chunk_extension(extension)) {
+        return Err(anyhow::anyhow!("Invalid chunk file extension '{}': expected a name like 'part'", extension));
+    }
+    if options.chunk_naming == ChunkNaming::Gnu && options.start_index() != DEFAULT_CHUNK_START_INDEX {
+        return Err(anyhow::anyhow!("Chunks named like GNU split's are lettered from 'aa', so they cannot be numbered from another index"));
+    }
+    if options.is_cat_compatible() && !sink.destinations().is_empty() {
+        return Err(anyhow::anyhow!("Chunks spread across several directories cannot be joined by `cat` in one of them"));
+    }
+    if options.is_cat_compatible()
+        && (options.codec.is_some() || options.encryption.is_some() || options.chunk_headers || options.info_trailer
+            || chunk_pool.is_some() || options.dedup.is_some())
+    {
+        let reason = match options.chunk_naming {
+            ChunkNaming::Gnu => "Chunks named like GNU split's",
+            _ => "Cat-compatible chunks",
+        };
+        return Err(anyhow::anyhow!(
+            "{} are stored as they are, so that `cat` joins them: they cannot be compressed, encrypted, have \
+             headers or an info file trailer, or go into a chunk pool",
+            reason
+        ));
+    }
+
+    // Each file's chunks go in a dedicated subdirectory
+    let chunks_sub_dir_name = format!("{}{}_parts", placement.parent, filename_str);
+
+    // Record built-in algorithms by their enum value, anything else by the custom codec's name
+    let codec = options.codec;
+    let builtin_algorithm = codec.and_then(|c| c.name().parse::<CompressionAlgorithm>().ok());
+    let custom_codec = match (codec, builtin_algorithm) {
+        (Some(c), None) => Some(c.name().to_string()),
+        _ => None,
+    };
+    let encryption_info = options.encryption.map(|e| e.info());
+
+    let journal_name = format!("{}/{}.journal", chunks_sub_dir_name, filename_str);
+    let settings = SplitSettings {
+        chunk_limit: size_limit,
+        split_mode: options.split_mode,
+        max_lines: options.max_lines,
+        delimiter: options.delimiter.map(escape_delimiter),
+        chunk_pool: chunk_pool.map(str::to_string),
+        checksum_algorithm,
+        codec: codec.map(|c| c.name().to_string()),
+        encryption: encryption_info.as_ref().map(serde_json::to_value).transpose()?,
+        chunk_headers: options.chunk_headers,
+        info_trailer: options.info_trailer,
+        chunk_naming: options.chunk_naming,
+        chunk_prefix: options.chunk_prefix.map(str::to_string),
+        chunk_extension: options.chunk_file_extension().map(str::to_string),
+        cat_compatible: options.is_cat_compatible(),
+        chunk_start_index: options.start_index(),
+    };
+    let emit = |event: SplitEvent| {
+        if let Some(cb) = &event_callback {
+            cb(event);
         }
-        
-        chunks_info.push(ChunkInfo {
-            chunk_filename,
-            chunk_size: actual_chunk_size, // Record actual size (compressed or uncompressed)
-            chunk_checksum: original_chunk_checksum, // Record checksum of original (uncompressed) data
-        });
-        total_bytes_processed += bytes_read as u64; // Total bytes processed is still the sum of original file bytes
-        
-        if let Some(cb) = &progress_callback {
-            cb(total_bytes_processed, original_file_size);
+    };
+    let message = |msg: String| emit(SplitEvent::Message(msg));
+    let finished = |info_name: String, chunks: usize| {
+        tracing::info!(info = %info_name, chunks, "split finished");
+        emit(SplitEvent::Finished { name: filename_str.to_string(), info_name, chunks });
+    };
+    emit(SplitEvent::Started { name: filename_str.to_string(), total: expected_size });
+
+    if let (Some(source), Some(checksum)) = (options.skip_unchanged, &original_checksum) {
+        let info_name = format!("{}/{}.{}", chunks_sub_dir_name, filename_str, info_extension);
+        if let Some(file_info) = split_up_to_date(source, &info_name, &settings, options.parity, expected_size, checksum, placement.metadata)? {
+            tracing::info!("split set is up to date, keeping it");
+            let size = expected_size.unwrap_or(0);
+            emit(SplitEvent::Progress { bytes: size, total: size, throughput: Throughput::default() });
+            message(format!("'{}' is up to date: {}", filename_str, source.describe(&info_name)));
+            finished(info_name, file_info.chunks.len());
+            return Ok(file_info);
         }
+    }
 
-        // If the number of bytes read is less than size_limit, it means it's the last part of the file
-        if (bytes_read as u64) < size_limit {
-            break;
+    let resumable = match options.resume_from {
+        Some(_) if options.parity > 0 => {
+            return Err(anyhow::anyhow!("A split with parity chunks cannot be resumed; split '{}' again", filename_str));
+        }
+        Some(source) => {
+            let (chunks, note) = read_journal(source, &journal_name, &settings);
+            tracing::info!(journaled_chunks = chunks.len(), "resuming split");
+            message(format!("Resuming '{}': {}", filename_str, note));
+            chunks
+        }
+        None => Vec::new(),
+    };
+
+    let hasher = match original_checksum {
+        Some(_) => None,
+        None => Some(checksum_algorithm.hasher()),
+    };
+    // The chunk count is only known up front for files split by bytes into chunks that all get the full size limit
+    let expected_chunks = expected_size
+        .filter(|_| options.split_mode == SplitMode::Bytes && !sink.limits_room())
+        .map(|size| size.div_ceil(size_limit).max(1));
+    let namer = options.chunk_namer(filename_str, expected_chunks);
+    if !options.overwrite && options.resume_from.is_none() && options.skip_unchanged.is_none() {
+        let info_name = format!("{}/{}.{}", chunks_sub_dir_name, filename_str, info_extension);
+        let first_chunk = format!("{}/{}", chunks_sub_dir_name, namer.name(1));
+        for name in [info_name, first_chunk] {
+            if sink.contains(&name)? {
+                return Err(overwrite_refused(&sink.describe(&name)));
+            }
+        }
+    }
+    // Filled in with each chunk's index and checksum
+    let header = options.chunk_headers.then(|| ChunkHeader {
+        original_filename: filename_str.to_string(),
+        index: 0,
+        total: expected_chunks,
+        codec: codec.map(|c| c.name().to_string()),
+        encrypted: options.encryption.is_some(),
+        checksum: String::new(),
+    });
+
+    // Reports the last of `chunks` as stored, along with the bytes split so far
+    let meter = Mutex::new(ProgressMeter::new());
+    let chunk_stored = |chunks: &[ChunkInfo], bytes: u64| {
+        if let Some(chunk_info) = chunks.last() {
+            tracing::debug!(
+                parent: &span,
+                index = chunks.len() - 1,
+                chunk = %chunk_info.chunk_filename,
+                size = chunk_info.chunk_size,
+                bytes,
+                "chunk stored"
+            );
+            emit(SplitEvent::ChunkWritten {
+                index: chunks.len() - 1,
+                name: chunk_info.chunk_filename.clone(),
+                key: chunk_key(chunk_pool.unwrap_or(&chunks_sub_dir_name), chunk_info),
+                size: chunk_info.chunk_size,
+                checksum: chunk_info.chunk_checksum.clone().unwrap_or_default(),
+            });
+        }
+        let total = expected_size.unwrap_or(0);
+        let throughput = meter.lock().unwrap().update(bytes, total);
+        emit(SplitEvent::Progress { bytes, total, throughput });
+    };
+    let mut journal = JournalSaver::new(sink, &journal_name, &settings);
+    let held_because = held_in_memory(options, boundary, input.mark().is_some());
+    let unbuffered = held_because.is_none();
+    if let Some(reason) = held_because.filter(|_| sink.limits_room()) {
+        return Err(anyhow::anyhow!(
+            "Splitting '{}' cannot cut chunks short to the room left where they go (e.g., on a volume), as they are {}",
+            filename_str,
+            reason
+        ));
+    }
+    if let Some(reason) = held_because.filter(|_| size_limit > STREAMING_CHUNK_SIZE) {
+        message(format!(
+            "Chunks of up to {} bytes are held in memory, as they are {}; lower the size limit if memory runs short",
+            size_limit,
+            reason
+        ));
+    }
+    // Uncompressed, unencrypted chunks of a local file cut by bytes are copied into their files by the sink, if it
+    // can; a copy would pass a whole chunk at once, which is too coarse to keep to a rate limit
+    let copyable = codec.is_none() && options.encryption.is_none() && options.rate_limit.is_none() && boundary.by_bytes();
+    let copied = match &input {
+        SplitInput::File { file, map } if unbuffered && copyable && cfg!(target_os = "linux") => {
+            copy_chunks((file, *map), size_limit, namer, hasher.clone(), sink, &chunks_sub_dir_name, options, &mut journal, &chunk_stored)?
+        }
+        _ => None,
+    };
+    // Besides a chunk per worker, the pipeline buffers hold a few chunks, and the parity encoder one per parity chunk
+    let held_chunks = 2 * PIPELINE_DEPTH + options.parity;
+    let threads = match options.max_memory {
+        Some(max_memory) => {
+            let fitting = usize::try_from(max_memory / size_limit).unwrap_or(usize::MAX);
+            options.threads.max(1).min(fitting.saturating_sub(held_chunks))
         }
+        None => options.threads.max(1),
+    };
+    if threads == 0 && !unbuffered {
+        return Err(anyhow::anyhow!(
+            "Splitting '{}' holds at least {} chunks of {} bytes in memory, more than the memory budget of {} bytes; \
+             raise the budget or lower the size limit",
+            filename_str,
+            held_chunks + 1,
+            size_limit,
+            options.max_memory.unwrap_or_default()
+        ));
     }
+    // Chunks that do not fit into memory (or the memory budget) along with the pipeline buffers are streamed into
+    // their files instead, where nothing else needs their bytes. Those of a local file cut by bytes can be read at
+    // their offsets by one worker each, so large chunks of a file are always streamed; chunks spread across
+    // destinations are not, as where each goes depends on those before it.
+    let parallel_streaming = matches!(input, SplitInput::File { .. })
+        && boundary.by_bytes()
+        && sink.destinations().is_empty()
+        && cfg!(any(unix, windows));
+    let held_bytes = ((threads + held_chunks) as u64).saturating_mul(size_limit);
+    let fits = threads > 0 && space::available_memory().is_none_or(|available| held_bytes <= available);
+    // Chunks that may be cut short where they go are placed before they are read, so they are streamed too
+    let streaming = unbuffered && (!fits || sink.limits_room() || (parallel_streaming && size_limit > STREAMING_CHUNK_SIZE));
+    if streaming && !parallel_streaming && copied.is_none() && options.threads > 1 {
+        message(format!("Chunks of up to {} bytes are encoded one at a time, as they are streamed into their files", size_limit));
+    }
+    let pool = if threads > 1 && !streaming && copied.is_none() {
+        Some(rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+            .context("Failed to start worker threads")?)
+    } else {
+        None
+    };
+    let (written, streamed_checksum) = if let Some(copied) = copied {
+        copied
+    } else if let (true, SplitInput::File { file, map }) = (streaming && parallel_streaming, &input) {
+        let threads = options.threads.max(1);
+        stream_file_chunks((file, *map), size_limit, threads, namer, hasher, sink, &chunks_sub_dir_name, options, &mut journal, &chunk_stored)?
+    } else if streaming {
+        stream_chunks(input, size_limit, boundary, namer, hasher, sink, &chunks_sub_dir_name, options, &mut journal, &chunk_stored)?
+    } else {
+        // Reading, encoding (hash, compress, encrypt) and writing run as three pipeline stages connected by
+        // bounded channels, so the disk and the CPU work at the same time
+        thread::scope(|scope| -> Result<(WrittenChunks, Option<String>)> {
+            let (read_tx, read_rx) = mpsc::sync_channel::<Result<(String, Cow<[u8]>)>>(PIPELINE_DEPTH);
+            let (write_tx, write_rx) = mpsc::sync_channel::<StagedChunk>(PIPELINE_DEPTH);
+
+            let reader = scope.spawn(move || match input {
+                SplitInput::File { map: Some(map), .. } => map_chunks(map, size_limit, boundary, namer, hasher, options.checkpoint(), read_tx),
+                reader => read_chunks(reader, size_limit, boundary, namer, hasher, options.checkpoint(), read_tx),
+            });
+
+            let chunks_sub_dir_name = &chunks_sub_dir_name;
+            let chunk_stored = &chunk_stored;
+            let (journal, resumable) = (&mut journal, &resumable);
+            let writer = scope.spawn(move || -> Result<WrittenChunks> {
+                let mut chunks_info = Vec::new();
+                let mut total_bytes_processed = 0u64;
+                let mut reused_chunks = 0;
+                let mut resumed_chunks = 0;
+                let mut parity = (options.parity > 0).then(|| ParityEncoder::new(options.parity));
+                // With an info file trailer, each chunk is held back until the next one shows it was not the last
+                let mut held = None;
+                for chunk in write_rx {
+                    let chunk_info = match chunk {
+                        StagedChunk::Encoded(chunk) => {
+                            if let Some(parity) = &mut parity {
+                                parity.add(&chunk.data)?;
+                            }
+                            let chunk = match options.info_trailer {
+                                true => match held.replace(chunk) {
+                                    Some(chunk) => chunk,
+                                    None => continue,
+                                },
+                                false => chunk,
+                            };
+                            let (chunk_info, stored) = chunk.write(sink, chunks_sub_dir_name, options)?;
+                            if !stored {
+                                reused_chunks += 1;
+                            }
+                            chunk_info
+                        }
+                        StagedChunk::Stored(chunk_info) => {
+                            resumed_chunks += 1;
+                            chunk_info
+                        }
+                    };
+                    // Total bytes processed is still the sum of original file bytes
+                    total_bytes_processed += chunk_info.original_size.unwrap_or(0);
+                    chunks_info.push(chunk_info);
+
+                    journal.chunk_stored(&chunks_info)?;
+
+                    chunk_stored(&chunks_info, total_bytes_processed);
+                }
+                // Recorded now, but only stored once the info file it ends with is complete
+                if let Some(chunk) = &held {
+                    total_bytes_processed += chunk.original_size;
+                    chunks_info.push(chunk.chunk_info());
+                }
+                Ok(WrittenChunks {
+                    chunks_info,
+                    total_bytes_processed,
+                    reused_chunks,
+                    resumed_chunks,
+                    parity,
+                    journaled: journal.saved(),
+                    last_chunk: held,
+                })
+            });
+
+            let encoded = (|| -> Result<()> {
+                let mut pending = read_rx.iter();
+                let mut next_index = 0;
+                loop {
+                    // Take the next batch of chunks, one per worker thread
+                    let batch = pending.by_ref().take(threads).collect::<Result<Vec<_>>>()?;
+                    if batch.is_empty() {
+                        return Ok(());
+                    }
+
+                    let first_index = next_index;
+                    next_index += batch.len();
+                    let encode = |(offset, (chunk_filename, data)): (usize, &(String, Cow<[u8]>))| -> Result<StagedChunk> {
+                        let _chunk = tracing::debug_span!(parent: &span, "encode_chunk", index = first_index + offset).entered();
+                        if let (Some(source), Some(chunk_info)) = (options.resume_from, resumable.get(first_index + offset)) {
+                            if chunk_still_stored(source, chunk_pool.unwrap_or(chunks_sub_dir_name), chunk_info, data, checksum_algorithm)? {
+                                return Ok(StagedChunk::Stored(chunk_info.clone()));
+                            }
+                        }
+                        let header = header.as_ref().map(|header| ChunkHeader { index: (first_index + offset + 1) as u64, ..header.clone() });
+                        encode_chunk(chunk_filename, data, header, options).map(StagedChunk::Encoded)
+                    };
+                    // Workers may finish out of order, but collecting keeps the chunks in file order
+                    let encoded = match &pool {
+                        Some(pool) => pool.install(|| batch.par_iter().enumerate().map(encode).collect::<Result<Vec<_>>>())?,
+                        None => batch.iter().enumerate().map(encode).collect::<Result<Vec<_>>>()?,
+                    };
+                    for chunk in encoded {
+                        if write_tx.send(chunk).is_err() {
+                            return Ok(()); // The writer stopped early; its error is reported below
+                        }
+                    }
+                }
+            })();
+            // Closing the channels lets the other stages finish
+            drop(read_rx);
+            drop(write_tx);
+
+            let written = writer.join().map_err(|_| anyhow::anyhow!("Chunk writer thread panicked"))??;
+            let streamed_checksum = reader.join().map_err(|_| anyhow::anyhow!("Chunk reader thread panicked"))?;
+            encoded?;
+            Ok((written, streamed_checksum))
+        })?
+    };
+    let WrittenChunks { chunks_info, total_bytes_processed, reused_chunks, resumed_chunks, parity, journaled, last_chunk } = written;
     
-    if let Some(cb) = &message_callback {
-        cb(format!("'{}' splitting complete", filename_str));
+    message(format!("'{}' splitting complete", filename_str));
+    if chunk_pool.is_some() || options.dedup.is_some() {
+        message(format!("{} of {} chunks were already stored", reused_chunks, chunks_info.len()));
+    }
+    if options.resume_from.is_some() {
+        message(format!("{} of {} chunks were kept from the interrupted split", resumed_chunks, chunks_info.len()));
     }
 
     // Verify total size matches
-    if total_bytes_processed != original_file_size {
+    let original_file_size = total_bytes_processed;
+    if let Some(expected_size) = expected_size.filter(|&size| size != total_bytes_processed) {
         return Err(anyhow::anyhow!(
             "File size mismatch during splitting: Expected {}, Actual {}",
-            original_file_size,
+            expected_size,
             total_bytes_processed
         ));
     }
+    let parity = parity
+        .map(|parity| parity.finish(sink, &chunks_sub_dir_name, filename_str))
+        .transpose()?;
+    let original_checksum = original_checksum
+        .or(streamed_checksum.map(|digest| checksum_algorithm.tag(&digest)))
+        .ok_or_else(|| anyhow::anyhow!("Failed to calculate the checksum of '{}'", filename_str))?;
 
     // Build SplitInfo
     let split_info = SplitInfo {
-        original_filename: filename_str.clone(),
+        format_version: FORMAT_VERSION,
+        manifest_checksum: None,
+        original_filename: filename_str.to_string(),
         original_file_size,
         chunk_limit: size_limit,
-        chunks_sub_dir: chunks_sub_dir_name,
+        split_mode: options.split_mode,
+        max_lines: options.max_lines,
+        delimiter: options.delimiter.map(escape_delimiter),
+        chunks_sub_dir: chunks_sub_dir_name.clone(),
+        chunk_pool: chunk_pool.map(str::to_string),
         chunks: chunks_info,
+        chunk_index_width: namer.width(),
+        chunk_naming: options.chunk_naming,
+        chunk_prefix: options.chunk_prefix.map(str::to_string),
+        chunk_extension: options.chunk_file_extension().map(str::to_string),
+        cat_compatible: options.is_cat_compatible(),
+        destinations: sink.destinations(),
+        chunk_start_index: options.start_index(),
         original_checksum,
-        is_compressed: compress, // Record whether compressed
+        checksum_algorithm,
+        is_compressed: codec.is_some(), // Record whether compressed
+        compression_algorithm: builtin_algorithm.unwrap_or_default(),
+        custom_codec,
+        encryption: encryption_info,
+        parity,
+        chunk_headers: options.chunk_headers,
+        info_trailer: options.info_trailer,
+        last_verified: None,
+        archive: placement.archive,
+        path: (!placement.parent.is_empty()).then(|| format!("{}{}", placement.parent, filename_str)),
+        metadata: placement.metadata.cloned(),
+        manifest_format: options.manifest_format,
+        info_extension: (info_extension != options.manifest_format.extension()).then(|| info_extension.to_string()),
     };
 
-    // Save SplitInfo to JSON file
-    let info_filename = format!("{}.json", filename_str);
-    let info_path = chunks_output_dir.join(&info_filename); // This returns PathBuf
-    let json_data = serde_json::to_string_pretty(&split_info)?;
-    fs::write(&info_path, json_data)
-        .with_context(|| format!("Failed to save split info JSON file: {}", info_path.display()))?;
+    if let Some(chunk) = last_chunk {
+        let chunk_name = format!("{}/{}", split_info.chunks_sub_dir, chunk.chunk_filename);
+        let mut data = chunk.data;
+        data.extend_from_slice(&encode_info_trailer(&split_info)?);
+        options.retry.run(options.checkpoint(), &sink.describe(&chunk_name), || sink.put(&chunk_name, &data))
+            .with_context(|| format!("Failed to write chunk file: {}", sink.describe(&chunk_name)))?;
+        chunk_stored(&split_info.chunks, split_info.original_file_size);
+    }
+
+    // Save SplitInfo to its info file
+    let info_name = split_info.info_name();
+    let info_data = split_info.manifest_format.encode(&split_info)?;
+    options.retry.run(options.checkpoint(), &sink.describe(&info_name), || sink.put(&info_name, info_data.as_bytes()))
+        .with_context(|| format!("Failed to save split info file: {}", sink.describe(&info_name)))?;
     
-    if let Some(cb) = &message_callback {
-        cb(format!("Split info for file '{}' saved to: {}", filename_str, info_path.display()));
+    message(format!("Split info for file '{}' saved to: {}", filename_str, sink.describe(&info_name)));
+
+    // The split is complete, so the journal is no longer needed; a journal left behind (e.g., by a sink that
+    // cannot delete) is harmless, as resuming checks every chunk it lists
+    if journaled || options.resume_from.is_some() {
+        let _ = sink.remove(&journal_name);
     }
+    finished(info_name, split_info.chunks.len());
 
-    Ok(())
+    Ok(split_info)
 }
 
-/// Restores a single file
+/// Storage key of `chunk` stored in `dir`, or of the identical chunk it refers to
+fn chunk_key(dir: &str, chunk: &ChunkInfo) -> String {
+    match &chunk.stored_as {
+        Some(stored_as) => stored_as.clone(),
+        None => format!("{}/{}", dir, chunk.chunk_filename),
+    }
+}
+
+/// Whether `name` is a relative, `/`-separated storage key that stays below the root (e.g., "pool/ab")
+fn is_relative_key(name: &str) -> bool {
+    !name.contains('\\') && name.split('/').all(|part| !matches!(part, "" | "." | ".."))
+}
+
+/// Number of chunks buffered between the read, encode and write stages of a split
+const PIPELINE_DEPTH: usize = 2;
+
+/// Chunks larger than this are streamed into their files instead of being held in memory, if the split allows
+const STREAMING_CHUNK_SIZE: u64 = 64 << 20;
+
+/// What the write stage of a split stored
+struct WrittenChunks {
+    chunks_info: Vec<ChunkInfo>,
+    total_bytes_processed: u64,
+    /// Chunks an identical copy of which was already stored
+    reused_chunks: usize,
+    /// Chunks kept from the interrupted split being resumed
+    resumed_chunks: usize,
+    parity: Option<ParityEncoder>,
+    /// Whether a journal was saved
+    journaled: bool,
+    /// The last chunk, recorded in `chunks_info` but not yet stored, if it is to end with the info file
+    last_chunk: Option<EncodedChunk>,
+}
+
+/// Why the chunks of a split must each be held in memory while it is stored, if they must
 ///
-/// `file_info`: Split information for the file to restore.
-/// `input_root_dir`: Root directory where the split sub-files are located.
-/// `output_dir`: Directory where the restored large file will be saved.
-/// `progress_callback`: Optional callback for reporting progress (current_bytes, total_bytes).
-/// `message_callback`: Optional callback for reporting messages (message string).
-pub fn restore_single_file(
+/// Only chunks stored under their own names can be stored while they are read, and only if they are cut by bytes or
+/// the input can be read again (`rewindable`) once the end of the chunk is found.
+fn held_in_memory(options: &SplitOptions, boundary: ChunkBoundary, rewindable: bool) -> Option<&'static str> {
+    if options.chunk_pool.is_some() || options.dedup.is_some() {
+        Some("stored by their content, which names them")
+    } else if options.parity > 0 {
+        Some("protected by parity chunks")
+    } else if options.resume_from.is_some() {
+        Some("compared with those of the split being resumed")
+    } else if options.chunk_headers {
+        Some("given headers")
+    } else if options.info_trailer {
+        Some("followed by the info file")
+    } else if !boundary.by_bytes() && !rewindable {
+        Some("cut at lines or delimiters of a stream")
+    } else {
+        None
+    }
+}
+
+/// How often a running split saves its journal
+const JOURNAL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Saves what a running split has stored so far every [`JOURNAL_INTERVAL`], for a later split to resume from
+struct JournalSaver<'a> {
+    sink: &'a dyn ChunkSink,
+    name: &'a str,
+    settings: &'a SplitSettings,
+    /// When the journal was last saved, or else when the split started
+    since: Instant,
+    saved: bool,
+}
+
+impl<'a> JournalSaver<'a> {
+    fn new(sink: &'a dyn ChunkSink, name: &'a str, settings: &'a SplitSettings) -> Self {
+        JournalSaver { sink, name, settings, since: Instant::now(), saved: false }
+    }
+
+    /// Saves `chunks_info`, all chunks stored so far, if the journal is due
+    fn chunk_stored(&mut self, chunks_info: &[ChunkInfo]) -> Result<()> {
+        if self.since.elapsed() < JOURNAL_INTERVAL {
+            return Ok(());
+        }
+        let journal = serde_json::to_string(&SplitJournal { settings: self.settings.clone(), chunks: chunks_info.to_vec() })?;
+        self.sink.put(self.name, journal.as_bytes())
+            .with_context(|| format!("Failed to save split journal: {}", self.sink.describe(self.name)))?;
+        self.since = Instant::now();
+        self.saved = true;
+        Ok(())
+    }
+
+    /// Whether a journal was saved
+    fn saved(&self) -> bool {
+        self.saved
+    }
+}
+
+/// Settings that decide what a split stores, so that only a split with the same settings resumes another
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct SplitSettings {
+    chunk_limit: u64,
+    split_mode: SplitMode,
+    max_lines: Option<u64>,
+    delimiter: Option<String>,
+    chunk_pool: Option<String>,
+    checksum_algorithm: ChecksumAlgorithm,
+    codec: Option<String>,
+    /// The split info's encryption parameters, which identify the key (a new password-derived key has a new salt)
+    encryption: Option<serde_json::Value>,
+    #[serde(default)]
+    chunk_headers: bool,
+    #[serde(default)]
+    info_trailer: bool,
+    #[serde(default)]
+    chunk_naming: ChunkNaming,
+    #[serde(default)]
+    chunk_prefix: Option<String>,
+    #[serde(default)]
+    chunk_extension: Option<String>,
+    #[serde(default)]
+    cat_compatible: bool,
+    #[serde(default = "default_chunk_start_index")]
+    chunk_start_index: u64,
+}
+
+impl SplitSettings {
+    /// Settings an existing split set was made with
+    fn of(file_info: &SplitInfo) -> Result<Self> {
+        Ok(SplitSettings {
+            chunk_limit: file_info.chunk_limit,
+            split_mode: file_info.split_mode,
+            max_lines: file_info.max_lines,
+            delimiter: file_info.delimiter.clone(),
+            chunk_pool: file_info.chunk_pool.clone(),
+            checksum_algorithm: file_info.checksum_algorithm,
+            codec: file_info.codec_name().map(str::to_string),
+            encryption: file_info.encryption.as_ref().map(serde_json::to_value).transpose()?,
+            chunk_headers: file_info.chunk_headers,
+            info_trailer: file_info.info_trailer,
+            chunk_naming: file_info.chunk_naming,
+            chunk_prefix: file_info.chunk_prefix.clone(),
+            chunk_extension: file_info.chunk_extension.clone(),
+            cat_compatible: file_info.cat_compatible,
+            chunk_start_index: file_info.chunk_start_index,
+        })
+    }
+}
+
+/// The split info in the info file `info_name` in `source`, if it describes a split of the same content with the
+/// same settings, all of whose chunks are still stored with their recorded sizes (see [`SplitOptions::skip_unchanged`])
+///
+/// A missing, unreadable or invalid info file just means the input has to be split.
+fn split_up_to_date(
+    source: &dyn ChunkSource,
+    info_name: &str,
+    settings: &SplitSettings,
+    parity_count: usize,
+    expected_size: Option<u64>,
+    original_checksum: &str,
+    metadata: Option<&FileMetadata>,
+) -> Result<Option<SplitInfo>> {
+    let mut content = String::new();
+    if source.open(info_name).and_then(|mut reader| Ok(reader.read_to_string(&mut content)?)).is_err() {
+        return Ok(None);
+    }
+    let Ok(file_info) = parse_split_info(info_name, &content) else {
+        return Ok(None);
+    };
+    if file_info.validate().is_err()
+        || file_info.original_checksum != original_checksum
+        || Some(file_info.original_file_size) != expected_size
+        || file_info.parity.as_ref().map_or(0, |parity| parity.chunks.len()) != parity_count
+        || SplitSettings::of(&file_info)? != *settings
+        || file_info.metadata.as_ref() != metadata
+    {
+        return Ok(None);
+    }
+    for (index, chunk_info) in file_info.chunks.iter().enumerate() {
+        let name = file_info.chunk_name(chunk_info);
+        let stored_size = source.size(&name)
+            .with_context(|| format!("Failed to look up chunk file: {}", source.describe(&name)))?;
+        if !stored_size.is_some_and(|size| file_info.stored_size_matches(index, size)) {
+            return Ok(None);
+        }
+    }
+    if let Some(parity_info) = &file_info.parity {
+        for parity_chunk in &parity_info.chunks {
+            let name = parity::parity_chunk_name(&file_info, parity_chunk);
+            let stored_size = source.size(&name)
+                .with_context(|| format!("Failed to look up chunk file: {}", source.describe(&name)))?;
+            if stored_size != Some(parity_info.shard_size) {
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(file_info))
+}
+
+/// Chunks stored so far by a running split, saved next to them (see [`SplitOptions::resume_from`])
+#[derive(Serialize, Deserialize)]
+struct SplitJournal {
+    settings: SplitSettings,
+    chunks: Vec<ChunkInfo>,
+}
+
+/// Reads the chunks an interrupted split journaled, along with a note on what is resumed
+///
+/// A missing or unreadable journal, or one written with other settings, resumes nothing.
+fn read_journal(source: &dyn ChunkSource, journal_name: &str, settings: &SplitSettings) -> (Vec<ChunkInfo>, String) {
+    let mut content = String::new();
+    if source.open(journal_name).and_then(|mut reader| Ok(reader.read_to_string(&mut content)?)).is_err() {
+        return (Vec::new(), "no interrupted split found, starting from the beginning".to_string());
+    }
+    match serde_json::from_str::<SplitJournal>(&content) {
+        Ok(journal) if journal.settings == *settings => {
+            let note = format!("checking {} chunks stored by the interrupted split", journal.chunks.len());
+            (journal.chunks, note)
+        }
+        Ok(_) => (Vec::new(), "the interrupted split used other settings, starting from the beginning".to_string()),
+        Err(_) => (Vec::new(), "the split journal is damaged, starting from the beginning".to_string()),
+    }
+}
+
+/// Whether a chunk journaled by an interrupted split can be kept: it holds the same original content (`data`)
+/// and is still stored with its recorded size
+fn chunk_still_stored(
+    source: &dyn ChunkSource,
+    dir: &str,
+    chunk_info: &ChunkInfo,
+    data: &[u8],
+    checksum_algorithm: ChecksumAlgorithm,
+) -> Result<bool> {
+    if chunk_info.original_size != Some(data.len() as u64)
+        || chunk_info.chunk_checksum.as_deref() != Some(&checksum_algorithm.tag(&calculate_buffer_checksum_with(data, checksum_algorithm)))
+    {
+        return Ok(false);
+    }
+    let name = chunk_key(dir, chunk_info);
+    let size = source.size(&name)
+        .with_context(|| format!("Failed to look up chunk file: {}", source.describe(&name)))?;
+    Ok(size == Some(chunk_info.chunk_size))
+}
+
+/// A chunk on its way to the write stage
+enum StagedChunk {
+    /// A chunk to store
+    Encoded(EncodedChunk),
+    /// A chunk the interrupted split being resumed already stored
+    Stored(ChunkInfo),
+}
+
+/// Bytes read at a time while filling a chunk
+const READ_BLOCK_SIZE: usize = 1 << 20;
+
+/// A chunk that has been hashed, compressed and encrypted, waiting to be written to its file
+struct EncodedChunk {
+    chunk_filename: String,
+    /// Bytes to store in the chunk file
+    data: Vec<u8>,
+    /// Size of the original (uncompressed) content
+    original_size: u64,
+    checksum: String,
+    nonce: Option<String>,
+    /// SHA-256 of `data`, computed when chunks are stored by content or deduplicated
+    digest: Option<String>,
+}
+
+impl EncodedChunk {
+    /// Stores the chunk in `sink` below `chunks_sub_dir` or in the chunk pool (see [`SplitOptions`]), returning
+    /// its recorded information and whether it had to be stored (`false` if an identical chunk already was)
+    fn write(self, sink: &dyn ChunkSink, chunks_sub_dir: &str, options: &SplitOptions) -> Result<(ChunkInfo, bool)> {
+        let recorded = self.chunk_info();
+        let digest = self.digest.as_deref();
+        let (dir, chunk_filename) = match (options.chunk_pool, digest) {
+            (Some(pool), Some(digest)) => (pool, format!("{}/{}", &digest[..2], digest)),
+            _ => (chunks_sub_dir, self.chunk_filename),
+        };
+        let chunk_name = format!("{}/{}", dir, chunk_filename);
+        let (stored, stored_as) = match (options.chunk_pool, options.dedup, digest) {
+            (Some(_), _, _) => {
+                let pooled = options.retry.run(options.checkpoint(), &sink.describe(&chunk_name), || sink.contains(&chunk_name))
+                    .with_context(|| format!("Failed to look up chunk file: {}", sink.describe(&chunk_name)))?;
+                (!pooled, None)
+            }
+            (None, Some(index), Some(digest)) => {
+                let stored_as = index.get(digest);
+                (stored_as.is_none(), stored_as)
+            }
+            _ => (true, None),
+        };
+        // A chunk referring to an identical one is wherever that one is
+        let destination = match stored_as {
+            Some(_) => None,
+            None => sink.place(&chunk_name)?.destination,
+        };
+        if stored {
+            options.retry.run(options.checkpoint(), &sink.describe(&chunk_name), || sink.put(&chunk_name, &self.data))
+                .with_context(|| format!("Failed to write chunk file: {}", sink.describe(&chunk_name)))?;
+            if let (Some(index), Some(digest)) = (options.dedup, digest) {
+                index.insert(digest, &chunk_name);
+            }
+        }
+
+        Ok((ChunkInfo { chunk_filename, stored_as, destination, ..recorded }, stored))
+    }
+
+    /// Information to record for the chunk if stored under its own name
+    fn chunk_info(&self) -> ChunkInfo {
+        ChunkInfo {
+            chunk_filename: self.chunk_filename.clone(),
+            chunk_size: self.data.len() as u64, // Record actual size (compressed or uncompressed)
+            original_size: Some(self.original_size),
+            chunk_checksum: Some(self.checksum.clone()), // Record checksum of original (uncompressed) data
+            nonce: self.nonce.clone(),
+            stored_as: None,
+            destination: None,
+        }
+    }
+}
+
+/// Reads the file in chunks of at most `size_limit` bytes, ending each where `boundary` allows, and
+/// sends them, named in order, to the encode stage
+///
+/// If given a `hasher`, feeds it everything read and returns the final whole-file digest. The reading waits at
+/// `checkpoint` while paused, and stops with an error once cancelled.
+fn read_chunks<R: Read>(
+    mut reader: R,
+    size_limit: u64,
+    boundary: ChunkBoundary,
+    namer: ChunkNamer,
+    mut hasher: Option<ChecksumHasher>,
+    checkpoint: Checkpoint,
+    chunks: mpsc::SyncSender<Result<(String, Cow<[u8]>)>>,
+) -> Option<String> {
+    let size_limit = size_limit as usize;
+    let mut chunk_index = 0; // Starts from 001
+    let mut chunk = Vec::new(); // Starts with whatever was read past the end of the previous chunk
+    let mut at_end = false;
+    loop {
+        // Fill the chunk up to size_limit, stopping early once it holds max_lines lines
+        let mut line_limit = boundary.line_limit();
+        let mut scanned = 0;
+        let mut end = None;
+        loop {
+            if let Some(line_end) = line_limit.as_mut().and_then(|limit| limit.scan(&chunk[scanned..])) {
+                end = Some(scanned + line_end);
+                break;
+            }
+            scanned = chunk.len();
+            if chunk.len() == size_limit || at_end {
+                break;
+            }
+            if let Err(e) = checkpoint.check() {
+                let _ = chunks.send(Err(e));
+                return None;
+            }
+            let filled = chunk.len();
+            chunk.resize(size_limit.min(filled + READ_BLOCK_SIZE), 0);
+            let bytes_read = match read_full(&mut reader, &mut chunk[filled..]) { // Read original data
+                Ok(bytes_read) => bytes_read,
+                Err(e) => {
+                    let _ = chunks.send(Err(e.into()));
+                    return None;
+                }
+            };
+            checkpoint.throttle(bytes_read as u64);
+            // If fewer bytes than requested were read, the file is exhausted
+            at_end = filled + bytes_read < chunk.len();
+            chunk.truncate(filled + bytes_read);
+        }
+        // An empty file still gets one (empty) chunk so that it can be restored
+        if chunk.is_empty() && chunk_index > 0 {
+            break;
+        }
+        let rest = chunk.split_off(end.unwrap_or_else(|| boundary.chunk_end(&chunk, at_end)));
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&chunk);
+        }
+        chunk_index += 1;
+        if chunks.send(Ok((namer.name(chunk_index), Cow::Owned(chunk)))).is_err() {
+            return None; // A later stage failed
+        }
+
+        if at_end && rest.is_empty() {
+            break;
+        }
+        chunk = rest;
+    }
+    hasher.map(|hasher| hasher.finalize())
+}
+
+/// Cuts the mapped file `map` into the same chunks [`read_chunks`] would read from it, and sends them to the
+/// encode stage as slices of the mapping, without copying them
+fn map_chunks<'a>(
+    map: &'a [u8],
+    size_limit: u64,
+    boundary: ChunkBoundary,
+    namer: ChunkNamer,
+    mut hasher: Option<ChecksumHasher>,
+    checkpoint: Checkpoint,
+    chunks: mpsc::SyncSender<Result<(String, Cow<'a, [u8]>)>>,
+) -> Option<String> {
+    let size_limit = usize::try_from(size_limit).unwrap_or(usize::MAX);
+    let mut start = 0;
+    let mut chunk_index = 0; // Starts from 001
+    // An empty file still gets one (empty) chunk so that it can be restored
+    while start < map.len() || chunk_index == 0 {
+        if let Err(e) = checkpoint.check() {
+            let _ = chunks.send(Err(e));
+            return None;
+        }
+        let window = &map[start..map.len().min(start.saturating_add(size_limit))];
+        // As when reading, only a window short of the size limit is known to hold the end of the file
+        let at_end = window.len() < size_limit;
+        let end = boundary.line_limit()
+            .and_then(|mut line_limit| line_limit.scan(window))
+            .unwrap_or_else(|| boundary.chunk_end(window, at_end));
+        let chunk = &window[..end];
+        checkpoint.throttle(chunk.len() as u64);
+        if let Some(hasher) = &mut hasher {
+            hasher.update(chunk);
+        }
+        chunk_index += 1;
+        if chunks.send(Ok((namer.name(chunk_index), Cow::Borrowed(chunk)))).is_err() {
+            return None; // A later stage failed
+        }
+        start += end;
+    }
+    hasher.map(|hasher| hasher.finalize())
+}
+
+/// Splits `input` into chunks of up to `size_limit` bytes, ending each where `boundary` allows, and encodes and
+/// stores each one while it is read
+///
+/// Unlike the pipeline in [`split_stream`], this never holds a chunk in memory, only a buffer of a fixed size,
+/// so any chunk size works on a machine with little memory; in exchange, one chunk is encoded at a time. Only
+/// chunks stored under their own names can be streamed (see [`held_in_memory`]): chunks stored by content need
+/// their digest before they are named, and parity chunks and resumed splits need the stored bytes. Where a chunk
+/// cut at lines or delimiters ends is found by reading ahead, so `input` must then be able to go back (see
+/// [`SplitInput::mark`]). Each chunk is placed before it is read, and a sink that limits the room there (see
+/// [`ChunkSink::limits_room`]) cuts it shorter. A chunk that fails to be stored is read again to retry it (see [`SplitOptions::retry`]),
+/// unless `input` is a stream.
+#[allow(clippy::too_many_arguments)]
+fn stream_chunks(
+    mut input: SplitInput,
+    size_limit: u64,
+    boundary: ChunkBoundary,
+    namer: ChunkNamer,
+    mut hasher: Option<ChecksumHasher>,
+    sink: &dyn ChunkSink,
+    chunks_sub_dir: &str,
+    options: &SplitOptions,
+    journal: &mut JournalSaver,
+    chunk_stored: &dyn Fn(&[ChunkInfo], u64),
+) -> Result<(WrittenChunks, Option<String>)> {
+    let mut chunks_info = Vec::new();
+    let mut total_bytes_processed = 0u64;
+    let mut buffer = vec![0u8; READ_BLOCK_SIZE];
+    let retry = match input.mark() {
+        Some(_) => options.retry,
+        None => RetryPolicy::default(),
+    };
+    loop {
+        let chunk_filename = namer.name(chunks_info.len() + 1);
+        let chunk_name = format!("{}/{}", chunks_sub_dir, chunk_filename);
+        // A retry reads the chunk again from where it started, hashing it over
+        let mark = input.mark();
+        let hashed_before = hasher.clone();
+        let first = chunks_info.is_empty();
+        // The chunk is placed before it is read, as where it goes may limit its size; where the input can tell, only
+        // once it is known to hold more, so that nothing is placed past its end
+        if let Some(mark) = mark.as_ref().filter(|_| !first) {
+            let more = read_full(&mut input, &mut buffer[..1])? > 0;
+            input.rewind(mark)?;
+            if !more {
+                break;
+            }
+        }
+        let placement = sink.place(&chunk_name)?;
+        let chunk_limit = placement.room.map_or(size_limit, |room| room.min(size_limit));
+        // Only a chunk that holds its limit is known to be followed by more of the input, unless it was read ahead
+        // to find its end
+        let (chunk_len, last) = match (&mark, boundary.by_bytes()) {
+            (Some(mark), false) => {
+                let scanned = scan_chunk(&mut input, &mut buffer, chunk_limit, boundary, options)?;
+                input.rewind(mark)?;
+                scanned
+            }
+            _ => (chunk_limit, false),
+        };
+        let chunk_info = retry.run(options.checkpoint(), &sink.describe(&chunk_name), || {
+            if let Some(mark) = &mark {
+                input.rewind(mark)?;
+                hasher.clone_from(&hashed_before);
+            }
+            let destination = placement.destination;
+            stream_chunk(&mut input, &mut buffer, chunk_len, &chunk_filename, &chunk_name, destination, hasher.as_mut(), sink, options, first)
+        })?;
+        let Some(chunk_info) = chunk_info else {
+            break;
+        };
+        let original_size = chunk_info.original_size.unwrap_or(0);
+        chunks_info.push(chunk_info);
+        total_bytes_processed += original_size;
+
+        journal.chunk_stored(&chunks_info)?;
+        chunk_stored(&chunks_info, total_bytes_processed);
+
+        if last || (boundary.by_bytes() && original_size < chunk_limit) {
+            break;
+        }
+    }
+    let written = WrittenChunks {
+        chunks_info,
+        total_bytes_processed,
+        reused_chunks: 0,
+        resumed_chunks: 0,
+        parity: None,
+        journaled: journal.saved(),
+        last_chunk: None,
+    };
+    Ok((written, hasher.map(|hasher| hasher.finalize())))
+}
+
+/// Streams the chunks of the local `file` (or of `map`, if it is mapped into memory) from its current position into
+/// their files like [`stream_chunks`], `threads` at a time
+///
+/// Each worker reads its chunk at its offset through its own buffer, so that the chunks are encoded in parallel
+/// while memory use stays small however large they are. The whole file is hashed in order alongside each batch of
+/// chunks, reading it once more (mostly from the cache, as the workers just read the same bytes).
+#[allow(clippy::too_many_arguments)]
+fn stream_file_chunks(
+    (mut file, map): (&File, Option<&[u8]>),
+    size_limit: u64,
+    threads: usize,
+    namer: ChunkNamer,
+    mut hasher: Option<ChecksumHasher>,
+    sink: &dyn ChunkSink,
+    chunks_sub_dir: &str,
+    options: &SplitOptions,
+    journal: &mut JournalSaver,
+    chunk_stored: &dyn Fn(&[ChunkInfo], u64),
+) -> Result<(WrittenChunks, Option<String>)> {
+    let (start, end) = match map {
+        Some(map) => (0, map.len() as u64),
+        None => (file.stream_position()?, file.metadata()?.len()),
+    };
+    // An empty file still gets one (empty) chunk so that it can be restored
+    let count = usize::try_from(end.saturating_sub(start).div_ceil(size_limit).max(1))?;
+    let range = |index: usize| {
+        let offset = (start + index as u64 * size_limit).min(end);
+        (offset, size_limit.min(end - offset))
+    };
+    let open = |offset: u64, len: u64| match map {
+        Some(map) => SplitInput::File { file, map: Some(&map[offset as usize..(offset + len) as usize]) },
+        None => SplitInput::Reader(Box::new(FileRange { file, offset, end: offset + len })),
+    };
+    let stream = |index: usize| -> Result<ChunkInfo> {
+        let chunk_filename = namer.name(index + 1);
+        let chunk_name = format!("{}/{}", chunks_sub_dir, chunk_filename);
+        let (offset, len) = range(index);
+        let mut buffer = vec![0u8; READ_BLOCK_SIZE];
+        // Chunks streamed in parallel go to storage that neither spreads them nor limits their room
+        let destination = sink.place(&chunk_name)?.destination;
+        let stored = options.retry.run(options.checkpoint(), &sink.describe(&chunk_name), || {
+            stream_chunk(&mut open(offset, len), &mut buffer, len, &chunk_filename, &chunk_name, destination, None, sink, options, true)
+        })?;
+        // Stored even if empty, as it counts as the first chunk
+        Ok(stored.expect("a chunk is stored even if empty"))
+    };
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+        .context("Failed to start worker threads")?;
+
+    let mut chunks_info = Vec::with_capacity(count);
+    let mut total_bytes_processed = 0u64;
+    for first in (0..count).step_by(threads) {
+        let batch = first..count.min(first + threads);
+        let (from, _) = range(batch.start);
+        let (last, last_len) = range(batch.end - 1);
+        let (stored, hashed) = thread::scope(|scope| {
+            let hashing = hasher.as_mut().map(|hasher| scope.spawn(move || -> Result<()> {
+                let mut input = open(from, last + last_len - from);
+                let mut buffer = vec![0u8; READ_BLOCK_SIZE];
+                loop {
+                    options.checkpoint().check()?;
+                    let filled = read_full(&mut input, &mut buffer)?;
+                    hasher.update(&buffer[..filled]);
+                    if filled < buffer.len() {
+                        return Ok(());
+                    }
+                }
+            }));
+            // Workers may finish out of order, but collecting keeps the chunks in file order
+            let stored = pool.install(|| batch.into_par_iter().map(stream).collect::<Result<Vec<_>>>());
+            let hashed = hashing.map_or(Ok(Ok(())), |hashing| hashing.join())
+                .map_err(|_| anyhow::anyhow!("Hashing thread panicked"));
+            (stored, hashed)
+        });
+        hashed??;
+        for chunk_info in stored? {
+            total_bytes_processed += chunk_info.original_size.unwrap_or(0);
+            chunks_info.push(chunk_info);
+
+            journal.chunk_stored(&chunks_info)?;
+            chunk_stored(&chunks_info, total_bytes_processed);
+        }
+    }
+    let written = WrittenChunks {
+        chunks_info,
+        total_bytes_processed,
+        reused_chunks: 0,
+        resumed_chunks: 0,
+        parity: None,
+        journaled: journal.saved(),
+        last_chunk: None,
+    };
+    Ok((written, hasher.map(|hasher| hasher.finalize())))
+}
+
+/// Part of a local file, read with positioned reads so that several parts can be read at once
+struct FileRange<'a> {
+    file: &'a File,
+    offset: u64,
+    end: u64,
+}
+
+impl Read for FileRange<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let want = buf.len().min(usize::try_from(self.end - self.offset).unwrap_or(usize::MAX));
+        let read = read_at(self.file, &mut buf[..want], self.offset)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+/// Reads from `file` at `offset`, leaving its position alone
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+/// Reads from `file` at `offset`; its position moves, but nothing here relies on it
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_at(_file: &File, _buf: &mut [u8], _offset: u64) -> std::io::Result<usize> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Reads ahead up to `size_limit` bytes of `input` through `buffer` to find where `boundary` ends the next chunk,
+/// as [`read_chunks`] would; returns its length, and whether it holds all that is left of the input
+fn scan_chunk(
+    input: &mut SplitInput,
+    buffer: &mut [u8],
+    size_limit: u64,
+    boundary: ChunkBoundary,
+    options: &SplitOptions,
+) -> Result<(u64, bool)> {
+    let mut scan = boundary.scan();
+    let mut read = 0u64;
+    while read < size_limit {
+        options.checkpoint().check()?;
+        let want = (size_limit - read).min(buffer.len() as u64) as usize;
+        let filled = read_full(&mut *input, &mut buffer[..want])?;
+        options.checkpoint().throttle(filled as u64);
+        if let Some(end) = scan.scan(&buffer[..filled]) {
+            return Ok((end, false));
+        }
+        read += filled as u64;
+        // If fewer bytes than requested were read, the input is exhausted
+        if filled < want {
+            return Ok((scan.end(true), true));
+        }
+    }
+    Ok((scan.end(false), false))
+}
+
+/// Reads the next chunk of up to `size_limit` bytes from `input` through `buffer`, and streams it into
+/// `chunk_name` in `sink`, recording it as stored at `destination` (see [`ChunkSink::place`])
+///
+/// Returns what was stored, or `None` at the end of the input; the `first` chunk is stored even then, as an empty
+/// file still gets one (empty) chunk so that it can be restored.
+#[allow(clippy::too_many_arguments)]
+fn stream_chunk(
+    input: &mut SplitInput,
+    buffer: &mut [u8],
+    size_limit: u64,
+    chunk_filename: &str,
+    chunk_name: &str,
+    destination: Option<usize>,
+    mut hasher: Option<&mut ChecksumHasher>,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    first: bool,
+) -> Result<Option<ChunkInfo>> {
+    let buffer_size = buffer.len() as u64;
+    let block = |remaining: u64| remaining.min(buffer_size) as usize;
+    options.checkpoint().check()?;
+    let mut filled = read_full(&mut *input, &mut buffer[..block(size_limit)])?;
+    options.checkpoint().throttle(filled as u64);
+    if filled == 0 && !first {
+        return Ok(None);
+    }
+
+    // Data flows through the codec first, then through encryption, then into the stored chunk
+    let mut stored = CountingWriter {
+        inner: sink.create(chunk_name)
+            .with_context(|| format!("Failed to write chunk file: {}", sink.describe(chunk_name)))?,
+        count: 0,
+    };
+    let (mut encrypted, nonce): (Box<dyn ChunkEncoder>, Option<String>) = match options.encryption {
+        Some(encryption) => encryption.writer(&mut stored)?,
+        None => (Box::new(StoredEncoder(&mut stored)), None),
+    };
+    let mut encoder: Box<dyn ChunkEncoder + '_> = match options.codec {
+        Some(codec) => codec.encoder(Box::new(&mut encrypted))?,
+        None => Box::new(StoredEncoder(&mut encrypted)),
+    };
+    let mut chunk_hasher = options.checksum_algorithm.hasher();
+    let mut original_size = 0u64;
+    while filled > 0 {
+        let data = &buffer[..filled];
+        chunk_hasher.update(data);
+        if let Some(hasher) = hasher.as_deref_mut() {
+            hasher.update(data);
+        }
+        encoder.write_all(data)
+            .with_context(|| format!("Failed to write chunk file: {}", sink.describe(chunk_name)))?;
+        original_size += filled as u64;
+        options.checkpoint().check()?;
+        filled = read_full(&mut *input, &mut buffer[..block(size_limit - original_size)])?;
+        options.checkpoint().throttle(filled as u64);
+    }
+    encoder.finish()
+        .and_then(|_| encrypted.finish())
+        .and_then(|_| stored.inner.finish())
+        .with_context(|| format!("Failed to write chunk file: {}", sink.describe(chunk_name)))?;
+
+    Ok(Some(ChunkInfo {
+        chunk_filename: chunk_filename.to_string(),
+        chunk_size: stored.count,
+        original_size: Some(original_size),
+        chunk_checksum: Some(options.checksum_algorithm.tag(&chunk_hasher.finalize())),
+        nonce,
+        stored_as: None,
+        destination,
+    }))
+}
+
+/// Stores the chunks of `file` with [`ChunkSink::copy_from`], so that their bytes are only read to hash them
+/// (from `map`, if the file is mapped into memory)
+///
+/// Returns `None`, having stored nothing, if `sink` cannot copy from a local file. Like [`stream_chunks`], this
+/// only works for splits by bytes whose chunks are stored under their own names, and the chunks must also be
+/// stored as they are, neither compressed nor encrypted.
+#[allow(clippy::too_many_arguments)]
+fn copy_chunks(
+    (mut file, map): (&File, Option<&[u8]>),
+    size_limit: u64,
+    namer: ChunkNamer,
+    mut hasher: Option<ChecksumHasher>,
+    sink: &dyn ChunkSink,
+    chunks_sub_dir: &str,
+    options: &SplitOptions,
+    journal: &mut JournalSaver,
+    chunk_stored: &dyn Fn(&[ChunkInfo], u64),
+) -> Result<Option<(WrittenChunks, Option<String>)>> {
+    let file_size = match map {
+        Some(map) => map.len() as u64,
+        None => file.metadata()?.len(),
+    };
+    let mut chunks_info = Vec::new();
+    let mut total_bytes_processed = 0u64;
+    let mut buffer = Vec::new();
+    // An empty file still gets one (empty) chunk so that it can be restored
+    while total_bytes_processed < file_size || chunks_info.is_empty() {
+        options.checkpoint().check()?;
+        let offset = total_bytes_processed;
+        let chunk_filename = namer.name(chunks_info.len() + 1);
+        let chunk_name = format!("{}/{}", chunks_sub_dir, chunk_filename);
+        let placement = sink.place(&chunk_name)?;
+        let len = placement.room.map_or(size_limit, |room| room.min(size_limit)).min(file_size - offset);
+        let copied = options.retry
+            .run(options.checkpoint(), &sink.describe(&chunk_name), || sink.copy_from(&chunk_name, file, offset, len))
+            .with_context(|| format!("Failed to write chunk file: {}", sink.describe(&chunk_name)))?;
+        if !copied {
+            return match chunks_info.is_empty() {
+                true => Ok(None),
+                false => Err(anyhow::anyhow!("Failed to write chunk file: {}: the storage stopped copying", sink.describe(&chunk_name))),
+            };
+        }
+
+        let mut chunk_hasher = options.checksum_algorithm.hasher();
+        let mut hash = |data: &[u8]| {
+            chunk_hasher.update(data);
+            if let Some(hasher) = &mut hasher {
+                hasher.update(data);
+            }
+        };
+        match map {
+            Some(map) => hash(&map[offset as usize..(offset + len) as usize]),
+            None => {
+                buffer.resize(READ_BLOCK_SIZE, 0);
+                file.seek(SeekFrom::Start(offset))?;
+                let mut remaining = len;
+                while remaining > 0 {
+                    options.checkpoint().check()?;
+                    let want = buffer.len().min(remaining as usize);
+                    file.read_exact(&mut buffer[..want])?;
+                    hash(&buffer[..want]);
+                    remaining -= want as u64;
+                }
+            }
+        }
+
+        chunks_info.push(ChunkInfo {
+            chunk_filename,
+            chunk_size: len,
+            original_size: Some(len),
+            chunk_checksum: Some(options.checksum_algorithm.tag(&chunk_hasher.finalize())),
+            nonce: None,
+            stored_as: None,
+            destination: placement.destination,
+        });
+        total_bytes_processed += len;
+
+        journal.chunk_stored(&chunks_info)?;
+        chunk_stored(&chunks_info, total_bytes_processed);
+    }
+    let written = WrittenChunks {
+        chunks_info,
+        total_bytes_processed,
+        reused_chunks: 0,
+        resumed_chunks: 0,
+        parity: None,
+        journaled: journal.saved(),
+        last_chunk: None,
+    };
+    Ok(Some((written, hasher.map(|hasher| hasher.finalize()))))
+}
+
+/// Writer that counts the bytes written through it
+struct CountingWriter<W: Write> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hashes, compresses and encrypts one chunk in memory, behind `header` with the chunk's checksum filled in
+fn encode_chunk(chunk_filename: &str, data: &[u8], header: Option<ChunkHeader>, options: &SplitOptions) -> Result<EncodedChunk> {
+    let checksum = options.checksum_algorithm.tag(&calculate_buffer_checksum_with(data, options.checksum_algorithm));
+
+    // Data flows through the codec first, then through encryption, then into the chunk buffer
+    let mut encoded = header
+        .map(|header| ChunkHeader { checksum: checksum.clone(), ..header }.to_bytes())
+        .unwrap_or_default();
+    let (mut sink, nonce): (Box<dyn ChunkEncoder>, Option<String>) = match options.encryption {
+        Some(encryption) => encryption.writer(&mut encoded)?,
+        None => (Box::new(StoredEncoder(&mut encoded)), None),
+    };
+    let mut encoder: Box<dyn ChunkEncoder + '_> = match options.codec {
+        Some(codec) => codec.encoder(Box::new(&mut sink))?,
+        None => Box::new(StoredEncoder(&mut sink)),
+    };
+    encoder.write_all(data)?;
+    encoder.finish()
+        .and_then(|_| sink.finish())
+        .with_context(|| format!("Failed to encode chunk: {}", chunk_filename))?;
+
+    // Chunks stored by content are named and matched by the digest of the bytes actually stored
+    let digest = (options.chunk_pool.is_some() || options.dedup.is_some())
+        .then(|| calculate_buffer_checksum_with(&encoded, ChecksumAlgorithm::Sha256));
+
+    Ok(EncodedChunk {
+        chunk_filename: chunk_filename.to_string(),
+        data: encoded,
+        original_size: data.len() as u64,
+        checksum,
+        nonce,
+        digest,
+    })
+}
+
+/// Reads until `buffer` is full or the reader is exhausted, returning the number of bytes read
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Options controlling how a file is restored
+#[derive(Clone, Copy, Default)]
+pub struct RestoreOptions<'a> {
+    /// Codec to decompress the sub-files with. Required if they were written with a custom [`ChunkCodec`];
+    /// `None` uses the built-in algorithm recorded in the split info.
+    pub codec: Option<&'a dyn ChunkCodec>,
+    /// Key or age identities to decrypt the sub-files with. Required if the split set is encrypted.
+    pub decryption: Option<&'a Decryption>,
+    /// Number of chunks decompressed, verified and written concurrently (0 or 1 restores one chunk at a time;
+    /// [`available_threads`] gives one worker per CPU core). Workers decode a batch of chunks at a time, which are
+    /// then written in order, so the checksum of the whole file is computed from the data as it is written either
+    /// way, without reading the restored file again.
+    pub threads: usize,
+    /// Bytes the workers may hold in memory at most (e.g., on a NAS with little memory): fewer chunks are then
+    /// restored or verified concurrently than `threads` asks for, but always at least one. Every worker holds a whole
+    /// chunk, except that a single one restoring into a file only holds a small buffer.
+    /// Rebuilding a chunk from parity chunks holds the chunks of its parity group on top. `None` sets no limit.
+    pub max_memory: Option<u64>,
+    /// Continue an interrupted restore: the leading chunks an existing output file already holds (checked against
+    /// their checksums) are kept, and only the rest is restored. Ignored when restoring into a writer.
+    pub resume: bool,
+    /// Whether an existing output file may be replaced. Otherwise the restore fails up front if the output file, or
+    /// the directory a directory tree unpacks into, already exists, unless it resumes (see `resume`).
+    pub overwrite: bool,
+    /// Skip over all-zero blocks of the restored content instead of writing them, so that the output file is
+    /// sparse (e.g., for disk images) on file systems that support it. Ignored when restoring into a writer.
+    pub sparse: bool,
+    /// Only warn about chunks and restored files whose checksums do not match, and keep their damaged content,
+    /// instead of failing the restore. Damaged chunks that can be rebuilt from parity chunks always are.
+    pub warn_only: bool,
+    /// Flag to abort the restore with: once it is set (e.g., from another thread), the restore stops after the
+    /// buffer or chunk at hand and fails. A partly restored file is removed, while a directory tree keeps the files
+    /// unpacked so far.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Gate to pause the restore with: while it is paused, the restore waits after the buffer or chunk at hand
+    pub pause: Option<&'a PauseGate>,
+    /// Limit on the bytes per second of restored content written, shared by the workers (see `threads`)
+    pub rate_limit: Option<&'a RateLimiter>,
+    /// How chunk reads that fail with an I/O error are tried again, before a chunk is rebuilt from parity chunks
+    /// or the restore fails
+    pub retry: RetryPolicy,
+}
+
+impl<'a> RestoreOptions<'a> {
+    fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint { cancel: self.cancel, pause: self.pause, rate_limit: self.rate_limit }
+    }
+
+    /// Number of workers to run, each holding `held` bytes, within `threads` and the memory budget
+    pub(crate) fn workers(&self, held: u64) -> usize {
+        let threads = self.threads.max(1);
+        match self.max_memory {
+            Some(max_memory) => threads.min(usize::try_from(max_memory / held.max(1)).unwrap_or(usize::MAX)).max(1),
+            None => threads,
+        }
+    }
+}
+
+/// What restoring a file did, as returned by [`restore_single_file`]
+#[derive(Debug, Clone)]
+pub struct RestoreReport {
+    /// Path of the restored file, or the directory a directory tree was unpacked into
+    pub output_path: PathBuf,
+    /// Bytes of original content restored; chunks kept by [`RestoreOptions::resume`] are not counted
+    pub bytes_written: u64,
+    /// Chunks whose content matched their recorded checksum, including those kept by [`RestoreOptions::resume`]
+    /// and those rebuilt from parity chunks
+    pub chunks_verified: usize,
+    /// Chunks whose content did not match their recorded checksum but was restored anyway, which only
+    /// [`RestoreOptions::warn_only`] allows
+    pub chunks_failed: usize,
+    /// How long the restore took
+    pub duration: Duration,
+}
+
+/// Restores a single file
+///
+/// `file_info`: Split information for the file to restore.
+/// `input_root_dir`: Root directory where the split sub-files are located (or the info file, if they were spread
+/// across several directories, see [`SplitInfo::destinations`]).
+/// `output_dir`: Directory where the restored large file will be saved.
+/// `options`: Codec, decryption and threading settings (see [`RestoreOptions`]).
+/// `progress_callback`: Optional callback for reporting progress (current_bytes, total_bytes).
+/// `message_callback`: Optional callback for reporting messages (message string).
+///
+/// Returns where the file was restored and how many of its chunks were verified (see [`RestoreReport`]).
+/// To pass plain closures instead of boxed ones, use [`restore_single_file_with`].
+pub fn restore_single_file(
     file_info: &SplitInfo,
     input_root_dir: &Path,
     output_dir: &Path,
+    options: &RestoreOptions,
     progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + 'static>>,
     message_callback: Option<Box<dyn Fn(String) + Send + Sync + 'static>>,
-) -> Result<()> {
-    let output_path = output_dir.join(&file_info.original_filename); // This returns PathBuf
-    let mut output_file = BufWriter::new(File::create(&output_path)
-        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?);
-    
-    if let Some(cb) = &message_callback {
-        cb(format!("Restoring '{}'", file_info.original_filename));
-    }
+) -> Result<RestoreReport> {
+    check_chunk_dir(file_info, input_root_dir)?;
+    restore_file_from_source(file_info, &SpreadSink::for_split_set(input_root_dir, file_info), output_dir, options, progress_callback, message_callback)
+}
 
-    let mut total_written = 0u64;
+/// Restores a single file like [`restore_single_file`], reporting to the closures `on_progress`
+/// (current_bytes, total_bytes) and `on_message`
+///
+/// The closures are called one at a time, and are dropped when the restore returns, so they may borrow from
+/// the caller.
+pub fn restore_single_file_with<P, M>(
+    file_info: &SplitInfo,
+    input_root_dir: &Path,
+    output_dir: &Path,
+    options: &RestoreOptions,
+    on_progress: P,
+    on_message: M,
+) -> Result<RestoreReport>
+where
+    P: FnMut(u64, u64) + Send,
+    M: FnMut(String) + Send,
+{
+    check_chunk_dir(file_info, input_root_dir)?;
+    let on_progress = Mutex::new(on_progress);
+    let on_message = Mutex::new(on_message);
+    restore_file_from_source(
+        file_info,
+        &SpreadSink::for_split_set(input_root_dir, file_info),
+        output_dir,
+        options,
+        Some(Box::new(|current, total| on_progress.lock().unwrap()(current, total))),
+        Some(Box::new(|msg| on_message.lock().unwrap()(msg))),
+    )
+}
 
-    // Locate the subdirectory containing chunks for the current file
-    let chunks_input_dir = input_root_dir.join(&file_info.chunks_sub_dir); // This returns PathBuf
-    if !chunks_input_dir.exists() {
-        return Err(anyhow::anyhow!(
-            "Chunk directory for file '{}' not found: {}",
-            file_info.original_filename,
-            chunks_input_dir.display()
-        ));
+/// Restores a single file from chunks stored in `source` (see [`ChunkSource`]) into `output_dir`
+///
+/// Works like [`restore_single_file`], reading the chunks below `file_info.chunks_sub_dir` in `source`.
+/// The restored file gets the recorded permissions and modification time (see [`SplitInfo::metadata`]).
+/// A directory tree split by [`split_directory_to_sink`] is unpacked into `output_dir` instead.
+pub fn restore_file_from_source(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    output_dir: &Path,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + '_>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + '_>>,
+) -> Result<RestoreReport> {
+    let mut written = false;
+    let restored = restore_file(file_info, source, output_dir, options, progress_callback, message_callback, &mut written);
+    // A failed restore leaves no partial file behind to be mistaken for a good one, unless a later one is to resume it
+    if restored.is_err() && written && (!options.resume || options.checkpoint().cancelled()) {
+        tracing::info!(file = %file_info.original_filename, "restore failed, removing the partly restored file");
+        let _ = fs::remove_file(file_info.restored_path(output_dir));
     }
+    restored
+}
+
+/// Fails if `path` already exists and the restore may not replace it (see [`RestoreOptions::overwrite`])
+pub(crate) fn check_overwrite(path: &Path, options: &RestoreOptions) -> Result<()> {
+    if !options.overwrite && !options.resume && fs::symlink_metadata(path).is_ok() {
+        return Err(overwrite_refused(&path.display().to_string()));
+    }
+    Ok(())
+}
 
-    for chunk_info in &file_info.chunks {
-        let chunk_path = chunks_input_dir.join(&chunk_info.chunk_filename); // This returns PathBuf
-        let chunk_file = File::open(&chunk_path)
-            .with_context(|| format!("Failed to open chunk file: {}", chunk_path.display()))?;
-        
-        let mut decompressed_data = Vec::new();
-        let bytes_read_current_chunk_decompressed;
+/// Error for an existing file that may not be replaced
+fn overwrite_refused(location: &str) -> anyhow::Error {
+    anyhow::anyhow!("Refusing to overwrite {}, which already exists (allow overwriting, e.g. with --force, to replace it)", location)
+}
 
-        if file_info.is_compressed {
-            let mut decoder = GzDecoder::new(chunk_file);
-            bytes_read_current_chunk_decompressed = decoder.read_to_end(&mut decompressed_data)?;
+/// Does the work of [`restore_file_from_source`], setting `written` once the output file is opened for writing
+fn restore_file(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    output_dir: &Path,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + '_>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + '_>>,
+    written: &mut bool,
+) -> Result<RestoreReport> {
+    // Chunks restored by worker threads name the span as their parent, as they do not run inside it
+    let span = tracing::info_span!("restore", file = %file_info.original_filename, size = file_info.original_file_size);
+    let _entered = span.enter();
+    let started = Instant::now();
+    if file_info.archive.is_some() {
+        let tally = archive::unpack_from_source(file_info, source, output_dir, options, progress_callback, message_callback)?;
+        return Ok(tally.report(output_dir.to_path_buf(), started));
+    }
+    // Workers hold the whole chunk they decoded, so that the chunks are written and hashed in order; a single one
+    // copies its chunk through one buffer
+    let threads = options.workers(file_info.chunk_limit);
+    let (codec, decryption) = resolve_chunk_readers(file_info, options)?;
+
+    if let Some(path) = file_info.path.as_deref().filter(|path| !is_relative_key(path)) {
+        return Err(anyhow::anyhow!("Invalid path for '{}': {}", file_info.original_filename, path));
+    }
+    let output_path = file_info.restored_path(output_dir);
+    check_overwrite(&output_path, options)?;
+    if file_info.path.is_some() {
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+        }
+    }
+    let layout = chunk_layout(file_info);
+    // Restoring one chunk after the other hashes the whole file on the way, so it need not be read again
+    let (algorithm, expected_digest) = parse_checksum(&file_info.original_checksum, file_info.checksum_algorithm)?;
+    let mut file_hasher = algorithm.hasher();
+    let resumed = if options.resume && output_path.is_file() {
+        restored_chunks(file_info, &layout, &output_path, &mut file_hasher)?
+    } else {
+        0
+    };
+    // Everything up to the first chunk still to restore is kept
+    let resume_offset = layout.get(resumed).map_or(file_info.original_file_size, |&(offset, _)| offset);
+    let output_file = if resumed > 0 {
+        fs::OpenOptions::new().write(true).open(&output_path)
+            .with_context(|| format!("Failed to open output file: {}", output_path.display()))?
+    } else {
+        File::create(&output_path)
+            .with_context(|| format!("Failed to create output file: {}", output_path.display()))?
+    };
+    *written = true;
+    
+    tracing::info!(chunks = file_info.chunks.len(), resumed, "restore started");
+    if let Some(cb) = &message_callback {
+        if resumed > 0 {
+            cb(format!("Resuming '{}' after {} of {} chunks", file_info.original_filename, resumed, file_info.chunks.len()));
         } else {
-            let mut reader = BufReader::new(chunk_file);
-            bytes_read_current_chunk_decompressed = reader.read_to_end(&mut decompressed_data)?;
+            cb(format!("Restoring '{}'", file_info.original_filename));
         }
-        
-        // Verify checksum of the original (uncompressed) chunk data (if available)
-        if let Some(expected_checksum) = &chunk_info.chunk_checksum {
-            let actual_checksum = calculate_buffer_checksum(&decompressed_data[..bytes_read_current_chunk_decompressed]);
-            if actual_checksum != *expected_checksum {
-                eprintln!("Warning: Checksum mismatch for chunk '{}'! Expected: {}, Actual: {}", 
-                          chunk_info.chunk_filename, expected_checksum, actual_checksum);
-                // You can choose to return an error here, or continue, depending on data integrity requirements
-            }
+    }
+
+    // Reserving the space once the file is sized (truncating releases it) surfaces a full disk now rather than
+    // hours into the restore; a sparse file would lose its holes
+    let preallocate = |output_file: &File| -> Result<()> {
+        if options.sparse {
+            return Ok(());
         }
+        preallocate::preallocate(output_file, file_info.original_file_size).with_context(|| format!(
+            "Not enough disk space to restore '{}' ({} bytes) to {}",
+            file_info.original_filename,
+            file_info.original_file_size,
+            output_path.display()
+        ))
+    };
 
-        output_file.write_all(&decompressed_data[..bytes_read_current_chunk_decompressed])?;
-        total_written += bytes_read_current_chunk_decompressed as u64;
-        
-        if let Some(cb) = &progress_callback {
-            cb(total_written, file_info.original_file_size);
+    let recovery = ParityRecovery::new(file_info, source, codec, decryption);
+    let restore = |index: usize, output: &mut BufWriter<File>, file_hasher: &mut ChecksumHasher| {
+        let _chunk = tracing::debug_span!(parent: &span, "restore_chunk", index).entered();
+        let restored = restore_chunk(file_info, source, index, codec, decryption, recovery.as_ref(), options, output, Some(file_hasher));
+        if let Ok((len, check)) = &restored {
+            tracing::debug!(size = len, verified = matches!(check, ChunkCheck::Verified), "chunk restored");
         }
+        restored
+    };
+    let read = |index: usize| {
+        let _chunk = tracing::debug_span!(parent: &span, "read_chunk", index).entered();
+        options.checkpoint().check()?;
+        read_chunk(file_info, source, index, codec, decryption, recovery.as_ref(), options)
+            .with_context(|| format!("Failed to restore '{}' to {}", file_info.chunks[index].chunk_filename, output_path.display()))
+    };
+    // Kept chunks were checked against their checksums
+    let mut tally = ChunkTally { verified: resumed, ..Default::default() };
+
+    let pool = if threads > 1 && file_info.chunks.len() - resumed > 1 {
+        Some(rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+            .context("Failed to start worker threads")?)
+    } else {
+        None
+    };
+    // Drop whatever follows the kept chunks, as it may be a partly written chunk
+    output_file.set_len(resume_offset)
+        .with_context(|| format!("Failed to truncate output file: {}", output_path.display()))?;
+    preallocate(&output_file)?;
+    let mut output_file = BufWriter::new(output_file);
+    output_file.seek(SeekFrom::Start(resume_offset))?;
+    match &pool {
+        // Chunks are decoded a batch at a time, then written and hashed in file order
+        Some(pool) => for start in (resumed..file_info.chunks.len()).step_by(threads) {
+            let batch = start..file_info.chunks.len().min(start + threads);
+            // Workers may finish out of order, but collecting keeps the chunks in file order
+            let decoded = pool.install(|| batch.into_par_iter().map(read).collect::<Result<Vec<_>>>())?;
+            for (data, check) in decoded {
+                write_output(&mut output_file, &data, options.sparse)
+                    .with_context(|| format!("Failed to write output file: {}", output_path.display()))?;
+                file_hasher.update(&data);
+                options.checkpoint().throttle(data.len() as u64);
+                tally.count(data.len() as u64, check);
+
+                if let Some(cb) = &progress_callback {
+                    cb(resume_offset + tally.bytes, file_info.original_file_size);
+                }
+            }
+        },
+        None => for index in resumed..file_info.chunks.len() {
+            let (len, check) = restore(index, &mut output_file, &mut file_hasher)
+                .with_context(|| format!("Failed to restore '{}' to {}", file_info.chunks[index].chunk_filename, output_path.display()))?;
+            tally.count(len, check);
+
+            if let Some(cb) = &progress_callback {
+                cb(resume_offset + tally.bytes, file_info.original_file_size);
+            }
+        },
     }
-    
+    let total_written = resume_offset + tally.bytes;
+
     output_file.flush()?;
+    if options.sparse {
+        // Skipping trailing zeros leaves the file short
+        output_file.get_ref().set_len(total_written)
+            .with_context(|| format!("Failed to write output file: {}", output_path.display()))?;
+    }
+
     if let Some(cb) = &message_callback {
         cb(format!("'{}' restoration complete", file_info.original_filename));
     }
@@ -257,29 +2645,692 @@ pub fn restore_single_file(
     let restored_file = File::open(&output_path)?;
     let restored_size = restored_file.metadata()?.len();
     if restored_size != file_info.original_file_size {
-        return Err(anyhow::anyhow!(
+        return Err(Failure::new(FailureKind::Verification, format!(
             "Restored file size mismatch: Expected {}, Actual {}",
             file_info.original_file_size,
             restored_size
-        ));
+        )).into());
     }
 
     // Verify original file checksum
-    let actual_original_checksum = calculate_checksum(&output_path)?;
-    if actual_original_checksum != file_info.original_checksum {
-        eprintln!("Warning: Original checksum mismatch for restored file '{}'! Expected: {}, Actual: {}", 
-                  file_info.original_filename, file_info.original_checksum, actual_original_checksum);
-        // You can choose to return an error here
+    check_original_checksum(file_info, &file_hasher.finalize(), expected_digest, options)?;
+
+    if let Some(metadata) = &file_info.metadata {
+        metadata.apply(&output_path)?;
     }
 
+    tracing::info!(bytes = tally.bytes, verified = tally.verified, failed = tally.failed, "restore finished");
+    Ok(tally.report(output_path, started))
+}
+
+/// Blocks of restored content checked for being all zeros when restoring sparse files; the usual file system block size
+const SPARSE_BLOCK_SIZE: usize = 4096;
+
+/// Writes `data` at the current position of `output`, seeking over all-zero blocks instead if `sparse` is set
+///
+/// Skipped blocks read as zeros only if nothing was written there before, and trailing ones do not extend the file.
+fn write_output<W: Write + Seek>(output: &mut W, data: &[u8], sparse: bool) -> std::io::Result<()> {
+    if !sparse {
+        return output.write_all(data);
+    }
+    let mut zeros = 0;
+    for block in data.chunks(SPARSE_BLOCK_SIZE) {
+        if block.iter().all(|&byte| byte == 0) {
+            zeros += block.len() as i64;
+            continue;
+        }
+        if zeros > 0 {
+            output.seek(SeekFrom::Current(zeros))?;
+            zeros = 0;
+        }
+        output.write_all(block)?;
+    }
+    if zeros > 0 {
+        output.seek(SeekFrom::Current(zeros))?;
+    }
     Ok(())
 }
 
+/// Number of leading chunks of `layout` the file at `path` already holds, checked against their checksums
+///
+/// Chunks without a recorded checksum cannot be checked, so they end the count.
+fn restored_chunks(file_info: &SplitInfo, layout: &[(u64, u64)], path: &Path, file_hasher: &mut ChecksumHasher) -> Result<usize> {
+    let mut file = BufReader::new(File::open(path)
+        .with_context(|| format!("Failed to open output file: {}", path.display()))?);
+    let file_size = file.get_ref().metadata()?.len();
+    let mut buffer = vec![0u8; READ_BLOCK_SIZE];
+    for (index, (chunk_info, &(offset, len))) in file_info.chunks.iter().zip(layout).enumerate() {
+        let Some(checksum) = &chunk_info.chunk_checksum else {
+            return Ok(index);
+        };
+        if offset + len > file_size {
+            return Ok(index);
+        }
+        let (algorithm, expected_digest) = parse_checksum(checksum, file_info.checksum_algorithm)?;
+        let mut hasher = algorithm.hasher();
+        let hashed_before = file_hasher.clone();
+        let mut remaining = len;
+        while remaining > 0 {
+            let want = buffer.len().min(remaining as usize);
+            file.read_exact(&mut buffer[..want])
+                .with_context(|| format!("Failed to read output file: {}", path.display()))?;
+            hasher.update(&buffer[..want]);
+            file_hasher.update(&buffer[..want]);
+            remaining -= want as u64;
+        }
+        if hasher.finalize() != expected_digest {
+            *file_hasher = hashed_before;
+            return Ok(index);
+        }
+    }
+    Ok(file_info.chunks.len())
+}
+
+/// Offset in the original file and original size of every chunk
+fn chunk_layout(file_info: &SplitInfo) -> Vec<(u64, u64)> {
+    let mut offset = 0;
+    file_info.chunks.iter().map(|chunk_info| {
+        // Older info files do not record chunk sizes, but every chunk but the last holds exactly `chunk_limit` bytes
+        let len = chunk_info.original_size
+            .unwrap_or_else(|| file_info.chunk_limit.min(file_info.original_file_size.saturating_sub(offset)));
+        let chunk = (offset, len);
+        offset += len;
+        chunk
+    }).collect()
+}
+
+/// Restores a single file into `writer` (e.g., stdout) instead of a file in a directory
+///
+/// Chunks are written in order as they are decoded (up to `options.threads` chunks decoded at a time),
+/// and the whole-file checksum is verified from the written data, so nothing is written to disk.
+///
+/// `file_info`: Split information for the file to restore.
+/// `input_root_dir`: Root directory where the split sub-files are located (or the info file, if they were spread
+/// across several directories, see [`SplitInfo::destinations`]).
+/// `writer`: Destination for the restored content.
+/// `options`: Codec, decryption and threading settings (see [`RestoreOptions`]).
+/// `progress_callback`: Optional callback for reporting progress (current_bytes, total_bytes).
+/// `message_callback`: Optional callback for reporting messages (message string).
+pub fn restore_to_writer<W: Write>(
+    file_info: &SplitInfo,
+    input_root_dir: &Path,
+    writer: W,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + 'static>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + 'static>>,
+) -> Result<()> {
+    check_chunk_dir(file_info, input_root_dir)?;
+    restore_from_source(file_info, &SpreadSink::for_split_set(input_root_dir, file_info), writer, options, progress_callback, message_callback)
+}
+
+/// Restores a single file from chunks stored in `source` (see [`ChunkSource`]) into `writer`
+///
+/// Works like [`restore_to_writer`], reading the chunks below `file_info.chunks_sub_dir` in `source`.
+pub fn restore_from_source<W: Write>(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    writer: W,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + 'static>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + 'static>>,
+) -> Result<()> {
+    restore_stream(file_info, source, writer, options, progress_callback, message_callback).map(|_| ())
+}
+
+/// Restores a single file from chunks stored in `source` into `writer`, counting the chunks checked on the way
+pub(crate) fn restore_stream<W: Write>(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    writer: W,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + '_>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + '_>>,
+) -> Result<ChunkTally> {
+    // Chunks read by worker threads name the span as their parent, as they do not run inside it
+    let span = tracing::info_span!("restore_stream", file = %file_info.original_filename, size = file_info.original_file_size);
+    let _entered = span.enter();
+    // Each worker holds the whole chunk it decoded
+    let threads = options.workers(file_info.chunk_limit);
+    let (codec, decryption) = resolve_chunk_readers(file_info, options)?;
+    let (algorithm, expected_digest) = parse_checksum(&file_info.original_checksum, file_info.checksum_algorithm)?;
+
+    tracing::info!(chunks = file_info.chunks.len(), "restore started");
+    if let Some(cb) = &message_callback {
+        cb(format!("Restoring '{}'", file_info.original_filename));
+    }
+
+    let pool = if threads > 1 {
+        Some(rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+            .context("Failed to start worker threads")?)
+    } else {
+        None
+    };
+
+    let mut writer = BufWriter::new(writer);
+    let mut hasher = algorithm.hasher();
+    let mut tally = ChunkTally::default();
+    let recovery = ParityRecovery::new(file_info, source, codec, decryption);
+    let read = |index: usize| {
+        let _chunk = tracing::debug_span!(parent: &span, "read_chunk", index).entered();
+        options.checkpoint().check()?;
+        read_chunk(file_info, source, index, codec, decryption, recovery.as_ref(), options)
+    };
+    for start in (0..file_info.chunks.len()).step_by(threads) {
+        let batch = start..file_info.chunks.len().min(start + threads);
+        // Workers may finish out of order, but collecting keeps the chunks in file order
+        let decoded = match &pool {
+            Some(pool) => pool.install(|| batch.into_par_iter().map(read).collect::<Result<Vec<_>>>())?,
+            None => batch.map(read).collect::<Result<Vec<_>>>()?,
+        };
+        for (data, check) in decoded {
+            hasher.update(&data);
+            writer.write_all(&data).context("Failed to write restored data")?;
+            options.checkpoint().throttle(data.len() as u64);
+            tally.count(data.len() as u64, check);
+
+            if let Some(cb) = &progress_callback {
+                cb(tally.bytes, file_info.original_file_size);
+            }
+        }
+    }
+    writer.flush().context("Failed to write restored data")?;
+
+    if let Some(cb) = &message_callback {
+        cb(format!("'{}' restoration complete", file_info.original_filename));
+    }
+
+    // Verify restored size
+    if tally.bytes != file_info.original_file_size {
+        return Err(Failure::new(FailureKind::Verification, format!(
+            "Restored file size mismatch: Expected {}, Actual {}",
+            file_info.original_file_size,
+            tally.bytes
+        )).into());
+    }
+
+    // Verify original file checksum
+    check_original_checksum(file_info, &hasher.finalize(), expected_digest, options)?;
+    tracing::info!(bytes = tally.bytes, verified = tally.verified, failed = tally.failed, "restore finished");
+    Ok(tally)
+}
+
+/// Fails if the whole-file checksum of restored content does not match the recorded one, or only warns with
+/// [`RestoreOptions::warn_only`]
+fn check_original_checksum(file_info: &SplitInfo, actual_digest: &str, expected_digest: &str, options: &RestoreOptions) -> Result<()> {
+    if actual_digest == expected_digest {
+        return Ok(());
+    }
+    if !options.warn_only {
+        return Err(Failure::new(FailureKind::Verification, format!(
+            "Original checksum mismatch for restored file '{}'! Expected: {}, Actual: {}",
+            file_info.original_filename,
+            file_info.original_checksum,
+            actual_digest
+        )).into());
+    }
+    eprintln!("Warning: Original checksum mismatch for restored file '{}'! Expected: {}, Actual: {}",
+              file_info.original_filename, file_info.original_checksum, actual_digest);
+    Ok(())
+}
+
+/// Checks the supplied decryption and codec against `file_info`, returning the ones to read its chunks with
+fn resolve_chunk_readers<'a>(
+    file_info: &SplitInfo,
+    options: &RestoreOptions<'a>,
+) -> Result<(Option<&'a dyn ChunkCodec>, Option<&'a Decryption>)> {
+    let decryption = match (&file_info.encryption, options.decryption) {
+        (Some(encryption), Some(decryption)) => {
+            decryption.verify(encryption)?;
+            Some(decryption)
+        }
+        (Some(encryption), None) => {
+            return Err(anyhow::anyhow!(
+                "File '{}' is encrypted ({}); a decryption key or identity is required to restore it",
+                file_info.original_filename,
+                encryption.algorithm
+            ));
+        }
+        (None, _) => None,
+    };
+
+    let codec: Option<&dyn ChunkCodec> = match (file_info.codec_name(), options.codec) {
+        (None, _) => None,
+        (Some(expected), Some(codec)) if codec.name() != expected => {
+            return Err(anyhow::anyhow!(
+                "Codec mismatch for file '{}': chunks were written with '{}', but '{}' was supplied",
+                file_info.original_filename,
+                expected,
+                codec.name()
+            ));
+        }
+        (Some(_), Some(codec)) => Some(codec),
+        // Built-in algorithms are looked up in the static list so the codec outlives this call
+        (Some(expected), None) => match file_info.compression() {
+            Some(algorithm) => CompressionAlgorithm::ALL.iter().find(|&&a| a == algorithm).map(|a| a as &dyn ChunkCodec),
+            None => {
+                return Err(anyhow::anyhow!(
+                    "File '{}' was compressed with custom codec '{}'; supply it to restore",
+                    file_info.original_filename,
+                    expected
+                ));
+            }
+        },
+    };
+
+    Ok((codec, decryption))
+}
+
+/// Checks that a restored file has the size and checksum recorded in its split info
+pub fn check_restored_file(file_info: &SplitInfo, path: &Path) -> Result<()> {
+    if file_info.archive.is_some() {
+        return Err(anyhow::anyhow!(
+            "'{}' was restored as a directory tree, which cannot be checked against the split set's checksum",
+            path.display()
+        ));
+    }
+    let size = fs::metadata(path)
+        .with_context(|| format!("Failed to read restored file: {}", path.display()))?
+        .len();
+    if size != file_info.original_file_size {
+        return Err(Failure::new(FailureKind::Verification, format!(
+            "Restored file size mismatch for '{}': Expected {}, Actual {}",
+            path.display(),
+            file_info.original_file_size,
+            size
+        )).into());
+    }
+    let (algorithm, expected_digest) = parse_checksum(&file_info.original_checksum, file_info.checksum_algorithm)?;
+    let actual_checksum = calculate_checksum_with(path, algorithm)?;
+    if actual_checksum != expected_digest {
+        return Err(Failure::new(FailureKind::Verification, format!(
+            "Restored file checksum mismatch for '{}': Expected {}, Actual {}",
+            path.display(),
+            file_info.original_checksum,
+            algorithm.tag(&actual_checksum)
+        )).into());
+    }
+    Ok(())
+}
+
+/// Deletes the chunk files of a split set below `input_root_dir` (or the directories they were spread to, see
+/// [`SplitInfo::chunk_root`]), along with its parity chunks and PAR2 files
+///
+/// Chunks in a pool or shared with another split set are kept, as other split sets may still refer to them.
+/// Chunk files that are already gone are skipped. Returns the paths of the deleted files.
+pub fn remove_split_set(file_info: &SplitInfo, input_root_dir: &Path) -> Result<Vec<PathBuf>> {
+    let chunks_dir = input_root_dir.join(&file_info.chunks_sub_dir);
+    let mut files: Vec<_> = file_info.chunks.iter()
+        .filter(|chunk| file_info.chunk_pool.is_none() && chunk.stored_as.is_none())
+        .map(|chunk| file_info.chunk_root(input_root_dir, chunk).join(&file_info.chunks_sub_dir).join(&chunk.chunk_filename))
+        .chain(file_info.parity.iter().flat_map(|parity| &parity.chunks).map(|chunk| chunks_dir.join(&chunk.chunk_filename)))
+        .collect();
+    // PAR2 files are "<name>.par2" and volumes "<name>.volXX+YY.par2" (see `write_par2`)
+    if let Ok(entries) = fs::read_dir(&chunks_dir) {
+        let volume_prefix = format!("{}.vol", file_info.original_filename);
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if name == format!("{}.par2", file_info.original_filename) || (name.starts_with(&volume_prefix) && name.ends_with(".par2")) {
+                files.push(chunks_dir.join(name));
+            }
+        }
+    }
+
+    let mut removed = Vec::new();
+    for file in files {
+        match fs::remove_file(&file) {
+            Ok(()) => removed.push(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("Failed to delete chunk file: {}", file.display())),
+        }
+    }
+    Ok(removed)
+}
+
+/// Checks that the subdirectory containing chunks for the file exists
+fn check_chunk_dir(file_info: &SplitInfo, input_root_dir: &Path) -> Result<()> {
+    let chunks_input_dir = input_root_dir.join(&file_info.chunks_sub_dir); // This returns PathBuf
+    if !chunks_input_dir.exists() {
+        return Err(anyhow::anyhow!(
+            "Chunk directory for file '{}' not found: {}",
+            file_info.original_filename,
+            chunks_input_dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the chunk at `index`, verifying the checksum of its original content
+///
+/// With a `recovery`, a chunk that is missing or fails verification is rebuilt from the split set's parity chunks.
+/// Otherwise a checksum mismatch is an error, unless [`RestoreOptions::warn_only`]. A chunk that cannot be read is
+/// tried again as [`RestoreOptions::retry`] says first.
+fn read_chunk(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    index: usize,
+    codec: Option<&dyn ChunkCodec>,
+    decryption: Option<&Decryption>,
+    recovery: Option<&ParityRecovery>,
+    options: &RestoreOptions,
+) -> Result<(Vec<u8>, ChunkCheck)> {
+    let chunk_info = &file_info.chunks[index];
+    let chunk_name = file_info.chunk_name(chunk_info);
+    let chunk_path = source.describe(&chunk_name);
+    let strict = recovery.is_some() || !options.warn_only;
+    let decoded = options.retry.run(options.checkpoint(), &chunk_path, || {
+        let chunk_file = open_chunk(source, &chunk_name, &chunk_path)?;
+        decode_chunk(file_info, chunk_info, chunk_file, &chunk_path, codec, decryption, strict)
+    });
+    match (decoded, recovery) {
+        (Err(e), Some(recovery)) => {
+            tracing::warn!(index, error = %format!("{:#}", e), "chunk unreadable, rebuilding it from parity");
+            let rebuilt = recovery.chunk(index)
+                .with_context(|| format!("{:#}; rebuilding it from parity failed", e))?;
+            decode_chunk(file_info, chunk_info, Box::new(std::io::Cursor::new(rebuilt)), &chunk_path, codec, decryption, true)
+                .with_context(|| format!("Failed to rebuild chunk file from parity: {}", chunk_path))
+        }
+        (decoded, _) => decoded,
+    }
+}
+
+/// Opens the chunk file stored under `chunk_name` in `source` (described as `chunk_path`), failing with
+/// [`FailureKind::MissingChunk`] if it is not there
+fn open_chunk<'s>(source: &'s dyn ChunkSource, chunk_name: &str, chunk_path: &str) -> Result<Box<dyn Read + 's>> {
+    source.open(chunk_name).map_err(|e| {
+        let message = format!("Failed to open chunk file: {}", chunk_path);
+        match is_not_found(&e) {
+            true => e.context(Failure::new(FailureKind::MissingChunk, message)),
+            false => e.context(message),
+        }
+    })
+}
+
+/// Decodes the chunk at `index` straight into `output` at its current position, returning the bytes written and
+/// what checking them found
+///
+/// The chunk is streamed through a fixed-size buffer, so memory use does not grow with the chunk size, and its
+/// checksum is verified on the way. With a `recovery`, a chunk that is missing or fails verification is rebuilt
+/// from the split set's parity chunks and written again over what was written of it; otherwise a checksum mismatch
+/// is an error unless [`RestoreOptions::warn_only`] is set. A chunk that cannot be read is tried again first as
+/// [`RestoreOptions::retry`] says, from its start. See [`write_output`] for [`RestoreOptions::sparse`].
+/// The content written is also fed to `file_hasher`, if given.
+#[allow(clippy::too_many_arguments)]
+fn restore_chunk<W: Write + Seek>(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    index: usize,
+    codec: Option<&dyn ChunkCodec>,
+    decryption: Option<&Decryption>,
+    recovery: Option<&ParityRecovery>,
+    options: &RestoreOptions,
+    output: &mut W,
+    mut file_hasher: Option<&mut ChecksumHasher>,
+) -> Result<(u64, ChunkCheck)> {
+    let chunk_info = &file_info.chunks[index];
+    let chunk_name = file_info.chunk_name(chunk_info);
+    let chunk_path = source.describe(&chunk_name);
+    let strict = recovery.is_some() || !options.warn_only;
+    let sparse = options.sparse;
+    let start = output.stream_position()?;
+    let hashed_before = file_hasher.as_deref().cloned();
+    // A retry writes the chunk again from where it started, hashing it over
+    let mut attempted = false;
+    let written = options.retry.run(options.checkpoint(), &chunk_path, || {
+        if std::mem::replace(&mut attempted, true) {
+            output.seek(SeekFrom::Start(start))?;
+            if let (Some(file_hasher), Some(hashed_before)) = (file_hasher.as_deref_mut(), &hashed_before) {
+                file_hasher.clone_from(hashed_before);
+            }
+        }
+        let chunk_file = open_chunk(source, &chunk_name, &chunk_path)?;
+        let reader = chunk_reader(file_info, chunk_info, chunk_file, &chunk_path, codec, decryption)?;
+        copy_chunk(file_info, chunk_info, reader, &chunk_path, strict, output, sparse, options.checkpoint(), file_hasher.as_deref_mut())
+    });
+    // A cancelled chunk is not rebuilt
+    options.checkpoint().check()?;
+    match (written, recovery) {
+        (Err(e), Some(recovery)) => {
+            tracing::warn!(index, error = %format!("{:#}", e), "chunk unreadable, rebuilding it from parity");
+            let rebuilt = recovery.chunk(index)
+                .with_context(|| format!("{:#}; rebuilding it from parity failed", e))?;
+            // Zeros skipped by a sparse write would leave what the damaged chunk wrote there
+            output.seek(SeekFrom::Start(start))?;
+            if let (Some(file_hasher), Some(hashed_before)) = (file_hasher.as_deref_mut(), hashed_before) {
+                *file_hasher = hashed_before;
+            }
+            chunk_reader(file_info, chunk_info, Box::new(std::io::Cursor::new(rebuilt)), &chunk_path, codec, decryption)
+                .and_then(|reader| copy_chunk(file_info, chunk_info, reader, &chunk_path, true, output, false, options.checkpoint(), file_hasher))
+                .with_context(|| format!("Failed to rebuild chunk file from parity: {}", chunk_path))
+        }
+        (written, _) => written,
+    }
+}
+
+/// Copies the original content of a chunk from `reader` to `output`, verifying its checksum, and returns the
+/// bytes written and what checking them found
+///
+/// A checksum mismatch is an error if `strict`, and only a warning otherwise. The copy waits at `checkpoint` while
+/// paused, and stops with an error once cancelled. The content is also fed to `file_hasher`, if given.
+#[allow(clippy::too_many_arguments)]
+fn copy_chunk<W: Write + Seek>(
+    file_info: &SplitInfo,
+    chunk_info: &ChunkInfo,
+    mut reader: Box<dyn Read + '_>,
+    chunk_path: &str,
+    strict: bool,
+    output: &mut W,
+    sparse: bool,
+    checkpoint: Checkpoint,
+    mut file_hasher: Option<&mut ChecksumHasher>,
+) -> Result<(u64, ChunkCheck)> {
+    let expected = chunk_info.chunk_checksum.as_deref()
+        .map(|checksum| parse_checksum(checksum, file_info.checksum_algorithm))
+        .transpose()?;
+    let mut hasher = expected.as_ref().map(|(algorithm, _)| algorithm.hasher());
+    let mut buffer = vec![0u8; READ_BLOCK_SIZE];
+    let mut written = 0u64;
+    loop {
+        checkpoint.check()?;
+        // Full buffers keep sparse writes aligned to their blocks
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to decompress chunk file: {}", chunk_path)),
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&buffer[..filled]);
+        }
+        if let Some(file_hasher) = file_hasher.as_deref_mut() {
+            file_hasher.update(&buffer[..filled]);
+        }
+        write_output(output, &buffer[..filled], sparse).context("Failed to write output file")?;
+        written += filled as u64;
+        checkpoint.throttle(filled as u64);
+    }
+
+    let (Some((_, expected_digest)), Some(hasher)) = (expected, hasher) else {
+        return Ok((written, ChunkCheck::Unchecked));
+    };
+    let actual_checksum = hasher.finalize();
+    if actual_checksum == expected_digest {
+        return Ok((written, ChunkCheck::Verified));
+    }
+    let expected_checksum = chunk_info.chunk_checksum.as_deref().unwrap_or_default();
+    if strict {
+        return Err(Failure::new(FailureKind::Verification, format!(
+            "Checksum mismatch for chunk '{}'! Expected: {}, Actual: {}",
+            chunk_info.chunk_filename,
+            expected_checksum,
+            actual_checksum
+        )).into());
+    }
+    eprintln!("Warning: Checksum mismatch for chunk '{}'! Expected: {}, Actual: {}",
+              chunk_info.chunk_filename, expected_checksum, actual_checksum);
+    Ok((written, ChunkCheck::Failed))
+}
+
+/// What checking the original content of a restored chunk against its recorded checksum found
+#[derive(Clone, Copy)]
+enum ChunkCheck {
+    Verified,
+    /// A mismatch, only warned about
+    Failed,
+    /// The chunk has no recorded checksum
+    Unchecked,
+}
+
+/// Chunks restored so far and what checking them found
+#[derive(Default)]
+pub(crate) struct ChunkTally {
+    /// Bytes of original content restored
+    bytes: u64,
+    verified: usize,
+    failed: usize,
+}
+
+impl ChunkTally {
+    fn count(&mut self, len: u64, check: ChunkCheck) {
+        self.bytes += len;
+        match check {
+            ChunkCheck::Verified => self.verified += 1,
+            ChunkCheck::Failed => self.failed += 1,
+            ChunkCheck::Unchecked => {}
+        }
+    }
+
+    fn report(self, output_path: PathBuf, started: Instant) -> RestoreReport {
+        RestoreReport {
+            output_path,
+            bytes_written: self.bytes,
+            chunks_verified: self.verified,
+            chunks_failed: self.failed,
+            duration: started.elapsed(),
+        }
+    }
+}
+
+/// Decrypts and decompresses one stored chunk, verifying the checksum of its original content, and returns it
+/// along with what checking it found
+///
+/// A checksum mismatch is an error if `strict`, and only a warning otherwise.
+fn decode_chunk(
+    file_info: &SplitInfo,
+    chunk_info: &ChunkInfo,
+    chunk_file: Box<dyn Read + '_>,
+    chunk_path: &str,
+    codec: Option<&dyn ChunkCodec>,
+    decryption: Option<&Decryption>,
+    strict: bool,
+) -> Result<(Vec<u8>, ChunkCheck)> {
+    let decompressed_data = decode_stored_chunk(file_info, chunk_info, chunk_file, chunk_path, codec, decryption)?;
+
+    // Verify checksum of the original (uncompressed) chunk data (if available)
+    if chunk_info.chunk_checksum.is_none() {
+        return Ok((decompressed_data, ChunkCheck::Unchecked));
+    }
+    if let Some(actual_checksum) = chunk_checksum_mismatch(file_info, chunk_info, &decompressed_data)? {
+        let expected_checksum = chunk_info.chunk_checksum.as_deref().unwrap_or_default();
+        if strict {
+            return Err(Failure::new(FailureKind::Verification, format!(
+                "Checksum mismatch for chunk '{}'! Expected: {}, Actual: {}",
+                chunk_info.chunk_filename,
+                expected_checksum,
+                actual_checksum
+            )).into());
+        }
+        eprintln!("Warning: Checksum mismatch for chunk '{}'! Expected: {}, Actual: {}", 
+                  chunk_info.chunk_filename, expected_checksum, actual_checksum);
+        return Ok((decompressed_data, ChunkCheck::Failed));
+    }
+
+    Ok((decompressed_data, ChunkCheck::Verified))
+}
+
+/// Decrypts and decompresses one stored chunk
+fn decode_stored_chunk(
+    file_info: &SplitInfo,
+    chunk_info: &ChunkInfo,
+    chunk_file: Box<dyn Read + '_>,
+    chunk_path: &str,
+    codec: Option<&dyn ChunkCodec>,
+    decryption: Option<&Decryption>,
+) -> Result<Vec<u8>> {
+    let mut decompressed_data = Vec::new();
+    chunk_reader(file_info, chunk_info, chunk_file, chunk_path, codec, decryption)?
+        .read_to_end(&mut decompressed_data)
+        .with_context(|| format!("Failed to decompress chunk file: {}", chunk_path))?;
+    Ok(decompressed_data)
+}
+
+/// Reader of the original content of one stored chunk, decrypting and decompressing it on the way
+///
+/// The [`ChunkHeader`] of a split set written with them is stripped first, and must name the chunk's checksum, and
+/// an info file trailer is left unread.
+fn chunk_reader<'a>(
+    file_info: &SplitInfo,
+    chunk_info: &ChunkInfo,
+    mut chunk_file: Box<dyn Read + 'a>,
+    chunk_path: &str,
+    codec: Option<&dyn ChunkCodec>,
+    decryption: Option<&Decryption>,
+) -> Result<Box<dyn Read + 'a>> {
+    // The last chunk file goes on with the info file
+    if file_info.info_trailer {
+        chunk_file = Box::new(chunk_file.take(chunk_info.chunk_size));
+    }
+    if file_info.chunk_headers {
+        let header = ChunkHeader::read_from(&mut chunk_file)
+            .with_context(|| format!("Failed to read chunk file: {}", chunk_path))?
+            .ok_or_else(|| anyhow::anyhow!("Chunk file has no chunk header: {}", chunk_path))?;
+        if chunk_info.chunk_checksum.as_ref().is_some_and(|checksum| *checksum != header.checksum) {
+            return Err(Failure::new(FailureKind::Verification, format!(
+                "Chunk file {} holds chunk {} of '{}', not chunk '{}'",
+                chunk_path,
+                header.index,
+                header.original_filename,
+                chunk_info.chunk_filename
+            )).into());
+        }
+    }
+    let chunk_reader: Box<dyn Read> = match decryption {
+        Some(decryption) => decryption.reader(chunk_file, chunk_info.nonce.as_deref())
+            .with_context(|| format!("Failed to decrypt chunk file: {}", chunk_path))?,
+        None => chunk_file,
+    };
+    Ok(match codec {
+        Some(codec) => codec.decoder(chunk_reader)?,
+        None => chunk_reader,
+    })
+}
+
+/// Checks the original content of a chunk against its recorded checksum, returning the actual checksum if
+/// they differ (chunks of old info files may have no checksum; they always pass)
+fn chunk_checksum_mismatch(file_info: &SplitInfo, chunk_info: &ChunkInfo, data: &[u8]) -> Result<Option<String>> {
+    let Some(expected_checksum) = &chunk_info.chunk_checksum else {
+        return Ok(None);
+    };
+    let (algorithm, expected_digest) = parse_checksum(expected_checksum, file_info.checksum_algorithm)?;
+    let actual_checksum = calculate_buffer_checksum_with(data, algorithm);
+    Ok((actual_checksum != expected_digest).then_some(actual_checksum))
+}
+
 /// Calculates the SHA256 checksum of file content
 pub fn calculate_checksum(file_path: &Path) -> Result<String> {
+    calculate_checksum_with(file_path, ChecksumAlgorithm::Sha256)
+}
+
+/// Calculates the checksum of file content with the given algorithm
+pub fn calculate_checksum_with(file_path: &Path, algorithm: ChecksumAlgorithm) -> Result<String> {
     let mut file = File::open(file_path)
         .with_context(|| format!("Failed to open file to calculate checksum: {}", file_path.display()))?;
-    let mut hasher = Sha256::new();
+    let mut hasher = algorithm.hasher();
     let mut buffer = vec![0u8; 8192]; // 8KB buffer
     
     loop {
@@ -290,13 +3341,17 @@ pub fn calculate_checksum(file_path: &Path) -> Result<String> {
         hasher.update(&buffer[..bytes_read]);
     }
     
-    Ok(hex::encode(hasher.finalize()))
+    Ok(hasher.finalize())
 }
 
 /// Calculates the SHA256 checksum of buffer content
 pub fn calculate_buffer_checksum(buffer: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(buffer);
-    hex::encode(hasher.finalize())
+    calculate_buffer_checksum_with(buffer, ChecksumAlgorithm::Sha256)
 }
 
+/// Calculates the checksum of buffer content with the given algorithm
+pub fn calculate_buffer_checksum_with(buffer: &[u8], algorithm: ChecksumAlgorithm) -> String {
+    let mut hasher = algorithm.hasher();
+    hasher.update(buffer);
+    hasher.finalize()
+}
@@ -10,144 +10,2420 @@ use std::fs;
 #[cfg(not(target_os = "windows"))] // This block compiles only if NOT targeting Windows
 mod cli {
     use super::*; // Import common items from outer scope
-    use clap::{Parser, Subcommand};
+    use clap::{Args, Parser, Subcommand};
+    use std::path::Path;
     use indicatif::{ProgressBar, ProgressStyle};
-    use file_splitter::split_single_file; // Import from our lib
-    use file_splitter::restore_single_file; // Import from our lib
+    use std::io::{self, Read, Write};
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use file_splitter::{plan_split, plan_split_reader, size_limit_for_parts, split_file_to_sink, split_to_sink}; // Import from our lib
+    use file_splitter::{directory_split_name, split_directory_to_sink, split_tree_to_sink, ProgressMeter, SplitEvent, TreeIndex}; // Import from our lib
+    use file_splitter::{restore_file_from_source, restore_from_chunk_dir, restore_from_source, restore_single_file, restore_to_writer}; // Import from our lib
+    use file_splitter::{open_store, ChunkSink, ChunkSource, ChunkStore, DedupIndex, LocalDir, SpreadPolicy, SpreadSink, VolumeSink, VolumeSource}; // Import from our lib
+    use file_splitter::{rebuild_split_info, repair_split_set, verify_split_set, write_par2, write_restore_scripts, ChunkProblem}; // Import from our lib
+    use file_splitter::{Failure, FailureKind}; // Import from our lib
+    use file_splitter::{check_restored_file, compare_file, remove_split_set}; // Import from our lib
     use file_splitter::SplitInfo; // Import from our lib
-    use serde_json; // For parsing SplitInfo from JSON
+    use file_splitter::CompressionAlgorithm; // Import from our lib
+    use file_splitter::{read_info_trailer, shell_quote, ChunkCodec, ChunkHeader}; // Import from our lib
+    use file_splitter::{parse_split_info, ManifestFormat}; // Import from our lib
+    use file_splitter::{BatchEntry, BatchIndex, ChunkNaming}; // Import from our lib
+    use file_splitter::EncryptionKey; // Import from our lib
+    use file_splitter::{Decryption, Encryption}; // Import from our lib
+    use file_splitter::{ChecksumAlgorithm, RateLimiter, RestoreOptions, RetryPolicy, RestoreReport, SizePreset, SplitMode, SplitOptions, Throughput}; // Import from our lib
+    use file_splitter::{available_threads, parse_delimiter, parse_size}; // Import from our lib
+    use file_splitter::{available_space, check_combined_free_space, check_free_space}; // Import from our lib
+    use file_splitter::KdfParams; // Import from our lib
+    #[cfg(feature = "http")]
+    use file_splitter::serve_directory; // Import from our lib
+    #[cfg(feature = "watch")]
+    use file_splitter::watch_directory; // Import from our lib
     use anyhow::Context; // <--- ADD THIS LINE
+    use serde::Deserialize;
+
+    #[derive(Parser, Debug)]
+    #[command(author, version, about, long_about = None)]
+    #[command(after_help = "Exit codes: 0 success, 1 other error, 2 verification failure (size or checksum mismatch), \
+        3 missing chunks, 4 I/O error, 64 invalid command line")]
+    pub struct Cli {
+        #[command(subcommand)]
+        pub command: Commands,
+    }
+
+    #[derive(Subcommand, Debug)]
+    pub enum Commands {
+        /// Split one or more files
+        Split {
+            /// List of file paths to split. Wildcards (`*`, `?`, `[abc]`, `**` for any depth) are expanded here,
+            /// so quoted patterns like 'backups/*.sql' work without the shell
+            #[arg(required_unless_present_any = ["stdin", "dir"])]
+            files: Vec<PathBuf>,
+
+            /// Split data read from standard input instead of files (e.g., `pg_dump mydb | file_splitter split --stdin --name dump.sql`)
+            #[arg(long, conflicts_with = "files")]
+            stdin: bool,
+
+            /// File name recorded for the data read with --stdin (the restored file gets this name). Default "stdin"
+            #[arg(long, value_name = "NAME", conflicts_with = "files")]
+            name: Option<String>,
+
+            /// Split a whole directory tree instead of files. It is bundled into a tar stream on the fly (nothing
+            /// extra is written to disk) and gets one info file, named after the directory; restore unpacks it
+            #[arg(long, value_name = "DIR", conflicts_with_all = ["files", "stdin", "name", "parts", "dry_run"])]
+            dir: Option<PathBuf>,
+
+            /// With --dir, split every file of the tree on its own instead of as one tar stream. Each file's split
+            /// set is stored at its path in the tree, and an index (`<dir>.index.json`) lists them all, so that
+            /// `restore --index` restores the whole tree
+            #[arg(long, requires = "dir", conflicts_with = "par2")]
+            per_file: bool,
+            
+            /// Split size limit, in bytes or with a unit (e.g., 100MB, 4.7GiB, 650M; K, M, G, T are powers of 1024,
+            /// KB, MB, GB, TB powers of 1000). If file size is greater than this, it will be split. Default 100MiB
+            #[arg(short, long, default_value = "100MiB", value_parser = parse_size_limit)]
+            size_limit: u64,
+
+            /// Use a well-known size limit instead of --size-limit: fat32 (4 GiB - 1 MiB), dvd (4480 MiB),
+            /// email25 (18 MB, for a 25 MB attachment limit), discord (9.5 MiB) or cd700 (700 MiB)
+            #[arg(long, value_name = "NAME", conflicts_with_all = ["size_limit", "parts"])]
+            preset: Option<SizePreset>,
+
+            /// Split each file into exactly this many parts of (almost) equal size instead of using --size-limit
+            #[arg(long, value_name = "N", conflicts_with_all = ["size_limit", "stdin", "split_by"])]
+            parts: Option<u64>,
+
+            /// Where chunks may end (bytes, lines, delimiter). With `lines` every chunk ends on a newline and holds
+            /// whole lines, up to the size limit; with `delimiter` every chunk ends right after --delimiter
+            #[arg(long, value_name = "MODE", default_value = "bytes")]
+            split_by: SplitMode,
+
+            /// Record delimiter for --split-by delimiter, with backslash escapes (e.g., `\0`, `\n}`, `\x1e`)
+            #[arg(long, value_name = "SEQ")]
+            delimiter: Option<String>,
+
+            /// Maximum number of lines per chunk with --split-by lines
+            #[arg(long, value_name = "N")]
+            max_lines: Option<u64>,
+            
+            /// Root directory where split sub-files and info files will be stored. Given several times (e.g.,
+            /// `-o /mnt/d1 -o /mnt/d2`), the sub-files are spread across them (see --spread) and the info files
+            /// go into the first, recording where each sub-file went
+            #[arg(short, long, default_value = ".")]
+            output_dir: Vec<PathBuf>,
+
+            /// How to spread the sub-files across several output directories: round-robin (each in turn) or
+            /// free-space (the one with the most free space)
+            #[arg(long, value_name = "POLICY", default_value = "round-robin")]
+            spread: SpreadPolicy,
+
+            /// Fill the removable volume (e.g., a USB stick) mounted at --output-dir up to its actual free space, then
+            /// ask for the next one to be mounted there, and so on, to span a file across several volumes. The last
+            /// sub-file on each volume is cut short to the space left there; the split info goes on the last volume,
+            /// and restoring from there asks for the others in turn
+            #[arg(long, conflicts_with_all = ["dest", "stdin", "per_file", "parts", "pool", "dedup", "parity", "par2", "resume", "skip_unchanged", "chunk_headers", "info_trailer", "emit_restore_script"])]
+            span: bool,
+
+            /// Store the split sub-files and info files at this location instead of --output-dir
+            /// (e.g., s3://bucket/prefix with the `s3` feature)
+            #[arg(long, value_name = "LOCATION", conflicts_with = "output_dir")]
+            dest: Option<String>,
+
+            /// Store chunks by content in this shared directory below the output directory (e.g., `--pool pool`)
+            /// instead of in each file's own directory, so chunks already in the pool are not stored again
+            #[arg(long, value_name = "DIR")]
+            pool: Option<String>,
+
+            /// Store chunks identical to ones already written by this run (e.g., of near-duplicate files like VM
+            /// snapshots) only once; the info files then refer to the first copy, so keep the split sets together
+            #[arg(long, conflicts_with = "pool")]
+            dedup: bool,
+
+            /// Number of Reed-Solomon parity chunks to write per file; restore rebuilds up to this many missing or
+            /// corrupted chunks from them
+            #[arg(long, value_name = "N", default_value_t = 0)]
+            parity: usize,
+
+            /// Also write PAR2 recovery files with N recovery blocks per file next to the chunks (`<name>.par2`
+            /// and volumes), so standard PAR2 tools can repair up to N damaged chunks
+            #[arg(long, value_name = "N", conflicts_with_all = ["pool", "dedup"])]
+            par2: Option<usize>,
+
+            /// Also write scripts next to the chunks that restore the file with standard tools (`restore.sh`, plus
+            /// `restore.ps1` and `restore.bat` where Windows can decompress the chunks), for recipients without
+            /// this program. Not for encrypted chunks
+            #[arg(long, conflicts_with_all = ["per_file", "key", "keyfile", "password", "recipients"])]
+            emit_restore_script: bool,
+
+            /// Start every chunk with a small header naming the file, the chunk's position and checksum, so that a
+            /// stray chunk can be identified (`identify`) and the file restored from its chunks alone
+            /// (`restore --from-dir`). Restoring strips the headers again
+            #[arg(long, conflicts_with_all = ["pool", "dedup", "emit_restore_script"])]
+            chunk_headers: bool,
+
+            /// Also append the info file to the last chunk, so the chunks hold everything needed to restore the
+            /// file: give that chunk to `restore` in place of the info file if it is lost
+            #[arg(long, conflicts_with_all = ["pool", "dedup", "resume", "par2", "emit_restore_script"])]
+            info_trailer: bool,
+
+            /// Format to write the split info in (json, toml, yaml), which names its extension
+            /// (e.g., my_file_parts/my_file.toml). Restoring tells the format by the extension or content
+            #[arg(long, value_name = "FORMAT", default_value = "json")]
+            manifest_format: ManifestFormat,
+
+            /// Extension to give the split info file instead of the format's (e.g., fsmeta.json, making
+            /// my_file_parts/my_file.fsmeta.json), so it is not taken for a JSON data file that is split as well
+            #[arg(long, value_name = "EXT")]
+            info_extension: Option<String>,
+
+            /// How to name the sub-files: dashed (my_file.zip-001), dot-numeric (my_file.zip.001, the volume naming
+            /// HJSplit and 7-Zip join, as do `copy /b` and `cat`, if the sub-files are neither compressed nor
+            /// encrypted) or gnu (xaa, xab, ... like GNU split, never compressed nor encrypted, so that
+            /// `cat my_file.zip_parts/x* > my_file.zip` restores the file)
+            #[arg(long, value_name = "NAMING", default_value = "dashed")]
+            naming: ChunkNaming,
+
+            /// Name the sub-files after PREFIX instead of the original file (e.g., `--prefix part` for part-001,
+            /// or partaa with `--naming gnu`, whose default prefix is x)
+            #[arg(long, value_name = "PREFIX")]
+            prefix: Option<String>,
+
+            /// End the sub-file names with EXT (e.g., `--chunk-extension part` for my_file.zip-001.part), so they
+            /// are not taken for truncated copies of the original file; restoring strips it again
+            #[arg(long, value_name = "EXT")]
+            chunk_extension: Option<String>,
+
+            /// Number the sub-files from N instead of 1 (e.g., `--start-index 0` for my_file.zip-000, as some tools
+            /// expect); the split info records it. Not with `--naming gnu`, which always starts from aa
+            #[arg(long, value_name = "N")]
+            start_index: Option<u64>,
+
+            /// Keep the sub-files joinable with `cat` (e.g., `cat my_file.zip-[0-9]* > my_file.zip` in the chunk
+            /// directory), which the split info then records: refuses compression, encryption, headers and pools
+            #[arg(long, conflicts_with_all = ["compress", "compress_algo", "key", "keyfile", "password", "recipients", "chunk_headers", "info_trailer", "pool", "dedup"])]
+            cat_compatible: bool,
+
+            /// Continue an interrupted split of the same files with the same settings: chunks it already stored
+            /// that still match (size and checksum) are kept, and only the rest is written
+            #[arg(long, conflicts_with = "parity")]
+            resume: bool,
+
+            /// Skip files whose split in the output directory is up to date: same content, same settings and
+            /// every chunk still present. Handy for splits run repeatedly, e.g. from cron
+            #[arg(long, conflicts_with = "stdin")]
+            skip_unchanged: bool,
+
+            /// Replace an existing split of the same file in the output directory. Without it, splitting refuses
+            /// to overwrite an existing info file or sub-file (unless resuming or with --skip-unchanged)
+            #[arg(long)]
+            force: bool,
+
+            /// Flush every sub-file and the split info to the disk, with the directories holding them, before
+            /// reporting success, so a drive removed right after the split (e.g., a USB stick) holds all of it
+            #[arg(long)]
+            fsync: bool,
+
+            /// Only warn if the output directory seems to lack the free space the sub-files need (as much as the
+            /// input, before compression), instead of failing before anything is written
+            #[arg(long)]
+            ignore_space: bool,
+
+            /// Split symbolic links as the files they point to. Without it, splitting a link fails, and `--dir`
+            /// keeps links as links
+            #[arg(long, conflicts_with = "stdin")]
+            follow_symlinks: bool,
+
+            /// Memory-map each file and split it straight from the mapping, saving read calls and copies (64-bit
+            /// systems only; the files must not change while they are split)
+            #[arg(long, conflicts_with = "stdin")]
+            mmap: bool,
+
+            /// Only list the chunks each file would be split into (names, offsets and sizes), without writing anything
+            #[arg(long, conflicts_with = "json")]
+            dry_run: bool,
+
+            /// Whether to compress the split sub-files (Gzip unless --compress-algo is given)
+            #[arg(short, long)]
+            compress: bool,
+
+            /// Compression algorithm for the split sub-files (gzip, zstd, xz, lz4). Implies --compress
+            #[arg(long, value_name = "ALGO")]
+            compress_algo: Option<CompressionAlgorithm>,
+
+            /// Checksum algorithm for the chunk and whole-file checksums (sha256, blake3, xxh3, crc32)
+            #[arg(long, value_name = "ALGO", default_value = "sha256")]
+            checksum: ChecksumAlgorithm,
+
+            /// Number of chunks to hash, compress and write in parallel, or `auto` for one per CPU core (each worker
+            /// holds one chunk in memory; chunks over 64 MiB of a file are streamed through a small buffer instead,
+            /// unless pooled, deduplicated, resumed or with parity)
+            #[arg(long, default_value = "auto", value_parser = parse_threads)]
+            threads: usize,
+
+            /// Most memory the chunks held at once may take up (e.g., 512M): fewer chunks are encoded in parallel
+            /// than --threads asks for, or they are streamed into their files one at a time if not even one fits
+            #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+            max_memory: Option<u64>,
+
+            /// Read the files at most this many bytes per second (e.g., 50M), so that a split running alongside
+            /// other work on the same disk leaves it enough of its throughput
+            #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+            limit_rate: Option<u64>,
+
+            /// Encrypt the split sub-files with AES-256-GCM using this key (64 hex characters)
+            #[arg(long, value_name = "HEX")]
+            key: Option<String>,
+
+            /// Encrypt the split sub-files with AES-256-GCM using the key in this file (exactly 32 raw bytes)
+            #[arg(long, value_name = "PATH", conflicts_with = "key")]
+            keyfile: Option<PathBuf>,
+
+            /// Encrypt the split sub-files with a key derived from a password (Argon2id). Prompts if no value is given
+            #[arg(long, value_name = "PASSWORD", num_args = 0..=1, conflicts_with_all = ["key", "keyfile"])]
+            password: Option<Option<String>>,
+
+            /// Encrypt the split sub-files to this age recipient ("age1..."). Can be given multiple times
+            #[arg(long = "recipient", value_name = "AGE_RECIPIENT", conflicts_with_all = ["key", "keyfile", "password"])]
+            recipients: Vec<String>,
+
+            /// Run this shell command after each chunk is stored, with {chunk_path} replaced by where the chunk went
+            /// (e.g., 'rclone copy {chunk_path} remote:backup'), so chunks can be uploaded while the split goes on.
+            /// The commands run one after the other; the split fails if one of them does
+            #[arg(long, value_name = "COMMAND", conflicts_with = "dry_run")]
+            on_chunk: Option<String>,
+
+            #[command(flatten)]
+            retry: RetryArgs,
+
+            #[command(flatten)]
+            hooks: HookArgs,
+
+            #[command(flatten)]
+            output: OutputArgs,
+        },
+        
+        /// Restore one or more files
+        Restore {
+            /// List of split info file paths, in JSON, TOML or YAML (e.g., my_file_parts/my_file.json), or the
+            /// `split-set.json` of a batch of files split at once, to restore them all
+            #[arg(required_unless_present_any = ["urls", "index", "from_dirs"])]
+            info_files: Vec<PathBuf>,
+
+            /// Restore from the chunk files in this directory alone (e.g., `--from-dir my_file_parts`), for when the
+            /// info file is lost: they are ordered by their names and decompressed if they are, but nothing can be
+            /// verified, so compare the checksum shown with one known from elsewhere. Can be given multiple times
+            #[arg(long = "from-dir", value_name = "DIR", conflicts_with_all = ["urls", "source", "stdout"])]
+            from_dirs: Vec<PathBuf>,
+
+            /// Restore every file of a directory tree split with `split --dir --per-file`, from its index
+            /// (e.g., `--index photos.index.json`), recreating the tree, with its empty directories and symbolic links, in
+            /// the output directory. Can be given multiple times
+            #[arg(long, value_name = "INDEX_FILE", conflicts_with_all = ["urls", "stdout"])]
+            index: Vec<PathBuf>,
+
+            /// Download an info file and its sub-files over HTTP(S) and restore it
+            /// (e.g., `--url https://host/backups/my_file.json`). Can be given multiple times;
+            /// `--threads` sets how many sub-files are downloaded at once
+            #[arg(long = "url", value_name = "URL", conflicts_with_all = ["input_dir", "source"])]
+            urls: Vec<String>,
+            
+            /// Root directory where the split sub-files are located (usually the same as the output_dir during split)
+            #[arg(short, long, default_value = ".")]
+            input_dir: PathBuf,
+            
+            /// Directory where the restored large files will be saved
+            #[arg(short, long, default_value = ".")]
+            output_dir: PathBuf,
+
+            /// Read the info files and sub-files from this location instead of the local disk; info files are then
+            /// given relative to it (e.g., `--source s3://bucket/prefix my_file_parts/my_file.json`)
+            #[arg(long, value_name = "LOCATION", conflicts_with = "input_dir")]
+            source: Option<String>,
+
+            /// Write the restored content to standard output instead of files (several files are concatenated),
+            /// e.g. `file_splitter restore --stdout backup.tar.json | tar -x`
+            #[arg(long, conflicts_with_all = ["output_dir", "json"])]
+            stdout: bool,
+
+            /// Number of chunks to decompress and verify in parallel, or `auto` for one per CPU core (each worker
+            /// holds one chunk in memory; a single one restoring into a file streams it through a small buffer)
+            #[arg(long, default_value = "auto", value_parser = parse_threads)]
+            threads: usize,
+
+            /// Most memory the chunks held at once may take up (e.g., 512M): fewer chunks are restored in parallel
+            /// than --threads asks for, but always at least one
+            #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+            max_memory: Option<u64>,
+
+            /// Write the restored content at most this many bytes per second (e.g., 50M), so that a restore running
+            /// alongside other work on the same disk leaves it enough of its throughput
+            #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+            limit_rate: Option<u64>,
+
+            /// Continue an interrupted restore: chunks an existing output file already holds (checked against their
+            /// checksums) are kept, and only the rest is restored
+            #[arg(long, conflicts_with = "stdout")]
+            resume: bool,
+
+            /// Skip over runs of zeros instead of writing them, so restored disk images and the like take up only
+            /// the space of their data (on file systems with sparse files)
+            #[arg(long, conflicts_with = "stdout")]
+            sparse: bool,
+
+            /// Replace existing files in the output directory. Without it, restoring refuses to overwrite a file
+            /// (unless resuming it)
+            #[arg(long, conflicts_with = "stdout")]
+            force: bool,
+
+            /// Only warn if the output directory seems to lack the free space the restored files need, instead of
+            /// failing before anything is restored
+            #[arg(long, conflicts_with = "stdout")]
+            ignore_space: bool,
+
+            /// Only warn about sub-files and restored files whose checksums do not match, and keep their damaged
+            /// content, instead of failing
+            #[arg(long)]
+            warn_only: bool,
+
+            #[command(flatten)]
+            retry: RetryArgs,
+
+            #[command(flatten)]
+            hooks: HookArgs,
+
+            #[command(flatten)]
+            decryption: DecryptionArgs,
+
+            #[command(flatten)]
+            output: OutputArgs,
+        },
+
+        /// Check that every sub-file of split sets exists and matches its recorded size and checksum, without
+        /// restoring anything; exits with an error if any split set is damaged
+        Verify {
+            /// List of split info JSON file paths (e.g., my_file_parts/my_file.json), or the `split-set.json` of a
+            /// batch of files split at once, to verify them all
+            #[arg(required = true)]
+            info_files: Vec<PathBuf>,
+
+            /// Root directory where the split sub-files are located
+            #[arg(short, long, default_value = ".")]
+            input_dir: PathBuf,
+
+            /// Check the info files and sub-files at this location instead of on the local disk; info files are
+            /// then given relative to it
+            #[arg(long, value_name = "LOCATION", conflicts_with = "input_dir")]
+            source: Option<String>,
+
+            /// Number of chunks to read and check in parallel, or `auto` for one per CPU core (each worker holds one
+            /// chunk in memory)
+            #[arg(long, default_value = "auto", value_parser = parse_threads)]
+            threads: usize,
+
+            /// Most memory the chunks held at once may take up (e.g., 512M): fewer chunks are checked in parallel
+            /// than --threads asks for, but always at least one
+            #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+            max_memory: Option<u64>,
+
+            #[command(flatten)]
+            retry: RetryArgs,
+
+            #[command(flatten)]
+            hooks: HookArgs,
+
+            #[command(flatten)]
+            decryption: DecryptionArgs,
+
+            #[command(flatten)]
+            output: OutputArgs,
+        },
+
+        /// Print a summary of split sets: original file, chunks, compression, encryption and stored size
+        Info {
+            /// List of split info JSON file paths (e.g., my_file_parts/my_file.json)
+            #[arg(required = true)]
+            info_files: Vec<PathBuf>,
+
+            /// Read the info files from this location instead of the local disk
+            #[arg(long, value_name = "LOCATION")]
+            source: Option<String>,
+        },
+
+        /// Print a table of the sub-files of split sets, with their size, checksum and whether they are present
+        List {
+            /// List of split info JSON file paths (e.g., my_file_parts/my_file.json)
+            #[arg(required = true)]
+            info_files: Vec<PathBuf>,
+
+            /// Root directory where the split sub-files are located
+            #[arg(short, long, default_value = ".")]
+            input_dir: PathBuf,
+
+            /// Look for the info files and sub-files at this location instead of on the local disk; info files are
+            /// then given relative to it
+            #[arg(long, value_name = "LOCATION", conflicts_with = "input_dir")]
+            source: Option<String>,
+        },
+
+        /// Check whether a file (e.g., restored by another tool) matches the original file of a split set;
+        /// exits with an error if it does not
+        Compare {
+            /// Split info JSON file path (e.g., my_file_parts/my_file.json)
+            info_file: PathBuf,
+
+            /// File to compare with the original file
+            file: PathBuf,
+
+            /// Also report which chunk ranges of the file differ
+            #[arg(long)]
+            chunks: bool,
+        },
+
+        /// Delete the sub-files and info files of split sets once their restored files are verified against them
+        Clean {
+            /// List of split info JSON file paths (e.g., my_file_parts/my_file.json)
+            #[arg(required = true)]
+            info_files: Vec<PathBuf>,
+
+            /// Root directory where the split sub-files are located
+            #[arg(short, long, default_value = ".")]
+            input_dir: PathBuf,
+
+            /// Directory the files were restored to
+            #[arg(short, long, default_value = ".")]
+            output_dir: PathBuf,
+        },
+
+        /// Rebuild missing or damaged sub-files of split sets in place from their parity chunks (see `split --parity`)
+        Repair {
+            /// List of split info JSON file paths (e.g., my_file_parts/my_file.json)
+            #[arg(required = true)]
+            info_files: Vec<PathBuf>,
+
+            /// Root directory where the split sub-files are located
+            #[arg(short, long, default_value = ".")]
+            input_dir: PathBuf,
+
+            /// Repair the info files and sub-files at this location instead of on the local disk; info files are
+            /// then given relative to it
+            #[arg(long, value_name = "LOCATION", conflicts_with = "input_dir")]
+            source: Option<String>,
+
+            #[command(flatten)]
+            decryption: DecryptionArgs,
+        },
+
+        /// Recreate a lost or damaged info file from the sub-files in a chunk directory (e.g., my_file_parts); an
+        /// existing info file is kept as `<name>.json.bak`
+        RebuildManifest {
+            /// Directory containing the sub-files (e.g., my_file_parts)
+            chunks_dir: PathBuf,
+
+            /// Checksum algorithm for the recomputed chunk and whole-file checksums (sha256, blake3, xxh3, crc32)
+            #[arg(long, value_name = "ALGO", default_value = "sha256")]
+            checksum: ChecksumAlgorithm,
+        },
+
+        /// Tell which file and position chunk files belong to, from the headers written by `split --chunk-headers`
+        Identify {
+            /// Chunk files to identify
+            #[arg(required = true)]
+            chunk_files: Vec<PathBuf>,
+        },
+
+        /// Serve split sets over HTTP so other machines can restore them with `restore --url`
+        #[cfg(feature = "http")]
+        Serve {
+            /// Directory containing the split sub-files and info files (the split output directory)
+            #[arg(default_value = ".")]
+            dir: PathBuf,
+
+            /// Address to listen on
+            #[arg(short, long, default_value = "0.0.0.0:8080")]
+            bind: String,
+        },
+
+        /// Watch a drop directory and split every new file that appears in it, until interrupted
+        #[cfg(feature = "watch")]
+        Watch {
+            /// Directory to watch. Files already there are left alone, as are hidden files (e.g., partial uploads)
+            /// and subdirectories
+            dir: PathBuf,
+
+            /// Only split new files of at least this size (e.g., 1G); smaller ones are left alone
+            #[arg(long, value_name = "SIZE", default_value = "0", value_parser = parse_size)]
+            min_size: u64,
+
+            /// Split size limit, in bytes or with a unit (e.g., 100MB, 4.7GiB, 650M). Default 100MiB
+            #[arg(short, long, default_value = "100MiB", value_parser = parse_size_limit)]
+            size_limit: u64,
+
+            /// Root directory where split sub-files and info files will be stored (may be the watched directory)
+            #[arg(short, long, default_value = ".")]
+            output_dir: PathBuf,
+
+            /// Store the split sub-files and info files at this location instead of --output-dir
+            /// (e.g., s3://bucket/prefix with the `s3` feature)
+            #[arg(long, value_name = "LOCATION", conflicts_with = "output_dir")]
+            dest: Option<String>,
+
+            /// Whether to compress the split sub-files (Gzip unless --compress-algo is given)
+            #[arg(short, long)]
+            compress: bool,
+
+            /// Compression algorithm for the split sub-files (gzip, zstd, xz, lz4). Implies --compress
+            #[arg(long, value_name = "ALGO")]
+            compress_algo: Option<CompressionAlgorithm>,
+
+            /// Checksum algorithm for the chunk and whole-file checksums (sha256, blake3, xxh3, crc32)
+            #[arg(long, value_name = "ALGO", default_value = "sha256")]
+            checksum: ChecksumAlgorithm,
+
+            /// Number of chunks to hash, compress and write in parallel, or `auto` for one per CPU core
+            #[arg(long, default_value = "auto", value_parser = parse_threads)]
+            threads: usize,
+
+            #[command(flatten)]
+            retry: RetryArgs,
+
+            #[command(flatten)]
+            output: OutputArgs,
+        },
+
+        /// Run the split, restore and verify jobs listed in a job file (e.g., for nightly backups), and report how
+        /// each of them went
+        Run {
+            /// Job file, in TOML (or YAML, with a .yaml or .yml extension): `parallel`, the number of jobs run at
+            /// once (default 1), and `jobs`, each with a `name` and the `args` of its command line, e.g.
+            /// `args = ["split", "db.sql", "-s", "1G", "-o", "/mnt/backup", "--quiet"]`
+            job_file: PathBuf,
+
+            /// Number of jobs to run at once, or `auto` for one per CPU core, instead of the job file's `parallel`
+            #[arg(long, value_name = "N", value_parser = parse_threads)]
+            parallel: Option<usize>,
+        },
+
+        /// Print a man page (roff) for the command line, generated from its own definition, e.g.
+        /// `file_splitter manpage > file_splitter.1`
+        Manpage {
+            /// Write a page for every subcommand as well (e.g., `file_splitter-split.1`) into this directory
+            /// instead of printing the main page
+            #[arg(long, value_name = "DIR")]
+            out_dir: Option<PathBuf>,
+        },
+    }
+
+    /// How to decrypt encrypted sub-files, for the commands that read them
+    #[derive(Args, Debug)]
+    pub struct DecryptionArgs {
+        /// Key to decrypt encrypted sub-files with (64 hex characters)
+        #[arg(long, value_name = "HEX")]
+        key: Option<String>,
+
+        /// Keyfile (exactly 32 raw bytes) to decrypt encrypted sub-files with
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        keyfile: Option<PathBuf>,
+
+        /// Password for password-encrypted sub-files. Prompts if no value is given (or if no key, keyfile or password is set)
+        #[arg(long, value_name = "PASSWORD", num_args = 0..=1, conflicts_with_all = ["key", "keyfile"])]
+        password: Option<Option<String>>,
+
+        /// age identity file for sub-files encrypted to age recipients. Can be given multiple times
+        #[arg(long = "identity", value_name = "FILE", conflicts_with_all = ["key", "keyfile", "password"])]
+        identities: Vec<PathBuf>,
+    }
+
+    impl DecryptionArgs {
+        /// The key or age identities given; password-derived keys depend on each info file (see [`Decryptor`])
+        fn decryption(&self) -> Result<Option<Decryption>> {
+            let mut decryption = match (&self.key, &self.keyfile) {
+                (Some(key), _) => Some(Decryption::from(EncryptionKey::from_hex(key)?)),
+                (None, Some(keyfile)) => Some(Decryption::from(EncryptionKey::from_keyfile(keyfile)?)),
+                (None, None) => None,
+            };
+            if !self.identities.is_empty() {
+                let mut all = Vec::new();
+                for identity_file in &self.identities {
+                    if let Decryption::AgeIdentities(parsed) = Decryption::age_identity_file(identity_file)? {
+                        all.extend(parsed);
+                    }
+                }
+                decryption = Some(Decryption::AgeIdentities(all));
+            }
+            Ok(decryption)
+        }
+
+        fn decryptor(&self) -> Result<Decryptor> {
+            Ok(Decryptor { decryption: self.decryption()?, password: self.password.clone().flatten() })
+        }
+    }
+
+    /// Picks the decryption for each split set of a run, asking for the password at most once
+    struct Decryptor {
+        decryption: Option<Decryption>,
+        password: Option<String>,
+    }
+
+    impl Decryptor {
+        fn for_file(&mut self, file_info: &SplitInfo) -> Result<Option<Decryption>> {
+            // Password-encrypted sets re-derive their key from the salt stored in the info file
+            match (&file_info.encryption, &self.decryption) {
+                (Some(encryption), None) if encryption.kdf.is_some() => {
+                    let entered = match &self.password {
+                        Some(password) => password.clone(),
+                        None => prompt_password(false)?,
+                    };
+                    let key = encryption.key_from_password(&entered)?;
+                    self.password = Some(entered); // Reuse for the remaining files
+                    Ok(Some(Decryption::from(key)))
+                }
+                _ => Ok(self.decryption.clone()),
+            }
+        }
+    }
+
+    /// How failed chunk reads and writes are tried again, for the commands that read or write chunks
+    #[derive(Args, Debug)]
+    pub struct RetryArgs {
+        /// Try a chunk read or write that fails with an I/O error (e.g., on a network file system) this many more
+        /// times before giving up
+        #[arg(long, value_name = "N", default_value = "0")]
+        retries: u32,
+
+        /// Seconds to wait before the first retry; the wait doubles before every further one, up to a minute
+        #[arg(long, value_name = "SECONDS", default_value = "1", value_parser = parse_seconds)]
+        retry_delay: Duration,
+    }
+
+    impl RetryArgs {
+        fn policy(&self) -> RetryPolicy {
+            RetryPolicy::new(self.retries, self.retry_delay)
+        }
+    }
+
+    impl Commands {
+        /// The operation this command runs between its --pre-cmd and --post-cmd, if it has them
+        fn hooks(&self) -> Option<(&'static str, &HookArgs)> {
+            match self {
+                Commands::Split { hooks, .. } => Some(("split", hooks)),
+                Commands::Restore { hooks, .. } => Some(("restore", hooks)),
+                Commands::Verify { hooks, .. } => Some(("verify", hooks)),
+                _ => None,
+            }
+        }
+    }
+
+    /// Commands run before and after an operation, for the commands that split, restore or verify
+    #[derive(Args, Debug)]
+    pub struct HookArgs {
+        /// Run this shell command before starting (e.g., to snapshot a volume); nothing is done if it fails.
+        /// FILE_SPLITTER_OPERATION tells it whether to split, restore or verify
+        #[arg(long, value_name = "COMMAND")]
+        pre_cmd: Option<String>,
+
+        /// Run this shell command when done, also after a failure (e.g., to send a notification), with
+        /// FILE_SPLITTER_OPERATION, FILE_SPLITTER_STATUS (success or failure), FILE_SPLITTER_MANIFEST (the info
+        /// files, one per line), FILE_SPLITTER_CHUNKS (the number of chunks) and FILE_SPLITTER_ERROR set
+        #[arg(long, value_name = "COMMAND")]
+        post_cmd: Option<String>,
+    }
+
+    impl HookArgs {
+        /// Runs `run` for `operation` between the pre and post commands
+        fn around(&self, operation: &str, run: impl FnOnce(&Arc<HookReport>) -> Result<()>) -> Result<()> {
+            if let Some(pre_cmd) = &self.pre_cmd {
+                run_shell("--pre-cmd", pre_cmd, &[("FILE_SPLITTER_OPERATION", operation.to_string())])?;
+            }
+            let report = Arc::new(HookReport::default());
+            let result = run(&report);
+            let Some(post_cmd) = &self.post_cmd else {
+                return result;
+            };
+            let env = [
+                ("FILE_SPLITTER_OPERATION", operation.to_string()),
+                ("FILE_SPLITTER_STATUS", if result.is_ok() { "success" } else { "failure" }.to_string()),
+                ("FILE_SPLITTER_MANIFEST", report.manifests.lock().unwrap().join("\n")),
+                ("FILE_SPLITTER_CHUNKS", report.chunks.load(Ordering::Relaxed).to_string()),
+                ("FILE_SPLITTER_ERROR", result.as_ref().err().map(|e| format!("{:#}", e)).unwrap_or_default()),
+            ];
+            let posted = run_shell("--post-cmd", post_cmd, &env);
+            match (result, posted) {
+                (Err(e), Err(post_error)) => {
+                    eprintln!("Error: {:#}", post_error);
+                    Err(e)
+                }
+                (result, posted) => result.and(posted),
+            }
+        }
+    }
+
+    /// What an operation did, for the environment of its --post-cmd
+    #[derive(Default)]
+    struct HookReport {
+        /// Info files written or read
+        manifests: Mutex<Vec<String>>,
+        /// Chunks stored, restored or verified
+        chunks: AtomicUsize,
+    }
+
+    impl HookReport {
+        fn add_manifest(&self, manifest: String) {
+            self.manifests.lock().unwrap().push(manifest);
+        }
+
+        fn add_chunks(&self, chunks: usize) {
+            self.chunks.fetch_add(chunks, Ordering::Relaxed);
+        }
+    }
+
+    /// How progress and status are reported, for the commands that show them
+    #[derive(Args, Debug)]
+    pub struct OutputArgs {
+        /// How to report progress: `bar` draws progress bars, `json` writes one JSON object per line to stderr
+        /// instead (for tools wrapping the CLI), with a record per event, including every stored chunk
+        #[arg(long = "progress", value_name = "FORMAT", default_value = "bar")]
+        format: ProgressFormat,
+
+        /// Only print errors and results: no progress bars or status messages (e.g., for cron jobs and pipes)
+        #[arg(short, long, conflicts_with = "verbose")]
+        quiet: bool,
+
+        /// Log what is done to stderr: `-v` every file split or restored, `-vv` every chunk as well
+        #[arg(short, long, action = clap::ArgAction::Count)]
+        verbose: u8,
+
+        /// Print a JSON summary of each file to stdout once it is done, in place of the status messages: where its
+        /// info file and chunks went, their sizes and checksums, and how long it took
+        #[arg(long)]
+        json: bool,
+    }
+
+    /// Progress bar being drawn, above which log lines are printed (see `--verbose`)
+    static CURRENT_BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+    /// Writes log lines to stderr, above the progress bar being drawn
+    struct LogWriter;
+
+    impl Write for LogWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match CURRENT_BAR.lock().unwrap().as_ref().filter(|bar| !bar.is_finished()) {
+                Some(bar) => bar.suspend(|| io::stderr().write(buf)),
+                None => io::stderr().write(buf),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            io::stderr().flush()
+        }
+    }
+
+    /// Output format of `--progress`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum ProgressFormat {
+        Bar,
+        Json,
+    }
+
+    impl std::str::FromStr for ProgressFormat {
+        type Err = anyhow::Error;
+
+        fn from_str(s: &str) -> Result<Self> {
+            match s.to_ascii_lowercase().as_str() {
+                "bar" => Ok(ProgressFormat::Bar),
+                "json" => Ok(ProgressFormat::Json),
+                _ => Err(anyhow::anyhow!("Unknown progress format '{}': expected bar or json", s)),
+            }
+        }
+    }
+
+    impl OutputArgs {
+        /// Starts logging the library's events for `--verbose`
+        fn init_logging(&self) {
+            let level = match self.verbose {
+                0 => return,
+                1 => tracing::Level::INFO,
+                _ => tracing::Level::DEBUG,
+            };
+            // Jobs of a job file run in one process, and log through the subscriber of the first
+            let _ = tracing_subscriber::fmt()
+                .with_max_level(level)
+                .with_target(false)
+                .with_writer(|| LogWriter)
+                .try_init();
+        }
+
+        /// Prints a status line to stdout, unless `--quiet` or `--json`
+        fn status(&self, msg: impl std::fmt::Display) {
+            if !self.quiet && !self.json {
+                println!("{}", msg);
+            }
+        }
+
+        /// Prints the summary of a finished file as a line of JSON to stdout, if `--json`
+        fn summary(&self, summary: serde_json::Value) {
+            if self.json {
+                println!("{}", summary);
+            }
+        }
+
+        /// Whether progress bars are drawn
+        fn draws_bars(&self) -> bool {
+            self.format == ProgressFormat::Bar && !self.quiet
+        }
+
+        /// Progress bar drawn with `template`, or a hidden one if progress is not drawn
+        fn bar(&self, template: &str) -> ProgressBar {
+            if !self.draws_bars() {
+                return ProgressBar::hidden();
+            }
+            let progress = ProgressBar::new(0); // Placeholder, the total is set by the progress callback
+            progress.set_style(ProgressStyle::default_bar().template(template).unwrap());
+            *CURRENT_BAR.lock().unwrap() = Some(progress.clone());
+            progress
+        }
+
+        /// Spinner drawn with `template`, for input of unknown size, or a hidden one if progress is not drawn
+        fn spinner(&self, template: &str) -> ProgressBar {
+            if !self.draws_bars() {
+                return ProgressBar::hidden();
+            }
+            let progress = ProgressBar::new_spinner();
+            progress.set_style(ProgressStyle::default_spinner().template(template).unwrap());
+            *CURRENT_BAR.lock().unwrap() = Some(progress.clone());
+            progress
+        }
+
+        /// Callback reporting the events of a split on `progress`, or as JSON records
+        fn split_events(&self, progress: &ProgressBar) -> Box<dyn Fn(SplitEvent) + Send + Sync> {
+            if self.format == ProgressFormat::Bar {
+                return show_split_events(progress);
+            }
+            // Only `Started` names the file, and a tree split reports one file after the other
+            let file = Mutex::new(String::new());
+            Box::new(move |event| {
+                let record = match event {
+                    SplitEvent::Started { name, total } => {
+                        let record = serde_json::json!({ "event": "started", "file": name, "total": total });
+                        *file.lock().unwrap() = name;
+                        record
+                    }
+                    SplitEvent::ChunkWritten { index, name, size, checksum, .. } => serde_json::json!({
+                        "event": "chunk",
+                        "file": *file.lock().unwrap(),
+                        "index": index,
+                        "name": name,
+                        "size": size,
+                        "checksum": checksum,
+                    }),
+                    SplitEvent::Progress { bytes, total, throughput } => {
+                        progress_record(&file.lock().unwrap(), bytes, total, throughput)
+                    }
+                    SplitEvent::Message(msg) => {
+                        serde_json::json!({ "event": "message", "file": *file.lock().unwrap(), "message": msg })
+                    }
+                    SplitEvent::Finished { name, info_name, chunks } => {
+                        serde_json::json!({ "event": "finished", "file": name, "info": info_name, "chunks": chunks })
+                    }
+                };
+                eprintln!("{}", record);
+            })
+        }
+
+        /// Callback reporting what `watch` does: a line for every file split and every message, a summary of
+        /// every split set in `store` (for --json), or JSON records (for --progress json)
+        #[cfg(feature = "watch")]
+        fn watch_events(&self, store: Arc<dyn ChunkStore>) -> Box<dyn Fn(SplitEvent) + Send + Sync> {
+            let records = (self.format == ProgressFormat::Json).then(|| self.split_events(&ProgressBar::hidden()));
+            let lines = records.is_none() && !self.quiet && !self.json;
+            let json = self.json;
+            Box::new(move |event| {
+                match &event {
+                    SplitEvent::Started { name, .. } if lines => println!("Splitting '{}'", name),
+                    SplitEvent::Finished { name, info_name, chunks } if json => println!("{}", serde_json::json!({
+                        "file": name,
+                        "info": ChunkSink::describe(store.as_ref(), info_name),
+                        "chunk_count": chunks,
+                    })),
+                    SplitEvent::Message(msg) if lines => println!("{}", msg),
+                    _ => {}
+                }
+                if let Some(records) = &records {
+                    records(event);
+                }
+            })
+        }
+
+        /// Progress and message callbacks for restoring or verifying `file`, reporting on `progress` or as JSON records
+        #[allow(clippy::type_complexity)]
+        fn restore_callbacks(&self, file: &str, progress: &ProgressBar) -> (Box<dyn Fn(u64, u64) + Send + Sync>, Box<dyn Fn(String) + Send + Sync>) {
+            let meter = Mutex::new(ProgressMeter::new());
+            if self.format == ProgressFormat::Json {
+                let (progress_file, message_file) = (file.to_string(), file.to_string());
+                return (
+                    Box::new(move |current, total| {
+                        eprintln!("{}", progress_record(&progress_file, current, total, meter.lock().unwrap().update(current, total)));
+                    }),
+                    Box::new(move |msg| {
+                        eprintln!("{}", serde_json::json!({ "event": "message", "file": message_file, "message": msg }));
+                    }),
+                );
+            }
+            let (bar, message_bar) = (progress.clone(), progress.clone());
+            (
+                Box::new(move |current, total| {
+                    if bar.length() != Some(total) {
+                        bar.set_length(total);
+                    }
+                    bar.set_prefix(format!("({})", meter.lock().unwrap().update(current, total)));
+                    bar.set_position(current);
+                }),
+                Box::new(move |msg| message_bar.set_message(msg)),
+            )
+        }
+
+        /// Reports the outcome of restoring `file` as a JSON record, if progress is reported as JSON
+        fn restored(&self, file: &str, report: &RestoreReport) {
+            if self.format == ProgressFormat::Json {
+                eprintln!("{}", serde_json::json!({
+                    "event": "finished",
+                    "file": file,
+                    "output": report.output_path,
+                    "bytes": report.bytes_written,
+                    "verified": report.chunks_verified,
+                    "failed": report.chunks_failed,
+                    "seconds": report.duration.as_secs_f64(),
+                }));
+            }
+        }
+    }
+
+    /// JSON summary of the split set `info`, written to `sink` in `elapsed`
+    fn split_summary(info: &SplitInfo, sink: &dyn ChunkSink, elapsed: Duration) -> serde_json::Value {
+        let chunks: Vec<serde_json::Value> = info.chunks.iter().map(|chunk| serde_json::json!({
+            "location": sink.describe(chunk.stored_as.as_deref().unwrap_or(&info.chunk_name(chunk))),
+            "size": chunk.chunk_size,
+            "checksum": chunk.chunk_checksum,
+        })).collect();
+        serde_json::json!({
+            "file": info.original_filename,
+            "info": sink.describe(&info.info_name()),
+            "size": info.original_file_size,
+            "stored_size": info.stored_size(),
+            "checksum": info.original_checksum,
+            "chunk_count": chunks.len(),
+            "chunks": chunks,
+            "seconds": elapsed.as_secs_f64(),
+        })
+    }
+
+    /// JSON record of `bytes` out of `total` (0 if not known) of `file` being done
+    fn progress_record(file: &str, bytes: u64, total: u64, throughput: Throughput) -> serde_json::Value {
+        serde_json::json!({
+            "event": "progress",
+            "file": file,
+            "bytes": bytes,
+            "total": total,
+            "bytes_per_sec": throughput.bytes_per_sec,
+            "remaining_secs": throughput.remaining.map(|remaining| remaining.as_secs_f64()),
+        })
+    }
+
+    /// Reads the info file at `path`, from `store` (with `path` relative to it) if given, else from the local disk
+    ///
+    /// A local chunk file that ends with the info file (see `split --info-trailer`) can be given instead.
+    fn read_info_file(store: Option<&dyn ChunkStore>, path: &Path) -> Result<SplitInfo> {
+        if store.is_none() && !is_manifest_path(path) {
+            let trailer = fs::File::open(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut file| read_info_trailer(&mut file))
+                .context(format!("Failed to read restore info file: {}", path.display()))?;
+            if let Some(file_info) = trailer {
+                return Ok(file_info);
+            }
+        }
+        let metadata_content = read_text_file(store, path, "restore info file")?;
+
+        parse_split_info(&path.to_string_lossy(), &metadata_content)
+            .context(format!("Failed to parse restore info file: {}", path.display())) // <--- CHANGED with_context TO context AND REMOVED CLOSURE
+    }
+
+    /// Whether `path` names an info file by its extension (e.g., "my_file.json" rather than a chunk file ending
+    /// with one)
+    fn is_manifest_path(path: &Path) -> bool {
+        ManifestFormat::from_extension(&path.to_string_lossy()).is_some()
+    }
+
+    /// Replaces every batch index among `info_files` (see [`BatchIndex::FILE_NAME`]) with the info files it lists,
+    /// each along with its entry in the index to check it against once read
+    ///
+    /// The listed info files are relative to the index's own directory (or key prefix in `store`).
+    fn expand_batch_indexes(store: Option<&dyn ChunkStore>, info_files: &[PathBuf]) -> Result<Vec<(PathBuf, Option<BatchEntry>)>> {
+        let mut expanded = Vec::new();
+        for path in info_files {
+            if path.file_name().is_none_or(|name| name != BatchIndex::FILE_NAME) {
+                expanded.push((path.clone(), None));
+                continue;
+            }
+            let batch_index: BatchIndex = serde_json::from_str(&read_text_file(store, path, "batch index file")?)
+                .context(format!("Failed to parse batch index file: {}", path.display()))?;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            for entry in batch_index.files {
+                expanded.push((dir.join(&entry.info_file), Some(entry)));
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Reads a text file from `store`, where `path` is a key, or from the local disk
+    fn read_text_file(store: Option<&dyn ChunkStore>, path: &Path, what: &str) -> Result<String> {
+        match store {
+            Some(store) => {
+                let mut content = String::new();
+                let name = path.to_string_lossy().replace('\\', "/");
+                store.open(&name)
+                    .and_then(|mut reader| Ok(reader.read_to_string(&mut content)?))
+                    .context(format!("Failed to read {}: {}", what, ChunkSource::describe(store, &name)))?;
+                Ok(content)
+            }
+            None => fs::read_to_string(path)
+                .context(format!("Failed to read {}: {}", what, path.display())),
+        }
+    }
+
+    /// Prints rows under a header, every column padded to its widest cell and numbers aligned right
+    fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+        let mut widths = header.map(str::len);
+        for row in rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let header = header.map(str::to_string);
+        for row in std::iter::once(&header).chain(rows) {
+            let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| {
+                if !cell.is_empty() && cell.bytes().all(|b| b.is_ascii_digit()) {
+                    format!("{:>width$}", cell)
+                } else {
+                    format!("{:<width$}", cell)
+                }
+            }).collect();
+            println!("{}", cells.join("  ").trim_end());
+        }
+    }
+
+    /// Job file of the `run` subcommand
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct JobFile {
+        /// Number of jobs run at once
+        parallel: Option<usize>,
+        jobs: Vec<Job>,
+    }
+
+    /// Command line run by `run`
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Job {
+        /// Name the job is reported by (default: its number)
+        name: Option<String>,
+        /// Arguments of the command line, starting with the subcommand (e.g., "split")
+        args: Vec<String>,
+    }
+
+    /// Reads the job file at `path`, in YAML if its extension says so, else in TOML
+    fn read_job_file(path: &Path) -> Result<JobFile> {
+        let content = fs::read_to_string(path).context(format!("Failed to read job file: {}", path.display()))?;
+        let job_file = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(&content).map_err(anyhow::Error::from),
+            _ => toml::from_str(&content).map_err(anyhow::Error::from),
+        };
+        job_file.context(format!("Failed to parse job file: {}", path.display()))
+    }
+
+    /// Runs `jobs`, `parallel` at once, reporting when each starts and ends, and a table of all of them at the end
+    ///
+    /// Every job runs, even after one failed; the run then fails.
+    fn run_jobs(jobs: &[Job], parallel: usize) -> Result<()> {
+        if parallel == 0 {
+            return Err(anyhow::anyhow!("The job file's parallel must be at least 1"));
+        }
+        // Parse every job first so that a bad one stops the run before anything is done
+        let mut commands = Vec::new();
+        for (i, job) in jobs.iter().enumerate() {
+            let name = job.name.clone().unwrap_or_else(|| format!("#{}", i + 1));
+            let cli = Cli::try_parse_from(std::iter::once("file_splitter").chain(job.args.iter().map(String::as_str)))
+                .map_err(|e| anyhow::anyhow!("Invalid job '{}': {}", name, e.render().to_string().trim_end()))?;
+            if !matches!(cli.command, Commands::Split { .. } | Commands::Restore { .. } | Commands::Verify { .. }) {
+                return Err(anyhow::anyhow!("Invalid job '{}': only split, restore and verify can run as jobs", name));
+            }
+            commands.push((name, cli));
+        }
+
+        let next = AtomicUsize::new(0);
+        let outcomes = Mutex::new(commands.iter().map(|_| None).collect::<Vec<_>>());
+        thread::scope(|scope| {
+            for _ in 0..parallel.min(commands.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((name, cli)) = commands.get(index) else {
+                        break;
+                    };
+                    println!("\nStarting job '{}'", name);
+                    let started = Instant::now();
+                    let result = run_hooked(cli);
+                    match &result {
+                        Ok(()) => println!("Job '{}' succeeded in {:.1?}", name, started.elapsed()),
+                        Err(e) => eprintln!("Job '{}' failed: {:#}", name, e),
+                    }
+                    outcomes.lock().unwrap()[index] = Some((result, started.elapsed()));
+                });
+            }
+        });
+
+        let outcomes = outcomes.into_inner().unwrap();
+        let rows: Vec<[String; 3]> = commands.iter().zip(&outcomes).map(|((name, _), outcome)| match outcome {
+            Some((Ok(()), elapsed)) => [name.clone(), "ok".to_string(), format!("{:.1?}", elapsed)],
+            Some((Err(e), elapsed)) => [name.clone(), format!("failed (exit code {})", exit_code(e)), format!("{:.1?}", elapsed)],
+            None => [name.clone(), "not run".to_string(), String::new()],
+        }).collect();
+        println!();
+        print_table(["JOB", "STATUS", "TIME"], &rows);
+        let failed = outcomes.iter().filter(|outcome| !matches!(outcome, Some((Ok(()), _)))).count();
+        if failed > 0 {
+            return Err(anyhow::anyhow!("{} of {} jobs failed", failed, commands.len()));
+        }
+        Ok(())
+    }
+
+    /// Saves `file_info` back to where [`read_info_file`] read it from
+    fn write_info_file(store: Option<&dyn ChunkStore>, path: &Path, file_info: &SplitInfo) -> Result<()> {
+        let info_data = file_info.manifest_format.encode(file_info)?;
+        match store {
+            Some(store) => {
+                let name = path.to_string_lossy().replace('\\', "/");
+                store.put(&name, info_data.as_bytes())
+                    .context(format!("Failed to save split info file: {}", ChunkSink::describe(store, &name)))
+            }
+            None => fs::write(path, info_data)
+                .context(format!("Failed to save split info file: {}", path.display())),
+        }
+    }
+
+    /// Downloads the info file at `url`, returning it with a source for its sub-files
+    #[cfg(feature = "http")]
+    fn open_info_url(url: &str) -> Result<(SplitInfo, Box<dyn ChunkSource>)> {
+        let (file_info, source) = file_splitter::HttpSource::from_info_url(url)?;
+        Ok((file_info, Box::new(source)))
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn open_info_url(url: &str) -> Result<(SplitInfo, Box<dyn ChunkSource>)> {
+        Err(anyhow::anyhow!("Cannot download '{}': restore --url is not supported by this build (enable the 'http' feature)", url))
+    }
+
+    /// Checks that `dirs` together have room for about `required` bytes, only warning if not with `ignore_space`
+    fn check_space(dirs: &[PathBuf], required: u64, ignore_space: bool) -> Result<()> {
+        let checked = match dirs {
+            [dir] => check_free_space(dir, required),
+            dirs => check_combined_free_space(dirs, required),
+        };
+        match checked {
+            Err(e) if ignore_space => {
+                eprintln!("Warning: {:#}", e);
+                Ok(())
+            }
+            checked => checked,
+        }
+    }
+
+    /// Bytes taken by the file at `path`, or by all files below it if it is a directory (0 if it does not exist)
+    fn disk_usage(path: &Path) -> u64 {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => fs::read_dir(path)
+                .map(|entries| entries.flatten().map(|entry| disk_usage(&entry.path())).sum())
+                .unwrap_or(0),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Tells how to restore the split set `info` with `cat`, if it is cat-compatible
+    fn report_cat_command(info: &SplitInfo, sink: &dyn ChunkSink, output: &OutputArgs) {
+        if let Some(cat_command) = info.cat_command() {
+            output.status(format!("Restore it without this tool with `{}` in '{}'.", cat_command, sink.describe(&info.chunks_sub_dir)));
+        }
+    }
+
+    /// Tells how many volumes the split set `info` spans, if more than one (see `split --span`)
+    fn report_volumes(info: &SplitInfo, output: &OutputArgs) {
+        if info.spans_volumes() {
+            output.status(format!(
+                "The sub-files span {} volumes, the last of which holds the split info; restoring from it asks for the others in turn.",
+                info.destinations.len()
+            ));
+        }
+    }
+
+    /// Shows `message` above the progress bar and waits for Enter, once volume `number` is mounted; fails if q is
+    /// typed instead (or standard input ends)
+    fn wait_for_volume(number: usize, message: &str) -> Result<()> {
+        let ask = || -> io::Result<String> {
+            eprint!("{}", message);
+            io::stderr().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            Ok(answer)
+        };
+        let bar = CURRENT_BAR.lock().unwrap().clone().filter(|bar| !bar.is_finished());
+        let answer = match bar {
+            Some(bar) => bar.suspend(ask),
+            None => ask(),
+        }.context("Failed to read the answer")?;
+        if answer.is_empty() || answer.trim().eq_ignore_ascii_case("q") {
+            return Err(anyhow::anyhow!("Stopped waiting for volume {}", number));
+        }
+        Ok(())
+    }
+
+    /// Source reading the split set `file_info`, which spans removable volumes mounted at `input_dir`, asking on
+    /// the terminal for each volume in turn
+    fn volume_source(input_dir: &Path, file_info: &SplitInfo) -> VolumeSource<'static> {
+        let mount = input_dir.display().to_string();
+        VolumeSource::new(input_dir, file_info, move |number, count, location| wait_for_volume(number, &format!(
+            "\n{} is on volume {} of {}. Mount it at {} and press Enter, or type q to stop: ",
+            location,
+            number,
+            count,
+            mount
+        )))
+    }
+
+    /// Writes the scripts restoring the split set `info` without this program into `sink`, flushing them to the disk
+    /// with `fsync`
+    fn emit_restore_scripts(info: &SplitInfo, sink: &dyn ChunkSink, fsync: bool, output: &OutputArgs) -> Result<()> {
+        for script in write_restore_scripts(info, sink)? {
+            if fsync {
+                sink.sync(&script)?;
+            }
+            output.status(format!("Restore script is saved to '{}'.", sink.describe(&script)));
+        }
+        Ok(())
+    }
+
+    /// Writes PAR2 recovery files for the split set of `name` that was just stored in `store` with the info file
+    /// `info_name`
+    fn write_par2_files(store: &dyn ChunkStore, name: &str, info_name: &str, recovery_blocks: usize, output: &OutputArgs) -> Result<()> {
+        let mut content = String::new();
+        store.open(info_name)
+            .and_then(|mut reader| Ok(reader.read_to_string(&mut content)?))
+            .context(format!("Failed to read split info file: {}", ChunkSource::describe(store, info_name)))?;
+        let file_info = parse_split_info(info_name, &content)
+            .context(format!("Failed to parse split info file: {}", ChunkSource::describe(store, info_name)))?;
+        write_par2(&file_info, store, recovery_blocks)?;
+        output.status(format!("PAR2 recovery files are saved to '{}'.", ChunkSource::describe(store, &format!("{0}_parts/{0}.par2", name))));
+        Ok(())
+    }
+
+    /// Expands the wildcard patterns among `paths` to the files they match, in alphabetical order
+    ///
+    /// A path that exists as given is kept as it is, even if it contains wildcard characters.
+    fn expand_globs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut expanded = Vec::new();
+        for path in paths {
+            let pattern = path.to_string_lossy();
+            if path.exists() || !pattern.contains(['*', '?', '[']) {
+                expanded.push(path.clone());
+                continue;
+            }
+            let mut matches = Vec::new();
+            for entry in glob::glob(&pattern).context(format!("Invalid wildcard pattern: {}", pattern))? {
+                let entry = entry.context(format!("Failed to expand wildcard pattern: {}", pattern))?;
+                if entry.is_file() {
+                    matches.push(entry);
+                }
+            }
+            if matches.is_empty() {
+                return Err(anyhow::anyhow!("No files match '{}'", pattern));
+            }
+            expanded.extend(matches);
+        }
+        Ok(expanded)
+    }
+
+    /// Runs the --on-chunk command for each chunk a split stores, one chunk after the other on a thread of its own
+    /// so that the split goes on meanwhile
+    struct ChunkHook {
+        paths: mpsc::Sender<String>,
+        runner: thread::JoinHandle<Result<()>>,
+        store: Arc<dyn ChunkStore>,
+    }
+
+    impl ChunkHook {
+        /// Starts running `command` for the chunks stored in `store`
+        fn start(command: &str, store: Arc<dyn ChunkStore>) -> Self {
+            let (paths, queued) = mpsc::channel::<String>();
+            let command = command.to_string();
+            let runner = thread::spawn(move || {
+                for path in queued {
+                    let line = command.replace("{chunk_path}", &shell_quote(&path));
+                    run_shell("--on-chunk", &line, &[])?;
+                }
+                Ok(())
+            });
+            ChunkHook { paths, runner, store }
+        }
 
-    #[derive(Parser, Debug)]
-    #[command(author, version, about, long_about = None)]
-    pub struct Cli {
-        #[command(subcommand)]
-        pub command: Commands,
+        /// Callback queuing the command for each chunk reported to it, before passing every event on to `events`
+        fn events(&self, events: Box<dyn Fn(SplitEvent) + Send + Sync>) -> Box<dyn Fn(SplitEvent) + Send + Sync> {
+            let (paths, store) = (self.paths.clone(), self.store.clone());
+            Box::new(move |event| {
+                if let SplitEvent::ChunkWritten { key, .. } = &event {
+                    // After a failed command nothing is run anymore, and `finish` says why
+                    let _ = paths.send(ChunkSink::describe(store.as_ref(), key));
+                }
+                events(event);
+            })
+        }
+
+        /// Waits until the command has run for every chunk reported, failing if it failed for one of them
+        fn finish(self) -> Result<()> {
+            drop(self.paths);
+            self.runner.join().unwrap_or_else(|_| Err(anyhow::anyhow!("The --on-chunk command runner crashed")))
+        }
     }
 
-    #[derive(Subcommand, Debug)]
-    pub enum Commands {
-        /// Split one or more files
-        Split {
-            /// List of file paths to split
-            #[arg(required = true)]
-            files: Vec<PathBuf>,
-            
-            /// Split size limit (bytes). If file size is greater than this, it will be split. Default 100MB (104857600 bytes)
-            #[arg(short, long, default_value = "104857600")]
-            size_limit: u64,
-            
-            /// Root directory where split sub-files and info files will be stored
-            #[arg(short, long, default_value = ".")]
-            output_dir: PathBuf,
+    /// Runs the shell command `line` given with `option`, with `env` added to its environment
+    ///
+    /// Its standard output goes to stderr, leaving stdout to the operation (e.g., for --json).
+    fn run_shell(option: &str, line: &str, env: &[(&str, String)]) -> Result<()> {
+        tracing::debug!(command = %line, "running {} command", option);
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(line)
+            .envs(env.iter().map(|(name, value)| (name, value)))
+            .stdout(io::stderr())
+            .status()
+            .context(format!("Failed to run {} command: {}", option, line))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("{} command failed ({}): {}", option, status, line));
+        }
+        Ok(())
+    }
 
-            /// Whether to Gzip compress the split sub-files
-            #[arg(short, long)]
-            compress: bool,
-        },
-        
-        /// Restore one or more files
-        Restore {
-            /// List of split info JSON file paths (e.g., my_file_parts/my_file.json)
-            #[arg(required = true)]
-            info_files: Vec<PathBuf>,
-            
-            /// Root directory where the split sub-files are located (usually the same as the output_dir during split)
-            #[arg(short, long, default_value = ".")]
-            input_dir: PathBuf,
-            
-            /// Directory where the restored large files will be saved
-            #[arg(short, long, default_value = ".")]
-            output_dir: PathBuf,
-        },
+    /// Callback showing the events of a split on `progress`: how far it got and how fast (as its prefix), and its
+    /// latest message
+    fn show_split_events(progress: &ProgressBar) -> Box<dyn Fn(SplitEvent) + Send + Sync> {
+        let progress = progress.clone();
+        Box::new(move |event| match event {
+            SplitEvent::Progress { bytes, total, throughput } => {
+                // Piped and archived input has no known total, and gets a spinner instead of a bar
+                if total > 0 && progress.length() != Some(total) {
+                    progress.set_length(total);
+                }
+                progress.set_prefix(format!("({})", throughput));
+                progress.set_position(bytes);
+            }
+            SplitEvent::Message(msg) => progress.set_message(msg),
+            _ => {}
+        })
+    }
+
+    /// Parses a --size-limit value, saying what it was read as if that is unusable
+    fn parse_size_limit(s: &str) -> Result<u64> {
+        match parse_size(s)? {
+            0 => Err(anyhow::anyhow!("'{}' is 0 bytes, but the size limit must be at least 1 byte", s)),
+            size_limit => Ok(size_limit),
+        }
+    }
+
+    /// Parses a --threads value: a number of workers, or `auto` for one per CPU core
+    fn parse_threads(s: &str) -> Result<usize> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(available_threads());
+        }
+        match s.parse() {
+            Ok(0) | Err(_) => Err(anyhow::anyhow!("Invalid thread count '{}': expected a number of at least 1, or auto", s)),
+            Ok(threads) => Ok(threads),
+        }
+    }
+
+    /// Parses a number of seconds (e.g., 0.5)
+    fn parse_seconds(s: &str) -> Result<Duration> {
+        s.parse().ok()
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid number of seconds '{}'", s))
+    }
+
+    /// Parses a --limit-rate value, a size per second
+    fn parse_rate(s: &str) -> Result<u64> {
+        match parse_size(s)? {
+            0 => Err(anyhow::anyhow!("'{}' is 0 bytes, but the rate limit must be at least 1 byte per second", s)),
+            rate => Ok(rate),
+        }
+    }
+
+    /// Reads a password from the terminal without echoing it
+    fn prompt_password(confirm: bool) -> Result<String> {
+        let password = rpassword::prompt_password("Password: ").context("Failed to read password")?;
+        if password.is_empty() {
+            return Err(anyhow::anyhow!("Password must not be empty"));
+        }
+        if confirm && rpassword::prompt_password("Confirm password: ").context("Failed to read password")? != password {
+            return Err(anyhow::anyhow!("Passwords do not match"));
+        }
+        Ok(password)
+    }
+
+    /// Exit code of an invalid command line (`EX_USAGE`), apart from the codes of [`exit_code`]
+    const EXIT_USAGE: i32 = 64;
+
+    /// Exit code for a run that failed with `error`, by its class, so scripts can branch on it
+    pub fn exit_code(error: &anyhow::Error) -> i32 {
+        match FailureKind::of(error) {
+            FailureKind::Verification => 2,
+            FailureKind::MissingChunk => 3,
+            FailureKind::Io => 4,
+            FailureKind::Other => 1,
+        }
     }
 
     pub fn run_cli() -> Result<()> {
-        let cli = Cli::parse();
+        // clap exits with 2 on its own errors, which is the code of a verification failure here
+        let cli = Cli::try_parse().unwrap_or_else(|e| {
+            let _ = e.print();
+            std::process::exit(if e.use_stderr() { EXIT_USAGE } else { 0 });
+        });
+
+        run_hooked(&cli)
+    }
+
+    /// Runs the command `cli` was parsed into between its --pre-cmd and --post-cmd, if it has them
+    fn run_hooked(cli: &Cli) -> Result<()> {
+        match cli.command.hooks() {
+            Some((operation, hooks)) => hooks.around(operation, |report| run_command(cli, report)),
+            None => run_command(cli, &Arc::default()),
+        }
+    }
 
+    /// Runs the command `cli` was parsed into, noting what it did in `hook_report` for the --post-cmd
+    fn run_command(cli: &Cli, hook_report: &Arc<HookReport>) -> Result<()> {
         match &cli.command {
-            Commands::Split { files, size_limit, output_dir, compress } => {
-                println!("\nStarting to process {} files for splitting...", files.len());
+            Commands::Split { files, stdin, name, dir, per_file, size_limit, preset, parts, split_by, max_lines, delimiter, output_dir: output_dirs, spread, span, dest, pool, dedup, parity, par2, emit_restore_script, chunk_headers, info_trailer, manifest_format, info_extension, naming, prefix, chunk_extension, start_index, cat_compatible, resume, skip_unchanged, force, fsync, ignore_space, follow_symlinks, mmap, dry_run, compress, compress_algo, checksum, threads, max_memory, limit_rate, key, keyfile, password, recipients, on_chunk, retry, hooks: _, output } => {
+                output.init_logging();
+                let output_dir = &output_dirs[0];
+                let spreading = dest.is_none() && output_dirs.len() > 1;
+                if spreading && (par2.is_some() || *emit_restore_script) {
+                    return Err(anyhow::anyhow!("PAR2 files and restore scripts need all sub-files in one directory, so they cannot be written for sub-files spread across several output directories"));
+                }
+                if dir.is_some() && !*per_file && *skip_unchanged {
+                    return Err(anyhow::anyhow!("--skip-unchanged needs the checksum of each file up front, so it only works with --dir together with --per-file"));
+                }
+                let files = &expand_globs(files)?;
+                if *span && (output_dirs.len() > 1 || files.len() > 1) {
+                    return Err(anyhow::anyhow!("--span fills the volumes mounted at one output directory with one file or directory at a time"));
+                }
+                if *span && available_space(output_dir)?.is_none() {
+                    return Err(anyhow::anyhow!("The free space of a volume cannot be told on this system, so --span cannot fill volumes"));
+                }
+                let delimiter = delimiter.as_deref().map(parse_delimiter).transpose()?;
+                let size_limit = preset.map_or(*size_limit, |preset| preset.size_limit());
+                let size_limit_for = |file_path: &Path| -> Result<u64> {
+                    match parts {
+                        Some(parts) => {
+                            let file_size = fs::metadata(file_path)
+                                .context(format!("Failed to open file: {}", file_path.display()))?
+                                .len();
+                            size_limit_for_parts(file_size, *parts)
+                        }
+                        None => Ok(size_limit),
+                    }
+                };
+                if *dry_run {
+                    let plan_options = SplitOptions {
+                        split_mode: *split_by,
+                        max_lines: *max_lines,
+                        delimiter: delimiter.as_deref(),
+                        follow_symlinks: *follow_symlinks,
+                        chunk_naming: *naming,
+                        chunk_prefix: prefix.as_deref(),
+                        chunk_extension: chunk_extension.as_deref(),
+                        chunk_start_index: *start_index,
+                        cat_compatible: *cat_compatible,
+                        ..Default::default()
+                    };
+                    let mut plans = Vec::new();
+                    if *stdin {
+                        let name = name.as_deref().unwrap_or("stdin");
+                        plans.push((name.to_string(), plan_split_reader(io::stdin(), name, size_limit, &plan_options)?));
+                    }
+                    for file_path in files {
+                        plans.push((file_path.display().to_string(), plan_split(file_path, size_limit_for(file_path)?, &plan_options)?));
+                    }
+                    for (name, chunks) in &plans {
+                        let total: u64 = chunks.iter().map(|chunk| chunk.length).sum();
+                        println!("\n{}: {} chunks, {} bytes", name, chunks.len(), total);
+                        let rows: Vec<[String; 4]> = chunks.iter().map(|chunk| [
+                            (chunk.index + 1).to_string(),
+                            chunk.chunk_filename.clone(),
+                            chunk.offset.to_string(),
+                            chunk.length.to_string(),
+                        ]).collect();
+                        print_table(["#", "CHUNK", "OFFSET", "SIZE"], &rows);
+                    }
+                    println!("\nDry run: nothing was written. Sizes are before compression (which shrinks the stored chunks) and encryption (which adds a little).");
+                    return Ok(());
+                }
+
+                let compression = compress_algo.or(if *compress { Some(CompressionAlgorithm::default()) } else { None });
+                let encryption = if let Some(key) = key {
+                    Some(Encryption::from(EncryptionKey::from_hex(key)?))
+                } else if let Some(keyfile) = keyfile {
+                    Some(Encryption::from(EncryptionKey::from_keyfile(keyfile)?))
+                } else if let Some(password) = password {
+                    let password = match password {
+                        Some(password) => password.clone(),
+                        None => prompt_password(true)?,
+                    };
+                    Some(Encryption::from(EncryptionKey::from_password(&password, &KdfParams::generate())?))
+                } else if !recipients.is_empty() {
+                    Some(Encryption::age_recipients(recipients)?)
+                } else {
+                    None
+                };
+                let dedup_index = DedupIndex::new();
+                let rate_limit = limit_rate.map(RateLimiter::new);
+                let shared_store: Arc<dyn ChunkStore> = match dest {
+                    Some(dest) => Arc::from(open_store(dest)?),
+                    None if spreading => Arc::new(SpreadSink::new(output_dirs.iter().cloned(), *spread)?),
+                    None if *span => {
+                        let mount = output_dir.display().to_string();
+                        Arc::new(VolumeSink::new(output_dir, size_limit, move |number, needed| wait_for_volume(number, &format!(
+                            "\nThe volume at {} has less than {} bytes free for the next sub-file. Mount volume {} there instead and press Enter, or type q to stop: ",
+                            mount,
+                            needed,
+                            number
+                        ))))
+                    }
+                    None => Arc::new(LocalDir::new(output_dir)),
+                };
+                let store: &dyn ChunkStore = shared_store.as_ref();
+                let sink: &dyn ChunkSink = store;
+                let hook = on_chunk.as_deref().map(|command| ChunkHook::start(command, shared_store.clone()));
+                let split_events = |progress: &ProgressBar| -> Box<dyn Fn(SplitEvent) + Send + Sync> {
+                    let events = match &hook {
+                        Some(hook) => hook.events(output.split_events(progress)),
+                        None => output.split_events(progress),
+                    };
+                    let hook_report = hook_report.clone();
+                    Box::new(move |event| {
+                        if let SplitEvent::ChunkWritten { .. } = event {
+                            hook_report.add_chunks(1);
+                        }
+                        events(event);
+                    })
+                };
+                let split_options = SplitOptions {
+                    codec: compression.as_ref().map(|algo| algo as &dyn ChunkCodec),
+                    encryption: encryption.as_ref(),
+                    checksum_algorithm: *checksum,
+                    split_mode: *split_by,
+                    max_lines: *max_lines,
+                    delimiter: delimiter.as_deref(),
+                    chunk_pool: pool.as_deref(),
+                    dedup: dedup.then_some(&dedup_index),
+                    parity: *parity,
+                    resume_from: resume.then_some(store as &dyn ChunkSource),
+                    skip_unchanged: skip_unchanged.then_some(store as &dyn ChunkSource),
+                    overwrite: *force,
+                    fsync: *fsync,
+                    follow_symlinks: *follow_symlinks,
+                    mmap: *mmap,
+                    threads: *threads,
+                    max_memory: *max_memory,
+                    chunk_headers: *chunk_headers,
+                    info_trailer: *info_trailer,
+                    manifest_format: *manifest_format,
+                    info_extension: info_extension.as_deref(),
+                    chunk_naming: *naming,
+                    chunk_prefix: prefix.as_deref(),
+                    chunk_extension: chunk_extension.as_deref(),
+                    chunk_start_index: *start_index,
+                    cat_compatible: *cat_compatible,
+                    cancel: None,
+                    pause: None,
+                    rate_limit: rate_limit.as_ref(),
+                    retry: retry.policy(),
+                };
+
+                // Chunks take up to as much as the input (compression aside), plus the parity chunks; an earlier
+                // split set of the same input counts as free, as it is replaced or kept; spanned volumes are filled
+                // as far as they go instead
+                if dest.is_none() && !*stdin && !*span {
+                    let inputs: Vec<&Path> = match dir {
+                        Some(dir) => vec![dir.as_path()],
+                        None => files.iter().map(PathBuf::as_path).collect(),
+                    };
+                    let mut required = 0u64;
+                    for input in inputs {
+                        let size = disk_usage(input);
+                        let parity_size = match dir {
+                            Some(_) => *parity as u64 * size_limit,
+                            None => *parity as u64 * size_limit_for(input)?,
+                        };
+                        let name = match dir {
+                            Some(dir) => directory_split_name(dir)?,
+                            None => input.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string(),
+                        };
+                        let existing: u64 = output_dirs.iter().map(|output_dir| disk_usage(&output_dir.join(format!("{}_parts", name)))).sum();
+                        required = required.saturating_add((size + parity_size).saturating_sub(existing));
+                    }
+                    check_space(output_dirs, required, *ignore_space)?;
+                }
+
+                if let Some(dir) = dir.as_deref().filter(|_| dest.is_none()) {
+                    // The growing chunks would end up in the archive they are split from
+                    for output_dir in output_dirs {
+                        if std::path::absolute(output_dir)?.starts_with(std::path::absolute(dir)?) {
+                            return Err(anyhow::anyhow!("The output directory must not be inside the directory to split: {}", dir.display()));
+                        }
+                    }
+                }
+                if let (Some(dir), true) = (dir, *per_file) {
+                    let progress = output.bar("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {prefix} {msg}");
+                    let started = Instant::now();
+                    let index = split_tree_to_sink(dir, size_limit, sink, &split_options, Some(split_events(&progress)))?;
+                    progress.finish();
+                    for file in &index.files {
+                        hook_report.add_manifest(sink.describe(&file.info_file));
+                    }
+                    if let Some(hook) = hook {
+                        hook.finish()?;
+                    }
+                    let files: Vec<serde_json::Value> = index.files.iter().map(|file| serde_json::json!({
+                        "path": file.path,
+                        "info": sink.describe(&file.info_file),
+                        "size": file.size,
+                    })).collect();
+                    output.summary(serde_json::json!({
+                        "directory": index.directory,
+                        "index": sink.describe(&index.index_name()),
+                        "files": files,
+                        "seconds": started.elapsed().as_secs_f64(),
+                    }));
+                    output.status(format!(
+                        "\nSplit {} files. The index to restore them from is saved to '{}'.",
+                        index.files.len(),
+                        sink.describe(&index.index_name())
+                    ));
+                    return Ok(());
+                }
+
+                if *stdin || dir.is_some() {
+                    let name = match dir {
+                        Some(dir) => directory_split_name(dir)?,
+                        None => name.as_deref().unwrap_or("stdin").to_string(),
+                    };
+                    let name = name.as_str();
+                    // The total size of piped or archived data is not known, so show a spinner instead of a bar
+                    let progress = output.spinner("{spinner:.green} [{elapsed_precise}] {bytes} {prefix} {msg}");
+                    let started = Instant::now();
+                    let info = match dir {
+                        Some(dir) => split_directory_to_sink(dir, size_limit, sink, &split_options, Some(split_events(&progress)))?,
+                        None => split_to_sink(io::stdin(), name, size_limit, sink, &split_options, Some(split_events(&progress)))?,
+                    };
+                    progress.finish_with_message(format!("'{}' splitting complete", name));
+                    hook_report.add_manifest(sink.describe(&info.info_name()));
+                    if let Some(hook) = hook {
+                        hook.finish()?;
+                    }
+                    output.summary(split_summary(&info, sink, started.elapsed()));
+                    output.status(format!("\nSplit information is saved to '{}'.", sink.describe(&info.info_name())));
+                    report_cat_command(&info, sink, output);
+                    report_volumes(&info, output);
+                    if let Some(recovery_blocks) = par2 {
+                        write_par2_files(store, name, &info.info_name(), *recovery_blocks, output)?;
+                    }
+                    if *emit_restore_script {
+                        emit_restore_scripts(&info, sink, *fsync, output)?;
+                    }
+                    return Ok(());
+                }
+
+                output.status(format!("\nStarting to process {} files for splitting...", files.len()));
+                let mut batch_infos = Vec::new();
                 for file_path in files {
-                    println!("\nProcessing file: {}", file_path.display());
-                    let progress = ProgressBar::new(0); // Placeholder, actual total will be set by callback
-                    progress.set_style(ProgressStyle::default_bar()
-                        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-                        .unwrap());
-                    
-                    let progress_cb = {
-                        let progress = progress.clone();
-                        move |current, total| {
-                            if progress.length().is_none() || progress.length().unwrap() != total {
-                                progress.set_length(total);
-                            }
-                            progress.set_position(current);
+                    output.status(format!("\nProcessing file: {}", file_path.display()));
+                    let size_limit = size_limit_for(file_path)?;
+                    if let Some(parts) = parts {
+                        output.status(format!("Splitting into {} parts of {} bytes", parts, size_limit));
+                    }
+                    let progress = output.bar("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {prefix} {msg}");
+
+                    let started = Instant::now();
+                    let info = split_file_to_sink(file_path, size_limit, sink, &split_options, Some(split_events(&progress)))?;
+                    hook_report.add_manifest(sink.describe(&info.info_name()));
+                    // Keep the last status, which says where the split info went or that the split was up to date
+                    progress.finish();
+                    output.summary(split_summary(&info, sink, started.elapsed()));
+                    report_cat_command(&info, sink, output);
+                    report_volumes(&info, output);
+                    if let Some(recovery_blocks) = par2 {
+                        let name = file_path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                        write_par2_files(store, name, &info.info_name(), *recovery_blocks, output)?;
+                    }
+                    if *emit_restore_script {
+                        emit_restore_scripts(&info, sink, *fsync, output)?;
+                    }
+                    batch_infos.push(info);
+                }
+                // Several files split at once get an index, to verify or restore the whole batch with
+                if batch_infos.len() > 1 {
+                    let batch_index = BatchIndex::new(&batch_infos);
+                    sink.put(BatchIndex::FILE_NAME, serde_json::to_string_pretty(&batch_index)?.as_bytes())
+                        .context(format!("Failed to save batch index file: {}", sink.describe(BatchIndex::FILE_NAME)))?;
+                    if *fsync {
+                        sink.sync(BatchIndex::FILE_NAME)?;
+                    }
+                    output.status(format!(
+                        "\nIndex of the {} split sets is saved to '{}'; give it to restore or verify to handle them all.",
+                        batch_infos.len(),
+                        sink.describe(BatchIndex::FILE_NAME)
+                    ));
+                }
+                if let Some(hook) = hook {
+                    hook.finish()?;
+                }
+                output.status("\nAll files split successfully!");
+                output.status("Each original file's split information (e.g., 'filename.json') is saved within its dedicated subdirectory (e.g., 'output_dir/filename_parts/').");
+            }
+            Commands::Restore { info_files, from_dirs, index, urls, input_dir, output_dir, source, stdout, threads, max_memory, limit_rate, resume, sparse, force, ignore_space, warn_only, retry, hooks: _, decryption, output } => {
+                output.init_logging();
+                let mut decryptor = decryption.decryptor()?;
+                let source_store = source.as_deref().map(open_store).transpose()?;
+                let rate_limit = limit_rate.map(RateLimiter::new);
+                // With --stdout the restored data owns standard output, so status lines go to stderr
+                let status = |msg: String| match (output.quiet || output.json, *stdout) {
+                    (true, _) => {}
+                    (false, true) => eprintln!("{}", msg),
+                    (false, false) => println!("{}", msg),
+                };
+                let mut stdout_sink = io::stdout().lock();
+                // Read every info file first so that a bad one stops the run before anything is restored
+                let mut restores: Vec<(SplitInfo, Option<Box<dyn ChunkSource>>)> = Vec::new();
+                let mut empty_dirs = Vec::new();
+                let mut symlinks = Vec::new();
+                for index_path in index {
+                    status(format!("\nReading tree index file: {}", index_path.display()));
+                    let tree_index: TreeIndex = serde_json::from_str(&read_text_file(source_store.as_deref(), index_path, "tree index file")?)
+                        .context(format!("Failed to parse tree index file: {}", index_path.display()))?;
+                    for file in &tree_index.files {
+                        // Info files are listed relative to the split output directory
+                        let info_file_path = match source_store {
+                            Some(_) => PathBuf::from(&file.info_file),
+                            None => input_dir.join(&file.info_file),
+                        };
+                        restores.push((read_info_file(source_store.as_deref(), &info_file_path)?, None));
+                        hook_report.add_manifest(info_file_path.display().to_string());
+                    }
+                    empty_dirs.extend(tree_index.empty_dirs);
+                    symlinks.extend(tree_index.symlinks);
+                }
+                let info_files = expand_batch_indexes(source_store.as_deref(), info_files)?;
+                status(format!("\nStarting to restore {} files...", info_files.len() + urls.len() + from_dirs.len() + restores.len()));
+                for url in urls {
+                    status(format!("\nReading restore info file: {}", url));
+                    let (file_info, url_source) = open_info_url(url)?;
+                    restores.push((file_info, Some(url_source)));
+                    hook_report.add_manifest(url.clone());
+                }
+                for (info_file_path, batch_entry) in &info_files {
+                    status(format!("\nReading restore info file: {}", info_file_path.display()));
+                    let file_info = read_info_file(source_store.as_deref(), info_file_path)?;
+                    if let Some(batch_entry) = batch_entry {
+                        batch_entry.check(&file_info)?;
+                    }
+                    restores.push((file_info, None));
+                    hook_report.add_manifest(info_file_path.display().to_string());
+                }
+
+                // A file restored over an existing one (resuming or with --force) only needs the difference
+                if !*stdout {
+                    let required = restores.iter()
+                        .map(|(file_info, _)| file_info.original_file_size.saturating_sub(disk_usage(&file_info.restored_path(output_dir))))
+                        .fold(0u64, u64::saturating_add);
+                    check_space(std::slice::from_ref(output_dir), required, *ignore_space)?;
+                }
+                for (file_info, url_source) in &restores {
+                    let chunk_source: Option<&dyn ChunkSource> = match url_source {
+                        Some(url_source) => Some(url_source.as_ref()),
+                        None => source_store.as_deref().map(|store| store as &dyn ChunkSource),
+                    };
+                    let volume_source = (chunk_source.is_none() && file_info.spans_volumes()).then(|| volume_source(input_dir, file_info));
+                    let chunk_source = chunk_source.or(volume_source.as_ref().map(|source| source as &dyn ChunkSource));
+
+                    let file_decryption = decryptor.for_file(file_info)?;
+
+                    let progress = output.bar("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {prefix}");
+                    let (progress_cb, message_cb) = output.restore_callbacks(&file_info.original_filename, &progress);
+
+                    let restore_options = RestoreOptions {
+                        decryption: file_decryption.as_ref(),
+                        // Volumes are asked for one at a time, so their chunks are read in order
+                        threads: if volume_source.is_some() { 1 } else { *threads },
+                        max_memory: *max_memory,
+                        rate_limit: rate_limit.as_ref(),
+                        retry: retry.policy(),
+                        resume: *resume,
+                        sparse: *sparse,
+                        overwrite: *force,
+                        warn_only: *warn_only,
+                        ..Default::default()
+                    };
+                    let report = match (chunk_source, *stdout) {
+                        (Some(chunk_source), true) => {
+                            restore_from_source(
+                                file_info,
+                                chunk_source,
+                                &mut stdout_sink,
+                                &restore_options,
+                                Some(progress_cb),
+                                Some(message_cb),
+                            )?;
+                            None
+                        }
+                        (Some(chunk_source), false) => Some(restore_file_from_source(
+                            file_info,
+                            chunk_source,
+                            output_dir,
+                            &restore_options,
+                            Some(progress_cb),
+                            Some(message_cb),
+                        )?),
+                        (None, true) => {
+                            restore_to_writer(
+                                file_info,
+                                input_dir,
+                                &mut stdout_sink,
+                                &restore_options,
+                                Some(progress_cb),
+                                Some(message_cb),
+                            )?;
+                            None
                         }
+                        (None, false) => Some(restore_single_file(
+                            file_info,
+                            input_dir,
+                            output_dir,
+                            &restore_options,
+                            Some(progress_cb),
+                            Some(message_cb), // <--- WRAP IN Box::new()
+                        )?),
                     };
+                    progress.finish_with_message(format!("'{}' restoration complete", file_info.original_filename));
+                    hook_report.add_chunks(file_info.chunks.len());
+                    if let Some(report) = report {
+                        output.restored(&file_info.original_filename, &report);
+                        output.summary(serde_json::json!({
+                            "file": file_info.original_filename,
+                            "output": report.output_path,
+                            "size": file_info.original_file_size,
+                            "checksum": file_info.original_checksum,
+                            "chunk_count": file_info.chunks.len(),
+                            "bytes": report.bytes_written,
+                            "verified": report.chunks_verified,
+                            "failed": report.chunks_failed,
+                            "seconds": report.duration.as_secs_f64(),
+                        }));
+                        status(format!(
+                            "Restored '{}' to {} in {:.1?}: {} bytes written, {} of {} chunks verified{}",
+                            file_info.original_filename,
+                            report.output_path.display(),
+                            report.duration,
+                            report.bytes_written,
+                            report.chunks_verified,
+                            file_info.chunks.len(),
+                            match report.chunks_failed {
+                                0 => String::new(),
+                                failed => format!(", {} damaged", failed),
+                            }
+                        ));
+                    }
+                }
+                for parts_dir in from_dirs {
+                    status(format!("\nRestoring without an info file from: {}", parts_dir.display()));
+                    let progress = output.bar("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {prefix}");
+                    let (progress_cb, message_cb) = output.restore_callbacks(&parts_dir.display().to_string(), &progress);
+                    let restore_options = RestoreOptions { rate_limit: rate_limit.as_ref(), overwrite: *force, ..Default::default() };
+                    let report = restore_from_chunk_dir(parts_dir, output_dir, &restore_options, Some(progress_cb), Some(message_cb))?;
+                    progress.finish_and_clear();
+                    hook_report.add_chunks(report.chunks);
+                    output.summary(serde_json::json!({
+                        "directory": parts_dir,
+                        "output": report.output_path,
+                        "chunk_count": report.chunks,
+                        "compression": report.compression.map(|algo| algo.name()),
+                        "bytes": report.bytes_written,
+                        "checksum": report.checksum,
+                        "seconds": report.duration.as_secs_f64(),
+                    }));
+                    status(format!(
+                        "Restored '{}' from {} chunks{} in {:.1?}: {} bytes written",
+                        report.output_path.display(),
+                        report.chunks,
+                        report.compression.map(|algo| format!(" ({} compressed)", algo)).unwrap_or_default(),
+                        report.duration,
+                        report.bytes_written
+                    ));
+                    status(format!("Without the info file it could not be verified; its checksum is {}", report.checksum));
+                }
+                let is_tree_path = |path: &str| Path::new(path).components().all(|part| matches!(part, std::path::Component::Normal(_)));
+                for dir in &empty_dirs {
+                    if !is_tree_path(dir) {
+                        return Err(anyhow::anyhow!("Invalid directory in tree index: {}", dir));
+                    }
+                    fs::create_dir_all(output_dir.join(dir))
+                        .context(format!("Failed to create directory: {}", output_dir.join(dir).display()))?;
+                }
+                // Links come last, so that no file is restored through one
+                for link in &symlinks {
+                    if !is_tree_path(&link.path) {
+                        return Err(anyhow::anyhow!("Invalid symbolic link in tree index: {}", link.path));
+                    }
+                    let link_path = output_dir.join(&link.path);
+                    if fs::read_link(&link_path).is_ok_and(|target| target == Path::new(&link.target)) {
+                        continue; // Restored before
+                    }
+                    if let Some(parent) = link_path.parent() {
+                        fs::create_dir_all(parent)
+                            .context(format!("Failed to create directory: {}", parent.display()))?;
+                    }
+                    std::os::unix::fs::symlink(&link.target, &link_path)
+                        .context(format!("Failed to create symbolic link: {}", link_path.display()))?;
+                }
+                status("\nAll files restored successfully!".to_string());
+            }
+            Commands::Verify { info_files, input_dir, source, threads, max_memory, retry, hooks: _, decryption, output } => {
+                output.init_logging();
+                let mut decryptor = decryption.decryptor()?;
+                let source_store = source.as_deref().map(open_store).transpose()?;
 
-                    let message_cb = {
-                        let progress = progress.clone();
-                        move |msg: String| {
-                            progress.set_message(msg);
+                // Check every split set, even after a damaged one, so that one run reports all the damage
+                let info_files = expand_batch_indexes(source_store.as_deref(), info_files)?;
+                let mut failed = 0;
+                let mut missing_chunks = false;
+                for (info_file_path, batch_entry) in &info_files {
+                    output.status(format!("\nVerifying: {}", info_file_path.display()));
+                    hook_report.add_manifest(info_file_path.display().to_string());
+                    let verified = read_info_file(source_store.as_deref(), info_file_path).and_then(|file_info| {
+                        if let Some(batch_entry) = batch_entry {
+                            batch_entry.check(&file_info)?;
+                        }
+                        let file_decryption = decryptor.for_file(&file_info)?;
+                        let spans_volumes = source_store.is_none() && file_info.spans_volumes();
+                        let verify_options = RestoreOptions {
+                            decryption: file_decryption.as_ref(),
+                            // Volumes are asked for one at a time, so their chunks are read in order
+                            threads: if spans_volumes { 1 } else { *threads },
+                            max_memory: *max_memory,
+                            retry: retry.policy(),
+                            ..Default::default()
+                        };
+                        let progress = output.bar("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {prefix}");
+                        let (progress_cb, _) = output.restore_callbacks(&file_info.original_filename, &progress);
+                        // Local chunks may be spread across several directories or volumes
+                        let local_dir: Box<dyn ChunkSource> = match spans_volumes {
+                            true => Box::new(volume_source(input_dir, &file_info)),
+                            false => Box::new(SpreadSink::for_split_set(input_dir, &file_info)),
+                        };
+                        let chunk_source: &dyn ChunkSource = match &source_store {
+                            Some(store) => store.as_ref(),
+                            None => local_dir.as_ref(),
+                        };
+                        let report = verify_split_set(&file_info, chunk_source, &verify_options, Some(progress_cb), None);
+                        progress.finish_and_clear();
+                        Ok((file_info, report?))
+                    });
+                    if let Ok((_, verified)) = &verified {
+                        hook_report.add_chunks(verified.chunks_checked);
+                    }
+                    let (file_info, report) = match verified {
+                        Ok(verified) => verified,
+                        Err(e) => {
+                            eprintln!("Error: {:#}", e);
+                            failed += 1;
+                            continue;
                         }
                     };
 
-                    split_single_file(
-                        file_path,
-                        *size_limit,
-                        output_dir,
-                        *compress,
-                        Some(Box::new(progress_cb)),
-                        Some(Box::new(message_cb)), // <--- WRAP IN Box::new()
-                    )?;
-                    progress.finish_with_message(format!("'{}' splitting complete", file_path.display()));
-                }
-                println!("\nAll files split successfully!");
-                println!("Each original file's split information (e.g., 'filename.json') is saved within its dedicated subdirectory (e.g., 'output_dir/filename_parts/').");
-            }
-            Commands::Restore { info_files, input_dir, output_dir } => {
-                println!("\nStarting to restore {} files...", info_files.len());
+                    let issues: Vec<serde_json::Value> = report.issues.iter().map(|issue| serde_json::json!({
+                        "chunk": issue.chunk_filename,
+                        "parity": issue.parity,
+                        "problem": issue.problem.to_string(),
+                    })).collect();
+                    output.summary(serde_json::json!({
+                        "file": file_info.original_filename,
+                        "info": info_file_path,
+                        "ok": report.is_ok(),
+                        "chunk_count": report.chunks_checked,
+                        "parity_chunk_count": report.parity_chunks_checked,
+                        "checksum_matches": report.file_checksum_matches,
+                        "issues": issues,
+                    }));
+                    for issue in report.issues.iter().filter(|_| !output.json) {
+                        let kind = if issue.parity { "Parity chunk" } else { "Chunk" };
+                        println!("{} '{}': {}", kind, issue.chunk_filename, issue.problem);
+                    }
+                    if report.file_checksum_matches == Some(false) && !output.json {
+                        println!("The chunks of '{}' do not match the original file's checksum", file_info.original_filename);
+                    }
+                    if report.is_ok() {
+                        output.status(format!(
+                            "'{}' is intact ({} chunks, {} parity chunks)",
+                            file_info.original_filename,
+                            report.chunks_checked,
+                            report.parity_chunks_checked
+                        ));
+                    } else {
+                        failed += 1;
+                        missing_chunks |= report.issues.iter().any(|issue| matches!(issue.problem, ChunkProblem::Missing));
+                        if !output.json {
+                            println!("'{}' failed verification", file_info.original_filename);
+                        }
+                        if report.is_repairable() && !output.json {
+                            println!("It can be repaired from its parity chunks with `file_splitter repair {}`", info_file_path.display());
+                        }
+                    }
+                }
+                if failed > 0 {
+                    let kind = if missing_chunks { FailureKind::MissingChunk } else { FailureKind::Verification };
+                    return Err(Failure::new(kind, format!("{} of {} split sets failed verification", failed, info_files.len())).into());
+                }
+                output.status("\nAll split sets verified successfully!");
+            }
+            Commands::Info { info_files, source } => {
+                let source_store = source.as_deref().map(open_store).transpose()?;
                 for info_file_path in info_files {
-                    println!("\nReading restore info file: {}", info_file_path.display());
-                    
-                    let metadata_content = fs::read_to_string(info_file_path)
-                        .context(format!("Failed to read restore info file: {}", info_file_path.display()))?; // <--- CHANGED with_context TO context AND REMOVED CLOSURE
-                    
-                    let file_info: SplitInfo = serde_json::from_str(&metadata_content)
-                        .context(format!("Failed to parse restore info JSON file: {}", info_file_path.display()))?; // <--- CHANGED with_context TO context AND REMOVED CLOSURE
-
-                    let progress = ProgressBar::new(0); // Placeholder
-                    progress.set_style(ProgressStyle::default_bar()
-                        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-                        .unwrap());
-                    
-                    let progress_cb = {
-                        let progress = progress.clone();
-                        move |current, total| {
-                            if progress.length().is_none() || progress.length().unwrap() != total {
-                                progress.set_length(total);
-                            }
-                            progress.set_position(current);
+                    let file_info = read_info_file(source_store.as_deref(), info_file_path)?;
+                    let stored_size = file_info.stored_size();
+                    let compression = match (&file_info.custom_codec, file_info.compression()) {
+                        (Some(codec), _) => format!("custom codec '{}'", codec),
+                        (None, Some(algorithm)) => algorithm.to_string(),
+                        (None, None) => "none".to_string(),
+                    };
+                    let mut rows = vec![
+                        ("File", match file_info.archive {
+                            Some(archive) => format!("{}/ (directory tree, bundled as {})", file_info.original_filename, archive),
+                            None => file_info.original_filename.clone(),
+                        }),
+                        ("Size", format!("{} bytes", file_info.original_file_size)),
+                        ("Checksum", file_info.original_checksum.clone()),
+                        ("Chunks", format!(
+                            "{} (up to {} bytes each, split by {}) in {}",
+                            file_info.chunks.len(),
+                            file_info.chunk_limit,
+                            file_info.split_mode,
+                            file_info.chunk_pool.as_ref().unwrap_or(&file_info.chunks_sub_dir)
+                        )),
+                        ("Compression", compression),
+                        ("Encryption", file_info.encryption.as_ref().map_or("none".to_string(), |e| e.algorithm.to_string())),
+                        ("Stored size", format!("{} bytes", stored_size)),
+                    ];
+                    if file_info.is_compressed && stored_size > 0 {
+                        rows.push(("Compression ratio", format!(
+                            "{:.2}:1 ({:.1}% of the original size)",
+                            file_info.original_file_size as f64 / stored_size as f64,
+                            stored_size as f64 * 100.0 / file_info.original_file_size.max(1) as f64
+                        )));
+                    }
+                    if let Some(parity) = &file_info.parity {
+                        rows.push(("Parity", format!(
+                            "{} chunks of {} bytes ({} bytes)",
+                            parity.chunks.len(),
+                            parity.shard_size,
+                            parity.chunks.len() as u64 * parity.shard_size
+                        )));
+                    }
+                    if let Some(mode) = file_info.metadata.as_ref().and_then(|metadata| metadata.mode) {
+                        rows.push(("Permissions", format!("{:04o}", mode)));
+                    }
+                    if let Some(modified) = file_info.metadata.as_ref().and_then(|metadata| metadata.modified) {
+                        rows.push(("Modified", modified.to_rfc3339()));
+                    }
+                    if let Some(metadata) = file_info.metadata.as_ref().filter(|metadata| !metadata.xattrs.is_empty()) {
+                        rows.push(("Extended attributes", metadata.xattrs.keys().cloned().collect::<Vec<_>>().join(", ")));
+                    }
+                    if let Some(last_verified) = file_info.last_verified {
+                        rows.push(("Last verified", last_verified.to_rfc3339()));
+                    }
+                    let chunks_at = |index: usize| file_info.chunks.iter().filter(|chunk| chunk.destination == Some(index)).count();
+                    if file_info.spans_volumes() {
+                        let chunks: Vec<String> = (0..file_info.destinations.len()).map(|index| chunks_at(index).to_string()).collect();
+                        rows.push(("Volumes", format!(
+                            "{} mounted in turn at {} ({} chunks)",
+                            file_info.destinations.len(),
+                            file_info.destinations[0],
+                            chunks.join(" + ")
+                        )));
+                    } else if file_info.destinations.len() > 1 {
+                        for (index, destination) in file_info.destinations.iter().enumerate() {
+                            rows.push(("Spread to", format!("{} ({} chunks)", destination, chunks_at(index))));
                         }
+                    }
+                    if let Some(cat_command) = file_info.cat_command() {
+                        rows.push(("Restore with cat", format!("{} (in {})", cat_command, file_info.chunks_sub_dir)));
+                    }
+
+                    println!("\n{}", info_file_path.display());
+                    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0) + 1;
+                    for (label, value) in rows {
+                        println!("  {:<width$} {}", format!("{}:", label), value);
+                    }
+                }
+            }
+            Commands::List { info_files, input_dir, source } => {
+                let source_store = source.as_deref().map(open_store).transpose()?;
+
+                for info_file_path in info_files {
+                    let file_info = read_info_file(source_store.as_deref(), info_file_path)?;
+                    println!("\n{} ({} bytes, {} chunks)", file_info.original_filename, file_info.original_file_size, file_info.chunks.len());
+                    let local_dir = SpreadSink::for_split_set(input_dir, &file_info);
+                    let store: &dyn ChunkStore = match &source_store {
+                        Some(store) => store.as_ref(),
+                        None => &local_dir,
                     };
 
-                    let message_cb = {
-                        let progress = progress.clone();
-                        move |msg: String| {
-                            progress.set_message(msg);
+                    let mut rows = Vec::new();
+                    let mut missing = 0;
+                    let mut status = |name: &str| -> Result<&'static str> {
+                        if store.contains(name)? {
+                            Ok("present")
+                        } else {
+                            missing += 1;
+                            Ok("missing")
                         }
                     };
+                    for (index, chunk_info) in file_info.chunks.iter().enumerate() {
+                        rows.push([
+                            (index + 1).to_string(),
+                            file_info.chunk_name(chunk_info),
+                            chunk_info.chunk_size.to_string(),
+                            chunk_info.chunk_checksum.clone().unwrap_or_else(|| "-".to_string()),
+                            status(&file_info.chunk_name(chunk_info))?.to_string(),
+                        ]);
+                    }
+                    for (index, parity_chunk) in file_info.parity.iter().flat_map(|parity| parity.chunks.iter().enumerate()) {
+                        let name = format!("{}/{}", file_info.chunks_sub_dir, parity_chunk.chunk_filename);
+                        rows.push([
+                            format!("P{}", index + 1),
+                            name.clone(),
+                            file_info.parity.as_ref().map_or(0, |parity| parity.shard_size).to_string(),
+                            parity_chunk.chunk_checksum.clone(),
+                            status(&name)?.to_string(),
+                        ]);
+                    }
+                    print_table(["#", "FILE", "SIZE", "CHECKSUM", "STATUS"], &rows);
+                    match missing {
+                        0 => println!("All {} chunks are present", rows.len()),
+                        missing => println!("{} of {} chunks are missing", missing, rows.len()),
+                    }
+                }
+            }
+            Commands::Compare { info_file, file, chunks } => {
+                let file_info = read_info_file(None, info_file)?;
+                let report = compare_file(&file_info, file, *chunks)?;
+                if report.size == report.expected_size {
+                    println!("Size:     matches ({} bytes)", report.size);
+                } else {
+                    println!("Size:     differs (expected {} bytes, actual {})", report.expected_size, report.size);
+                }
+                println!("Checksum: {}", if report.checksum_matches { "matches" } else { "differs" });
+                if let Some(differing_chunks) = report.differing_chunks.as_ref().filter(|chunks| !chunks.is_empty()) {
+                    println!("\nDiffering chunks:");
+                    let rows: Vec<_> = differing_chunks.iter().map(|range| [
+                        (range.index + 1).to_string(),
+                        range.chunk_filename.clone(),
+                        range.offset.to_string(),
+                        range.length.to_string(),
+                    ]).collect();
+                    print_table(["#", "CHUNK", "OFFSET", "LENGTH"], &rows);
+                }
+                if !report.is_match() {
+                    return Err(Failure::new(FailureKind::Verification, format!(
+                        "'{}' does not match the original file '{}'",
+                        file.display(),
+                        file_info.original_filename
+                    )).into());
+                }
+                println!("\n'{}' matches the original file '{}'", file.display(), file_info.original_filename);
+            }
+            Commands::Clean { info_files, input_dir, output_dir } => {
+                // Check every restored file before deleting anything, so that one bad restore keeps all split sets
+                let mut cleanups = Vec::new();
+                for info_file_path in info_files {
+                    let file_info = read_info_file(None, info_file_path)?;
+                    let restored_path = file_info.restored_path(output_dir);
+                    println!("Verifying restored file: {}", restored_path.display());
+                    check_restored_file(&file_info, &restored_path)
+                        .context("Keeping all split sets, as a restored file could not be verified")?;
+                    cleanups.push((info_file_path, file_info));
+                }
+
+                for (info_file_path, file_info) in cleanups {
+                    let removed = remove_split_set(&file_info, input_dir)?;
+                    fs::remove_file(info_file_path)
+                        .with_context(|| format!("Failed to delete split info file: {}", info_file_path.display()))?;
+                    let chunks_dir = input_dir.join(&file_info.chunks_sub_dir);
+                    // Other files may share the directory (e.g., a pool or a foreign file); keep it then
+                    let dir_kept = fs::read_dir(&chunks_dir).map_or(true, |mut entries| entries.next().is_some())
+                        || fs::remove_dir(&chunks_dir).is_err();
+                    println!(
+                        "Cleaned up '{}': removed {} chunk files, {}{}",
+                        file_info.original_filename,
+                        removed.len(),
+                        info_file_path.display(),
+                        if dir_kept { String::new() } else { format!(" and {}", chunks_dir.display()) }
+                    );
+                }
+                println!("\nAll split sets cleaned up successfully!");
+            }
+            Commands::Repair { info_files, input_dir, source, decryption } => {
+                let mut decryptor = decryption.decryptor()?;
+                let source_store = source.as_deref().map(open_store).transpose()?;
+
+                for info_file_path in info_files {
+                    println!("\nRepairing: {}", info_file_path.display());
+                    let mut file_info = read_info_file(source_store.as_deref(), info_file_path)?;
+                    let local_dir = SpreadSink::for_split_set(input_dir, &file_info);
+                    let store: &dyn ChunkStore = match &source_store {
+                        Some(store) => store.as_ref(),
+                        None => &local_dir,
+                    };
+                    let file_decryption = decryptor.for_file(&file_info)?;
+                    let repair_options = RestoreOptions {
+                        decryption: file_decryption.as_ref(),
+                        ..Default::default()
+                    };
+                    let report = repair_split_set(&mut file_info, store, &repair_options)?;
+                    for chunk_filename in report.rebuilt_chunks.iter().chain(&report.rebuilt_parity_chunks) {
+                        println!("Rebuilt '{}'", chunk_filename);
+                    }
+                    if report.rebuilt_chunks.is_empty() && report.rebuilt_parity_chunks.is_empty() {
+                        println!("All chunks of '{}' are intact", file_info.original_filename);
+                    }
+                    // Record that the set was verified, unless the info file came from the end of a chunk file
+                    if source_store.is_some() || is_manifest_path(info_file_path) {
+                        write_info_file(source_store.as_deref(), info_file_path, &file_info)?;
+                    }
+                }
+                println!("\nAll split sets repaired successfully!");
+            }
+            Commands::RebuildManifest { chunks_dir, checksum } => {
+                println!("Reading the sub-files in {}", chunks_dir.display());
+                let file_info = rebuild_split_info(chunks_dir, *checksum, None)?;
 
-                    restore_single_file(
-                        &file_info,
-                        input_dir,
-                        output_dir,
-                        Some(Box::new(progress_cb)),
-                        Some(Box::new(message_cb)), // <--- WRAP IN Box::new()
-                    )?;
-                    progress.finish_with_message(format!("'{}' restoration complete", file_info.original_filename));
+                let info_path = chunks_dir.join(format!("{}.json", file_info.original_filename));
+                if info_path.exists() {
+                    let backup_path = chunks_dir.join(format!("{}.json.bak", file_info.original_filename));
+                    fs::rename(&info_path, &backup_path)
+                        .context(format!("Failed to move aside old split info JSON file: {}", info_path.display()))?;
+                    println!("Kept the old info file as {}", backup_path.display());
+                }
+                write_info_file(None, &info_path, &file_info)?;
+                println!(
+                    "Rebuilt {} from {} chunks{}: {} bytes, {}",
+                    info_path.display(),
+                    file_info.chunks.len(),
+                    file_info.compression().map(|algo| format!(" ({} compressed)", algo)).unwrap_or_default(),
+                    file_info.original_file_size,
+                    file_info.original_checksum
+                );
+                println!("Compare the checksum with one known from elsewhere: the chunks alone cannot prove the content is right.");
+            }
+            Commands::Identify { chunk_files } => {
+                let mut unidentified = 0;
+                for chunk_file in chunk_files {
+                    let mut file = fs::File::open(chunk_file)
+                        .context(format!("Failed to open chunk file: {}", chunk_file.display()))?;
+                    let header = ChunkHeader::read_from(&mut file)
+                        .context(format!("Failed to read chunk file: {}", chunk_file.display()))?;
+                    let Some(header) = header else {
+                        println!("{}: no chunk header", chunk_file.display());
+                        unidentified += 1;
+                        continue;
+                    };
+                    let mut notes = Vec::new();
+                    if let Some(codec) = &header.codec {
+                        notes.push(format!("{} compressed", codec));
+                    }
+                    if header.encrypted {
+                        notes.push("encrypted".to_string());
+                    }
+                    println!(
+                        "{}: chunk {}{} of '{}'{}, {}",
+                        chunk_file.display(),
+                        header.index,
+                        header.total.map(|total| format!(" of {}", total)).unwrap_or_default(),
+                        header.original_filename,
+                        if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) },
+                        header.checksum
+                    );
+                }
+                if unidentified > 0 {
+                    return Err(anyhow::anyhow!("{} of {} files have no chunk header", unidentified, chunk_files.len()));
+                }
+            }
+            #[cfg(feature = "http")]
+            Commands::Serve { dir, bind } => {
+                serve_directory(dir, bind, Some(Box::new(|msg: String| println!("{}", msg))))?;
+            }
+            #[cfg(feature = "watch")]
+            Commands::Watch { dir, min_size, size_limit, output_dir, dest, compress, compress_algo, checksum, threads, retry, output } => {
+                output.init_logging();
+                let compression = compress_algo.or(if *compress { Some(CompressionAlgorithm::default()) } else { None });
+                let store: Arc<dyn ChunkStore> = match dest {
+                    Some(dest) => Arc::from(open_store(dest)?),
+                    None => Arc::new(LocalDir::new(output_dir)),
+                };
+                // A file that changes again is split again over its earlier split set
+                let split_options = SplitOptions {
+                    codec: compression.as_ref().map(|algo| algo as &dyn ChunkCodec),
+                    checksum_algorithm: *checksum,
+                    threads: *threads,
+                    overwrite: true,
+                    retry: retry.policy(),
+                    ..Default::default()
+                };
+                watch_directory(dir, *min_size, *size_limit, store.as_ref(), &split_options, Some(output.watch_events(store.clone())))?;
+            }
+            Commands::Run { job_file, parallel } => {
+                let job_file = read_job_file(job_file)?;
+                run_jobs(&job_file.jobs, parallel.or(job_file.parallel).unwrap_or(1))?;
+            }
+            Commands::Manpage { out_dir } => {
+                let command = <Cli as clap::CommandFactory>::command();
+                let Some(out_dir) = out_dir else {
+                    clap_mangen::Man::new(command).render(&mut io::stdout().lock())?;
+                    return Ok(());
+                };
+                fs::create_dir_all(out_dir).context(format!("Failed to create directory: {}", out_dir.display()))?;
+                let name = command.get_name().to_string();
+                let mut pages = vec![(name.clone(), command.clone())];
+                pages.extend(command.get_subcommands().filter(|sub| sub.get_name() != "help").map(|sub| {
+                    let page_name = format!("{}-{}", name, sub.get_name());
+                    let page = sub.clone().display_name(page_name.clone()).bin_name(format!("{} {}", name, sub.get_name()))
+                        .version(env!("CARGO_PKG_VERSION"));
+                    (page_name, page)
+                }));
+                for (page_name, page) in pages {
+                    let page_path = out_dir.join(format!("{}.1", page_name));
+                    let mut page_file = fs::File::create(&page_path)
+                        .context(format!("Failed to create file: {}", page_path.display()))?;
+                    clap_mangen::Man::new(page).render(&mut page_file)
+                        .context(format!("Failed to write file: {}", page_path.display()))?;
+                    println!("Wrote {}", page_path.display());
                 }
-                println!("\nAll files restored successfully!");
             }
         }
         Ok(())
@@ -159,19 +2435,27 @@ mod cli {
 mod gui {
     use super::*;
     use eframe::{egui, NativeOptions};
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::mpsc::{self, Sender, Receiver};
+    use std::sync::{Arc, Mutex};
+    use std::path::Path;
     use std::thread;
     use file_splitter::split_single_file;
+    use file_splitter::parse_size;
+    use file_splitter::available_threads;
     use file_splitter::restore_single_file;
-    use file_splitter::SplitInfo;
-    use rfd::FileDialog;
-    use serde_json;
+    use file_splitter::{parse_split_info, SplitInfo};
+    use file_splitter::CompressionAlgorithm;
+    use file_splitter::ChunkCodec;
+    use file_splitter::{PauseGate, ProgressMeter, RestoreOptions, SplitEvent, SplitOptions, Throughput};
+    use rfd::{FileDialog, MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
 
     // Messages sent from worker thread to GUI thread
     enum WorkerMessage {
-        Progress(u64, u64),
+        Progress(u64, u64, Throughput),
         Message(String),
         Error(String),
+        Cancelled,
         Done,
     }
 
@@ -181,12 +2465,16 @@ mod gui {
         split_size_limit: String,
         split_output_dir: String,
         split_compress: bool,
+        split_compress_algo: CompressionAlgorithm,
         restore_info_files_input: String,
         restore_input_dir: String,
         restore_output_dir: String,
         current_progress: f32,
+        throughput: Option<Throughput>,
         status_message: String,
         is_processing: bool,
+        cancel: Arc<AtomicBool>,
+        pause: Arc<PauseGate>,
         tx: Option<Sender<WorkerMessage>>,
         rx: Option<Receiver<WorkerMessage>>,
     }
@@ -221,7 +2509,7 @@ mod gui {
                         }
                     });
                     ui.horizontal(|ui| {
-                        ui.label("Split size limit (bytes):");
+                        ui.label("Split size limit (e.g., 100MB, 4.7GiB):");
                         ui.text_edit_singleline(&mut self.split_size_limit);
                     });
                     ui.horizontal(|ui| {
@@ -233,7 +2521,18 @@ mod gui {
                             }
                         }
                     });
-                    ui.checkbox(&mut self.split_compress, "Compress Sub-files (Gzip)");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.split_compress, "Compress Sub-files");
+                        ui.add_enabled_ui(self.split_compress, |ui| {
+                            egui::ComboBox::from_id_source("split_compress_algo")
+                                .selected_text(self.split_compress_algo.to_string())
+                                .show_ui(ui, |ui| {
+                                    for algo in CompressionAlgorithm::ALL {
+                                        ui.selectable_value(&mut self.split_compress_algo, *algo, algo.to_string());
+                                    }
+                                });
+                        });
+                    });
 
                     if ui.add_enabled(!self.is_processing, egui::Button::new("Start Splitting")).clicked() {
                         self.start_operation(ctx.clone(), OperationType::Split);
@@ -248,7 +2547,10 @@ mod gui {
                         ui.label("Split Info JSON Files (comma-separated):");
                         ui.text_edit_singleline(&mut self.restore_info_files_input);
                         if ui.button("Select JSON Files").clicked() {
-                            if let Some(paths) = FileDialog::new().add_filter("JSON Files", &["json"]).pick_files() {
+                            if let Some(paths) = FileDialog::new()
+                                .add_filter("Split Info Files", &["json", "toml", "yaml", "yml"])
+                                .add_filter("All Files", &["*"])
+                                .pick_files() {
                                 self.restore_info_files_input = paths.iter()
                                     .map(|p| p.to_string_lossy().into_owned())
                                     .collect::<Vec<_>>()
@@ -286,13 +2588,33 @@ mod gui {
                     ui.label("Status:");
                     ui.label(&self.status_message);
                 });
-                ui.add(egui::ProgressBar::new(self.current_progress).show_percentage());
+                ui.horizontal(|ui| {
+                    let paused = self.pause.is_paused();
+                    if ui.add_enabled(self.is_processing, egui::Button::new(if paused { "Resume" } else { "Pause" })).clicked() {
+                        if paused {
+                            self.pause.resume();
+                            self.status_message = "Resumed".to_string();
+                        } else {
+                            self.pause.pause();
+                            self.status_message = "Paused".to_string();
+                        }
+                    }
+                    if ui.add_enabled(self.is_processing, egui::Button::new("Cancel")).clicked() {
+                        self.cancel.store(true, Ordering::Relaxed);
+                        self.status_message = "Cancelling...".to_string();
+                    }
+                    ui.add(egui::ProgressBar::new(self.current_progress).show_percentage());
+                    if let Some(throughput) = self.throughput.filter(|_| self.is_processing) {
+                        ui.label(throughput.to_string());
+                    }
+                });
 
                 if let Some(rx) = &self.rx {
                     while let Ok(msg) = rx.try_recv() {
                         match msg {
-                            WorkerMessage::Progress(current, total) => {
+                            WorkerMessage::Progress(current, total, throughput) => {
                                 self.current_progress = if total > 0 { current as f32 / total as f32 } else { 0.0 };
+                                self.throughput = Some(throughput);
                                 ctx.request_repaint();
                             }
                             WorkerMessage::Message(msg) => {
@@ -304,6 +2626,11 @@ mod gui {
                                 self.is_processing = false;
                                 ctx.request_repaint();
                             }
+                            WorkerMessage::Cancelled => {
+                                self.status_message = "Operation Cancelled".to_string();
+                                self.is_processing = false;
+                                ctx.request_repaint();
+                            }
                             WorkerMessage::Done => {
                                 self.status_message = "Operation Complete!".to_string();
                                 self.is_processing = false;
@@ -323,20 +2650,68 @@ mod gui {
     }
 
     impl FileSplitterApp {
+        /// What the operation would replace in its output directory: the split sets of the files to split, or the
+        /// files to restore
+        fn existing_targets(&self, op_type: &OperationType) -> Vec<PathBuf> {
+            let listed = |input: &str| -> Vec<String> {
+                input.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+            };
+            let targets: Vec<PathBuf> = match op_type {
+                OperationType::Split => {
+                    let output_dir = PathBuf::from(&self.split_output_dir);
+                    listed(&self.split_files_input).iter()
+                        .filter_map(|file| Path::new(file).file_name().and_then(|n| n.to_str()))
+                        .map(|name| output_dir.join(format!("{}_parts", name)))
+                        .collect()
+                }
+                OperationType::Restore => {
+                    let output_dir = PathBuf::from(&self.restore_output_dir);
+                    listed(&self.restore_info_files_input).iter()
+                        .filter_map(|info_file| parse_split_info(info_file, &fs::read_to_string(info_file).ok()?).ok())
+                        .map(|info| info.restored_path(&output_dir))
+                        .collect()
+                }
+            };
+            targets.into_iter().filter(|path| path.exists()).collect()
+        }
+
         fn start_operation(&mut self, ctx: egui::Context, op_type: OperationType) {
+            // Replacing earlier output must be confirmed
+            let existing = self.existing_targets(&op_type);
+            let overwrite = !existing.is_empty();
+            if overwrite {
+                let confirmed = MessageDialog::new()
+                    .set_level(MessageLevel::Warning)
+                    .set_title("Overwrite existing files?")
+                    .set_description(format!(
+                        "These already exist and will be replaced:\n\n{}\n\nContinue?",
+                        existing.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join("\n")
+                    ))
+                    .set_buttons(MessageButtons::YesNo)
+                    .show();
+                if confirmed != MessageDialogResult::Yes {
+                    self.status_message = "Cancelled: nothing was overwritten.".to_string();
+                    return;
+                }
+            }
             self.is_processing = true;
             self.status_message = "Preparing...".to_string();
             self.current_progress = 0.0;
+            self.throughput = None;
 
             let (tx, rx) = mpsc::channel();
             self.tx = Some(tx);
             self.rx = Some(rx);
+            self.cancel = Arc::new(AtomicBool::new(false));
+            self.pause = Arc::new(PauseGate::new());
+            let cancel = self.cancel.clone();
+            let pause = self.pause.clone();
 
             let tx_clone = self.tx.as_ref().unwrap().clone();
             let split_files_input_clone = self.split_files_input.clone();
             let split_size_limit_clone = self.split_size_limit.clone();
             let split_output_dir_clone = self.split_output_dir.clone();
-            let split_compress_clone = self.split_compress;
+            let split_compression_clone = if self.split_compress { Some(self.split_compress_algo) } else { None };
 
             let restore_info_files_input_clone = self.restore_info_files_input.clone();
             let restore_input_dir_clone = self.restore_input_dir.clone();
@@ -349,7 +2724,11 @@ mod gui {
                             .filter(|s| !s.trim().is_empty())
                             .map(|s| PathBuf::from(s.trim()))
                             .collect();
-                        let size_limit = match split_size_limit_clone.parse::<u64>() {
+                        let size_limit = match parse_size(&split_size_limit_clone) {
+                            Ok(0) => return tx_clone.send(WorkerMessage::Error(format!(
+                                "Invalid size limit: '{}' is 0 bytes, but it must be at least 1 byte",
+                                split_size_limit_clone.trim()
+                            ))).unwrap(),
                             Ok(s) => s,
                             Err(e) => return tx_clone.send(WorkerMessage::Error(format!("Invalid size limit: {}", e))).unwrap(),
                         };
@@ -367,29 +2746,42 @@ mod gui {
                         }
 
                         for file_path in files {
-                            let ctx_for_progress = ctx.clone();
-                            let ctx_for_message = ctx.clone();
-
-                            let tx_progress = tx_clone.clone();
-                            let tx_message = tx_clone.clone();
-                            let progress_cb = Box::new(move |current, total| {
-                                tx_progress.send(WorkerMessage::Progress(current, total)).unwrap();
-                                ctx_for_progress.request_repaint();
-                            });
-                            let message_cb = Box::new(move |msg: String| {
-                                tx_message.send(WorkerMessage::Message(msg)).unwrap();
-                                ctx_for_message.request_repaint();
+                            let ctx_for_events = ctx.clone();
+                            let tx_events = tx_clone.clone();
+                            let event_cb = Box::new(move |event| {
+                                match event {
+                                    SplitEvent::Progress { bytes, total, throughput } => {
+                                        tx_events.send(WorkerMessage::Progress(bytes, total, throughput)).unwrap()
+                                    }
+                                    SplitEvent::Message(msg) => tx_events.send(WorkerMessage::Message(msg)).unwrap(),
+                                    _ => return,
+                                }
+                                ctx_for_events.request_repaint();
                             });
 
-                            if let Err(e) = split_single_file(
+                            match split_single_file(
                                 &file_path,
                                 size_limit,
                                 &output_dir,
-                                split_compress_clone,
-                                Some(progress_cb),
-                                Some(message_cb),
+                                &SplitOptions {
+                                    codec: split_compression_clone.as_ref().map(|algo| algo as &dyn ChunkCodec),
+                                    threads: available_threads(),
+                                    overwrite,
+                                    cancel: Some(&cancel),
+                                    pause: Some(&pause),
+                                    ..Default::default()
+                                },
+                                Some(event_cb),
                             ) {
-                                return tx_clone.send(WorkerMessage::Error(format!("File splitting failed: {}", e))).unwrap();
+                                Ok((info, info_path)) => tx_clone.send(WorkerMessage::Message(format!(
+                                    "Split {} into {} chunks ({} bytes stored), described by {}",
+                                    info.original_filename,
+                                    info.chunks.len(),
+                                    info.stored_size(),
+                                    info_path.display()
+                                ))).unwrap(),
+                                Err(_) if cancel.load(Ordering::Relaxed) => return tx_clone.send(WorkerMessage::Cancelled).unwrap(),
+                                Err(e) => return tx_clone.send(WorkerMessage::Error(format!("File splitting failed: {}", e))).unwrap(),
                             }
                         }
                         Ok(())
@@ -422,9 +2814,9 @@ mod gui {
                                 Err(e) => return tx_clone.send(WorkerMessage::Error(format!("Failed to read restore info file {}: {}", info_file_path.display(), e))).unwrap(),
                             };
                             
-                            let file_info: SplitInfo = match serde_json::from_str(&metadata_content) {
+                            let file_info: SplitInfo = match parse_split_info(&info_file_path.to_string_lossy(), &metadata_content) {
                                 Ok(info) => info,
-                                Err(e) => return tx_clone.send(WorkerMessage::Error(format!("Failed to parse restore info JSON file {}: {}", info_file_path.display(), e))).unwrap(),
+                                Err(e) => return tx_clone.send(WorkerMessage::Error(format!("Failed to parse restore info file {}: {}", info_file_path.display(), e))).unwrap(),
                             };
 
                             let ctx_for_progress = ctx.clone();
@@ -432,8 +2824,10 @@ mod gui {
 
                             let tx_progress = tx_clone.clone();
                             let tx_message = tx_clone.clone();
+                            let meter = Mutex::new(ProgressMeter::new());
                             let progress_cb = Box::new(move |current, total| {
-                                tx_progress.send(WorkerMessage::Progress(current, total)).unwrap();
+                                let throughput = meter.lock().unwrap().update(current, total);
+                                tx_progress.send(WorkerMessage::Progress(current, total, throughput)).unwrap();
                                 ctx_for_progress.request_repaint();
                             });
                             let message_cb = Box::new(move |msg: String| {
@@ -441,14 +2835,30 @@ mod gui {
                                 ctx_for_message.request_repaint();
                             });
 
-                            if let Err(e) = restore_single_file(
+                            match restore_single_file(
                                 &file_info,
                                 &input_dir,
                                 &output_dir,
+                                &RestoreOptions {
+                                    threads: available_threads(),
+                                    overwrite,
+                                    cancel: Some(&cancel),
+                                    pause: Some(&pause),
+                                    ..Default::default()
+                                },
                                 Some(progress_cb),
                                 Some(message_cb),
                             ) {
-                                return tx_clone.send(WorkerMessage::Error(format!("File restoration failed: {}", e))).unwrap();
+                                Ok(report) => tx_clone.send(WorkerMessage::Message(format!(
+                                    "Restored {} ({} bytes, {} of {} chunks verified) in {:.1?}",
+                                    report.output_path.display(),
+                                    report.bytes_written,
+                                    report.chunks_verified,
+                                    file_info.chunks.len(),
+                                    report.duration
+                                ))).unwrap(),
+                                Err(_) if cancel.load(Ordering::Relaxed) => return tx_clone.send(WorkerMessage::Cancelled).unwrap(),
+                                Err(e) => return tx_clone.send(WorkerMessage::Error(format!("File restoration failed: {}", e))).unwrap(),
                             }
                         }
                         Ok(())
@@ -498,6 +2908,10 @@ fn main() -> Result<()> {
 
     #[cfg(not(target_os = "windows"))]
     {
-        cli::run_cli()
+        if let Err(e) = cli::run_cli() {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(cli::exit_code(&e));
+        }
+        Ok(())
     }
-}
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,191 @@
+// src/manifest.rs
+use std::fmt;
+use std::str::FromStr;
+use anyhow::Result;
+
+use crate::{ChecksumAlgorithm, Failure, FailureKind, SplitInfo};
+
+/// Format an info file is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ManifestFormat {
+    /// JSON, the original and default format
+    #[default]
+    Json,
+    /// TOML (needs the `toml` feature)
+    Toml,
+    /// YAML (needs the `serde_yaml` feature)
+    Yaml,
+}
+
+impl ManifestFormat {
+    /// All supported formats, in the order they are offered to users
+    pub const ALL: &'static [ManifestFormat] = &[ManifestFormat::Json, ManifestFormat::Toml, ManifestFormat::Yaml];
+
+    /// Short lowercase name of the format (e.g., "toml")
+    pub fn name(&self) -> &'static str {
+        match self {
+            ManifestFormat::Json => "json",
+            ManifestFormat::Toml => "toml",
+            ManifestFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Extension of info files in this format, which is also its name
+    pub fn extension(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Format of an info file named `name`, if its extension tells
+    pub fn from_extension(name: &str) -> Option<ManifestFormat> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(ManifestFormat::Json),
+            "toml" => Some(ManifestFormat::Toml),
+            "yaml" | "yml" => Some(ManifestFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of an info file from its content: JSON starts with a brace, and the first line of TOML
+    /// is a table header or a `key = value` pair; anything else is taken for YAML
+    pub fn detect(content: &str) -> ManifestFormat {
+        let Some(line) = content.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#')) else {
+            return ManifestFormat::Json;
+        };
+        if line.starts_with('{') {
+            return ManifestFormat::Json;
+        }
+        let equals = line.find('=');
+        let colon = line.find(':');
+        if line.starts_with('[') || equals.is_some_and(|equals| colon.is_none_or(|colon| equals < colon)) {
+            ManifestFormat::Toml
+        } else {
+            ManifestFormat::Yaml
+        }
+    }
+
+    /// Writes `split_info` in this format
+    ///
+    /// The written info file records the checksum of its content (see [`SplitInfo::manifest_checksum`]).
+    pub fn encode(&self, split_info: &SplitInfo) -> Result<String> {
+        let split_info = &with_content_checksum(split_info)?;
+        match self {
+            ManifestFormat::Json => Ok(serde_json::to_string_pretty(split_info)?),
+            #[cfg(feature = "toml")]
+            ManifestFormat::Toml => Ok(toml::to_string_pretty(split_info)?),
+            #[cfg(feature = "serde_yaml")]
+            ManifestFormat::Yaml => Ok(serde_yaml::to_string(split_info)?),
+            #[allow(unreachable_patterns)]
+            format => Err(unsupported(*format)),
+        }
+    }
+
+    /// Reads split info written in this format
+    ///
+    /// Fails if the info file records a checksum of its content that the content does not match (see
+    /// [`SplitInfo::manifest_checksum`]).
+    pub fn decode(&self, content: &str) -> Result<SplitInfo> {
+        let mut split_info: SplitInfo = match self {
+            ManifestFormat::Json => serde_json::from_str(content)?,
+            #[cfg(feature = "toml")]
+            ManifestFormat::Toml => toml::from_str(content)?,
+            #[cfg(feature = "serde_yaml")]
+            ManifestFormat::Yaml => serde_yaml::from_str(content)?,
+            #[allow(unreachable_patterns)]
+            format => return Err(unsupported(*format)),
+        };
+        check_content_checksum(&split_info)?;
+        split_info.manifest_format = *self;
+        Ok(split_info)
+    }
+}
+
+/// Checksum of the canonical content of `split_info`: its compact JSON, without the checksum itself, so that it
+/// is the same whichever format the info file is written in
+fn content_checksum(split_info: &SplitInfo) -> Result<String> {
+    let canonical = SplitInfo { manifest_checksum: None, ..split_info.clone() };
+    let mut hasher = ChecksumAlgorithm::Sha256.hasher();
+    hasher.update(&serde_json::to_vec(&canonical)?);
+    Ok(ChecksumAlgorithm::Sha256.tag(&hasher.finalize()))
+}
+
+/// Copy of `split_info` that records the checksum of its content, to be written out
+pub(crate) fn with_content_checksum(split_info: &SplitInfo) -> Result<SplitInfo> {
+    Ok(SplitInfo { manifest_checksum: Some(content_checksum(split_info)?), ..split_info.clone() })
+}
+
+/// Checks the content of `split_info`, as read from an info file, against the checksum it records, if any
+pub(crate) fn check_content_checksum(split_info: &SplitInfo) -> Result<()> {
+    let Some(expected) = &split_info.manifest_checksum else {
+        return Ok(());
+    };
+    let actual = content_checksum(split_info)?;
+    if actual != *expected {
+        return Err(Failure::new(
+            FailureKind::Verification,
+            format!(
+                "The info file of '{}' is damaged: its content does not match the checksum it records (expected {}, \
+                 actual {}), so it may be truncated or edited. Remove its manifest_checksum to read it anyway",
+                split_info.original_filename,
+                expected,
+                actual
+            ),
+        ).into());
+    }
+    Ok(())
+}
+
+/// Error for a format this build cannot read or write
+#[allow(dead_code)]
+fn unsupported(format: ManifestFormat) -> anyhow::Error {
+    let feature = match format {
+        ManifestFormat::Yaml => "serde_yaml",
+        format => format.name(),
+    };
+    anyhow::anyhow!(
+        "{} info files are not supported by this build (enable the '{}' feature)",
+        format.name().to_uppercase(),
+        feature
+    )
+}
+
+impl fmt::Display for ManifestFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ManifestFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        ManifestFormat::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name().eq_ignore_ascii_case(s))
+            .or_else(|| s.eq_ignore_ascii_case("yml").then_some(ManifestFormat::Yaml))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown manifest format '{}' (expected one of: {})",
+                s,
+                ManifestFormat::ALL.iter().map(|f| f.name()).collect::<Vec<_>>().join(", ")
+            ))
+    }
+}
+
+/// Parses the info file named `name` (a path or storage key), in the format its extension tells, or that its
+/// content looks like if the extension does not tell (see [`ManifestFormat::detect`])
+///
+/// The returned split info's [`SplitInfo::manifest_format`] is the format the file was read in, and its
+/// [`SplitInfo::info_extension`] the extension of `name` if it is not that of the format (e.g., "fsmeta.json").
+pub fn parse_split_info(name: &str, content: &str) -> Result<SplitInfo> {
+    let format = ManifestFormat::from_extension(name).unwrap_or_else(|| ManifestFormat::detect(content));
+    let mut split_info = format.decode(content)?;
+    let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    split_info.info_extension = file_name.strip_prefix(split_info.original_filename.as_str())
+        .and_then(|rest| rest.strip_prefix('.'))
+        .filter(|extension| *extension != format.extension())
+        .filter(|extension| extension.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')))
+        .map(str::to_string);
+    Ok(split_info)
+}
@@ -0,0 +1,117 @@
+// src/metadata.rs
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Attributes of the original file beyond its content, reapplied to the restored file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Unix permission bits (e.g., 0o755 for an executable); not recorded on other systems
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Last modification time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+    /// Extended attributes (e.g., security labels) by name, with hex-encoded values; only recorded on Linux and
+    /// macOS, and only those with UTF-8 names
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+}
+
+impl FileMetadata {
+    /// Records the attributes of the file at `path`, whose `metadata` is already known
+    pub fn read(path: &Path, metadata: &fs::Metadata) -> Result<Self> {
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+        Ok(FileMetadata {
+            mode,
+            modified: metadata.modified().ok().map(DateTime::from),
+            xattrs: read_xattrs(path)
+                .with_context(|| format!("Failed to read the extended attributes of {}", path.display()))?,
+        })
+    }
+
+    /// Sets the recorded attributes on the file at `path`
+    ///
+    /// The extended attributes and modification time are set first, as the permissions may make the file
+    /// read-only. Outside Unix, only whether the file is writable is taken from the mode bits.
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        write_xattrs(path, &self.xattrs)?;
+        if let Some(modified) = self.modified {
+            File::options().write(true).open(path)
+                .and_then(|file| file.set_modified(modified.into()))
+                .with_context(|| format!("Failed to set the modification time of {}", path.display()))?;
+        }
+        if let Some(mode) = self.mode {
+            let mut permissions = fs::metadata(path)?.permissions();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                permissions.set_mode(mode);
+            }
+            #[cfg(not(unix))]
+            permissions.set_readonly(mode & 0o222 == 0);
+            fs::set_permissions(path, permissions)
+                .with_context(|| format!("Failed to set the permissions of {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read_xattrs(path: &Path) -> std::io::Result<BTreeMap<String, String>> {
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        // Nothing to record on a file system without extended attributes
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut xattrs = BTreeMap::new();
+    for name in names {
+        let Some(utf8_name) = name.to_str() else {
+            continue;
+        };
+        // An attribute removed since it was listed is simply not recorded
+        if let Some(value) = xattr::get(path, &name)? {
+            xattrs.insert(utf8_name.to_string(), hex::encode(value));
+        }
+    }
+    Ok(xattrs)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_xattrs(_path: &Path) -> std::io::Result<BTreeMap<String, String>> {
+    Ok(BTreeMap::new())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn write_xattrs(path: &Path, xattrs: &BTreeMap<String, String>) -> Result<()> {
+    for (name, value) in xattrs {
+        let value = hex::decode(value)
+            .with_context(|| format!("Invalid value of extended attribute '{}': expected hex digits", name))?;
+        xattr::set(path, name, &value)
+            .with_context(|| format!("Failed to set extended attribute '{}' on {}", name, path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn write_xattrs(path: &Path, xattrs: &BTreeMap<String, String>) -> Result<()> {
+    if let Some(name) = xattrs.keys().next() {
+        return Err(anyhow::anyhow!(
+            "Cannot set extended attribute '{}' on {}: extended attributes are only supported on Linux and macOS",
+            name,
+            path.display()
+        ));
+    }
+    Ok(())
+}
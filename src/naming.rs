@@ -0,0 +1,225 @@
+// src/naming.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use anyhow::Result;
+
+/// Fewest digits chunk numbers are padded to, which is all up to 999 chunks take
+pub(crate) const MIN_INDEX_WIDTH: usize = 3;
+
+/// Number of digits chunk numbers are zero-padded to for a split into `chunk_count` chunks, so that the chunk
+/// files sort in order by name (e.g., 4 for 1000 to 9999 chunks). If the count is not known up front (e.g., for
+/// stdin or a split by lines), numbers are padded to [`MIN_INDEX_WIDTH`] digits and grow longer past 999.
+pub(crate) fn index_width(chunk_count: Option<u64>) -> usize {
+    let digits = chunk_count.map_or(0, |count| count.max(1).ilog10() as usize + 1);
+    digits.max(MIN_INDEX_WIDTH)
+}
+
+/// Chunk count to pad chunk numbers for if the count is not known up front but the chunk files must sort in
+/// order by name all the same (see [`SplitOptions::cat_compatible`](crate::SplitOptions::cat_compatible))
+pub(crate) const SORTABLE_CHUNK_COUNT: u64 = 999_999;
+
+/// How the chunk files of a split set are named after the original file and the chunk number
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChunkNaming {
+    /// "<file name>-<number>" (e.g., "my_file.zip-001"), the original naming
+    #[default]
+    Dashed,
+    /// "<file name>.<number>" (e.g., "my_file.zip.001"), the volume naming of HJSplit and 7-Zip, which join such
+    /// chunks back together (as do `copy /b` and `cat`) if they are stored as they are
+    DotNumeric,
+    /// "<prefix><letters>" (e.g., "xaa", "xab", ...), the naming of GNU `split` with the prefix "x" by default,
+    /// so that `cat x* > file` joins the chunks, which are always stored as they are. Like GNU `split`, the
+    /// suffix grows past "yz" to "zaaa" and so on, keeping the chunk files in order by name.
+    Gnu,
+}
+
+impl ChunkNaming {
+    /// All supported namings, in the order they are offered to users
+    pub const ALL: &'static [ChunkNaming] = &[ChunkNaming::Dashed, ChunkNaming::DotNumeric, ChunkNaming::Gnu];
+
+    /// Short lowercase name of the naming (e.g., "dot-numeric")
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChunkNaming::Dashed => "dashed",
+            ChunkNaming::DotNumeric => "dot-numeric",
+            ChunkNaming::Gnu => "gnu",
+        }
+    }
+
+    /// Base name of the chunk files if no prefix is given: the original file name, or "x" like GNU `split`
+    pub(crate) fn default_prefix<'n>(&self, filename: &'n str) -> &'n str {
+        match self {
+            ChunkNaming::Gnu => "x",
+            _ => filename,
+        }
+    }
+
+    /// Shell glob that follows the base name to match the chunk files of a split into `chunk_count` chunks, and
+    /// nothing else next to them (e.g., the info file), in order
+    pub(crate) fn cat_pattern(&self, chunk_count: usize) -> &'static str {
+        match self {
+            ChunkNaming::Dashed => "-[0-9]*",
+            ChunkNaming::DotNumeric => ".[0-9]*",
+            // Two-letter suffixes run out after "yz"
+            ChunkNaming::Gnu if chunk_count <= 25 * 26 => "[a-z][a-z]",
+            ChunkNaming::Gnu => "[a-z][a-z]*",
+        }
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == ChunkNaming::default()
+    }
+
+    /// Naming of `chunk_filename`, a chunk file of `filename`, as far as it can be told
+    pub(crate) fn of(filename: &str, chunk_filename: &str) -> ChunkNaming {
+        match parse_chunk_name(chunk_filename) {
+            Some(parsed) if parsed.name == filename && parsed.separator == '.' => ChunkNaming::DotNumeric,
+            _ => ChunkNaming::Dashed,
+        }
+    }
+}
+
+impl fmt::Display for ChunkNaming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ChunkNaming {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ChunkNaming::ALL
+            .iter()
+            .copied()
+            .find(|naming| naming.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown chunk naming '{}' (expected one of: {})",
+                s,
+                ChunkNaming::ALL.iter().map(|n| n.name()).collect::<Vec<_>>().join(", ")
+            ))
+    }
+}
+
+/// Names the chunk files of a split set after the original file and the chunk number (e.g., "my_file-001")
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkNamer<'n> {
+    filename: &'n str,
+    naming: ChunkNaming,
+    width: usize,
+    start_index: u64,
+    extension: Option<&'n str>,
+}
+
+impl<'n> ChunkNamer<'n> {
+    /// Names the chunks of a split of `filename` into `chunk_count` chunks, if known (see [`index_width`]), numbered
+    /// from `start_index` and ending with `.<extension>` if given (e.g., "my_file-001.part")
+    pub(crate) fn new(
+        filename: &'n str,
+        naming: ChunkNaming,
+        chunk_count: Option<u64>,
+        start_index: u64,
+        extension: Option<&'n str>,
+    ) -> Self {
+        // The numbers must fit the width up to the last one
+        let last_index = chunk_count.map(|count| count.saturating_add(start_index).saturating_sub(1));
+        ChunkNamer { filename, naming, width: index_width(last_index), start_index, extension }
+    }
+
+    /// Number of digits chunk numbers are padded to
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    /// File name of the chunk at `position` in the file, starting from 1 (the chunk numbered `start_index`)
+    pub(crate) fn name(&self, position: usize) -> String {
+        let number = self.start_index + position as u64 - 1;
+        let mut name = match self.naming {
+            ChunkNaming::Dashed => format!("{}-{:0width$}", self.filename, number, width = self.width),
+            ChunkNaming::DotNumeric => format!("{}.{:0width$}", self.filename, number, width = self.width),
+            ChunkNaming::Gnu => format!("{}{}", self.filename, gnu_suffix(position as u64 - 1)),
+        };
+        if let Some(extension) = self.extension {
+            name.push('.');
+            name.push_str(extension);
+        }
+        name
+    }
+}
+
+/// Parts of a numbered chunk file name, `<name><separator><number>` with an optional `.<extension>` (e.g.,
+/// "my_file-001.part")
+pub(crate) struct ParsedChunkName<'n> {
+    /// Name of the original file (e.g., "my_file")
+    pub(crate) name: &'n str,
+    /// '-' or '.'
+    pub(crate) separator: char,
+    pub(crate) number: u64,
+    /// Extension after the number, without the dot (e.g., "part")
+    pub(crate) extension: Option<&'n str>,
+}
+
+/// Splits `file_name` into its parts if it is a numbered chunk file name (see [`ParsedChunkName`])
+pub(crate) fn parse_chunk_name(file_name: &str) -> Option<ParsedChunkName<'_>> {
+    let parse = |stem: &str| -> Option<(usize, u64)> {
+        let (name, number) = stem.rsplit_once(['-', '.'])?;
+        if name.is_empty() || number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some((name.len(), number.parse().ok()?))
+    };
+    let (stem, extension) = match parse(file_name) {
+        Some(_) => (file_name, None),
+        None => {
+            let (stem, extension) = file_name.rsplit_once('.')?;
+            (stem, Some(extension))
+        }
+    };
+    let (name_len, number) = parse(stem)?;
+    Some(ParsedChunkName {
+        name: &stem[..name_len],
+        separator: stem[name_len..].chars().next()?,
+        number,
+        extension,
+    })
+}
+
+/// Whether `extension` (without a leading dot) can end chunk file names: a plain name that does not look like a
+/// chunk number
+pub(crate) fn is_valid_chunk_extension(extension: &str) -> bool {
+    !extension.is_empty()
+        && !extension.bytes().all(|b| b.is_ascii_digit())
+        && extension.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// `name` quoted as a single word for a POSIX shell, unless it needs no quoting
+pub fn shell_quote(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+' | '/')) {
+        return name.to_string();
+    }
+    format!("'{}'", name.replace('\'', "'\\''"))
+}
+
+/// Suffix GNU `split` gives its `index`th output file (from 0) with the default, growing suffix length: "aa" to
+/// "yz", then "zaaa" to "zyzz", then "zzaaaa" and so on
+fn gnu_suffix(mut index: u64) -> String {
+    // Each length holds 25 starting letters ("z" marks a longer suffix) times 26 for every other letter
+    let mut zs = 0;
+    let mut letters = 1;
+    while index >= 25 * 26u64.pow(letters) {
+        index -= 25 * 26u64.pow(letters);
+        zs += 1;
+        letters += 1;
+    }
+    let mut suffix = vec![b'z'; zs];
+    let mut tail = Vec::new();
+    for _ in 0..letters {
+        tail.push(b'a' + (index % 26) as u8);
+        index /= 26;
+    }
+    suffix.push(b'a' + index as u8);
+    suffix.extend(tail.iter().rev());
+    String::from_utf8(suffix).expect("ASCII letters")
+}
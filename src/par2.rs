@@ -0,0 +1,352 @@
+// src/par2.rs
+use std::io::Read;
+use anyhow::{Result, Context};
+use md5::{Digest, Md5};
+use rayon::prelude::*;
+
+use crate::galois;
+use crate::{ChunkSink, ChunkSource, ChunkStore, SplitInfo};
+
+/// Largest number of input slices in a PAR2 recovery set (the exponents relatively prime to 65535)
+const MAX_INPUT_SLICES: usize = 32768;
+
+/// Largest number of recovery blocks (exponents 0 to 65534)
+const MAX_RECOVERY_BLOCKS: usize = 65535;
+
+/// Bytes at the start of a file that its PAR2 file ID is derived from
+const HEAD_SIZE: u64 = 16 * 1024;
+
+const MAGIC: &[u8; 8] = b"PAR2\0PKT";
+const MAIN_PACKET: &[u8; 16] = b"PAR 2.0\0Main\0\0\0\0";
+const FILE_DESCRIPTION_PACKET: &[u8; 16] = b"PAR 2.0\0FileDesc";
+const SLICE_CHECKSUM_PACKET: &[u8; 16] = b"PAR 2.0\0IFSC\0\0\0\0";
+const RECOVERY_SLICE_PACKET: &[u8; 16] = b"PAR 2.0\0RecvSlic";
+const CREATOR_PACKET: &[u8; 16] = b"PAR 2.0\0Creator\0";
+
+/// One chunk file of the recovery set
+struct InputFile {
+    name: String,
+    length: u64,
+    head_md5: [u8; 16],
+    id: [u8; 16],
+}
+
+/// Writes PAR2 recovery files for the chunks of a split set, for repair with standard PAR2 tools
+///
+/// The index file `<name>.par2` and volumes `<name>.volXX+YY.par2` holding `recovery_blocks` recovery blocks in
+/// total are stored next to the chunks in `file_info.chunks_sub_dir`. Every chunk is one PAR2 slice, so up to
+/// `recovery_blocks` missing or damaged chunks can be repaired, e.g. with `par2 repair my_file.par2`.
+/// An empty file gets no PAR2 files. Chunks stored in a pool, shared with another file or spread across several
+/// directories, and a last chunk that holds the info file (see [`crate::SplitOptions`]) are not supported.
+pub fn write_par2(file_info: &SplitInfo, store: &dyn ChunkStore, recovery_blocks: usize) -> Result<()> {
+    if file_info.chunk_pool.is_some() || file_info.chunks.iter().any(|chunk| chunk.stored_as.is_some()) {
+        return Err(anyhow::anyhow!(
+            "PAR2 files only cover chunks in their own directory, but some chunks of '{}' are stored elsewhere",
+            file_info.original_filename
+        ));
+    }
+    if !file_info.destinations.is_empty() {
+        return Err(anyhow::anyhow!(
+            "PAR2 files only cover chunks in their own directory, but the chunks of '{}' are spread across several",
+            file_info.original_filename
+        ));
+    }
+    if file_info.info_trailer {
+        return Err(anyhow::anyhow!(
+            "The last chunk of '{}' holds the info file, which PAR2 files cannot cover",
+            file_info.original_filename
+        ));
+    }
+    if recovery_blocks == 0 || recovery_blocks > MAX_RECOVERY_BLOCKS {
+        return Err(anyhow::anyhow!("The number of PAR2 recovery blocks must be between 1 and {}", MAX_RECOVERY_BLOCKS));
+    }
+    // Empty chunks (only that of an empty file) hold no data to protect
+    let chunks: Vec<_> = file_info.chunks.iter().filter(|chunk| chunk.chunk_size > 0).collect();
+    if chunks.len() > MAX_INPUT_SLICES {
+        return Err(anyhow::anyhow!(
+            "PAR2 supports at most {} chunks, but '{}' has {}; split into larger chunks",
+            MAX_INPUT_SLICES,
+            file_info.original_filename,
+            chunks.len()
+        ));
+    }
+    if chunks.is_empty() {
+        return Ok(()); // An empty file has nothing to protect
+    }
+    let slice_size = chunks.iter().map(|chunk| chunk.chunk_size).max().unwrap_or(0).next_multiple_of(4) as usize;
+    let dir = &file_info.chunks_sub_dir;
+
+    // Files are identified by their name, length and first 16 KiB, and the recovery set is ordered by file ID
+    let mut files = Vec::new();
+    for chunk in &chunks {
+        let name = format!("{}/{}", dir, chunk.chunk_filename);
+        let mut head = Vec::new();
+        store.open(&name)
+            .and_then(|reader| Ok(reader.take(HEAD_SIZE).read_to_end(&mut head)?))
+            .with_context(|| format!("Failed to read chunk file: {}", ChunkSource::describe(store, &name)))?;
+        let head_md5 = md5(&[&head]);
+        let id = md5(&[&head_md5, &chunk.chunk_size.to_le_bytes(), chunk.chunk_filename.as_bytes()]);
+        files.push(InputFile { name: chunk.chunk_filename.clone(), length: chunk.chunk_size, head_md5, id });
+    }
+    // File IDs compare as little-endian 128-bit numbers
+    files.sort_by_key(|file| u128::from_le_bytes(file.id));
+
+    let mut main_body = Vec::new();
+    main_body.extend_from_slice(&(slice_size as u64).to_le_bytes());
+    main_body.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for file in &files {
+        main_body.extend_from_slice(&file.id);
+    }
+    let set_id = md5(&[&main_body]);
+
+    // Every input slice i contributes with the factor base_i^exponent to the recovery block of that exponent,
+    // where the logarithms of the bases are the numbers relatively prime to 65535 in increasing order
+    let mut critical_packets = packet(&set_id, MAIN_PACKET, &main_body);
+    let mut recovery = vec![vec![0u8; slice_size]; recovery_blocks];
+    let mut bases = (1..).filter(|&log: &usize| log % 3 != 0 && log % 5 != 0 && log % 17 != 0 && log % 257 != 0);
+    for file in &files {
+        let name = format!("{}/{}", dir, file.name);
+        let mut data = Vec::with_capacity(slice_size);
+        store.open(&name)
+            .and_then(|mut reader| Ok(reader.read_to_end(&mut data)?))
+            .with_context(|| format!("Failed to read chunk file: {}", ChunkSource::describe(store, &name)))?;
+        if data.len() as u64 != file.length {
+            return Err(anyhow::anyhow!(
+                "Chunk file {} holds {} bytes instead of the recorded {}",
+                ChunkSource::describe(store, &name),
+                data.len(),
+                file.length
+            ));
+        }
+
+        let mut description = Vec::new();
+        description.extend_from_slice(&file.id);
+        description.extend_from_slice(&md5(&[&data]));
+        description.extend_from_slice(&file.head_md5);
+        description.extend_from_slice(&file.length.to_le_bytes());
+        description.extend_from_slice(&padded(file.name.as_bytes()));
+        critical_packets.extend(packet(&set_id, FILE_DESCRIPTION_PACKET, &description));
+
+        // The chunk is a single slice, zero-padded to the slice size for its checksums
+        let base = galois::exp(bases.next().expect("unbounded"));
+        data.resize(slice_size, 0);
+        let mut slice_checksums = Vec::new();
+        slice_checksums.extend_from_slice(&file.id);
+        slice_checksums.extend_from_slice(&md5(&[&data]));
+        slice_checksums.extend_from_slice(&crc32fast::hash(&data).to_le_bytes());
+        critical_packets.extend(packet(&set_id, SLICE_CHECKSUM_PACKET, &slice_checksums));
+
+        recovery.par_iter_mut().enumerate().for_each(|(exponent, block)| {
+            galois::mul_add(galois::pow(base, exponent), &data, block);
+        });
+    }
+    critical_packets.extend(packet(&set_id, CREATOR_PACKET, &padded(
+        format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")).as_bytes(),
+    )));
+
+    let par2_name = |suffix: &str| format!("{}/{}{}.par2", dir, file_info.original_filename, suffix);
+    let put = |name: &str, data: &[u8]| store.put(name, data)
+        .with_context(|| format!("Failed to write PAR2 file: {}", ChunkSink::describe(store, name)));
+    put(&par2_name(""), &critical_packets)?;
+
+    // Volumes hold 1, 2, 4, ... recovery blocks, so that a small repair needs only a small download
+    let width = recovery_blocks.to_string().len().max(2);
+    let mut first = 0;
+    while first < recovery_blocks {
+        let count = (first + 1).min(recovery_blocks - first);
+        let mut volume = Vec::new();
+        for (exponent, block) in recovery.iter().enumerate().skip(first).take(count) {
+            let mut body = Vec::with_capacity(4 + slice_size);
+            body.extend_from_slice(&(exponent as u32).to_le_bytes());
+            body.extend_from_slice(block);
+            volume.extend(packet(&set_id, RECOVERY_SLICE_PACKET, &body));
+        }
+        volume.extend_from_slice(&critical_packets);
+        put(&par2_name(&format!(".vol{:0w$}+{:0w$}", first, count, w = width)), &volume)?;
+        first += count;
+    }
+    Ok(())
+}
+
+/// Builds a packet of the given type, with its header
+fn packet(set_id: &[u8; 16], packet_type: &[u8; 16], body: &[u8]) -> Vec<u8> {
+    let hash = md5(&[set_id, packet_type, body]);
+    let mut packet = Vec::with_capacity(64 + body.len());
+    packet.extend_from_slice(MAGIC);
+    packet.extend_from_slice(&(64 + body.len() as u64).to_le_bytes());
+    packet.extend_from_slice(&hash);
+    packet.extend_from_slice(set_id);
+    packet.extend_from_slice(packet_type);
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Pads a string field with zero bytes to a multiple of 4 bytes
+fn padded(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().next_multiple_of(4), 0);
+    padded
+}
+
+fn md5(parts: &[&[u8]]) -> [u8; 16] {
+    let mut hasher = Md5::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use super::*;
+    use crate::{split_to_sink, LocalDir, SplitOptions};
+
+    /// Bytes that do not repeat within the test data, from a xorshift generator
+    fn test_data(len: usize) -> Vec<u8> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        (0..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect()
+    }
+
+    /// A fresh, empty directory for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("file_splitter-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Repairs the chunks in `dir` from the PAR2 files there the way a PAR2 client does, for machines without
+    /// `par2`: checks every packet, finds the chunks that are missing or do not match their description, and
+    /// solves for them with as many recovery blocks
+    fn repair(dir: &Path) {
+        let mut set_id = None;
+        let mut main = None;
+        let mut descriptions = HashMap::new();
+        let mut recovery = HashMap::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "par2") {
+                continue;
+            }
+            let bytes = fs::read(&path).unwrap();
+            let mut offset = 0;
+            while offset < bytes.len() {
+                assert_eq!(&bytes[offset..offset + 8], MAGIC);
+                let len = u64::from_le_bytes(bytes[offset + 8..offset + 16].try_into().unwrap()) as usize;
+                let packet = &bytes[offset..offset + len];
+                assert_eq!(packet[16..32], md5(&[&packet[32..]]), "packet hash in {}", path.display());
+                assert_eq!(*set_id.get_or_insert(packet[32..48].to_vec()), &packet[32..48]);
+                let body = &packet[64..];
+                match &packet[48..64] {
+                    t if t == MAIN_PACKET => {
+                        assert_eq!(packet[32..48], md5(&[body]), "recovery set ID");
+                        let slice_size = u64::from_le_bytes(body[..8].try_into().unwrap()) as usize;
+                        let ids: Vec<_> = body[12..].chunks(16).map(<[u8]>::to_vec).collect();
+                        main = Some((slice_size, ids));
+                    }
+                    t if t == FILE_DESCRIPTION_PACKET => {
+                        let length = u64::from_le_bytes(body[48..56].try_into().unwrap()) as usize;
+                        let name = String::from_utf8(body[56..].to_vec()).unwrap();
+                        descriptions.insert(body[..16].to_vec(), (name.trim_end_matches('\0').to_string(), body[16..32].to_vec(), length));
+                    }
+                    t if t == RECOVERY_SLICE_PACKET => {
+                        recovery.insert(u32::from_le_bytes(body[..4].try_into().unwrap()) as usize, body[4..].to_vec());
+                    }
+                    _ => {}
+                }
+                offset += len;
+            }
+        }
+
+        // The constant of the ith input slice is 2 to the power of the ith number relatively prime to 65535
+        let (slice_size, ids) = main.expect("no main packet");
+        let gcd = |mut a: usize, mut b: usize| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        let constants: Vec<u16> = (1..).filter(|&log| gcd(log, 65535) == 1).take(ids.len()).map(galois::exp).collect();
+        let mut slices = Vec::new();
+        let mut damaged = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            let (name, hash, _) = &descriptions[id];
+            match fs::read(dir.join(name)) {
+                Ok(mut data) if md5(&[&data]) == hash[..] => {
+                    data.resize(slice_size, 0);
+                    slices.push(data);
+                }
+                _ => {
+                    damaged.push(i);
+                    slices.push(Vec::new());
+                }
+            }
+        }
+        assert!(damaged.len() <= recovery.len(), "too few recovery blocks");
+
+        // Recovery block e less the intact slices is the sum of constant_d^e * slice_d over the damaged slices d
+        let exponents: Vec<usize> = recovery.keys().copied().take(damaged.len()).collect();
+        let matrix = exponents.iter()
+            .map(|&exponent| damaged.iter().map(|&d| galois::pow(constants[d], exponent)).collect())
+            .collect();
+        let inverse = galois::invert(matrix).expect("singular matrix");
+        let sums: Vec<Vec<u8>> = exponents.iter().map(|exponent| {
+            let mut sum = recovery[exponent].clone();
+            for (i, slice) in slices.iter().enumerate().filter(|(i, _)| !damaged.contains(i)) {
+                galois::mul_add(galois::pow(constants[i], *exponent), slice, &mut sum);
+            }
+            sum
+        }).collect();
+        for (row, &d) in damaged.iter().enumerate() {
+            let mut slice = vec![0u8; slice_size];
+            for (coefficient, sum) in inverse[row].iter().zip(&sums) {
+                galois::mul_add(*coefficient, sum, &mut slice);
+            }
+            let (name, hash, length) = &descriptions[&ids[d]];
+            slice.truncate(*length);
+            assert_eq!(md5(&[&slice]), hash[..], "repaired {}", name);
+            fs::write(dir.join(name), slice).unwrap();
+        }
+    }
+
+    #[test]
+    fn par2_files_repair_lost_and_damaged_chunks() {
+        let dir = test_dir("par2");
+        let store = LocalDir::new(&dir);
+        // Ten chunks, the last one shorter and of an odd length
+        let data = test_data(9 * 4096 + 1001);
+        let info = split_to_sink(&data[..], "data.bin", 4096, &store, &SplitOptions::default(), None).unwrap();
+        assert_eq!(info.chunks.len(), 10);
+        write_par2(&info, &store, 3).unwrap();
+
+        let chunks_dir = dir.join(&info.chunks_sub_dir);
+        let chunk_path = |i: usize| chunks_dir.join(&info.chunks[i].chunk_filename);
+        let originals: Vec<_> = (0..info.chunks.len()).map(|i| fs::read(chunk_path(i)).unwrap()).collect();
+        fs::remove_file(chunk_path(2)).unwrap();
+        let mut damaged = originals[5].clone();
+        damaged[100] ^= 0xff;
+        fs::write(chunk_path(5), damaged).unwrap();
+        fs::remove_file(chunk_path(9)).unwrap();
+
+        // Standard PAR2 tools are the real test, where one is installed
+        let index = chunks_dir.join("data.bin.par2");
+        match Command::new("par2").arg("repair").arg("-q").arg(&index).status() {
+            Ok(status) => assert!(status.success(), "par2 repair failed"),
+            Err(_) => repair(&chunks_dir),
+        }
+        for (i, original) in originals.iter().enumerate() {
+            assert_eq!(&fs::read(chunk_path(i)).unwrap(), original, "chunk {}", i);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,372 @@
+// src/parity.rs
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use std::sync::Mutex;
+use anyhow::{Result, Context};
+
+use crate::galois;
+use crate::trailer::encode_info_trailer;
+use crate::{
+    calculate_buffer_checksum_with, decode_chunk, resolve_chunk_readers, ChecksumAlgorithm, ChunkCodec, ChunkSink,
+    ChunkSource, ChunkStore, Decryption, RestoreOptions, SplitInfo,
+};
+
+/// Largest number of data and parity chunks one split set can have together
+const MAX_PARITY_SHARDS: usize = 1 << 16;
+
+/// Reed-Solomon parity chunks stored alongside the data chunks of a split set
+///
+/// Any `chunks.len()` data or parity chunks may be lost or corrupted and the data chunks can still be rebuilt.
+/// The code works on the stored (compressed and encrypted) chunk files, every one zero-padded to `shard_size`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParityInfo {
+    /// Size of every parity chunk: the largest stored data chunk, rounded up to an even number of bytes
+    pub shard_size: u64,
+    /// Parity chunks, stored in the file's `*_parts` directory
+    pub chunks: Vec<ParityChunk>,
+}
+
+/// One parity chunk
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParityChunk {
+    /// Filename of the parity chunk (e.g., "my_file.parity-001")
+    pub chunk_filename: String,
+    /// Algorithm-tagged SHA-256 of the parity chunk file
+    pub chunk_checksum: String,
+}
+
+/// Coefficient of data chunk `data_index` in parity chunk `parity_index`
+///
+/// The coefficients form a Cauchy matrix, every square submatrix of which is invertible, so any set of lost
+/// chunks no larger than the number of parity chunks can be solved for.
+fn coefficient(parity_count: usize, parity_index: usize, data_index: usize) -> u16 {
+    galois::inv((parity_index ^ (parity_count + data_index)) as u16)
+}
+
+/// Computes parity chunks from the stored data chunks as they are written
+pub(crate) struct ParityEncoder {
+    shards: Vec<Vec<u8>>,
+    data_chunks: usize,
+}
+
+impl ParityEncoder {
+    pub(crate) fn new(parity_count: usize) -> Self {
+        ParityEncoder { shards: vec![Vec::new(); parity_count], data_chunks: 0 }
+    }
+
+    /// Adds the next stored data chunk
+    pub(crate) fn add(&mut self, data: &[u8]) -> Result<()> {
+        let parity_count = self.shards.len();
+        if self.data_chunks + parity_count >= MAX_PARITY_SHARDS {
+            return Err(anyhow::anyhow!(
+                "Parity supports at most {} chunks in total; split into larger chunks",
+                MAX_PARITY_SHARDS
+            ));
+        }
+        for (parity_index, shard) in self.shards.iter_mut().enumerate() {
+            // Shorter chunks count as zero-padded, so growing the shards keeps the parity computed so far valid
+            if shard.len() < data.len() {
+                shard.resize(data.len().next_multiple_of(2), 0);
+            }
+            galois::mul_add(coefficient(parity_count, parity_index, self.data_chunks), data, shard);
+        }
+        self.data_chunks += 1;
+        Ok(())
+    }
+
+    /// The finished parity chunks, all of the same size
+    fn into_shards(self) -> Vec<Vec<u8>> {
+        let shard_size = self.shards.iter().map(Vec::len).max().unwrap_or(0);
+        self.shards.into_iter().map(|mut shard| {
+            shard.resize(shard_size, 0);
+            shard
+        }).collect()
+    }
+
+    /// Stores the parity chunks in `sink` below `chunks_sub_dir`
+    pub(crate) fn finish(self, sink: &dyn ChunkSink, chunks_sub_dir: &str, filename: &str) -> Result<ParityInfo> {
+        let shards = self.into_shards();
+        let shard_size = shards.first().map_or(0, Vec::len);
+        let mut chunks = Vec::new();
+        for (index, shard) in shards.into_iter().enumerate() {
+            let chunk_filename = format!("{}.parity-{:03}", filename, index + 1);
+            let chunk_name = format!("{}/{}", chunks_sub_dir, chunk_filename);
+            sink.put(&chunk_name, &shard)
+                .with_context(|| format!("Failed to write parity chunk file: {}", sink.describe(&chunk_name)))?;
+            chunks.push(ParityChunk {
+                chunk_filename,
+                chunk_checksum: parity_checksum(&shard),
+            });
+        }
+        Ok(ParityInfo { shard_size: shard_size as u64, chunks })
+    }
+}
+
+/// Rebuilt stored bytes of every damaged data chunk by index, or why they could not be rebuilt
+type Rebuilt = Result<BTreeMap<usize, Vec<u8>>, String>;
+
+/// Rebuilds the damaged data chunks of a split set from its parity chunks, on first request
+pub(crate) struct ParityRecovery<'a> {
+    file_info: &'a SplitInfo,
+    parity: &'a ParityInfo,
+    source: &'a dyn ChunkSource,
+    codec: Option<&'a dyn ChunkCodec>,
+    decryption: Option<&'a Decryption>,
+    rebuilt: Mutex<Option<Rebuilt>>,
+}
+
+impl<'a> ParityRecovery<'a> {
+    /// Returns `None` if the split set has no parity chunks
+    pub(crate) fn new(
+        file_info: &'a SplitInfo,
+        source: &'a dyn ChunkSource,
+        codec: Option<&'a dyn ChunkCodec>,
+        decryption: Option<&'a Decryption>,
+    ) -> Option<Self> {
+        let parity = file_info.parity.as_ref().filter(|parity| !parity.chunks.is_empty())?;
+        Some(ParityRecovery { file_info, parity, source, codec, decryption, rebuilt: Mutex::new(None) })
+    }
+
+    /// Stored bytes of the data chunk at `index`, rebuilt from the other chunks
+    pub(crate) fn chunk(&self, index: usize) -> Result<Vec<u8>> {
+        let mut rebuilt = self.rebuilt.lock().unwrap();
+        let rebuilt = rebuilt.get_or_insert_with(|| {
+            let rebuilt = self.rebuild().map_err(|e| format!("{:#}", e))?;
+            eprintln!(
+                "Warning: Rebuilt {} missing or damaged chunks of '{}' from parity",
+                rebuilt.len(),
+                self.file_info.original_filename
+            );
+            Ok(rebuilt)
+        });
+        match rebuilt {
+            Ok(chunks) => chunks.get(&index).cloned().ok_or_else(|| anyhow::anyhow!(
+                "Chunk '{}' was intact when the damaged chunks were rebuilt",
+                self.file_info.chunks[index].chunk_filename
+            )),
+            Err(e) => Err(anyhow::anyhow!("{}", e)),
+        }
+    }
+
+    /// Reads every chunk once, rebuilding the stored bytes of the data chunks that are missing or fail verification
+    pub(crate) fn rebuild(&self) -> Result<BTreeMap<usize, Vec<u8>>> {
+        let file_info = self.file_info;
+        let parity_count = self.parity.chunks.len();
+        let shard_size = self.parity.shard_size as usize;
+
+        // Sum up what every parity chunk receives from the intact data chunks; the rest of each parity chunk
+        // (its syndrome) then only depends on the damaged ones
+        let mut syndromes = vec![vec![0u8; shard_size]; parity_count];
+        let mut damaged = Vec::new();
+        for (index, chunk_info) in file_info.chunks.iter().enumerate() {
+            match self.read_chunk(index) {
+                Some(data) if data.len() as u64 == chunk_info.chunk_size && data.len() <= shard_size
+                    && decode_chunk(file_info, chunk_info, Box::new(Cursor::new(&data)), "", self.codec, self.decryption, true).is_ok() => {
+                    for (parity_index, syndrome) in syndromes.iter_mut().enumerate() {
+                        galois::mul_add(coefficient(parity_count, parity_index, index), &data, syndrome);
+                    }
+                }
+                _ => damaged.push(index),
+            }
+        }
+        if damaged.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+
+        // Complete the syndromes of as many intact parity chunks as there are damaged data chunks
+        let mut equations = Vec::new();
+        for (parity_index, parity_chunk) in self.parity.chunks.iter().enumerate() {
+            if equations.len() == damaged.len() {
+                break;
+            }
+            let Some(data) = self.read(&parity_chunk_name(file_info, parity_chunk)) else {
+                continue;
+            };
+            if data.len() == shard_size && parity_checksum(&data) == parity_chunk.chunk_checksum {
+                galois::mul_add(1, &data, &mut syndromes[parity_index]);
+                equations.push(parity_index);
+            }
+        }
+        if equations.len() < damaged.len() {
+            return Err(anyhow::anyhow!(
+                "{} chunks of '{}' are missing or damaged, but only {} of its {} parity chunks are intact",
+                damaged.len(),
+                file_info.original_filename,
+                equations.len(),
+                parity_count
+            ));
+        }
+
+        // Each used syndrome is a combination of the damaged chunks; solve for them
+        let matrix = equations.iter()
+            .map(|&parity_index| damaged.iter().map(|&index| coefficient(parity_count, parity_index, index)).collect())
+            .collect();
+        let inverse = galois::invert(matrix)
+            .ok_or_else(|| anyhow::anyhow!("Failed to solve for the damaged chunks of '{}'", file_info.original_filename))?;
+        let mut rebuilt = BTreeMap::new();
+        for (row, &index) in inverse.iter().zip(&damaged) {
+            let mut data = vec![0u8; shard_size];
+            for (&factor, &parity_index) in row.iter().zip(&equations) {
+                galois::mul_add(factor, &syndromes[parity_index], &mut data);
+            }
+            data.truncate(file_info.chunks[index].chunk_size as usize);
+            rebuilt.insert(index, data);
+        }
+        Ok(rebuilt)
+    }
+
+    fn read(&self, name: &str) -> Option<Vec<u8>> {
+        read_whole(self.source, name)
+    }
+
+    /// Stored bytes of the data chunk at `index`, without an info file trailer
+    fn read_chunk(&self, index: usize) -> Option<Vec<u8>> {
+        read_stored_chunk(self.source, self.file_info, index)
+    }
+}
+
+/// Chunks rewritten by [`repair_split_set`]
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    /// File names of the data chunks that were missing or damaged and have been rebuilt
+    pub rebuilt_chunks: Vec<String>,
+    /// File names of the parity chunks that were missing or damaged and have been recomputed
+    pub rebuilt_parity_chunks: Vec<String>,
+}
+
+/// Rebuilds the missing or damaged chunks of a split set in `store` from its parity chunks
+///
+/// Every data chunk is checked against its checksum (decrypted with `options.decryption` if the set is encrypted)
+/// and every parity chunk against its recorded SHA-256. Damaged data chunks are rebuilt and written back in place,
+/// then damaged parity chunks are recomputed. Once everything is intact, `file_info.last_verified` is set to the
+/// current time; save the info file to keep it.
+pub fn repair_split_set(file_info: &mut SplitInfo, store: &dyn ChunkStore, options: &RestoreOptions) -> Result<RepairReport> {
+    let mut report = RepairReport::default();
+    {
+        let file_info = &*file_info;
+        let (codec, decryption) = resolve_chunk_readers(file_info, options)?;
+        let recovery = ParityRecovery::new(file_info, store, codec, decryption).ok_or_else(|| anyhow::anyhow!(
+            "'{}' has no parity chunks to repair from (split it with --parity)",
+            file_info.original_filename
+        ))?;
+        let parity = recovery.parity;
+
+        for (index, data) in recovery.rebuild()? {
+            let chunk_info = &file_info.chunks[index];
+            let name = file_info.chunk_name(chunk_info);
+            decode_chunk(file_info, chunk_info, Box::new(Cursor::new(&data)), &name, codec, decryption, true)
+                .with_context(|| format!("Failed to rebuild chunk file from parity: {}", ChunkSource::describe(store, &name)))?;
+            let data = match file_info.has_info_trailer(index) {
+                true => [data, encode_info_trailer(file_info)?].concat(),
+                false => data,
+            };
+            store.put(&name, &data)
+                .with_context(|| format!("Failed to write chunk file: {}", ChunkSink::describe(store, &name)))?;
+            report.rebuilt_chunks.push(chunk_info.chunk_filename.clone());
+        }
+
+        let damaged_parity: Vec<usize> = parity.chunks.iter().enumerate()
+            .filter(|(_, parity_chunk)| {
+                read_whole(store, &parity_chunk_name(file_info, parity_chunk))
+                    .is_none_or(|data| parity_checksum(&data) != parity_chunk.chunk_checksum)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if !damaged_parity.is_empty() {
+            // The data chunks are whole again, so the parity can be computed afresh
+            let mut encoder = ParityEncoder::new(parity.chunks.len());
+            for (index, chunk_info) in file_info.chunks.iter().enumerate() {
+                let name = file_info.chunk_name(chunk_info);
+                let data = read_stored_chunk(store, file_info, index)
+                    .ok_or_else(|| anyhow::anyhow!("Failed to read chunk file: {}", ChunkSource::describe(store, &name)))?;
+                encoder.add(&data)?;
+            }
+            let shards = encoder.into_shards();
+            for index in damaged_parity {
+                let parity_chunk = &parity.chunks[index];
+                let name = parity_chunk_name(file_info, parity_chunk);
+                if parity_checksum(&shards[index]) != parity_chunk.chunk_checksum {
+                    return Err(anyhow::anyhow!(
+                        "Recomputed parity chunk does not match its recorded checksum: {}",
+                        ChunkSource::describe(store, &name)
+                    ));
+                }
+                store.put(&name, &shards[index])
+                    .with_context(|| format!("Failed to write parity chunk file: {}", ChunkSink::describe(store, &name)))?;
+                report.rebuilt_parity_chunks.push(parity_chunk.chunk_filename.clone());
+            }
+        }
+    }
+    file_info.last_verified = Some(chrono::Utc::now());
+    Ok(report)
+}
+
+/// Storage key of a parity chunk, relative to the split output directory
+pub(crate) fn parity_chunk_name(file_info: &SplitInfo, parity_chunk: &ParityChunk) -> String {
+    format!("{}/{}", file_info.chunks_sub_dir, parity_chunk.chunk_filename)
+}
+
+pub(crate) fn parity_checksum(data: &[u8]) -> String {
+    ChecksumAlgorithm::Sha256.tag(&calculate_buffer_checksum_with(data, ChecksumAlgorithm::Sha256))
+}
+
+/// Reads a whole chunk file, or returns `None` if it cannot be read
+fn read_whole(source: &dyn ChunkSource, name: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    source.open(name).ok()?.read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+/// Reads the stored bytes of the data chunk at `index`, without an info file trailer, or returns `None` if they
+/// cannot be read
+fn read_stored_chunk(source: &dyn ChunkSource, file_info: &SplitInfo, index: usize) -> Option<Vec<u8>> {
+    let chunk_info = &file_info.chunks[index];
+    let mut data = read_whole(source, &file_info.chunk_name(chunk_info))?;
+    if file_info.has_info_trailer(index) && data.len() as u64 > chunk_info.chunk_size {
+        data.truncate(chunk_info.chunk_size as usize);
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_to_sink, MemoryStore, SplitOptions};
+
+    #[test]
+    fn any_lost_chunks_up_to_the_parity_count_are_rebuilt() {
+        let store = MemoryStore::new();
+        // Five data chunks, the last one shorter and of an odd length
+        let data: Vec<u8> = (0..4 * 1000 + 333u32).map(|i| (i * 7 + i / 251) as u8).collect();
+        let options = SplitOptions { parity: 2, ..SplitOptions::default() };
+        let info = split_to_sink(&data[..], "data.bin", 1000, &store, &options, None).unwrap();
+        let parity = info.parity.as_ref().unwrap();
+        assert_eq!((info.chunks.len(), parity.chunks.len()), (5, 2));
+
+        let names: Vec<String> = info.chunks.iter().map(|chunk| info.chunk_name(chunk))
+            .chain(parity.chunks.iter().map(|chunk| parity_chunk_name(&info, chunk)))
+            .collect();
+        let without = |lost: &[usize]| {
+            let damaged = MemoryStore::new();
+            for (_, name) in names.iter().enumerate().filter(|(index, _)| !lost.contains(index)) {
+                damaged.put(name, &store.get(name).unwrap()).unwrap();
+            }
+            damaged
+        };
+        // Every pair of data and parity chunks
+        for first in 0..names.len() {
+            for second in first + 1..names.len() {
+                let damaged = without(&[first, second]);
+                let recovery = ParityRecovery::new(&info, &damaged, None, None).unwrap();
+                for index in [first, second].into_iter().filter(|&index| index < info.chunks.len()) {
+                    assert_eq!(recovery.chunk(index).unwrap(), store.get(&names[index]).unwrap(), "chunk {} without {} and {}", index, first, second);
+                }
+            }
+        }
+
+        let damaged = without(&[0, 2, 6]);
+        let recovery = ParityRecovery::new(&info, &damaged, None, None).unwrap();
+        assert!(recovery.chunk(0).is_err());
+    }
+}
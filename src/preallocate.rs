@@ -0,0 +1,57 @@
+// src/preallocate.rs
+use std::fs::File;
+use std::io;
+
+/// Reserves disk space for the first `len` bytes of `file` without changing its size
+///
+/// This keeps a large output file from being fragmented, and makes a lack of disk space show up before anything
+/// is written. Preallocation is only an optimization: apart from a full disk, failures (e.g., on file systems
+/// that cannot preallocate) are ignored, and on systems other than Linux and Windows nothing is done.
+pub(crate) fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    match reserve(file, len) {
+        Err(e) if e.kind() == io::ErrorKind::StorageFull => {
+            // Space reserved before running out stays taken until released by truncating
+            file.set_len(file.metadata()?.len())?;
+            Err(e)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn reserve(file: &File, len: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let len = libc::off_t::try_from(len).map_err(io::Error::other)?;
+    // SAFETY: the descriptor belongs to `file`, which outlives the call
+    if unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, len) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn reserve(file: &File, len: u64) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{FileAllocationInfo, SetFileInformationByHandle, FILE_ALLOCATION_INFO};
+
+    let info = FILE_ALLOCATION_INFO { AllocationSize: i64::try_from(len).map_err(io::Error::other)? };
+    // SAFETY: the handle belongs to `file`, which outlives the call, and `info` matches the information class
+    let reserved = unsafe {
+        SetFileInformationByHandle(
+            file.as_raw_handle(),
+            FileAllocationInfo,
+            &info as *const FILE_ALLOCATION_INFO as *const _,
+            std::mem::size_of::<FILE_ALLOCATION_INFO>() as u32,
+        )
+    };
+    if reserved == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn reserve(_file: &File, _len: u64) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// src/retry.rs
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::control::Checkpoint;
+use crate::failure::{is_not_found, FailureKind};
+
+/// How chunk reads and writes that failed are tried again (see [`crate::SplitOptions::retry`]), e.g. against a
+/// network file system that fails now and then
+///
+/// Only I/O errors are retried: a missing chunk or a checksum mismatch would fail the same way again. The wait
+/// before each retry doubles, up to `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per chunk operation, including the first; 1 (the default) never retries
+    pub attempts: u32,
+    /// Wait before the first retry
+    pub initial_delay: Duration,
+    /// Longest wait between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { attempts: 1, initial_delay: Duration::from_secs(1), max_delay: Duration::from_secs(60) }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy trying a failed chunk operation `retries` more times, waiting `initial_delay` before the first retry
+    pub fn new(retries: u32, initial_delay: Duration) -> Self {
+        RetryPolicy { attempts: retries.saturating_add(1), initial_delay, ..Default::default() }
+    }
+
+    /// Runs `operation` on `target` (e.g., a chunk file, for the log) until it succeeds, fails with an error that
+    /// is not worth retrying, runs out of attempts or is cancelled at `checkpoint`
+    pub(crate) fn run<T>(&self, checkpoint: Checkpoint, target: &str, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut delay = self.initial_delay;
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(e) if attempt < self.attempts && is_transient(&e) && !checkpoint.cancelled() => {
+                    tracing::warn!(target = %target, attempt, error = %format!("{:#}", e), "retrying in {:.1?}", delay);
+                    checkpoint.wait_until(Instant::now() + delay);
+                    delay = (delay * 2).min(self.max_delay);
+                    attempt += 1;
+                }
+                Err(e) if attempt > 1 => return Err(e.context(format!("Gave up after {} attempts", attempt))),
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether `error` may go away if the operation is tried again
+fn is_transient(error: &anyhow::Error) -> bool {
+    FailureKind::of(error) == FailureKind::Io && !is_not_found(error)
+}
@@ -0,0 +1,242 @@
+// src/s3.rs
+use std::io::{self, Cursor, Read, Write};
+use anyhow::{Result, Context};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use tokio::runtime::Runtime;
+
+use crate::{ChunkEncoder, ChunkSink, ChunkSource};
+
+/// Size of the parts a chunk larger than this is uploaded in; S3 takes up to 10,000 parts of at least 5 MiB, so
+/// chunks of up to about 160 GB can be stored
+const PART_SIZE: usize = 16 << 20;
+
+/// Chunk storage in an S3 bucket (or an S3-compatible service)
+///
+/// Credentials, region and endpoint come from the standard AWS configuration: environment variables
+/// (`AWS_ACCESS_KEY_ID`, `AWS_REGION`, `AWS_ENDPOINT_URL`, ...), `~/.aws/config` and instance metadata.
+/// Every chunk is uploaded as soon as it is written, with a single PUT or, if larger than [`PART_SIZE`], in a
+/// multipart upload whose parts are sent while the chunk is encoded, so that only one part is held in memory.
+/// Chunks are downloaded when they are read.
+pub struct S3Store {
+    client: Client,
+    bucket: String,
+    prefix: String,
+    runtime: Runtime,
+}
+
+impl S3Store {
+    /// Stores chunks in `bucket` below the key prefix `prefix` (may be empty)
+    pub fn new(bucket: &str, prefix: &str) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start the S3 client runtime")?;
+        let sdk_config = runtime.block_on(aws_config::load_defaults(aws_config::BehaviorVersion::latest()));
+        // Custom endpoints (MinIO, Ceph, ...) usually do not support virtual-hosted bucket names
+        let config = aws_sdk_s3::config::Builder::from(&sdk_config)
+            .force_path_style(sdk_config.endpoint_url().is_some())
+            .build();
+
+        Ok(S3Store {
+            client: Client::from_conf(config),
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            runtime,
+        })
+    }
+
+    /// Parses an `s3://bucket/prefix` URL
+    pub fn from_url(url: &str) -> Result<Self> {
+        let path = url.strip_prefix("s3://")
+            .ok_or_else(|| anyhow::anyhow!("Invalid S3 URL (expected s3://bucket/prefix): {}", url))?;
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        if bucket.is_empty() {
+            return Err(anyhow::anyhow!("Invalid S3 URL (missing bucket name): {}", url));
+        }
+        S3Store::new(bucket, prefix)
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.prefix, name)
+        }
+    }
+
+    /// Size of the object stored under `name`, or `None` if there is none
+    fn head(&self, name: &str) -> Result<Option<u64>> {
+        let head = self.runtime.block_on(
+            self.client.head_object()
+                .bucket(&self.bucket)
+                .key(self.key(name))
+                .send(),
+        );
+        match head {
+            Ok(object) => Ok(Some(object.content_length().unwrap_or(0).max(0) as u64)),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Object being written by [`S3Store::create`]: held in memory until it outgrows one part, then sent part by part
+struct S3Upload<'a> {
+    store: &'a S3Store,
+    key: String,
+    /// Data not yet sent
+    buffer: Vec<u8>,
+    /// Multipart upload, once started, with the parts sent so far
+    upload: Option<(String, Vec<CompletedPart>)>,
+}
+
+impl S3Upload<'_> {
+    /// Sends the buffered data as the next part, starting the multipart upload first if needed
+    fn send_part(&mut self) -> Result<()> {
+        let store = self.store;
+        if self.upload.is_none() {
+            let started = store.runtime.block_on(
+                store.client.create_multipart_upload()
+                    .bucket(&store.bucket)
+                    .key(&self.key)
+                    .send(),
+            )?;
+            let upload_id = started.upload_id()
+                .ok_or_else(|| anyhow::anyhow!("S3 started no multipart upload for {}", self.key))?;
+            self.upload = Some((upload_id.to_string(), Vec::new()));
+        }
+        let (upload_id, parts) = self.upload.as_mut().unwrap();
+        let part_number = parts.len() as i32 + 1;
+        let sent = store.runtime.block_on(
+            store.client.upload_part()
+                .bucket(&store.bucket)
+                .key(&self.key)
+                .upload_id(upload_id.as_str())
+                .part_number(part_number)
+                .body(ByteStream::from(std::mem::take(&mut self.buffer)))
+                .send(),
+        )?;
+        parts.push(CompletedPart::builder()
+            .part_number(part_number)
+            .set_e_tag(sent.e_tag().map(str::to_string))
+            .build());
+        Ok(())
+    }
+}
+
+impl Write for S3Upload<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(PART_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == PART_SIZE {
+            self.send_part().map_err(io::Error::other)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ChunkEncoder for S3Upload<'_> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let store = self.store;
+        if self.upload.is_none() {
+            store.runtime.block_on(
+                store.client.put_object()
+                    .bucket(&store.bucket)
+                    .key(&self.key)
+                    .body(ByteStream::from(std::mem::take(&mut self.buffer)))
+                    .send(),
+            )?;
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.send_part()?;
+        }
+        let (upload_id, parts) = self.upload.as_ref().unwrap();
+        store.runtime.block_on(
+            store.client.complete_multipart_upload()
+                .bucket(&store.bucket)
+                .key(&self.key)
+                .upload_id(upload_id)
+                .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts.clone())).build())
+                .send(),
+        )?;
+        // Completed, so there is nothing left to abort
+        self.upload = None;
+        Ok(())
+    }
+}
+
+impl Drop for S3Upload<'_> {
+    /// Aborts a multipart upload that was not completed, so that its parts are not kept (and billed) forever
+    fn drop(&mut self) {
+        if let Some((upload_id, _)) = self.upload.take() {
+            let store = self.store;
+            let _ = store.runtime.block_on(
+                store.client.abort_multipart_upload()
+                    .bucket(&store.bucket)
+                    .key(&self.key)
+                    .upload_id(upload_id)
+                    .send(),
+            );
+        }
+    }
+}
+
+impl ChunkSink for S3Store {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        let mut upload = self.create(name)?;
+        upload.write_all(data)?;
+        upload.finish()
+    }
+
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        Ok(Box::new(S3Upload { store: self, key: self.key(name), buffer: Vec::new(), upload: None }))
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.head(name)?.is_some())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        // Deleting a missing key succeeds too
+        self.runtime.block_on(
+            self.client.delete_object()
+                .bucket(&self.bucket)
+                .key(self.key(name))
+                .send(),
+        )?;
+        Ok(())
+    }
+
+    fn describe(&self, name: &str) -> String {
+        format!("s3://{}/{}", self.bucket, self.key(name))
+    }
+}
+
+impl ChunkSource for S3Store {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let data = self.runtime.block_on(async {
+            let object = self.client.get_object()
+                .bucket(&self.bucket)
+                .key(self.key(name))
+                .send()
+                .await?;
+            anyhow::Ok(object.body.collect().await?.into_bytes())
+        })?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        self.head(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        format!("s3://{}/{}", self.bucket, self.key(name))
+    }
+}
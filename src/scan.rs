@@ -0,0 +1,465 @@
+// src/scan.rs
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+
+use crate::{check_overwrite, parse_checksum, read_info_trailer, ChecksumAlgorithm, ChecksumHasher, ChunkCodec, ChunkHeader, ChunkInfo, CompressionAlgorithm, Failure, FailureKind, ChunkNaming, ManifestFormat, RestoreOptions, SplitInfo, SplitMode, DEFAULT_CHUNK_START_INDEX, FORMAT_VERSION, READ_BLOCK_SIZE};
+use crate::naming::{index_width, parse_chunk_name};
+
+/// Chunk files of a split set found by [`scan_chunk_dir`], without its info file
+pub(crate) struct ScannedSet {
+    /// Name of the original file, taken from the chunk headers or file names (e.g., "my_file")
+    pub(crate) name: String,
+    /// Chunk file names, in order (e.g., "my_file-001")
+    pub(crate) chunks: Vec<String>,
+    /// Compression the chunks are in, named by their headers or detected by their magic bytes, if any
+    pub(crate) compression: Option<CompressionAlgorithm>,
+    /// Whether the chunk files start with a [`ChunkHeader`]
+    pub(crate) headers: bool,
+    /// Checksums of the chunks' original content their headers or info file trailer name, in order (empty
+    /// without either)
+    pub(crate) checksums: Vec<String>,
+    /// Stored size of the last chunk, if its file goes on with an info file trailer
+    pub(crate) last_size: Option<u64>,
+    /// Info file found at the end of the last chunk file (see [`crate::read_info_trailer`])
+    pub(crate) trailer: Option<SplitInfo>,
+}
+
+/// Finds the chunk files of a split set in `dir` (e.g., "my_file_parts")
+///
+/// Chunk files that start with a [`ChunkHeader`] are found by it, whatever they are named; otherwise they are
+/// found by their names, `<name>-<number>` or `<name>.<number>`, optionally followed by an extension. If chunks
+/// of several files are there, the one the directory is named after is taken. Fails if a chunk is missing from
+/// the numbered sequence, or if headers tell the chunks are encrypted or compressed with a custom codec.
+pub(crate) fn scan_chunk_dir(dir: &Path) -> Result<ScannedSet> {
+    // Chunk file names by original file name and index, found by header and by name
+    let mut by_header: BTreeMap<String, BTreeMap<u64, (String, ChunkHeader)>> = BTreeMap::new();
+    let mut by_name: BTreeMap<String, BTreeMap<u64, String>> = BTreeMap::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory: {}", dir.display()))? {
+        let entry = entry.with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let Ok(file_name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let path = entry.path();
+        let header = File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut file| ChunkHeader::read_from(&mut file))
+            .with_context(|| format!("Failed to read chunk file: {}", path.display()))?;
+        let duplicate = match header {
+            Some(header) => by_header.entry(header.original_filename.clone()).or_default()
+                .insert(header.index, (file_name.clone(), header))
+                .map(|(other, _)| other),
+            None => {
+                let Some(parsed) = parse_chunk_name(&file_name) else {
+                    continue;
+                };
+                by_name.entry(parsed.name.to_string()).or_default().insert(parsed.number, file_name.clone())
+            }
+        };
+        if let Some(other) = duplicate {
+            return Err(anyhow::anyhow!("Cannot tell the order of '{}' and '{}' in {}", other, file_name, dir.display()));
+        }
+    }
+
+    if by_header.is_empty() {
+        let name = pick_set(dir, &by_name)?;
+        let numbered = by_name.remove(&name).unwrap_or_default();
+        check_numbering(dir, &name, &numbered, None)?;
+        let chunks: Vec<String> = numbered.into_values().collect();
+        let compression = detect_compression(dir, &chunks)?;
+        let set = ScannedSet { name, chunks, compression, headers: false, checksums: Vec::new(), last_size: None, trailer: None };
+        return read_trailer(dir, set);
+    }
+
+    let name = pick_set(dir, &by_header)?;
+    let numbered = by_header.remove(&name).unwrap_or_default();
+    let total = numbered.values().find_map(|(_, header)| header.total);
+    check_numbering(dir, &name, &numbered, total)?;
+    let (chunks, headers): (Vec<String>, Vec<ChunkHeader>) = numbered.into_values().unzip();
+    if headers.iter().any(|header| header.encrypted) {
+        return Err(anyhow::anyhow!("The chunks of '{}' are encrypted, so they cannot be restored without the info file", name));
+    }
+    let compression = match headers[0].codec.as_deref() {
+        _ if headers.iter().any(|header| header.codec != headers[0].codec) => {
+            return Err(anyhow::anyhow!("The chunk headers of '{}' name different codecs", name));
+        }
+        Some(codec) => Some(codec.parse::<CompressionAlgorithm>().map_err(|_| anyhow::anyhow!(
+            "The chunks of '{}' are compressed with the custom codec '{}', so they cannot be restored without the info file",
+            name,
+            codec
+        ))?),
+        None => None,
+    };
+    let checksums = headers.into_iter().map(|header| header.checksum).collect();
+    let set = ScannedSet { name, chunks, compression, headers: true, checksums, last_size: None, trailer: None };
+    read_trailer(dir, set)
+}
+
+/// Completes `set` from the info file at the end of its last chunk file, if there is one
+fn read_trailer(dir: &Path, mut set: ScannedSet) -> Result<ScannedSet> {
+    let Some(last) = set.chunks.last() else {
+        return Ok(set);
+    };
+    let path = dir.join(last);
+    let info = File::open(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut file| read_info_trailer(&mut file))
+        .with_context(|| format!("Failed to read chunk file: {}", path.display()))?;
+    let Some(info) = info else {
+        return Ok(set);
+    };
+    if info.chunks.len() != set.chunks.len() {
+        return Err(Failure::new(FailureKind::MissingChunk, format!(
+            "The info file at the end of {} lists {} chunks, but {} are in {}",
+            path.display(),
+            info.chunks.len(),
+            set.chunks.len(),
+            dir.display()
+        )).into());
+    }
+    set.name = info.original_filename.clone();
+    set.compression = info.compression();
+    set.headers = info.chunk_headers;
+    set.last_size = info.chunks.last().map(|chunk| chunk.chunk_size);
+    if let Some(checksums) = info.chunks.iter().map(|chunk| chunk.chunk_checksum.clone()).collect::<Option<Vec<_>>>() {
+        set.checksums = checksums;
+    }
+    set.trailer = Some(info);
+    Ok(set)
+}
+
+/// Picks the file whose chunks to take from those found in `dir`: the one the directory is named after, or the
+/// only one
+fn pick_set<T>(dir: &Path, sets: &BTreeMap<String, T>) -> Result<String> {
+    let dir_name = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    match dir_name.strip_suffix("_parts").filter(|name| sets.contains_key(*name)) {
+        Some(name) => Ok(name.to_string()),
+        None if sets.len() == 1 => Ok(sets.keys().next().unwrap().clone()),
+        None if sets.is_empty() => {
+            Err(anyhow::anyhow!("No chunk files (named like 'my_file-001') found in {}", dir.display()))
+        }
+        None => Err(anyhow::anyhow!(
+            "Chunk files of several split sets are in {}: {}",
+            dir.display(),
+            sets.keys().cloned().collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Fails with [`FailureKind::MissingChunk`] if a chunk of `name` is missing from `numbered`, up to its `total`
+/// count if known. Chunks are numbered from 1, or from 0 if there is a chunk 0 (see
+/// [`SplitOptions::chunk_start_index`](crate::SplitOptions::chunk_start_index)).
+fn check_numbering<T>(dir: &Path, name: &str, numbered: &BTreeMap<u64, T>, total: Option<u64>) -> Result<()> {
+    let first = numbered.keys().next().map_or(1, |first| (*first).min(1));
+    let last = total.unwrap_or(0).max(*numbered.keys().last().unwrap_or(&0));
+    let missing: Vec<String> = (first..=last)
+        .filter(|index| !numbered.contains_key(index))
+        .map(|index| index.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(Failure::new(
+            FailureKind::MissingChunk,
+            format!("Chunk files of '{}' missing from {}: number {}", name, dir.display(), missing.join(", ")),
+        ).into());
+    }
+    Ok(())
+}
+
+/// Compression all of `chunks` in `dir` start with the magic bytes of, if any
+fn detect_compression(dir: &Path, chunks: &[String]) -> Result<Option<CompressionAlgorithm>> {
+    // A file split as it was (e.g., a .gz file split uncompressed) has the magic bytes only in its first chunk
+    let mut compression = None;
+    for (i, chunk) in chunks.iter().enumerate() {
+        let path = dir.join(chunk);
+        let mut header = Vec::new();
+        File::open(&path)
+            .and_then(|file| file.take(8).read_to_end(&mut header))
+            .with_context(|| format!("Failed to read chunk file: {}", path.display()))?;
+        let detected = CompressionAlgorithm::detect(&header);
+        if i > 0 && detected != compression {
+            return Ok(None);
+        }
+        compression = detected;
+    }
+    Ok(compression)
+}
+
+/// Outcome of [`restore_from_chunk_dir`]
+#[derive(Debug, Clone)]
+pub struct ChunkDirRestoreReport {
+    /// Path of the restored file
+    pub output_path: PathBuf,
+    /// Number of chunk files found and joined
+    pub chunks: usize,
+    /// Compression the chunk files were found to be in, and decompressed from
+    pub compression: Option<CompressionAlgorithm>,
+    /// Bytes of restored content
+    pub bytes_written: u64,
+    /// Tagged SHA-256 checksum (e.g., "sha256:abcd...") of the restored file. Without the info file there is
+    /// nothing to check it against, so compare it with one known from elsewhere.
+    pub checksum: String,
+    /// How long the restore took
+    pub duration: Duration,
+}
+
+/// Restores a file from the chunk files in `parts_dir` alone, for when its info file is lost
+///
+/// The chunks are found and ordered by their headers (see [`crate::SplitOptions::chunk_headers`]), or else by their
+/// file names (see [`crate::ChunkInfo::chunk_filename`]), and joined into `output_dir`, under the name they were
+/// split from. Chunks whose headers name a built-in compression algorithm, or that all start with its magic bytes,
+/// are decompressed. This is a best effort: only chunks with headers are checked, against the checksums in them,
+/// and encrypted chunks, chunks in a pool, and permissions and other metadata are not restored. `options` only
+/// contributes its cancellation, pause and rate limit.
+pub fn restore_from_chunk_dir(
+    parts_dir: &Path,
+    output_dir: &Path,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + '_>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + '_>>,
+) -> Result<ChunkDirRestoreReport> {
+    let started = Instant::now();
+    let set = scan_chunk_dir(parts_dir)?;
+    if let Some(info) = set.trailer.as_ref().filter(|info| info.encryption.is_some() || info.custom_codec.is_some()) {
+        return Err(anyhow::anyhow!(
+            "The chunks of '{}' are encrypted or compressed with a custom codec; restore them with the info file at \
+             the end of {}",
+            info.original_filename,
+            parts_dir.join(set.chunks.last().unwrap()).display()
+        ));
+    }
+    let message = |msg: String| {
+        if let Some(cb) = &message_callback {
+            cb(msg);
+        }
+    };
+    message(format!(
+        "Found {} chunks of '{}'{}",
+        set.chunks.len(),
+        set.name,
+        set.compression.map(|algo| format!(", {} compressed", algo)).unwrap_or_default()
+    ));
+    tracing::info!(dir = %parts_dir.display(), chunks = set.chunks.len(), "restoring from chunk files alone");
+
+    let total: u64 = set.chunks.iter()
+        .map(|chunk| fs::metadata(parts_dir.join(chunk)).map(|m| m.len()).unwrap_or(0))
+        .sum();
+    fs::create_dir_all(output_dir).with_context(|| format!("Failed to create directory: {}", output_dir.display()))?;
+    let output_path = output_dir.join(&set.name);
+    check_overwrite(&output_path, options)?;
+    let mut output = BufWriter::new(
+        File::create(&output_path).with_context(|| format!("Failed to create file: {}", output_path.display()))?,
+    );
+
+    let checkpoint = options.checkpoint();
+    let mut hasher = ChecksumAlgorithm::Sha256.hasher();
+    let mut buffer = vec![0u8; READ_BLOCK_SIZE];
+    let (mut bytes_written, mut bytes_read) = (0u64, 0u64);
+    for (i, chunk) in set.chunks.iter().enumerate() {
+        checkpoint.check()?;
+        let path = parts_dir.join(chunk);
+        let (mut reader, stored_size) = open_chunk(&path, &set, i + 1 == set.chunks.len())?;
+        let mut check = ChunkCheck::new(&set, i)?;
+        loop {
+            let n = reader.read(&mut buffer).with_context(|| format!("Failed to read chunk file: {}", path.display()))?;
+            if n == 0 {
+                break;
+            }
+            checkpoint.throttle(n as u64);
+            hasher.update(&buffer[..n]);
+            check.update(&buffer[..n]);
+            output.write_all(&buffer[..n]).with_context(|| format!("Failed to write file: {}", output_path.display()))?;
+            bytes_written += n as u64;
+        }
+        check.finish()?;
+        bytes_read += stored_size;
+        if let Some(cb) = &progress_callback {
+            cb(bytes_read, total);
+        }
+    }
+    output.flush().with_context(|| format!("Failed to write file: {}", output_path.display()))?;
+
+    Ok(ChunkDirRestoreReport {
+        output_path,
+        chunks: set.chunks.len(),
+        compression: set.compression,
+        bytes_written,
+        checksum: ChecksumAlgorithm::Sha256.tag(&hasher.finalize()),
+        duration: started.elapsed(),
+    })
+}
+
+/// Recreates the info file of the split set in `parts_dir` from its chunk files, for when it is lost or damaged
+///
+/// The chunks are found as [`restore_from_chunk_dir`] does, then read through to record their sizes and
+/// `checksum_algorithm` checksums of their content and of the whole file; chunks with headers are checked against
+/// the checksums in them on the way. If the last chunk file ends with the info file (see
+/// [`crate::read_info_trailer`]), that is returned as it is instead. Otherwise the result is a valid [`SplitInfo`] for
+/// a set split by bytes, with `chunks_sub_dir` named after `parts_dir`; what the chunk files do not tell (the split
+/// mode, encryption, parity chunks, a directory tree's path and file metadata) is not recovered.
+pub fn rebuild_split_info(
+    parts_dir: &Path,
+    checksum_algorithm: ChecksumAlgorithm,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + '_>>,
+) -> Result<SplitInfo> {
+    let set = scan_chunk_dir(parts_dir)?;
+    if let Some(info) = set.trailer {
+        tracing::info!(dir = %parts_dir.display(), "taking the split info from the info file trailer");
+        info.validate()?;
+        return Ok(info);
+    }
+    let chunks_sub_dir = parts_dir.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Cannot tell the name of directory: {}", parts_dir.display()))?
+        .to_string();
+    tracing::info!(dir = %parts_dir.display(), chunks = set.chunks.len(), "rebuilding split info");
+
+    let total: u64 = set.chunks.iter()
+        .map(|chunk| fs::metadata(parts_dir.join(chunk)).map(|m| m.len()).unwrap_or(0))
+        .sum();
+    let mut file_hasher = checksum_algorithm.hasher();
+    let mut buffer = vec![0u8; READ_BLOCK_SIZE];
+    let mut chunks = Vec::with_capacity(set.chunks.len());
+    let (mut file_size, mut bytes_read) = (0u64, 0u64);
+    for (i, chunk) in set.chunks.iter().enumerate() {
+        let path = parts_dir.join(chunk);
+        let (mut reader, stored_size) = open_chunk(&path, &set, i + 1 == set.chunks.len())?;
+        let mut check = ChunkCheck::new(&set, i)?;
+        let mut chunk_hasher = checksum_algorithm.hasher();
+        let mut original_size = 0u64;
+        loop {
+            let n = reader.read(&mut buffer).with_context(|| format!("Failed to read chunk file: {}", path.display()))?;
+            if n == 0 {
+                break;
+            }
+            chunk_hasher.update(&buffer[..n]);
+            file_hasher.update(&buffer[..n]);
+            check.update(&buffer[..n]);
+            original_size += n as u64;
+        }
+        check.finish()?;
+        file_size += original_size;
+        chunks.push(ChunkInfo {
+            chunk_filename: chunk.clone(),
+            chunk_size: stored_size,
+            original_size: Some(original_size),
+            chunk_checksum: Some(checksum_algorithm.tag(&chunk_hasher.finalize())),
+            nonce: None,
+            stored_as: None,
+            destination: None,
+        });
+        bytes_read += stored_size;
+        if let Some(cb) = &progress_callback {
+            cb(bytes_read, total);
+        }
+    }
+
+    let first_name = chunks.first().and_then(|chunk| parse_chunk_name(&chunk.chunk_filename));
+    let chunk_extension = first_name.as_ref().and_then(|parsed| parsed.extension.map(str::to_string));
+    let chunk_start_index = first_name.map_or(DEFAULT_CHUNK_START_INDEX, |parsed| parsed.number);
+    let chunk_naming = chunks.first().map_or_else(ChunkNaming::default, |chunk| ChunkNaming::of(&set.name, &chunk.chunk_filename));
+    let split_info = SplitInfo {
+        format_version: FORMAT_VERSION,
+        manifest_checksum: None,
+        original_filename: set.name,
+        original_file_size: file_size,
+        chunk_limit: chunks.iter().filter_map(|chunk| chunk.original_size).max().unwrap_or(0),
+        split_mode: SplitMode::Bytes,
+        max_lines: None,
+        delimiter: None,
+        chunks_sub_dir,
+        chunk_pool: None,
+        chunk_index_width: index_width(Some(chunks.len() as u64)),
+        chunk_naming,
+        chunk_prefix: None,
+        chunk_extension,
+        cat_compatible: false,
+        destinations: Vec::new(),
+        chunk_start_index,
+        chunks,
+        original_checksum: checksum_algorithm.tag(&file_hasher.finalize()),
+        checksum_algorithm,
+        is_compressed: set.compression.is_some(),
+        compression_algorithm: set.compression.unwrap_or_default(),
+        custom_codec: None,
+        encryption: None,
+        parity: None,
+        chunk_headers: set.headers,
+        info_trailer: false,
+        last_verified: None,
+        archive: None,
+        path: None,
+        metadata: None,
+        manifest_format: ManifestFormat::Json,
+        info_extension: None,
+    };
+    split_info.validate()?;
+    Ok(split_info)
+}
+
+/// Check of a chunk's original content against the checksum its header names, if it has one
+struct ChunkCheck<'s> {
+    chunk: &'s str,
+    expected: Option<(&'s str, ChecksumAlgorithm, ChecksumHasher)>,
+}
+
+impl<'s> ChunkCheck<'s> {
+    /// Starts checking the chunk at `index` of `set`
+    fn new(set: &'s ScannedSet, index: usize) -> Result<Self> {
+        let expected = match set.checksums.get(index) {
+            Some(checksum) => {
+                let (algorithm, _) = parse_checksum(checksum, ChecksumAlgorithm::Sha256)?;
+                Some((checksum.as_str(), algorithm, algorithm.hasher()))
+            }
+            None => None,
+        };
+        Ok(ChunkCheck { chunk: &set.chunks[index], expected })
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        if let Some((_, _, hasher)) = &mut self.expected {
+            hasher.update(data);
+        }
+    }
+
+    /// Fails with [`FailureKind::Verification`] if the content read does not match the checksum
+    fn finish(self) -> Result<()> {
+        let Some((expected, algorithm, hasher)) = self.expected else {
+            return Ok(());
+        };
+        let actual = algorithm.tag(&hasher.finalize());
+        if actual != expected {
+            return Err(Failure::new(FailureKind::Verification, format!(
+                "Checksum mismatch for chunk '{}'! Expected: {}, Actual: {}",
+                self.chunk,
+                expected,
+                actual
+            )).into());
+        }
+        Ok(())
+    }
+}
+
+/// Opens the chunk file of `set` at `path` (its `last` one or not) to read its content, past its header, short of
+/// an info file trailer and decompressed, along with its stored size
+fn open_chunk(path: &Path, set: &ScannedSet, last: bool) -> Result<(Box<dyn Read>, u64)> {
+    let file = File::open(path).with_context(|| format!("Failed to open chunk file: {}", path.display()))?;
+    let mut stored_size = file.metadata()?.len();
+    if let Some(size) = set.last_size.filter(|_| last) {
+        stored_size = size;
+    }
+    let mut file = BufReader::new(file.take(stored_size));
+    if set.headers {
+        ChunkHeader::read_from(&mut file).with_context(|| format!("Failed to read chunk file: {}", path.display()))?;
+    }
+    let reader: Box<dyn Read> = match set.compression {
+        Some(algo) => algo.decoder(Box::new(file))?,
+        None => Box::new(file),
+    };
+    Ok((reader, stored_size))
+}
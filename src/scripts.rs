@@ -0,0 +1,225 @@
+// src/scripts.rs
+use std::fmt::Write as _;
+use anyhow::Result;
+
+use crate::naming::shell_quote;
+use crate::{ArchiveFormat, ChecksumAlgorithm, ChunkSink, CompressionAlgorithm, SplitInfo};
+
+/// Longest chunk list put on one `copy` line of the batch script, well below cmd.exe's limit of 8191 characters
+const BATCH_LINE_LIMIT: usize = 4000;
+
+/// Writes scripts that restore a split set with standard tools, for recipients without this program
+///
+/// The scripts are stored next to the chunks in `file_info.chunks_sub_dir`: `restore.sh` (POSIX shell, using
+/// `gzip`, `zstd`, `xz` or `lz4` to decompress), `restore.ps1` (PowerShell, for uncompressed or Gzip compressed
+/// chunks) and `restore.bat` (cmd.exe, for uncompressed chunks). Each takes the directory to restore into as its
+/// only argument (default: the current directory), concatenates the chunks in order, unpacks a directory tree
+/// with `tar`, and checks a SHA-256 checksum where a standard tool for it is at hand. Permissions and other
+/// metadata are not restored. Returns the names of the scripts written; encrypted split sets, ones written with a
+/// custom codec, ones whose chunks hold headers or the info file and ones spread across several directories cannot
+/// be restored this way.
+pub fn write_restore_scripts(file_info: &SplitInfo, sink: &dyn ChunkSink) -> Result<Vec<String>> {
+    if file_info.encryption.is_some() {
+        return Err(anyhow::anyhow!("'{}' is encrypted, so it cannot be restored by a script", file_info.original_filename));
+    }
+    if let Some(codec) = &file_info.custom_codec {
+        return Err(anyhow::anyhow!(
+            "'{}' is compressed with the custom codec '{}', so it cannot be restored by a script",
+            file_info.original_filename,
+            codec
+        ));
+    }
+    if file_info.chunk_headers || file_info.info_trailer {
+        return Err(anyhow::anyhow!(
+            "The chunks of '{}' hold chunk headers or the info file, so they cannot be restored by a script",
+            file_info.original_filename
+        ));
+    }
+    if !file_info.destinations.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The chunks of '{}' are spread across several directories, so they cannot be restored by a script",
+            file_info.original_filename
+        ));
+    }
+    let chunks = chunk_paths(file_info);
+    let compression = file_info.compression();
+    let mut scripts = vec![("restore.sh", shell_script(file_info, &chunks))];
+    if matches!(compression, None | Some(CompressionAlgorithm::Gzip)) {
+        scripts.push(("restore.ps1", powershell_script(file_info, &chunks)));
+    }
+    if compression.is_none() {
+        scripts.push(("restore.bat", batch_script(file_info, &chunks)));
+    }
+
+    let mut names = Vec::new();
+    for (script, content) in scripts {
+        let name = format!("{}/{}", file_info.chunks_sub_dir, script);
+        sink.put(&name, content.as_bytes())?;
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// Paths of the chunks of `file_info` in order, relative to its `chunks_sub_dir` where the scripts are
+fn chunk_paths(file_info: &SplitInfo) -> Vec<String> {
+    let prefix = format!("{}/", file_info.chunks_sub_dir);
+    let up = "../".repeat(file_info.chunks_sub_dir.split('/').count());
+    file_info.chunks.iter().map(|chunk| {
+        let key = file_info.chunk_name(chunk);
+        match key.strip_prefix(&prefix) {
+            Some(path) => path.to_string(),
+            None => format!("{}{}", up, key),
+        }
+    }).collect()
+}
+
+/// Hex SHA-256 of the original file, if that is what was recorded
+fn sha256_hex(file_info: &SplitInfo) -> Option<&str> {
+    match file_info.original_checksum.split_once(':') {
+        Some(("sha256", hex)) => Some(hex),
+        Some(_) => None,
+        None => (file_info.checksum_algorithm == ChecksumAlgorithm::Sha256).then_some(&file_info.original_checksum),
+    }
+}
+
+fn shell_script(file_info: &SplitInfo, chunks: &[String]) -> String {
+    let name = shell_quote(&file_info.original_filename);
+    let decode = match file_info.compression() {
+        None => "cat",
+        Some(CompressionAlgorithm::Gzip) => "gzip -dc",
+        Some(CompressionAlgorithm::Zstd) => "zstd -dcq",
+        Some(CompressionAlgorithm::Xz) => "xz -dc",
+        Some(CompressionAlgorithm::Lz4) => "lz4 -dcq",
+    };
+    let mut script = String::new();
+    let _ = writeln!(script, "#!/bin/sh");
+    let _ = writeln!(script, "# Restores the split file from the chunks next to this script, without file_splitter.");
+    let _ = writeln!(script, "# Usage: sh restore.sh [directory to restore into, default: the current directory]");
+    let _ = writeln!(script, "set -e");
+    let _ = writeln!(script, "dir=$(dirname \"$0\")");
+    let _ = writeln!(script, "out_dir=${{1:-.}}");
+    let _ = writeln!(script, "mkdir -p \"$out_dir\"");
+    if chunks.is_empty() {
+        let _ = writeln!(script, ": > \"$out_dir\"/{}", name);
+        return script;
+    }
+    let _ = writeln!(script, "{{");
+    for chunk in chunks {
+        let _ = writeln!(script, "  {} \"$dir\"/{}", decode, shell_quote(chunk));
+    }
+    match file_info.archive {
+        Some(ArchiveFormat::Tar) => {
+            let _ = writeln!(script, "}} | tar -xf - -C \"$out_dir\"");
+        }
+        None => {
+            let _ = writeln!(script, "}} > \"$out_dir\"/{}", name);
+            if let Some(hex) = sha256_hex(file_info) {
+                let _ = writeln!(script, "if command -v sha256sum >/dev/null 2>&1; then");
+                let _ = writeln!(script, "  printf '%s  %s\\n' {} \"$out_dir\"/{} | sha256sum -c -", hex, name);
+                let _ = writeln!(script, "fi");
+            }
+        }
+    }
+    script
+}
+
+fn powershell_script(file_info: &SplitInfo, chunks: &[String]) -> String {
+    let (output, tar) = match file_info.archive {
+        Some(_) => (format!("{}.tar", file_info.original_filename), true),
+        None => (file_info.original_filename.clone(), false),
+    };
+    let mut script = String::new();
+    let _ = writeln!(script, "# Restores the split file from the chunks next to this script, without file_splitter.");
+    let _ = writeln!(script, "# Usage: powershell -ExecutionPolicy Bypass -File restore.ps1 [directory to restore into]");
+    let _ = writeln!(script, "param([string]$OutputDir = '.')");
+    let _ = writeln!(script, "$ErrorActionPreference = 'Stop'");
+    let _ = writeln!(script, "$chunks = @(");
+    for chunk in chunks {
+        let _ = writeln!(script, "    {}", powershell_quote(chunk));
+    }
+    let _ = writeln!(script, ")");
+    let _ = writeln!(script, "New-Item -ItemType Directory -Force -Path $OutputDir | Out-Null");
+    let _ = writeln!(script, "$dir = $ExecutionContext.SessionState.Path.GetUnresolvedProviderPathFromPSPath($OutputDir)");
+    let _ = writeln!(script, "$path = Join-Path $dir {}", powershell_quote(&output));
+    let _ = writeln!(script, "$output = [System.IO.File]::Create($path)");
+    let _ = writeln!(script, "try {{");
+    let _ = writeln!(script, "    foreach ($chunk in $chunks) {{");
+    let _ = writeln!(script, "        $source = [System.IO.File]::OpenRead((Join-Path $PSScriptRoot $chunk))");
+    let _ = writeln!(script, "        try {{");
+    if file_info.compression().is_some() {
+        let _ = writeln!(script, "            $stream = New-Object System.IO.Compression.GZipStream($source, [System.IO.Compression.CompressionMode]::Decompress)");
+        let _ = writeln!(script, "            $stream.CopyTo($output)");
+    } else {
+        let _ = writeln!(script, "            $source.CopyTo($output)");
+    }
+    let _ = writeln!(script, "        }} finally {{");
+    let _ = writeln!(script, "            $source.Dispose()");
+    let _ = writeln!(script, "        }}");
+    let _ = writeln!(script, "    }}");
+    let _ = writeln!(script, "}} finally {{");
+    let _ = writeln!(script, "    $output.Dispose()");
+    let _ = writeln!(script, "}}");
+    if tar {
+        let _ = writeln!(script, "tar -xf $path -C $dir");
+        let _ = writeln!(script, "Remove-Item -LiteralPath $path");
+    } else if let Some(hex) = sha256_hex(file_info) {
+        let _ = writeln!(script, "if ((Get-FileHash -Algorithm SHA256 -LiteralPath $path).Hash -ne '{}') {{", hex);
+        let _ = writeln!(script, "    throw \"The restored file does not match its checksum: $path\"");
+        let _ = writeln!(script, "}}");
+        let _ = writeln!(script, "Write-Host \"${{path}}: OK\"");
+    }
+    script.replace('\n', "\r\n")
+}
+
+fn batch_script(file_info: &SplitInfo, chunks: &[String]) -> String {
+    let (output, tar) = match file_info.archive {
+        Some(_) => (format!("{}.tar", file_info.original_filename), true),
+        None => (file_info.original_filename.clone(), false),
+    };
+    let output = format!("\"%out%\\{}\"", batch_escape(&output));
+    let mut script = String::new();
+    let _ = writeln!(script, "@echo off");
+    let _ = writeln!(script, "rem Restores the split file from the chunks next to this script, without file_splitter.");
+    let _ = writeln!(script, "rem Usage: restore.bat [directory to restore into, default: the current directory]");
+    let _ = writeln!(script, "setlocal");
+    let _ = writeln!(script, "set \"out=%~1\"");
+    let _ = writeln!(script, "if \"%out%\"==\"\" set \"out=.\"");
+    let _ = writeln!(script, "if not exist \"%out%\" mkdir \"%out%\"");
+    if chunks.is_empty() {
+        let _ = writeln!(script, "type nul > {}", output);
+    }
+    // Every line appends a group of chunks to what the lines before wrote
+    let mut line = String::new();
+    for chunk in chunks {
+        let source = format!("\"%~dp0{}\"", batch_escape(&chunk.replace('/', "\\")));
+        if !line.is_empty() && line.len() + source.len() > BATCH_LINE_LIMIT {
+            let _ = writeln!(script, "copy /b {} {} >nul || exit /b 1", line, output);
+            line = output.clone();
+        }
+        if !line.is_empty() {
+            line.push_str(" + ");
+        }
+        line.push_str(&source);
+    }
+    if !line.is_empty() {
+        let _ = writeln!(script, "copy /b {} {} >nul || exit /b 1", line, output);
+    }
+    if tar {
+        let _ = writeln!(script, "tar -xf {} -C \"%out%\" || exit /b 1", output);
+        let _ = writeln!(script, "del {}", output);
+    } else if let Some(hex) = sha256_hex(file_info) {
+        let _ = writeln!(script, "echo Expected SHA-256: {}", hex);
+        let _ = writeln!(script, "certutil -hashfile {} SHA256", output);
+    }
+    script.replace('\n', "\r\n")
+}
+
+/// Quotes `s` as a literal PowerShell string
+fn powershell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Escapes `s` for use inside double quotes in a batch file
+fn batch_escape(s: &str) -> String {
+    s.replace('%', "%%")
+}
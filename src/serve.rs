@@ -0,0 +1,156 @@
+// src/serve.rs
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use anyhow::Result;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+/// Number of requests handled concurrently by [`serve_directory`]
+const SERVER_THREADS: usize = 4;
+
+/// Serves the split sets below `root_dir` over HTTP until the process exits
+///
+/// Every file below `root_dir` is available at its relative path (e.g., `/my_file_parts/my_file-001`), with
+/// `Range` request support. The info file of each split set is also available at the top level
+/// (`/my_file.json`), so that `restore --url http://host:port/my_file.json` finds it and its chunks.
+///
+/// `root_dir`: Directory containing the `*_parts` subdirectories (the split output directory).
+/// `address`: Address to listen on (e.g., "0.0.0.0:8080").
+/// `message_callback`: Optional callback for reporting messages (one line per request).
+pub fn serve_directory(
+    root_dir: &Path,
+    address: &str,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + 'static>>,
+) -> Result<()> {
+    let server = Server::http(address)
+        .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", address, e))?;
+
+    if let Some(cb) = &message_callback {
+        cb(format!("Serving '{}' on http://{}", root_dir.display(), address));
+    }
+
+    thread::scope(|scope| {
+        for _ in 0..SERVER_THREADS {
+            scope.spawn(|| {
+                while let Ok(request) = server.recv() {
+                    let line = format!("{} {}", request.method(), request.url());
+                    let status = respond(root_dir, request);
+                    if let Some(cb) = &message_callback {
+                        cb(format!("{} -> {}", line, status));
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Answers one request, returning the status code sent
+fn respond(root_dir: &Path, request: Request) -> u16 {
+    if !matches!(request.method(), Method::Get | Method::Head) {
+        return send(request, Response::empty(405));
+    }
+    let Some(path) = resolve(root_dir, request.url()) else {
+        return send(request, Response::empty(404));
+    };
+    let Ok(mut file) = File::open(&path) else {
+        return send(request, Response::empty(404));
+    };
+    let Ok(size) = file.metadata().map(|m| m.len()) else {
+        return send(request, Response::empty(500));
+    };
+
+    let content_type = if path.extension().is_some_and(|ext| ext == "json") {
+        "application/json"
+    } else {
+        "application/octet-stream"
+    };
+    let mut headers = vec![header("Content-Type", content_type), header("Accept-Ranges", "bytes")];
+
+    let range = request.headers().iter()
+        .find(|h| h.field.equiv("Range"))
+        .map(|h| parse_range(h.value.as_str(), size));
+    match range {
+        None => send(request, Response::new(StatusCode(200), headers, file, Some(size as usize), None)),
+        Some(Some((start, end))) => {
+            if file.seek(SeekFrom::Start(start)).is_err() {
+                return send(request, Response::empty(500));
+            }
+            let length = end - start + 1;
+            headers.push(header("Content-Range", &format!("bytes {}-{}/{}", start, end, size)));
+            send(request, Response::new(StatusCode(206), headers, file.take(length), Some(length as usize), None))
+        }
+        Some(None) => send(
+            request,
+            Response::empty(416).with_header(header("Content-Range", &format!("bytes */{}", size))),
+        ),
+    }
+}
+
+fn send<R: Read>(request: Request, response: Response<R>) -> u16 {
+    let status = response.status_code().0;
+    let _ = request.respond(response); // The client may have gone away; nothing else to do
+    status
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+/// Maps a request URL to a file below `root_dir`, refusing anything that would escape it
+fn resolve(root_dir: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let relative = PathBuf::from(percent_decode(path.trim_start_matches('/'))?);
+    if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return None;
+    }
+
+    let full_path = root_dir.join(&relative);
+    if full_path.is_file() {
+        return Some(full_path);
+    }
+    // A top-level "<name>.json" (or any other info file extension, e.g. ".fsmeta.json") refers to the info file
+    // in "<name>_parts/"
+    if relative.components().count() != 1 {
+        return None;
+    }
+    let file_name = relative.to_str()?;
+    file_name.match_indices('.').find_map(|(dot, _)| {
+        let info_path = root_dir.join(format!("{}_parts", &file_name[..dot])).join(file_name);
+        info_path.is_file().then_some(info_path)
+    })
+}
+
+/// Parses a single `bytes=start-end` range, returning `None` if it cannot be satisfied
+fn parse_range(value: &str, size: u64) -> Option<(u64, u64)> {
+    let (start, end) = value.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            // "bytes=-N" asks for the last N bytes
+            let suffix: u64 = suffix.parse().ok()?;
+            (size.checked_sub(suffix.min(size))?, size.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, size.checked_sub(1)?),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(size.checked_sub(1)?)),
+    };
+    (start <= end && start < size).then_some((start, end))
+}
+
+/// Decodes `%XX` escapes in a URL path
+pub(crate) fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = path.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
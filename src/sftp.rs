@@ -0,0 +1,227 @@
+// src/sftp.rs
+use std::io::{self, BufWriter, Cursor, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{Result, Context};
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+
+use crate::{ChunkEncoder, ChunkSink, ChunkSource};
+
+/// SFTP status code for a missing file (`SSH_FX_NO_SUCH_FILE`)
+const NO_SUCH_FILE: i32 = 2;
+
+/// Chunk storage on a remote server reached over SFTP
+///
+/// The server's host key must already be in `~/.ssh/known_hosts`. Authentication uses the password from
+/// the URL or the `SFTP_PASSWORD` environment variable if given, and otherwise the SSH agent followed by
+/// the default keys in `~/.ssh`. All chunks travel over the one connection opened here.
+pub struct SftpStore {
+    // Keeps the connection open for `sftp`
+    _session: Session,
+    sftp: Mutex<Sftp>,
+    host: String,
+    /// Remote directory, `/`-separated; relative paths start in the user's home directory
+    root: String,
+}
+
+impl SftpStore {
+    /// Parses an `sftp://[user[:password]@]host[:port]/path` URL and connects
+    ///
+    /// The path is absolute; start it with `~/` (`sftp://host/~/backups`) for a path in the user's home directory.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let rest = url.strip_prefix("sftp://")
+            .ok_or_else(|| anyhow::anyhow!("Invalid SFTP URL (expected sftp://user@host/path): {}", url))?;
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let (userinfo, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => (Some(userinfo), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().with_context(|| format!("Invalid port in SFTP URL: {}", url))?),
+            None => (host_port, 22),
+        };
+        if host.is_empty() {
+            return Err(anyhow::anyhow!("Invalid SFTP URL (missing host): {}", url));
+        }
+        let (user, password) = match userinfo.map(|u| u.split_once(':').map_or((u, None), |(u, p)| (u, Some(p)))) {
+            Some((user, password)) => (user.to_string(), password.map(str::to_string)),
+            None => (std::env::var("USER").or_else(|_| std::env::var("USERNAME"))
+                .context("No user name in the SFTP URL and none found in the environment")?, None),
+        };
+        let root = match path.strip_prefix("~/") {
+            Some(home_relative) => home_relative.to_string(),
+            None => format!("/{}", path),
+        };
+        let password = password.or_else(|| std::env::var("SFTP_PASSWORD").ok());
+
+        SftpStore::connect(host, port, &user, password.as_deref(), &root)
+    }
+
+    /// Connects to `host` and stores chunks below the remote directory `root` on it
+    pub fn connect(host: &str, port: u16, user: &str, password: Option<&str>, root: &str) -> Result<Self> {
+        let tcp = TcpStream::connect((host, port))
+            .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.handshake().with_context(|| format!("SSH handshake with {} failed", host))?;
+        verify_host_key(&session, host, port)?;
+
+        match password {
+            Some(password) => session.userauth_password(user, password)?,
+            None => {
+                if session.userauth_agent(user).is_err() {
+                    for key in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+                        let key_path = ssh_dir()?.join(key);
+                        if key_path.exists() && session.userauth_pubkey_file(user, None, &key_path, None).is_ok() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        if !session.authenticated() {
+            return Err(anyhow::anyhow!("SSH authentication as '{}' on {} failed", user, host));
+        }
+
+        let sftp = session.sftp().context("Failed to start the SFTP subsystem")?;
+        Ok(SftpStore {
+            _session: session,
+            sftp: Mutex::new(sftp),
+            host: host.to_string(),
+            root: root.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Remote path of `name`, always `/`-separated regardless of the local platform
+    fn remote_path(&self, name: &str) -> String {
+        if self.root.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.root, name)
+        }
+    }
+
+    fn url(&self, name: &str) -> String {
+        let path = self.remote_path(name);
+        match path.strip_prefix('/') {
+            Some(absolute) => format!("sftp://{}/{}", self.host, absolute),
+            None => format!("sftp://{}/~/{}", self.host, path),
+        }
+    }
+}
+
+/// Creates the missing parent directories of the remote `path`, one level at a time
+fn create_parent_dirs(sftp: &Sftp, path: &str) -> Result<()> {
+    if let Some((parent, _)) = path.rsplit_once('/') {
+        let ends = parent.match_indices('/').map(|(i, _)| i).chain([parent.len()]);
+        for dir in ends.map(|end| &parent[..end]).filter(|dir| !dir.is_empty()) {
+            if sftp.stat(Path::new(dir)).is_err() {
+                sftp.mkdir(Path::new(dir), 0o755)
+                    .with_context(|| format!("Failed to create subdirectory: {}", dir))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the server's host key against `~/.ssh/known_hosts`
+fn verify_host_key(session: &Session, host: &str, port: u16) -> Result<()> {
+    let mut known_hosts = session.known_hosts()?;
+    let known_hosts_path = ssh_dir()?.join("known_hosts");
+    if known_hosts_path.exists() {
+        known_hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)
+            .with_context(|| format!("Failed to read {}", known_hosts_path.display()))?;
+    }
+    let (key, _) = session.host_key().ok_or_else(|| anyhow::anyhow!("{} did not send a host key", host))?;
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => Err(anyhow::anyhow!(
+            "Host key for {} is not in {}; connect once with ssh to add it",
+            host,
+            known_hosts_path.display()
+        )),
+        CheckResult::Mismatch => Err(anyhow::anyhow!(
+            "Host key for {} does not match {}; refusing to connect",
+            host,
+            known_hosts_path.display()
+        )),
+        CheckResult::Failure => Err(anyhow::anyhow!("Failed to check the host key for {}", host)),
+    }
+}
+
+fn ssh_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".ssh"))
+        .ok_or_else(|| anyhow::anyhow!("Cannot locate the home directory for SSH keys"))
+}
+
+impl ChunkSink for SftpStore {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        let mut entry = self.create(name)?;
+        entry.write_all(data)?;
+        entry.finish()
+    }
+
+    /// Writes straight into the remote file
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        let path = self.remote_path(name);
+        let sftp = self.sftp.lock().unwrap();
+        create_parent_dirs(&sftp, &path)?;
+        let file = sftp.create(Path::new(&path))?;
+        Ok(Box::new(SftpEntry(BufWriter::new(file))))
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.sftp.lock().unwrap().stat(Path::new(&self.remote_path(name))).is_ok())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        match self.sftp.lock().unwrap().unlink(Path::new(&self.remote_path(name))) {
+            Err(e) if e.code() != ssh2::ErrorCode::SFTP(NO_SUCH_FILE) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.url(name)
+    }
+}
+
+/// Remote file being written by [`SftpStore::create`]
+struct SftpEntry(BufWriter<ssh2::File>);
+
+impl Write for SftpEntry {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl ChunkEncoder for SftpEntry {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+}
+
+impl ChunkSource for SftpStore {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let sftp = self.sftp.lock().unwrap();
+        let mut data = Vec::new();
+        sftp.open(Path::new(&self.remote_path(name)))?.read_to_end(&mut data)?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        Ok(self.sftp.lock().unwrap().stat(Path::new(&self.remote_path(name))).ok().map(|stat| stat.size.unwrap_or(0)))
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.url(name)
+    }
+}
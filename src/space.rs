@@ -0,0 +1,150 @@
+// src/space.rs
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+
+use crate::{Failure, FailureKind};
+
+/// Bytes free for the current user on the file system holding `path`, or on that of its nearest existing
+/// ancestor if `path` is still to be created; `None` on systems other than Linux and Windows, where this is not
+/// told
+pub fn available_space(path: &Path) -> io::Result<Option<u64>> {
+    query(nearest_existing(path))
+}
+
+fn nearest_existing(path: &Path) -> &Path {
+    path.ancestors()
+        .map(|ancestor| if ancestor.as_os_str().is_empty() { Path::new(".") } else { ancestor })
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."))
+}
+
+/// Fails if the file system holding `dir` (see [`available_space`]) has less than `required` bytes free, so that
+/// a split or restore that would run out of space stops before writing anything. Passes where the free space
+/// cannot be told.
+pub fn check_free_space(dir: &Path, required: u64) -> Result<()> {
+    let Some(available) = available_space(dir)? else {
+        return Ok(());
+    };
+    if available < required {
+        return Err(not_enough_space(&dir.display().to_string(), required, available));
+    }
+    Ok(())
+}
+
+/// Fails like [`check_free_space`] if the file systems holding `dirs` together have less than `required` bytes
+/// free, for chunks spread across them (see [`crate::SpreadSink`]). A file system holding several of the
+/// directories is only counted once where that can be told (on Unix).
+pub fn check_combined_free_space(dirs: &[PathBuf], required: u64) -> Result<()> {
+    let mut counted = HashSet::new();
+    let mut available = 0u64;
+    for dir in dirs {
+        if file_system_id(dir).is_some_and(|id| !counted.insert(id)) {
+            continue;
+        }
+        let Some(free) = available_space(dir)? else {
+            return Ok(());
+        };
+        available = available.saturating_add(free);
+    }
+    if available < required {
+        let location = dirs.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ");
+        return Err(not_enough_space(&location, required, available));
+    }
+    Ok(())
+}
+
+fn not_enough_space(location: &str, required: u64, available: u64) -> anyhow::Error {
+    Failure::new(
+        FailureKind::Io,
+        format!(
+            "Not enough free space in {}: about {} bytes are needed, but only {} bytes are free",
+            location,
+            required,
+            available
+        ),
+    ).into()
+}
+
+/// Device of the file system holding `path`, to tell directories on the same file system apart from others
+#[cfg(unix)]
+fn file_system_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::metadata(nearest_existing(path)).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn file_system_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn query(path: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    // SAFETY: `path` is a valid C string, and `stats` is only read after statvfs filled it in
+    let stats = unsafe {
+        let mut stats: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stats) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stats
+    };
+    // Blocks reserved for root are not available to everyone else
+    Ok(Some((stats.f_bavail as u64).saturating_mul(stats.f_frsize as u64)))
+}
+
+#[cfg(windows)]
+fn query(path: &Path) -> io::Result<Option<u64>> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    // SAFETY: `wide` is NUL-terminated, and the totals not asked for may be null
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(available))
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn query(_path: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Bytes of memory available to start new work with, without swapping; `None` on systems other than Linux and
+/// Windows, or if it cannot be told
+#[cfg(target_os = "linux")]
+pub(crate) fn available_memory() -> Option<u64> {
+    // "MemAvailable:   12345678 kB"
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find_map(|line| line.strip_prefix("MemAvailable:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib.saturating_mul(1024))
+}
+
+#[cfg(windows)]
+pub(crate) fn available_memory() -> Option<u64> {
+    use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    // SAFETY: `status` is zeroed with its length set, as GlobalMemoryStatusEx requires
+    let status = unsafe {
+        let mut status: MEMORYSTATUSEX = std::mem::zeroed();
+        status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+        if GlobalMemoryStatusEx(&mut status) == 0 {
+            return None;
+        }
+        status
+    };
+    Some(status.ullAvailPhys)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(crate) fn available_memory() -> Option<u64> {
+    None
+}
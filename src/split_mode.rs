@@ -0,0 +1,354 @@
+// src/split_mode.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use anyhow::Result;
+
+use crate::SplitOptions;
+
+/// Where chunk boundaries may fall when splitting
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitMode {
+    /// Every chunk but the last holds exactly the size limit, the original and default mode
+    #[default]
+    Bytes,
+    /// Chunks end on a newline, so each one holds whole lines (a line longer than the size limit is cut at the limit)
+    Lines,
+    /// Chunks end right after a user-specified delimiter, so each one holds whole records
+    /// (a record longer than the size limit is cut at the limit)
+    Delimiter,
+}
+
+impl SplitMode {
+    /// All supported modes, in the order they are offered to users
+    pub const ALL: &'static [SplitMode] = &[SplitMode::Bytes, SplitMode::Lines, SplitMode::Delimiter];
+
+    /// Short lowercase name of the mode (e.g., "lines")
+    pub fn name(&self) -> &'static str {
+        match self {
+            SplitMode::Bytes => "bytes",
+            SplitMode::Lines => "lines",
+            SplitMode::Delimiter => "delimiter",
+        }
+    }
+}
+
+impl fmt::Display for SplitMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SplitMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SplitMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown split mode '{}' (expected one of: {})",
+                s,
+                SplitMode::ALL.iter().map(|m| m.name()).collect::<Vec<_>>().join(", ")
+            ))
+    }
+}
+
+/// Parses a delimiter written with backslash escapes (`\n`, `\r`, `\t`, `\0`, `\\` and `\xHH`), e.g. `\n}`
+pub fn parse_delimiter(s: &str) -> Result<Vec<u8>> {
+    let mut delimiter = Vec::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0u8; 4];
+            delimiter.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        delimiter.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 2)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid escape '\\x{}' in delimiter '{}'", hex, s))?
+            }
+            other => return Err(anyhow::anyhow!(
+                "Invalid escape '\\{}' in delimiter '{}'",
+                other.map(String::from).unwrap_or_default(),
+                s
+            )),
+        });
+    }
+    if delimiter.is_empty() {
+        return Err(anyhow::anyhow!("The delimiter must not be empty"));
+    }
+    Ok(delimiter)
+}
+
+/// Writes `delimiter` with the backslash escapes [`parse_delimiter`] reads, for the info file
+pub fn escape_delimiter(delimiter: &[u8]) -> String {
+    delimiter.iter().map(|&b| match b {
+        b'\n' => "\\n".to_string(),
+        b'\r' => "\\r".to_string(),
+        b'\t' => "\\t".to_string(),
+        0 => "\\0".to_string(),
+        b'\\' => "\\\\".to_string(),
+        b' '..=b'~' => (b as char).to_string(),
+        _ => format!("\\x{:02x}", b),
+    }).collect()
+}
+
+/// Well-known size limits for common storage and transfer targets
+///
+/// Each limit leaves some room below the target's hard limit, for compression and encryption overhead
+/// (incompressible data grows slightly) and file system metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizePreset {
+    /// Files on a FAT32 volume, which holds at most 4 GiB minus one byte per file: 4 GiB minus 1 MiB
+    Fat32,
+    /// A single-layer 4.7 GB DVD: 4480 MiB
+    Dvd,
+    /// Email with a 25 MB attachment limit, after base64 grows the attachment by a third: 18 MB
+    Email25,
+    /// Discord's 10 MB upload limit without Nitro: 9.5 MiB
+    Discord,
+    /// A 700 MB CD-R: 700 MiB
+    Cd700,
+}
+
+impl SizePreset {
+    /// All presets, in the order they are offered to users
+    pub const ALL: &'static [SizePreset] = &[
+        SizePreset::Fat32,
+        SizePreset::Dvd,
+        SizePreset::Email25,
+        SizePreset::Discord,
+        SizePreset::Cd700,
+    ];
+
+    /// Short lowercase name of the preset (e.g., "fat32")
+    pub fn name(&self) -> &'static str {
+        match self {
+            SizePreset::Fat32 => "fat32",
+            SizePreset::Dvd => "dvd",
+            SizePreset::Email25 => "email25",
+            SizePreset::Discord => "discord",
+            SizePreset::Cd700 => "cd700",
+        }
+    }
+
+    /// Chunk size limit in bytes
+    pub fn size_limit(&self) -> u64 {
+        match self {
+            SizePreset::Fat32 => (4 << 30) - (1 << 20),
+            SizePreset::Dvd => 4480 << 20,
+            SizePreset::Email25 => 18_000_000,
+            SizePreset::Discord => 19 << 19,
+            SizePreset::Cd700 => 700 << 20,
+        }
+    }
+}
+
+impl fmt::Display for SizePreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SizePreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SizePreset::ALL
+            .iter()
+            .copied()
+            .find(|preset| preset.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown size preset '{}' (expected one of: {})",
+                s,
+                SizePreset::ALL.iter().map(|p| p.name()).collect::<Vec<_>>().join(", ")
+            ))
+    }
+}
+
+/// Size units [`parse_size`] accepts, with the number of bytes in one
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("B", 1),
+    ("K", 1 << 10), ("KiB", 1 << 10), ("KB", 1_000),
+    ("M", 1 << 20), ("MiB", 1 << 20), ("MB", 1_000_000),
+    ("G", 1 << 30), ("GiB", 1 << 30), ("GB", 1_000_000_000),
+    ("T", 1 << 40), ("TiB", 1 << 40), ("TB", 1_000_000_000_000),
+];
+
+/// Parses a size in bytes, optionally with a fraction and a unit (e.g., `104857600`, `100MB`, `4.7GiB`, `650M`)
+///
+/// As in GNU `split`, `K`, `M`, `G` and `T` (and `KiB` to `TiB`) are powers of 1024, while `KB` to `TB` are
+/// powers of 1000. Units are case-insensitive. A fractional size is rounded down to a whole number of bytes.
+pub fn parse_size(s: &str) -> Result<u64> {
+    let trimmed = s.trim();
+    let number_end = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(number_end);
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() || fraction.contains('.') {
+        return Err(anyhow::anyhow!("Invalid size '{}': expected a number of bytes, optionally with a unit (e.g., 100MB)", s));
+    }
+    let unit = unit.trim();
+    let multiplier = match unit {
+        "" => 1,
+        _ => SIZE_UNITS.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+            .map(|&(_, multiplier)| multiplier)
+            .ok_or_else(|| anyhow::anyhow!(
+                "Invalid size '{}': unknown unit '{}' (expected one of: {})",
+                s,
+                unit,
+                SIZE_UNITS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+            ))?,
+    };
+    // Exact integer arithmetic, so that e.g. 0.5KiB is exactly 512 bytes
+    let too_large = || anyhow::anyhow!("Invalid size '{}': larger than the maximum of {} bytes", s, u64::MAX);
+    let whole: u128 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| too_large())? };
+    let fraction_digits = &fraction[..fraction.len().min(20)];
+    let fraction_bytes = if fraction_digits.is_empty() {
+        0
+    } else {
+        let numerator: u128 = fraction_digits.parse().map_err(|_| too_large())?;
+        numerator * multiplier as u128 / 10u128.pow(fraction_digits.len() as u32)
+    };
+    whole.checked_mul(multiplier as u128)
+        .and_then(|bytes| bytes.checked_add(fraction_bytes))
+        .and_then(|bytes| u64::try_from(bytes).ok())
+        .ok_or_else(too_large)
+}
+
+/// Where the chunks of one split may end, checked against its [`SplitOptions`]
+#[derive(Clone, Copy)]
+pub(crate) struct ChunkBoundary<'a> {
+    /// Sequence every chunk but the last ends with, if any
+    delimiter: Option<&'a [u8]>,
+    max_lines: Option<u64>,
+}
+
+impl<'a> ChunkBoundary<'a> {
+    pub(crate) fn new(options: &SplitOptions<'a>) -> Result<Self> {
+        match options.max_lines {
+            Some(0) => return Err(anyhow::anyhow!("The maximum number of lines per chunk must be at least 1")),
+            Some(_) if options.split_mode != SplitMode::Lines => {
+                return Err(anyhow::anyhow!("A maximum number of lines per chunk needs the '{}' split mode", SplitMode::Lines));
+            }
+            _ => {}
+        }
+        let delimiter = match (options.split_mode, options.delimiter) {
+            (SplitMode::Bytes, None) => None,
+            (SplitMode::Lines, None) => Some(&b"\n"[..]),
+            (SplitMode::Delimiter, Some(delimiter)) if !delimiter.is_empty() => Some(delimiter),
+            (SplitMode::Delimiter, _) => {
+                return Err(anyhow::anyhow!("The '{}' split mode needs a (non-empty) delimiter", SplitMode::Delimiter));
+            }
+            (_, Some(_)) => return Err(anyhow::anyhow!("A delimiter needs the '{}' split mode", SplitMode::Delimiter)),
+        };
+        Ok(ChunkBoundary { delimiter, max_lines: options.max_lines })
+    }
+
+    /// Counter that ends a chunk after the maximum number of lines, if limited
+    pub(crate) fn line_limit(&self) -> Option<LineLimit> {
+        self.max_lines.map(LineLimit::new)
+    }
+
+    /// Whether every chunk but the last simply holds the size limit
+    pub(crate) fn by_bytes(&self) -> bool {
+        self.delimiter.is_none()
+    }
+
+    /// Finds the end of a chunk read piece by piece, giving the same end as [`ChunkBoundary::chunk_end`] on the
+    /// whole of it
+    pub(crate) fn scan(&self) -> ChunkScan<'a> {
+        ChunkScan { boundary: *self, line_limit: self.line_limit(), scanned: 0, last_end: None, tail: Vec::new() }
+    }
+
+    /// Length of the chunk at the start of `data`, which holds either the size limit or, if `at_end`,
+    /// everything left of the input
+    pub(crate) fn chunk_end(&self, data: &[u8], at_end: bool) -> usize {
+        match self.delimiter {
+            Some(delimiter) if !at_end => data.windows(delimiter.len())
+                .rposition(|window| window == delimiter)
+                .map_or(data.len(), |i| i + delimiter.len()),
+            _ => data.len(),
+        }
+    }
+}
+
+/// Looks for the end of a chunk in what is read of it, holding no more of it than a delimiter's length (see
+/// [`ChunkBoundary::scan`])
+pub(crate) struct ChunkScan<'a> {
+    boundary: ChunkBoundary<'a>,
+    line_limit: Option<LineLimit>,
+    /// Bytes of the chunk scanned so far
+    scanned: u64,
+    /// End of the last delimiter found so far
+    last_end: Option<u64>,
+    /// Last bytes scanned, too few to hold a delimiter, which may start one that ends in the next `data`
+    tail: Vec<u8>,
+}
+
+impl ChunkScan<'_> {
+    /// Scans the next `data` of the chunk, returning the length of the chunk once it holds the maximum number of
+    /// lines
+    pub(crate) fn scan(&mut self, data: &[u8]) -> Option<u64> {
+        if let Some(line_end) = self.line_limit.as_mut().and_then(|limit| limit.scan(data)) {
+            return Some(self.scanned + line_end as u64);
+        }
+        if let Some(delimiter) = self.boundary.delimiter {
+            let found = data.windows(delimiter.len()).rposition(|window| window == delimiter)
+                .map(|i| self.scanned + (i + delimiter.len()) as u64)
+                .or_else(|| {
+                    // A delimiter split across the previous and this `data`
+                    let edge = [&self.tail[..], &data[..data.len().min(delimiter.len() - 1)]].concat();
+                    edge.windows(delimiter.len()).rposition(|window| window == delimiter)
+                        .map(|i| self.scanned + (i + delimiter.len()) as u64 - self.tail.len() as u64)
+                });
+            self.last_end = found.or(self.last_end);
+            self.tail.extend_from_slice(&data[data.len().saturating_sub(delimiter.len() - 1)..]);
+            self.tail.drain(..self.tail.len().saturating_sub(delimiter.len() - 1));
+        }
+        self.scanned += data.len() as u64;
+        None
+    }
+
+    /// Length of the chunk once everything up to the size limit or, if `at_end`, all that is left of the input is
+    /// scanned
+    pub(crate) fn end(&self, at_end: bool) -> u64 {
+        match at_end {
+            true => self.scanned,
+            false => self.last_end.unwrap_or(self.scanned),
+        }
+    }
+}
+
+/// Counts the lines of a chunk as it is read, to end it after a maximum number of lines
+pub(crate) struct LineLimit {
+    max_lines: u64,
+    lines: u64,
+}
+
+impl LineLimit {
+    fn new(max_lines: u64) -> Self {
+        LineLimit { max_lines, lines: 0 }
+    }
+
+    /// Scans the next `data` of the chunk, returning the end of the chunk within `data` once it holds `max_lines` lines
+    pub(crate) fn scan(&mut self, data: &[u8]) -> Option<usize> {
+        for (i, _) in data.iter().enumerate().filter(|(_, &b)| b == b'\n') {
+            self.lines += 1;
+            if self.lines == self.max_lines {
+                return Some(i + 1);
+            }
+        }
+        None
+    }
+}
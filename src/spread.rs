@@ -0,0 +1,213 @@
+// src/spread.rs
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use anyhow::Result;
+
+use crate::{available_space, ChunkEncoder, ChunkSink, ChunkSource, LocalDir, Placement, SplitInfo};
+
+/// How a [`SpreadSink`] picks the directory each chunk goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpreadPolicy {
+    /// Each directory in turn, so that they fill up evenly
+    #[default]
+    RoundRobin,
+    /// The directory with the most free space at the time (see [`available_space`]), so that drives of different
+    /// sizes fill up together. Where free space cannot be told, the directories are taken in turn.
+    FreeSpace,
+}
+
+impl SpreadPolicy {
+    /// All supported policies, in the order they are offered to users
+    pub const ALL: &'static [SpreadPolicy] = &[SpreadPolicy::RoundRobin, SpreadPolicy::FreeSpace];
+
+    /// Short lowercase name of the policy (e.g., "free-space")
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpreadPolicy::RoundRobin => "round-robin",
+            SpreadPolicy::FreeSpace => "free-space",
+        }
+    }
+}
+
+impl fmt::Display for SpreadPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SpreadPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SpreadPolicy::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!(
+                "Unknown spread policy '{}' (expected one of: {})",
+                s,
+                SpreadPolicy::ALL.iter().map(|p| p.name()).collect::<Vec<_>>().join(", ")
+            ))
+    }
+}
+
+/// Chunk storage spread across several local directories (e.g., on different drives), so that a file larger
+/// than any one of them can still be split
+///
+/// Each chunk goes to the directory [`SpreadPolicy`] picks, which the split records with it (see
+/// [`crate::ChunkInfo::destination`]); everything else (info file, journal, parity chunks, ...) stays in the
+/// first directory. Reading looks for a chunk where it was recorded first, then in every directory, so chunks
+/// moved between the directories are still found.
+#[derive(Debug)]
+pub struct SpreadSink {
+    roots: Vec<PathBuf>,
+    dirs: Vec<LocalDir>,
+    policy: SpreadPolicy,
+    /// Index into `dirs` of every chunk placed so far
+    placed: Mutex<HashMap<String, usize>>,
+    /// Number of chunks placed in turn so far
+    turn: Mutex<usize>,
+}
+
+impl SpreadSink {
+    /// Spreads chunks across `dirs`, the first of which holds everything else
+    pub fn new<P: Into<PathBuf>>(dirs: impl IntoIterator<Item = P>, policy: SpreadPolicy) -> Result<Self> {
+        let roots: Vec<PathBuf> = dirs.into_iter().map(Into::into).collect();
+        if roots.is_empty() {
+            return Err(anyhow::anyhow!("Chunks cannot be spread across no directories"));
+        }
+        Ok(SpreadSink {
+            dirs: roots.iter().map(LocalDir::new).collect(),
+            roots,
+            policy,
+            placed: Mutex::new(HashMap::new()),
+            turn: Mutex::new(0),
+        })
+    }
+
+    /// Reads the chunks of `file_info` where its split spread them, with the info file in `input_root_dir` (see
+    /// [`SplitInfo::destinations`]); a split set that was not spread is read from `input_root_dir` alone
+    pub fn for_split_set(input_root_dir: impl Into<PathBuf>, file_info: &SplitInfo) -> Self {
+        let roots: Vec<PathBuf> = std::iter::once(input_root_dir.into())
+            .chain(file_info.destinations.iter().skip(1).map(PathBuf::from))
+            .collect();
+        let placed = file_info.chunks.iter()
+            .filter_map(|chunk| chunk.destination.filter(|&index| index < roots.len()).map(|index| (file_info.chunk_name(chunk), index)))
+            .collect();
+        SpreadSink {
+            dirs: roots.iter().map(LocalDir::new).collect(),
+            roots,
+            policy: SpreadPolicy::default(),
+            placed: Mutex::new(placed),
+            turn: Mutex::new(0),
+        }
+    }
+
+    /// Index of the directory holding `name`: where it was placed if it is there, else the first directory that
+    /// holds it, else where it was placed or the first directory
+    fn index_of(&self, name: &str) -> usize {
+        let placed = self.placed.lock().unwrap().get(name).copied();
+        if let Some(index) = placed.filter(|&index| self.dirs[index].contains(name).unwrap_or(false)) {
+            return index;
+        }
+        self.holding(name).or(placed).unwrap_or(0)
+    }
+
+    /// Index of the first directory that holds `name`, if any
+    fn holding(&self, name: &str) -> Option<usize> {
+        self.dirs.iter().position(|dir| dir.contains(name).unwrap_or(false))
+    }
+
+    fn dir(&self, name: &str) -> &LocalDir {
+        &self.dirs[self.index_of(name)]
+    }
+
+    /// Picks the directory for a new chunk
+    fn choose(&self) -> Result<usize> {
+        if self.policy == SpreadPolicy::FreeSpace {
+            let mut most_free = None;
+            for (index, root) in self.roots.iter().enumerate() {
+                if let Some(available) = available_space(root)? {
+                    if most_free.is_none_or(|(_, most)| available > most) {
+                        most_free = Some((index, available));
+                    }
+                }
+            }
+            if let Some((index, _)) = most_free {
+                return Ok(index);
+            }
+        }
+        let mut turn = self.turn.lock().unwrap();
+        let index = *turn % self.dirs.len();
+        *turn += 1;
+        Ok(index)
+    }
+}
+
+impl ChunkSink for SpreadSink {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.dir(name).put(name, data)
+    }
+
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        self.dir(name).create(name)
+    }
+
+    fn copy_from(&self, name: &str, file: &File, offset: u64, len: u64) -> Result<bool> {
+        self.dir(name).copy_from(name, file, offset, len)
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.holding(name).is_some())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.dir(name).remove(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        ChunkSink::describe(self.dir(name), name)
+    }
+
+    fn sync(&self, name: &str) -> Result<()> {
+        self.dir(name).sync(name)
+    }
+
+    /// A chunk stored before (e.g., by a resumed split) stays where it is; any other goes where the policy says
+    fn place(&self, name: &str) -> Result<Placement> {
+        if let Some(&index) = self.placed.lock().unwrap().get(name) {
+            return Ok(Placement { destination: Some(index), room: None });
+        }
+        let index = match self.holding(name) {
+            Some(index) => index,
+            None => self.choose()?,
+        };
+        self.placed.lock().unwrap().insert(name.to_string(), index);
+        Ok(Placement { destination: Some(index), room: None })
+    }
+
+    fn destinations(&self) -> Vec<String> {
+        self.roots.iter()
+            .map(|root| std::path::absolute(root).unwrap_or_else(|_| root.clone()).display().to_string())
+            .collect()
+    }
+}
+
+impl ChunkSource for SpreadSink {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        self.dir(name).open(name)
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        self.dir(name).size(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        ChunkSource::describe(self.dir(name), name)
+    }
+}
@@ -0,0 +1,518 @@
+// src/storage.rs
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use anyhow::{Result, Context};
+
+use crate::ChunkEncoder;
+
+/// Destination the split sub-files and info file are written to
+///
+/// Implement this trait to store split output somewhere other than the local filesystem.
+/// Names are relative, `/`-separated keys such as `"my_file_parts/my_file-001"`.
+pub trait ChunkSink: Send + Sync {
+    /// Stores `data` under `name`, replacing anything already stored there
+    fn put(&self, name: &str, data: &[u8]) -> Result<()>;
+
+    /// Opens `name` for writing, replacing anything already stored there, so that a chunk can be stored while
+    /// it is encoded instead of being held in memory; [`ChunkEncoder::finish`] completes it. The default collects
+    /// the whole chunk in memory and stores it with [`ChunkSink::put`] once finished, so storage that can take
+    /// data piece by piece should override it.
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        Ok(Box::new(BufferedEntry { sink: self, name: name.to_string(), data: Vec::new() }))
+    }
+
+    /// Stores the `len` bytes of `file` starting at `offset` under `name`, replacing anything already stored there,
+    /// and returns `true`; or returns `false` without storing anything if this storage cannot copy from a local
+    /// file. Used to store uncompressed chunks without passing their bytes through the program; the default
+    /// returns `false`, so such chunks are written like any other.
+    fn copy_from(&self, _name: &str, _file: &File, _offset: u64, _len: u64) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Whether something is already stored under `name`. Used to skip chunks a content-addressed pool already
+    /// holds; the default answers `false`, so such chunks are simply stored again.
+    fn contains(&self, _name: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Deletes what is stored under `name`, if anything. Used to clean up after a split (e.g., its resume
+    /// journal); the default fails, so such files are left behind.
+    fn remove(&self, name: &str) -> Result<()> {
+        Err(anyhow::anyhow!("Deleting is not supported by this storage: {}", self.describe(name)))
+    }
+
+    /// Human-readable location of `name`, used in messages (e.g., a full path or URL)
+    fn describe(&self, name: &str) -> String {
+        name.to_string()
+    }
+
+    /// Makes what is stored under `name` durable, so that it survives a crash or a removed drive (see
+    /// [`crate::SplitOptions::fsync`]); the default does nothing, for storage that is durable once it accepts
+    /// something.
+    fn sync(&self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Picks where the chunk `name`, about to be stored, goes if this storage spreads chunks across several
+    /// destinations (see [`crate::SpreadSink`]), and how many bytes it may take up there (see
+    /// [`Placement::room`]). The default keeps every chunk in one place, with no limit on its size.
+    fn place(&self, _name: &str) -> Result<Placement> {
+        Ok(Placement::default())
+    }
+
+    /// Whether [`ChunkSink::place`] may give a chunk less room than the split's size limit, so that the split has
+    /// to place each chunk before reading it. The default answers `false`.
+    fn limits_room(&self) -> bool {
+        false
+    }
+
+    /// Locations the chunks are spread across (see [`ChunkSink::place`]), the first holding everything else; the
+    /// default, for storage that keeps everything in one place, is empty
+    fn destinations(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Where a chunk goes, as picked by [`ChunkSink::place`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Placement {
+    /// Index of the chunk's destination among [`ChunkSink::destinations`], which the split records with the chunk,
+    /// or `None` for storage that keeps every chunk in one place
+    pub destination: Option<usize>,
+    /// Most bytes of the file the chunk may hold there, if fewer than the split's size limit (e.g., to fill what is
+    /// left of a volume, see [`crate::VolumeSink`]); the split then ends the chunk early. Only storage that
+    /// [limits room](ChunkSink::limits_room) sets it.
+    pub room: Option<u64>,
+}
+
+/// Entry written through the default [`ChunkSink::create`], stored in one piece when finished
+struct BufferedEntry<'a, S: ChunkSink + ?Sized> {
+    sink: &'a S,
+    name: String,
+    data: Vec<u8>,
+}
+
+impl<S: ChunkSink + ?Sized> Write for BufferedEntry<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: ChunkSink + ?Sized> ChunkEncoder for BufferedEntry<'_, S> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.sink.put(&self.name, &self.data)
+    }
+}
+
+/// File being written by [`LocalDir::create`]
+struct FileEntry(BufWriter<File>);
+
+impl Write for FileEntry {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl ChunkEncoder for FileEntry {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+}
+
+/// Source the split sub-files are read from when restoring
+///
+/// Implement this trait to restore from somewhere other than the local filesystem.
+/// Names are the same relative keys a [`ChunkSink`] was given.
+pub trait ChunkSource: Send + Sync {
+    /// Opens the data stored under `name` for reading
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>>;
+
+    /// Size of the data stored under `name`, or `None` if nothing is. Used to check the chunks a resumed split
+    /// keeps; the default reads the data to count it.
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        match self.open(name) {
+            Ok(mut reader) => Ok(Some(io::copy(&mut reader, &mut io::sink())?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Human-readable location of `name`, used in messages (e.g., a full path or URL)
+    fn describe(&self, name: &str) -> String {
+        name.to_string()
+    }
+}
+
+/// A storage backend that can both store and serve chunks
+pub trait ChunkStore: ChunkSink + ChunkSource {}
+
+impl<T: ChunkSink + ChunkSource> ChunkStore for T {}
+
+/// Opens the chunk storage for `location`
+///
+/// Supports `s3://bucket/prefix` (with the `s3` cargo feature), `sftp://user@host/path` (with the `sftp`
+/// cargo feature), `ftp://user@host/path` (with the `ftp` cargo feature) and, with the `cloud` cargo feature, every other URL scheme handled by [`crate::ObjectStorage`]
+/// (`gcs://`, `b2://`, `oss://`, ...). Anything without a `scheme://` prefix is a local directory.
+pub fn open_store(location: &str) -> Result<Box<dyn ChunkStore>> {
+    match location.split_once("://") {
+        #[cfg(feature = "s3")]
+        Some(("s3", _)) => Ok(Box::new(crate::S3Store::from_url(location)?)),
+        #[cfg(feature = "sftp")]
+        Some(("sftp", _)) => Ok(Box::new(crate::SftpStore::from_url(location)?)),
+        #[cfg(feature = "ftp")]
+        Some(("ftp", _)) => Ok(Box::new(crate::FtpStore::from_url(location)?)),
+        #[cfg(feature = "cloud")]
+        Some(_) => Ok(Box::new(crate::ObjectStorage::from_url(location)?)),
+        #[cfg(not(feature = "cloud"))]
+        Some((scheme, _)) => Err(anyhow::anyhow!(
+            "Unsupported storage location '{}': '{}://' is not supported by this build",
+            location,
+            scheme
+        )),
+        None => Ok(Box::new(LocalDir::new(location))),
+    }
+}
+
+/// Chunk storage in a local directory, the default for [`crate::split_single_file`] and [`crate::restore_single_file`]
+#[derive(Debug, Clone)]
+pub struct LocalDir {
+    root: PathBuf,
+}
+
+impl LocalDir {
+    /// Stores chunks below `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalDir { root: root.into() }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+}
+
+impl ChunkSink for LocalDir {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create subdirectory: {}", parent.display()))?;
+        }
+        fs::write(&path, data)?;
+        Ok(())
+    }
+
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create subdirectory: {}", parent.display()))?;
+        }
+        Ok(Box::new(FileEntry(BufWriter::new(File::create(&path)?))))
+    }
+
+    fn copy_from(&self, name: &str, mut file: &File, offset: u64, len: u64) -> Result<bool> {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create subdirectory: {}", parent.display()))?;
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        // Between two files, Linux copies in the kernel (copy_file_range), and file systems like Btrfs and XFS
+        // then share the data between both files (a reflink) instead of writing it again
+        let copied = io::copy(&mut file.take(len), &mut File::create(&path)?)?;
+        if copied != len {
+            return Err(anyhow::anyhow!("Expected to copy {} bytes, but the file ended after {}", len, copied));
+        }
+        Ok(true)
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.path(name).is_file())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        match fs::remove_file(self.path(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.path(name).display().to_string()
+    }
+
+    /// Flushes the file to the disk, then the directory holding it, so that its entry is on the disk as well
+    fn sync(&self, name: &str) -> Result<()> {
+        let path = self.path(name);
+        File::open(&path)
+            .and_then(|file| file.sync_all())
+            .with_context(|| format!("Failed to sync file to disk: {}", path.display()))?;
+        // Windows cannot open directories as files, and flushes directory entries with the files
+        #[cfg(unix)]
+        if let Some(parent) = path.parent() {
+            File::open(parent)
+                .and_then(|dir| dir.sync_all())
+                .with_context(|| format!("Failed to sync directory to disk: {}", parent.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl ChunkSource for LocalDir {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(BufReader::new(File::open(self.path(name))?)))
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        match fs::metadata(self.path(name)) {
+            Ok(metadata) => Ok(metadata.is_file().then_some(metadata.len())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.path(name).display().to_string()
+    }
+}
+
+/// Remembers where chunks were stored, so that identical chunks of later splits are not stored again
+///
+/// Pass the same index to every split into one [`ChunkSink`] (see [`crate::SplitOptions::dedup`]). A chunk whose
+/// stored bytes match a chunk stored earlier is then recorded as a reference to that chunk instead.
+#[derive(Debug, Default)]
+pub struct DedupIndex {
+    /// Storage key of the first chunk stored with each SHA-256 digest
+    stored: Mutex<HashMap<String, String>>,
+}
+
+impl DedupIndex {
+    /// Creates an empty index
+    pub fn new() -> Self {
+        DedupIndex::default()
+    }
+
+    /// Storage key of an earlier chunk whose stored bytes have the SHA-256 `digest`
+    pub(crate) fn get(&self, digest: &str) -> Option<String> {
+        self.stored.lock().unwrap().get(digest).cloned()
+    }
+
+    pub(crate) fn insert(&self, digest: &str, name: &str) {
+        self.stored.lock().unwrap().entry(digest.to_string()).or_insert_with(|| name.to_string());
+    }
+}
+
+/// Chunk storage held in memory, e.g. for tests or for handing chunks to another component
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Names of everything stored, in sorted order
+    pub fn names(&self) -> Vec<String> {
+        self.entries.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Returns a copy of the data stored under `name`
+    pub fn get(&self, name: &str) -> Option<Vec<u8>> {
+        self.entries.lock().unwrap().get(name).cloned()
+    }
+}
+
+impl ChunkSink for MemoryStore {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.entries.lock().unwrap().insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.entries.lock().unwrap().contains_key(name))
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.entries.lock().unwrap().remove(name);
+        Ok(())
+    }
+}
+
+impl ChunkSource for MemoryStore {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let data = self.get(name).ok_or_else(|| anyhow::anyhow!("No such entry: {}", name))?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        Ok(self.entries.lock().unwrap().get(name).map(|data| data.len() as u64))
+    }
+}
+
+/// Sink that remembers everything stored through it in the sink it wraps, so that it can be removed again
+/// (e.g., when a split is cancelled)
+pub(crate) struct TrackingSink<'a> {
+    inner: &'a dyn ChunkSink,
+    stored: Mutex<BTreeSet<String>>,
+}
+
+impl<'a> TrackingSink<'a> {
+    pub(crate) fn new(inner: &'a dyn ChunkSink) -> Self {
+        TrackingSink { inner, stored: Mutex::new(BTreeSet::new()) }
+    }
+
+    /// Removes everything stored so far; storage that cannot delete keeps it
+    pub(crate) fn remove_stored(self) {
+        for name in self.stored.into_inner().unwrap() {
+            let _ = self.inner.remove(&name);
+        }
+    }
+
+    fn track(&self, name: &str) {
+        self.stored.lock().unwrap().insert(name.to_string());
+    }
+}
+
+impl ChunkSink for TrackingSink<'_> {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.track(name);
+        self.inner.put(name, data)
+    }
+
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        // Tracked before anything is written, as a partly written entry is removed as well
+        self.track(name);
+        self.inner.create(name)
+    }
+
+    fn copy_from(&self, name: &str, file: &File, offset: u64, len: u64) -> Result<bool> {
+        self.track(name);
+        self.inner.copy_from(name, file, offset, len)
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        self.inner.contains(name)
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.stored.lock().unwrap().remove(name);
+        self.inner.remove(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.inner.describe(name)
+    }
+
+    fn sync(&self, name: &str) -> Result<()> {
+        self.inner.sync(name)
+    }
+
+    fn place(&self, name: &str) -> Result<Placement> {
+        self.inner.place(name)
+    }
+
+    fn limits_room(&self) -> bool {
+        self.inner.limits_room()
+    }
+
+    fn destinations(&self) -> Vec<String> {
+        self.inner.destinations()
+    }
+}
+
+/// Sink that makes everything stored through it in the sink it wraps durable before reporting it stored (see
+/// [`ChunkSink::sync`])
+pub(crate) struct SyncedSink<'a> {
+    inner: &'a dyn ChunkSink,
+}
+
+impl<'a> SyncedSink<'a> {
+    pub(crate) fn new(inner: &'a dyn ChunkSink) -> Self {
+        SyncedSink { inner }
+    }
+}
+
+impl ChunkSink for SyncedSink<'_> {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.inner.put(name, data)?;
+        self.inner.sync(name)
+    }
+
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        Ok(Box::new(SyncedEntry { inner: self.inner.create(name)?, sink: self.inner, name: name.to_string() }))
+    }
+
+    fn copy_from(&self, name: &str, file: &File, offset: u64, len: u64) -> Result<bool> {
+        let copied = self.inner.copy_from(name, file, offset, len)?;
+        if copied {
+            self.inner.sync(name)?;
+        }
+        Ok(copied)
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        self.inner.contains(name)
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.inner.remove(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        self.inner.describe(name)
+    }
+
+    fn sync(&self, name: &str) -> Result<()> {
+        self.inner.sync(name)
+    }
+
+    fn place(&self, name: &str) -> Result<Placement> {
+        self.inner.place(name)
+    }
+
+    fn limits_room(&self) -> bool {
+        self.inner.limits_room()
+    }
+
+    fn destinations(&self) -> Vec<String> {
+        self.inner.destinations()
+    }
+}
+
+/// Entry written through [`SyncedSink::create`], made durable when finished
+struct SyncedEntry<'a> {
+    inner: Box<dyn ChunkEncoder + 'a>,
+    sink: &'a dyn ChunkSink,
+    name: String,
+}
+
+impl Write for SyncedEntry<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl ChunkEncoder for SyncedEntry<'_> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.inner.finish()?;
+        self.sink.sync(&self.name)
+    }
+}
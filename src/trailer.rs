@@ -0,0 +1,58 @@
+// src/trailer.rs
+use std::io::{Read, Seek, SeekFrom};
+use anyhow::{Context, Result};
+
+use crate::manifest::{check_content_checksum, with_content_checksum};
+use crate::SplitInfo;
+
+/// Last bytes of a chunk file that holds its split set's info file behind its own bytes
+const MAGIC: &[u8; 8] = b"FSPINFO1";
+
+/// Size of what follows the info file: its length and the magic bytes
+const FOOTER_SIZE: u64 = 8 + MAGIC.len() as u64;
+
+/// Largest info file accepted when reading a trailer
+const MAX_INFO_SIZE: u64 = 1 << 30;
+
+/// Encodes `split_info` as the trailer appended to its last chunk (see [`crate::SplitOptions::info_trailer`]): the
+/// info file's JSON, its little-endian `u64` length and the magic bytes "FSPINFO1"
+pub(crate) fn encode_info_trailer(split_info: &SplitInfo) -> Result<Vec<u8>> {
+    let mut trailer = serde_json::to_vec(&with_content_checksum(split_info)?)?;
+    let len = trailer.len() as u64;
+    trailer.extend_from_slice(&len.to_le_bytes());
+    trailer.extend_from_slice(MAGIC);
+    Ok(trailer)
+}
+
+/// Reads the info file stored at the end of a split set's last chunk file (see [`crate::SplitOptions::info_trailer`])
+///
+/// Returns `None` if `reader` does not end with an info file trailer; fails if it does but the info file cannot
+/// be read. Only the end of the chunk file is read.
+pub fn read_info_trailer<R: Read + Seek>(reader: &mut R) -> Result<Option<SplitInfo>> {
+    let size = reader.seek(SeekFrom::End(0)).context("Failed to read chunk file")?;
+    if size < FOOTER_SIZE {
+        return Ok(None);
+    }
+    let mut footer = [0u8; FOOTER_SIZE as usize];
+    reader.seek(SeekFrom::Start(size - FOOTER_SIZE))
+        .and_then(|_| reader.read_exact(&mut footer))
+        .context("Failed to read chunk file")?;
+    if &footer[8..] != MAGIC {
+        return Ok(None);
+    }
+    let len = u64::from_le_bytes(footer[..8].try_into().unwrap());
+    if len > MAX_INFO_SIZE || len > size - FOOTER_SIZE {
+        return Err(anyhow::anyhow!("Invalid info file trailer: it claims {} bytes", len));
+    }
+
+    let mut json = vec![0u8; len as usize];
+    reader.seek(SeekFrom::Start(size - FOOTER_SIZE - len))
+        .and_then(|_| reader.read_exact(&mut json))
+        .context("Failed to read chunk file")?;
+    let split_info: SplitInfo = serde_json::from_slice(&json).context("Failed to parse the info file trailer")?;
+    if !split_info.info_trailer {
+        return Err(anyhow::anyhow!("Invalid info file trailer: the info file does not record it"));
+    }
+    check_content_checksum(&split_info)?;
+    Ok(Some(split_info))
+}
@@ -0,0 +1,182 @@
+// src/tree.rs
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+
+use crate::{directory_split_name, split_file_in, ChunkSink, LocalDir, ProgressMeter, SplitEvent, SplitOptions};
+
+/// Index of the split sets made of every file in a directory tree, so that the tree restores in one go
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TreeIndex {
+    /// Name of the directory that was split (e.g., "photos"); all paths below start with it
+    pub directory: String,
+    /// Every file of the tree, in path order
+    pub files: Vec<TreeFile>,
+    /// Directories without any entries, to recreate as well (e.g., "photos/empty")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub empty_dirs: Vec<String>,
+    /// Symbolic links, to recreate as links
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symlinks: Vec<TreeLink>,
+}
+
+/// One file of a [`TreeIndex`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TreeFile {
+    /// Path of the file, starting with the tree's directory (e.g., "photos/2024/img.jpg")
+    pub path: String,
+    /// Key of the file's info file, relative to the split output directory
+    /// (e.g., "photos/2024/img.jpg_parts/img.jpg.json", see [`crate::SplitInfo::info_name`])
+    pub info_file: String,
+    /// Size of the file in bytes
+    pub size: u64,
+}
+
+/// One symbolic link of a [`TreeIndex`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TreeLink {
+    /// Path of the link, starting with the tree's directory (e.g., "photos/latest")
+    pub path: String,
+    /// What the link points to, as stored in the link (e.g., "2024/img.jpg")
+    pub target: String,
+}
+
+impl TreeIndex {
+    /// Key of the index file, relative to the split output directory (e.g., "photos.index.json")
+    pub fn index_name(&self) -> String {
+        format!("{}.index.json", self.directory)
+    }
+}
+
+/// Splits every file in a directory tree on its own, below `output_root_dir`
+///
+/// Works like [`split_tree_to_sink`] with the chunks stored on the local disk.
+pub fn split_tree(
+    dir: &Path,
+    size_limit: u64,
+    output_root_dir: &Path,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<TreeIndex> {
+    split_tree_to_sink(dir, size_limit, &LocalDir::new(output_root_dir), options, event_callback)
+}
+
+/// Splits every file in a directory tree on its own into `sink`, and saves an index of them
+///
+/// Unlike [`crate::split_directory_to_sink`], which splits the tree as a single tar stream, every file gets its
+/// own split set, stored at the file's path in the tree (e.g., "photos/2024/img.jpg_parts/"), whose info file
+/// records that path (see [`crate::SplitInfo::path`]). Single files can then be restored (or re-split, e.g. with
+/// [`SplitOptions::skip_unchanged`]) on their own. The index (see [`TreeIndex::index_name`]) lists every info
+/// file, to restore the whole tree with. Symbolic links are listed in the index rather than followed, unless
+/// [`SplitOptions::follow_symlinks`] is set, in which case links to files are split like the files they point to.
+/// Other special files (e.g., sockets) are skipped. [`SplitEvent::Progress`] reports the bytes of the whole tree.
+pub fn split_tree_to_sink(
+    dir: &Path,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<TreeIndex> {
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("Not a directory: {}", dir.display()));
+    }
+    let directory = directory_split_name(dir)?;
+    let mut files = Vec::new();
+    let mut empty_dirs = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut skipped = Vec::new();
+    walk(dir, &directory, options.follow_symlinks, &mut files, &mut empty_dirs, &mut symlinks, &mut skipped)?;
+    let event_callback: Option<Arc<dyn Fn(SplitEvent) + Send + Sync>> = event_callback.map(Arc::from);
+    if let Some(cb) = &event_callback {
+        for path in &skipped {
+            cb(SplitEvent::Message(format!("Skipping '{}', which is neither a file, a directory nor a symbolic link", path)));
+        }
+    }
+
+    let total_size = files.iter().map(|(_, file)| file.size).sum();
+    let _span = tracing::info_span!("split_tree", directory = %directory, files = files.len(), size = total_size).entered();
+    let meter = Arc::new(Mutex::new(ProgressMeter::new()));
+    let mut done = 0;
+    for (file_path, file) in &mut files {
+        let (parent, _) = file.path.rsplit_once('/').expect("tree paths start with the directory");
+        // Progress through the file is progress through the tree
+        let file_events = event_callback.clone().map(|cb| {
+            let meter = meter.clone();
+            Box::new(move |event| match event {
+                SplitEvent::Progress { bytes, .. } => {
+                    let bytes = done + bytes;
+                    let throughput = meter.lock().unwrap().update(bytes, total_size);
+                    cb(SplitEvent::Progress { bytes, total: total_size, throughput })
+                }
+                event => cb(event),
+            }) as Box<dyn Fn(SplitEvent) + Send + Sync>
+        });
+        let info = split_file_in(file_path, &format!("{}/", parent), size_limit, sink, options, file_events)
+            .with_context(|| format!("Failed to split '{}'", file.path))?;
+        file.info_file = info.info_name();
+        done += file.size;
+    }
+
+    let index = TreeIndex {
+        directory,
+        files: files.into_iter().map(|(_, file)| file).collect(),
+        empty_dirs,
+        symlinks,
+    };
+    let index_name = index.index_name();
+    sink.put(&index_name, serde_json::to_string_pretty(&index)?.as_bytes())
+        .with_context(|| format!("Failed to save tree index file: {}", sink.describe(&index_name)))?;
+    if options.fsync {
+        sink.sync(&index_name)?;
+    }
+    tracing::info!(index = %index_name, files = index.files.len(), "tree split finished");
+    if let Some(cb) = &event_callback {
+        cb(SplitEvent::Message(format!("Index of {} files saved to: {}", index.files.len(), sink.describe(&index_name))));
+    }
+    Ok(index)
+}
+
+/// Collects the regular files below `dir` (whose path in the tree is `path`) in path order, along with the
+/// empty directories, the symbolic links (unless links to files are followed) and the special files
+fn walk(
+    dir: &Path,
+    path: &str,
+    follow_symlinks: bool,
+    files: &mut Vec<(PathBuf, TreeFile)>,
+    empty_dirs: &mut Vec<String>,
+    symlinks: &mut Vec<TreeLink>,
+    skipped: &mut Vec<String>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    if entries.is_empty() {
+        empty_dirs.push(path.to_string());
+    }
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().into_string()
+            .map_err(|name| anyhow::anyhow!("Invalid filename: {}", Path::new(&name).display()))?;
+        let entry_path = format!("{}/{}", path, name);
+        let file_type = entry.file_type()?;
+        // Links to directories are never followed, as they may lead back up the tree
+        let links_to_file = file_type.is_symlink() && follow_symlinks && entry.path().is_file();
+        if file_type.is_dir() {
+            walk(&entry.path(), &entry_path, follow_symlinks, files, empty_dirs, symlinks, skipped)?;
+        } else if file_type.is_file() || links_to_file {
+            let size = fs::metadata(entry.path())?.len();
+            let info_file = format!("{0}_parts/{1}.json", entry_path, name);
+            files.push((entry.path(), TreeFile { path: entry_path, info_file, size }));
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(entry.path())?.into_os_string().into_string()
+                .map_err(|target| anyhow::anyhow!("Invalid link target of '{}': {}", entry_path, Path::new(&target).display()))?;
+            symlinks.push(TreeLink { path: entry_path, target });
+        } else {
+            skipped.push(entry_path);
+        }
+    }
+    Ok(())
+}
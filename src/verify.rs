@@ -0,0 +1,305 @@
+// src/verify.rs
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+use anyhow::{Result, Context};
+use rayon::prelude::*;
+
+use crate::failure::is_not_found;
+use crate::parity::{parity_checksum, parity_chunk_name};
+use crate::{
+    chunk_checksum_mismatch, chunk_layout, decode_stored_chunk, parse_checksum, resolve_chunk_readers, ChunkCodec,
+    ChunkSource, Decryption, RestoreOptions, SplitInfo,
+};
+
+/// What is wrong with one chunk file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkProblem {
+    /// The chunk file does not exist
+    Missing,
+    /// The chunk file could not be read, decrypted or decompressed
+    Unreadable(String),
+    /// The chunk file does not have its recorded size
+    SizeMismatch { expected: u64, actual: u64 },
+    /// The chunk's content does not match its recorded checksum
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for ChunkProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkProblem::Missing => f.write_str("missing"),
+            ChunkProblem::Unreadable(reason) => write!(f, "unreadable ({})", reason),
+            ChunkProblem::SizeMismatch { expected, actual } => {
+                write!(f, "holds {} bytes instead of the recorded {}", actual, expected)
+            }
+            ChunkProblem::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum mismatch (expected {}, actual {})", expected, actual)
+            }
+        }
+    }
+}
+
+/// A damaged data or parity chunk found by [`verify_split_set`]
+#[derive(Debug, Clone)]
+pub struct ChunkIssue {
+    /// Filename of the chunk (e.g., "my_file-001")
+    pub chunk_filename: String,
+    /// Whether this is a parity chunk rather than a data chunk
+    pub parity: bool,
+    pub problem: ChunkProblem,
+}
+
+/// Outcome of [`verify_split_set`]
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// Number of data chunks checked
+    pub chunks_checked: usize,
+    /// Number of parity chunks checked
+    pub parity_chunks_checked: usize,
+    /// Every damaged chunk, data chunks first
+    pub issues: Vec<ChunkIssue>,
+    /// Whether the chunks together match the checksum of the original file, or `None` if a damaged chunk
+    /// kept it from being checked
+    pub file_checksum_matches: Option<bool>,
+}
+
+impl VerifyReport {
+    /// Whether every chunk is intact and the chunks add up to the original file
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty() && self.file_checksum_matches != Some(false)
+    }
+
+    /// Whether the damage can be undone with [`crate::repair_split_set`], i.e., there are no more damaged chunks
+    /// than parity chunks
+    pub fn is_repairable(&self) -> bool {
+        !self.issues.is_empty() && self.issues.len() <= self.parity_chunks_checked
+    }
+}
+
+/// Checks every chunk of a split set without restoring it
+///
+/// The split info must be consistent (see [`SplitInfo::validate`]). Every data chunk must exist, have its recorded
+/// size, decrypt and decompress, and match its recorded checksum; if all do, their content is checked against
+/// the checksum of the original file. Parity chunks are checked against their size and SHA-256. Damaged chunks
+/// are collected in the report rather than returned as errors.
+///
+/// `options`: Codec, decryption and threading settings (see [`RestoreOptions`]); `threads` chunks are checked at once.
+/// `progress_callback`: Optional callback for reporting progress (current_bytes, total_bytes).
+/// `message_callback`: Optional callback for reporting messages (message string).
+pub fn verify_split_set(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    options: &RestoreOptions,
+    progress_callback: Option<Box<dyn Fn(u64, u64) + Send + Sync + 'static>>,
+    message_callback: Option<Box<dyn Fn(String) + Send + Sync + 'static>>,
+) -> Result<VerifyReport> {
+    file_info.validate()?;
+    let (codec, decryption) = resolve_chunk_readers(file_info, options)?;
+    // Each worker holds the whole chunk it checks
+    let threads = options.workers(file_info.chunk_limit);
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+        .context("Failed to start worker threads")?;
+
+    if let Some(cb) = &message_callback {
+        cb(format!("Verifying '{}'", file_info.original_filename));
+    }
+
+    // The original file's checksum is computed over the chunks in order, as long as all of them are intact
+    let (algorithm, expected_digest) = parse_checksum(&file_info.original_checksum, file_info.checksum_algorithm)?;
+    let mut hasher = Some(algorithm.hasher());
+    let mut report = VerifyReport::default();
+    let mut total_checked = 0u64;
+    for start in (0..file_info.chunks.len()).step_by(threads) {
+        let batch = &file_info.chunks[start..(start + threads).min(file_info.chunks.len())];
+        let results: Vec<_> = pool.install(|| batch.par_iter()
+            .enumerate()
+            .map(|(offset, _)| check_chunk(file_info, source, start + offset, codec, decryption, options))
+            .collect());
+        for (chunk_info, result) in batch.iter().zip(results) {
+            report.chunks_checked += 1;
+            match result {
+                Ok(data) => {
+                    if let Some(hasher) = &mut hasher {
+                        hasher.update(&data);
+                    }
+                    total_checked += data.len() as u64;
+                }
+                Err(problem) => {
+                    hasher = None;
+                    total_checked += chunk_info.original_size.unwrap_or(0);
+                    report.issues.push(ChunkIssue { chunk_filename: chunk_info.chunk_filename.clone(), parity: false, problem });
+                }
+            }
+            if let Some(cb) = &progress_callback {
+                cb(total_checked.min(file_info.original_file_size), file_info.original_file_size);
+            }
+        }
+    }
+    report.file_checksum_matches = hasher.map(|hasher| hasher.finalize() == expected_digest);
+
+    if let Some(parity) = &file_info.parity {
+        for parity_chunk in &parity.chunks {
+            report.parity_chunks_checked += 1;
+            let name = parity_chunk_name(file_info, parity_chunk);
+            let problem = match read_chunk_file(source, &name, options) {
+                Err(problem) => Some(problem),
+                Ok(data) if data.len() as u64 != parity.shard_size => {
+                    Some(ChunkProblem::SizeMismatch { expected: parity.shard_size, actual: data.len() as u64 })
+                }
+                Ok(data) => Some(parity_checksum(&data)).filter(|actual| *actual != parity_chunk.chunk_checksum)
+                    .map(|actual| ChunkProblem::ChecksumMismatch { expected: parity_chunk.chunk_checksum.clone(), actual }),
+            };
+            if let Some(problem) = problem {
+                report.issues.push(ChunkIssue { chunk_filename: parity_chunk.chunk_filename.clone(), parity: true, problem });
+            }
+        }
+    }
+
+    if let Some(cb) = &message_callback {
+        cb(format!("'{}' verification complete", file_info.original_filename));
+    }
+    Ok(report)
+}
+
+/// Reads, decodes and checks the data chunk at `index`, returning its original content
+fn check_chunk(
+    file_info: &SplitInfo,
+    source: &dyn ChunkSource,
+    index: usize,
+    codec: Option<&dyn ChunkCodec>,
+    decryption: Option<&Decryption>,
+    options: &RestoreOptions,
+) -> Result<Vec<u8>, ChunkProblem> {
+    let chunk_info = &file_info.chunks[index];
+    let name = file_info.chunk_name(chunk_info);
+    let mut stored = read_chunk_file(source, &name, options)?;
+    if file_info.has_info_trailer(index) && stored.len() as u64 > chunk_info.chunk_size {
+        stored.truncate(chunk_info.chunk_size as usize);
+    }
+    if stored.len() as u64 != chunk_info.chunk_size {
+        return Err(ChunkProblem::SizeMismatch { expected: chunk_info.chunk_size, actual: stored.len() as u64 });
+    }
+    let data = decode_stored_chunk(file_info, chunk_info, Box::new(Cursor::new(stored)), &source.describe(&name), codec, decryption)
+        .map_err(|e| ChunkProblem::Unreadable(format!("{:#}", e)))?;
+    match chunk_checksum_mismatch(file_info, chunk_info, &data) {
+        Ok(None) => Ok(data),
+        Ok(Some(actual)) => {
+            let expected = chunk_info.chunk_checksum.clone().unwrap_or_default();
+            let (algorithm, _) = parse_checksum(&expected, file_info.checksum_algorithm)
+                .map_err(|e| ChunkProblem::Unreadable(format!("{:#}", e)))?;
+            Err(ChunkProblem::ChecksumMismatch { expected, actual: algorithm.tag(&actual) })
+        }
+        Err(e) => Err(ChunkProblem::Unreadable(format!("{:#}", e))),
+    }
+}
+
+/// Reads the stored bytes of the chunk file `name`, trying again as [`RestoreOptions::retry`] says if that fails
+fn read_chunk_file(source: &dyn ChunkSource, name: &str, options: &RestoreOptions) -> Result<Vec<u8>, ChunkProblem> {
+    let read = options.retry.run(options.checkpoint(), &source.describe(name), || {
+        let mut data = Vec::new();
+        source.open(name)?.read_to_end(&mut data)?;
+        Ok(data)
+    });
+    match read {
+        Ok(data) => Ok(data),
+        Err(e) if is_not_found(&e) => Err(ChunkProblem::Missing),
+        Err(e) => Err(ChunkProblem::Unreadable(format!("{}: {:#}", source.describe(name), e))),
+    }
+}
+
+/// The part of the original file a chunk holds (e.g., one that differs in a compared file, see [`compare_file`])
+#[derive(Debug, Clone)]
+pub struct ChunkRange {
+    /// Position of the chunk in the split set, starting at 0
+    pub index: usize,
+    /// Filename of the chunk (e.g., "my_file-001")
+    pub chunk_filename: String,
+    /// Offset of the chunk in the original file
+    pub offset: u64,
+    /// Length of the chunk's original content
+    pub length: u64,
+}
+
+/// Outcome of [`compare_file`]
+#[derive(Debug, Clone)]
+pub struct CompareReport {
+    /// Size of the compared file
+    pub size: u64,
+    /// Size of the original file, as recorded in the split info
+    pub expected_size: u64,
+    /// Whether the compared file matches the checksum of the original file
+    pub checksum_matches: bool,
+    /// Chunks whose range of the compared file does not match the chunk's checksum, if requested
+    pub differing_chunks: Option<Vec<ChunkRange>>,
+}
+
+impl CompareReport {
+    /// Whether the compared file is identical to the original file
+    pub fn is_match(&self) -> bool {
+        self.size == self.expected_size && self.checksum_matches
+    }
+}
+
+/// Compares a file (e.g., restored by another tool) with the original file a split set was made from
+///
+/// The file is read once and checked against the recorded size and checksum of the original file. With
+/// `chunk_ranges`, the range of every chunk is also checked against the chunk's checksum, to find where the file
+/// differs; chunks of older info files without a checksum are only reported if the file ends within them.
+pub fn compare_file(file_info: &SplitInfo, path: &Path, chunk_ranges: bool) -> Result<CompareReport> {
+    file_info.validate()?;
+    let (algorithm, expected_digest) = parse_checksum(&file_info.original_checksum, file_info.checksum_algorithm)?;
+    let mut file = BufReader::new(File::open(path)
+        .with_context(|| format!("Failed to open file to compare: {}", path.display()))?);
+    let mut hasher = algorithm.hasher();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    // Hashes `length` bytes of the file (or up to its end) into `hasher` and returns how many there were
+    let mut read_range = |length: u64, chunk_hasher: &mut dyn FnMut(&[u8])| -> Result<u64> {
+        let mut read = 0;
+        while read < length {
+            let want = buffer.len().min((length - read) as usize);
+            let n = file.read(&mut buffer[..want])
+                .with_context(|| format!("Failed to read file to compare: {}", path.display()))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+            chunk_hasher(&buffer[..n]);
+            read += n as u64;
+        }
+        size += read;
+        Ok(read)
+    };
+
+    let mut differing_chunks = Vec::new();
+    for (index, (chunk_info, (offset, length))) in file_info.chunks.iter().zip(chunk_layout(file_info)).enumerate() {
+        let expected = match &chunk_info.chunk_checksum {
+            Some(checksum) if chunk_ranges => Some(parse_checksum(checksum, file_info.checksum_algorithm)?),
+            _ => None,
+        };
+        let mut chunk_hasher = expected.as_ref().map(|(algorithm, _)| algorithm.hasher());
+        let read = read_range(length, &mut |data| {
+            if let Some(chunk_hasher) = &mut chunk_hasher {
+                chunk_hasher.update(data);
+            }
+        })?;
+        let matches = read == length && match (chunk_hasher, expected) {
+            (Some(chunk_hasher), Some((_, expected_digest))) => chunk_hasher.finalize() == expected_digest,
+            _ => true,
+        };
+        if !matches {
+            differing_chunks.push(ChunkRange { index, chunk_filename: chunk_info.chunk_filename.clone(), offset, length });
+        }
+    }
+    // Anything after the last chunk makes the file differ, but belongs to no chunk
+    read_range(u64::MAX, &mut |_| {})?;
+
+    Ok(CompareReport {
+        size,
+        expected_size: file_info.original_file_size,
+        checksum_matches: hasher.finalize() == expected_digest,
+        differing_chunks: chunk_ranges.then_some(differing_chunks),
+    })
+}
@@ -0,0 +1,202 @@
+// src/volume.rs
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use anyhow::Result;
+
+use crate::{available_space, ChunkEncoder, ChunkSink, ChunkSource, LocalDir, Placement, SplitInfo};
+
+/// Free space a volume keeps beyond the bytes of the file its chunks hold: room for what compression or
+/// encryption adds to a chunk, and for the info file that follows the last one
+const VOLUME_HEADROOM: u64 = 1 << 20;
+
+/// Fewest bytes of the file worth starting a chunk with on a volume; one with less room left is full
+const MIN_CHUNK_ROOM: u64 = 1 << 20;
+
+/// Asks for volume `number` (from 1) to be mounted, with at least `needed` bytes free; returns once it is, or
+/// fails to stop
+type NextVolume<'a> = Box<dyn FnMut(usize, u64) -> Result<()> + Send + 'a>;
+
+/// Chunk storage on removable volumes (e.g., USB sticks) mounted one after another at the same directory, each
+/// filled up to its actual free space before the next one is asked for
+///
+/// Before each chunk, the free space of the mounted volume is queried (see [`available_space`]); a chunk that would
+/// not fit is cut short to the room left (see [`Placement::room`]), so that each volume is filled up, and once not
+/// even a short chunk fits, `next_volume` is called until a volume with room is mounted. Each chunk records the
+/// volume it went to (see [`crate::ChunkInfo::destination`]), and everything stored after the last chunk (the info
+/// file) ends up on the last volume. Where free space cannot be told, everything goes on the first volume. Restore
+/// such a split set through a [`VolumeSource`].
+pub struct VolumeSink<'a> {
+    root: PathBuf,
+    dir: LocalDir,
+    size_limit: u64,
+    next_volume: Mutex<NextVolume<'a>>,
+    /// Volume mounted now (from 0), and the number of chunks placed on it so far
+    mounted: Mutex<(usize, usize)>,
+}
+
+impl<'a> VolumeSink<'a> {
+    /// Stores chunks of up to `size_limit` bytes on the volumes mounted at `root`, calling `next_volume` with the
+    /// number of the next volume (from 2) and the free space it needs at least once the mounted one is full
+    pub fn new(
+        root: impl Into<PathBuf>,
+        size_limit: u64,
+        next_volume: impl FnMut(usize, u64) -> Result<()> + Send + 'a,
+    ) -> Self {
+        let root = root.into();
+        VolumeSink {
+            dir: LocalDir::new(&root),
+            root,
+            size_limit,
+            next_volume: Mutex::new(Box::new(next_volume)),
+            mounted: Mutex::new((0, 0)),
+        }
+    }
+
+    /// Number of volumes written to so far
+    pub fn volumes(&self) -> usize {
+        self.mounted.lock().unwrap().0 + 1
+    }
+}
+
+impl ChunkSink for VolumeSink<'_> {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.dir.put(name, data)
+    }
+
+    fn create(&self, name: &str) -> Result<Box<dyn ChunkEncoder + '_>> {
+        self.dir.create(name)
+    }
+
+    fn copy_from(&self, name: &str, file: &File, offset: u64, len: u64) -> Result<bool> {
+        self.dir.copy_from(name, file, offset, len)
+    }
+
+    fn contains(&self, name: &str) -> Result<bool> {
+        self.dir.contains(name)
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.dir.remove(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        ChunkSink::describe(&self.dir, name)
+    }
+
+    fn sync(&self, name: &str) -> Result<()> {
+        self.dir.sync(name)
+    }
+
+    /// Asks for the next volume first if the mounted one is full, and cuts the chunk short to the room left on the
+    /// mounted one; a volume that has no chunk yet is asked for again instead of being skipped
+    fn place(&self, _name: &str) -> Result<Placement> {
+        let needed = VOLUME_HEADROOM + self.size_limit.min(MIN_CHUNK_ROOM);
+        let mut mounted = self.mounted.lock().unwrap();
+        let available = loop {
+            match available_space(&self.root)? {
+                Some(available) if available < needed => {
+                    if mounted.1 > 0 {
+                        *mounted = (mounted.0 + 1, 0);
+                    }
+                    (self.next_volume.lock().unwrap())(mounted.0 + 1, needed)?;
+                }
+                available => break available,
+            }
+        };
+        mounted.1 += 1;
+        Ok(Placement {
+            destination: Some(mounted.0),
+            room: available.map(|available| available - VOLUME_HEADROOM).filter(|&room| room < self.size_limit),
+        })
+    }
+
+    fn limits_room(&self) -> bool {
+        true
+    }
+
+    /// The mount directory, once for every volume written to
+    fn destinations(&self) -> Vec<String> {
+        let root = std::path::absolute(&self.root).unwrap_or_else(|_| self.root.clone());
+        vec![root.display().to_string(); self.volumes()]
+    }
+}
+
+impl ChunkSource for VolumeSink<'_> {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        self.dir.open(name)
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        self.dir.size(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        ChunkSource::describe(&self.dir, name)
+    }
+}
+
+/// Asks for volume `number` (from 1) of `count` to be mounted, as it holds the chunk at `location`; returns once
+/// it is, or fails to stop
+type WantVolume<'a> = Box<dyn FnMut(usize, usize, &str) -> Result<()> + Send + 'a>;
+
+/// Reads the chunks of a split set written by a [`VolumeSink`] from the volume mounted at a directory, asking for
+/// the volume holding a chunk whenever the mounted one does not
+pub struct VolumeSource<'a> {
+    dir: LocalDir,
+    /// Volume (from 0) of every chunk
+    volumes: HashMap<String, usize>,
+    count: usize,
+    want_volume: Mutex<WantVolume<'a>>,
+}
+
+impl<'a> VolumeSource<'a> {
+    /// Reads the chunks of `file_info` from the volumes mounted at `input_root_dir`, calling `want_volume` with the
+    /// number of the volume needed (from 1), the number of volumes and the missing chunk's location
+    pub fn new(
+        input_root_dir: impl Into<PathBuf>,
+        file_info: &SplitInfo,
+        want_volume: impl FnMut(usize, usize, &str) -> Result<()> + Send + 'a,
+    ) -> Self {
+        let volumes = file_info.chunks.iter()
+            .filter_map(|chunk| chunk.destination.map(|volume| (file_info.chunk_name(chunk), volume)))
+            .collect();
+        VolumeSource {
+            dir: LocalDir::new(input_root_dir),
+            volumes,
+            count: file_info.destinations.len(),
+            want_volume: Mutex::new(Box::new(want_volume)),
+        }
+    }
+
+    /// Waits until `name` is on the mounted volume, if it is a chunk whose volume is known
+    fn mounted(&self, name: &str) -> Result<()> {
+        let Some(&volume) = self.volumes.get(name) else {
+            return Ok(());
+        };
+        // Held while waiting, so that only one volume is asked for at a time
+        let mut want_volume = self.want_volume.lock().unwrap();
+        while !self.dir.contains(name)? {
+            want_volume(volume + 1, self.count, &ChunkSource::describe(&self.dir, name))?;
+        }
+        Ok(())
+    }
+}
+
+impl ChunkSource for VolumeSource<'_> {
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        self.mounted(name)?;
+        self.dir.open(name)
+    }
+
+    fn size(&self, name: &str) -> Result<Option<u64>> {
+        self.mounted(name)?;
+        self.dir.size(name)
+    }
+
+    fn describe(&self, name: &str) -> String {
+        ChunkSource::describe(&self.dir, name)
+    }
+}
@@ -0,0 +1,117 @@
+// src/watch.rs
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{split_file_in, ChunkSink, SplitEvent, SplitOptions};
+
+/// How long a file must go unchanged before [`watch_directory`] splits it
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// How often [`watch_directory`] looks for settled files and checks whether it is cancelled
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Watches `dir` and splits every file that appears in it into `sink`, until cancelled
+///
+/// A file created in (or moved into) `dir` is split like [`crate::split_file_to_sink`] does once it has not
+/// changed for two seconds, so that files still being copied in are not split half-written; a file that changes
+/// again later is split again. Left alone are files smaller than `min_size`, hidden files (e.g., partial uploads
+/// named ".file.part"), subdirectories (where the split sets go if `sink` stores into `dir`), and the files that
+/// were already there when watching started.
+///
+/// Every split reports its events to `event_callback`; one that fails is reported with a [`SplitEvent::Message`]
+/// and watching goes on. Returns once [`SplitOptions::cancel`] is set, or fails if `dir` cannot be watched.
+pub fn watch_directory(
+    dir: &Path,
+    min_size: u64,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    event_callback: Option<Box<dyn Fn(SplitEvent) + Send + Sync + 'static>>,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start watching for files")?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch directory: {}", dir.display()))?;
+
+    let emit = |event: SplitEvent| {
+        if let Some(cb) = &event_callback {
+            cb(event);
+        }
+    };
+    tracing::info!(dir = %dir.display(), "watching for new files");
+    emit(SplitEvent::Message(format!("Watching '{}' for new files", dir.display())));
+
+    let checkpoint = options.checkpoint();
+    // Files seen changing, with when they last did
+    let mut changed: HashMap<PathBuf, Instant> = HashMap::new();
+    while !checkpoint.cancelled() {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => match event.kind {
+                EventKind::Create(_) | EventKind::Modify(_) => {
+                    for path in event.paths {
+                        changed.insert(path, Instant::now());
+                    }
+                }
+                EventKind::Remove(_) => {
+                    for path in &event.paths {
+                        changed.remove(path);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Err(e)) => emit(SplitEvent::Message(format!("Error watching '{}': {}", dir.display(), e))),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(anyhow::anyhow!("Stopped watching directory: {}", dir.display()));
+            }
+        }
+
+        let settled: Vec<PathBuf> = changed.iter()
+            .filter(|(_, at)| at.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            changed.remove(&path);
+            let split = split_new_file(&path, min_size, size_limit, sink, options, &emit);
+            if let Err(e) = split {
+                if checkpoint.cancelled() {
+                    break;
+                }
+                tracing::warn!(file = %path.display(), error = %format!("{:#}", e), "split failed");
+                emit(SplitEvent::Message(format!("Failed to split '{}': {:#}", path.display(), e)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits the file at `path` that settled in the watched directory, unless it is one to leave alone
+fn split_new_file(
+    path: &Path,
+    min_size: u64,
+    size_limit: u64,
+    sink: &dyn ChunkSink,
+    options: &SplitOptions,
+    emit: &(dyn Fn(SplitEvent) + Send + Sync),
+) -> Result<()> {
+    let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    // Gone again (e.g., moved on), or not a regular file
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if hidden || !metadata.is_file() {
+        return Ok(());
+    }
+    if metadata.len() < min_size {
+        tracing::debug!(file = %path.display(), size = metadata.len(), "file is below the minimum size");
+        return Ok(());
+    }
+    tracing::info!(file = %path.display(), "splitting new file");
+    split_file_in(path, "", size_limit, sink, options, Some(Box::new(emit)))?;
+    Ok(())
+}